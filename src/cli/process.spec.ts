@@ -72,26 +72,42 @@ function __createExecMock(
 function __createSpawnIPCMock(
   options: {
     ipcData?: string;
+    stdoutData?: string;
+    stderrData?: string;
     exitCode?: number;
     errorOnEvent?: string;
+    captureOptions?: (options: any) => void;
   } = {}
 ) {
-  const { ipcData = '', exitCode = 0, errorOnEvent } = options;
+  const {
+    ipcData = '',
+    stdoutData,
+    stderrData,
+    exitCode = 0,
+    errorOnEvent,
+    captureOptions,
+  } = options;
+
+  return (cmdName?: string, cmdArgs?: string[], spawnOptions?: any) => {
+    captureOptions?.(spawnOptions);
 
-  return () => ({
-    stdio: [
-      null, // stdin
-      null, // stdout
-      null, // stderr
-      {
-        // fd3 for IPC
-        on: __createIPCEventHandler(ipcData),
-      },
-    ],
-    on: __createProcessEventHandler(exitCode, errorOnEvent),
-    stdin: { write: () => {}, end: () => {} },
-    kill: () => {},
-  });
+    return {
+      stdout: { on: __createStreamEventHandler(stdoutData) },
+      stderr: { on: __createStreamEventHandler(stderrData) },
+      stdio: [
+        null, // stdin
+        null, // stdout
+        null, // stderr
+        {
+          // fd3 for IPC
+          on: __createIPCEventHandler(ipcData),
+        },
+      ],
+      on: __createProcessEventHandler(exitCode, errorOnEvent),
+      stdin: { write: () => {}, end: () => {} },
+      kill: () => {},
+    };
+  };
 }
 
 function __createHangingProcessMock(
@@ -381,6 +397,60 @@ describe('ipc', () => {
     await __testIpcScenario({ exitCode: 1 }, true, { type: 'Unknown', id: 'ipc-error-unknown' });
   });
 
+  it('should read the reply from fd 3 and say so to the child', async () => {
+    let spawnOptions: any = {};
+    moxxy.spawn.mock(
+      __createSpawnIPCMock({
+        ipcData: '{"ok":true,"value":1}',
+        captureOptions: (options) => (spawnOptions = options),
+      })
+    );
+
+    const result = await processModule.ipc('test-command', { channel: 'fd' });
+
+    expect(result).toBe('{"ok":true,"value":1}');
+    expect(spawnOptions.env.GUD_IPC_CHANNEL).toBe('fd:3');
+    expect(spawnOptions.stdio).toEqual(['pipe', 'inherit', 'pipe', 'pipe']);
+  });
+
+  it('should read framed replies from stdout where fd 3 is unavailable', async () => {
+    let spawnOptions: any = {};
+    const payload = '{"ok":true,"value":"é"}';
+    moxxy.spawn.mock(
+      __createSpawnIPCMock({
+        stdoutData: `\x1egud-frame ${Buffer.byteLength(payload)}\n${payload}\n`,
+        captureOptions: (options) => (spawnOptions = options),
+      })
+    );
+
+    const result = await processModule.ipc('test-command', { channel: 'stdout' });
+
+    expect(result).toBe(payload);
+    expect(spawnOptions.env.GUD_IPC_CHANNEL).toBe('stdout');
+    expect(spawnOptions.stdio).toEqual(['pipe', 'pipe', 'pipe']);
+  });
+
+  it('should attach the error records logged on stderr to a failure', async () => {
+    const warning = { level: 'warn', message: 'Slow' };
+    const failure = { level: 'error', message: 'IPC error: broken pipe' };
+    const logged: unknown[] = [];
+    moxxy.spawn.mock(
+      __createSpawnIPCMock({
+        exitCode: 1,
+        stderrData: ['not a record', JSON.stringify(warning), JSON.stringify(failure)].join('\n'),
+      })
+    );
+
+    try {
+      await processModule.ipc('test-command', { onLog: (record) => logged.push(record) });
+      expect(true).toBe(false);
+    } catch (error: any) {
+      expect(error.id).toBe('ipc-error-unknown');
+      expect(error.context.logs).toEqual([failure]);
+      expect(logged).toEqual([warning, failure]);
+    }
+  });
+
   it('should handle IPC with timeout', async () => {
     let killCalled = false;
 
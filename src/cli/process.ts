@@ -27,8 +27,21 @@ export type ExecOptions = ProcessOptions & {
   preserveColors?: boolean;
 };
 
+// Where the child writes its reply: fd 3, or framed on stdout where no
+// extra descriptor can be inherited
+export type IpcChannel = 'fd' | 'stdout';
+
+// A JSON log record the child wrote to stderr
+export type IpcLogRecord = {
+  level: string;
+  message: string;
+  [field: string]: unknown;
+};
+
 export type IpcOptions = ProcessOptions & {
   data?: string;
+  channel?: IpcChannel;
+  onLog?: (record: IpcLogRecord) => void;
 };
 
 const defaultExecOptions: ExecOptions = {
@@ -47,8 +60,12 @@ const defaultIpcOptions: IpcOptions = {
   args: [],
   cwd: process.cwd(),
   env: process.env as Record<string, string>,
+  channel: process.platform === 'win32' ? 'stdout' : 'fd',
 };
 
+const IPC_CHANNEL_ENV = 'GUD_IPC_CHANNEL';
+const FRAME_MARKER = Buffer.from('\x1egud-frame ');
+
 function __execError(
   command: string,
   code: number | null,
@@ -92,6 +109,72 @@ function __receive(stream: Stream.Readable): Promise<string> {
   });
 }
 
+function __receiveBytes(stream: Stream.Readable): Promise<Buffer> {
+  return new Promise<Buffer>((resolve) => {
+    const chunks: Buffer[] = [];
+    stream.on('data', (chunk) => chunks.push(Buffer.from(chunk)));
+    stream.on('end', () => resolve(Buffer.concat(chunks)));
+  });
+}
+
+// Split stdout into the payloads of `\x1egud-frame <len>\n<payload>\n` frames
+// and the text written around them
+function __unframe(data: Buffer): { payload: string; text: string } {
+  const payloads: Buffer[] = [];
+  const text: Buffer[] = [];
+  let position = 0;
+  while (position < data.length) {
+    const start = data.indexOf(FRAME_MARKER, position);
+    const newline = start === -1 ? -1 : data.indexOf('\n', start);
+    const length = Number(data.subarray(start + FRAME_MARKER.length, newline).toString());
+    if (newline === -1 || !Number.isInteger(length)) {
+      text.push(data.subarray(position));
+      break;
+    }
+    text.push(data.subarray(position, start));
+    payloads.push(data.subarray(newline + 1, newline + 1 + length));
+    // Skip the newline closing the frame
+    position = newline + 1 + length + 1;
+  }
+  return { payload: Buffer.concat(payloads).toString(), text: Buffer.concat(text).toString() };
+}
+
+function __parseLogRecord(line: string): IpcLogRecord | undefined {
+  try {
+    const record = JSON.parse(line);
+    return typeof record?.level === 'string' ? record : undefined;
+  } catch {
+    return undefined;
+  }
+}
+
+// Pass stderr through while keeping the JSON log records written to it
+function __receiveLogs(stream: Stream.Readable): Promise<IpcLogRecord[]> {
+  return new Promise<IpcLogRecord[]>((resolve) => {
+    let text = '';
+    stream.on('data', (chunk) => {
+      process.stderr.write(chunk);
+      text += chunk.toString();
+    });
+    stream.on('end', () =>
+      resolve(
+        text
+          .split('\n')
+          .map(__parseLogRecord)
+          .filter((record): record is IpcLogRecord => record !== undefined)
+      )
+    );
+  });
+}
+
+async function __receiveFramed(stream: Stream.Readable): Promise<string> {
+  const { payload, text } = __unframe(await __receiveBytes(stream));
+  if (text) {
+    process.stdout.write(text);
+  }
+  return payload;
+}
+
 function __handleProcessCompletion(
   childProcess: ChildProcess,
   command: string,
@@ -133,25 +216,37 @@ function __send(childProcess: ChildProcess, data?: string): void {
   childProcess.stdin?.end();
 }
 
-// IPC communication using stdin and fd3
+// IPC communication using stdin and fd3, or framed stdout
 export async function ipc(cmd: string, options: IpcOptions = {}): Promise<string> {
+  let errors: IpcLogRecord[] = [];
   try {
-    const { args, cwd, env, timeout, data } = { ...defaultIpcOptions, ...options };
+    const { args, cwd, env, timeout, data, channel, onLog } = {
+      ...defaultIpcOptions,
+      ...options,
+    };
     const command = __withCrossPlatformArgs(cmd, args || []);
+    const framed = channel === 'stdout';
 
     const childProcess = spawn(cmd, args || [], {
       cwd,
-      env,
-      stdio: ['pipe', 'inherit', 'inherit', 'pipe'], // stdin, stdout, stderr, fd3
+      env: { ...env, [IPC_CHANNEL_ENV]: framed ? 'stdout' : 'fd:3' },
+      stdio: framed
+        ? ['pipe', 'pipe', 'pipe'] // stdin, framed stdout, stderr
+        : ['pipe', 'inherit', 'pipe', 'pipe'], // stdin, stdout, stderr, fd3
     });
 
     __send(childProcess, data);
 
-    const [output, { code }] = await Promise.all([
-      __receive(childProcess.stdio[3] as Stream.Readable),
+    const [output, logs, { code }] = await Promise.all([
+      framed
+        ? __receiveFramed(childProcess.stdout as Stream.Readable)
+        : __receive(childProcess.stdio[3] as Stream.Readable),
+      __receiveLogs(childProcess.stderr as Stream.Readable),
       __handleProcessCompletion(childProcess, command, timeout),
     ]);
 
+    logs.forEach((record) => onLog?.(record));
+    errors = logs.filter((record) => record.level === 'error');
     if (code !== 0) {
       throw __execError(command, code);
     }
@@ -163,6 +258,7 @@ export async function ipc(cmd: string, options: IpcOptions = {}): Promise<string
       context: {
         command: cmd,
         error: e instanceof Error ? e.stack : `Unknown error ${e}`,
+        ...(errors.length > 0 && { logs: errors }),
       },
     });
   }
@@ -5,6 +5,9 @@ import rustClient from './rust-client.ts';
 import kerror from './kerror.ts';
 import { normalizeCommand } from '../testing/utils/xplat-helpers.ts';
 
+const GUD_PATH = 'src/rust/target/release/gud';
+const TEST_APIS = [{ module: 'test', api: 'api' }];
+
 function __createPathMock() {
   return {
     resolve: (p: string) => {
@@ -19,15 +22,28 @@ function __createPathMock() {
   };
 }
 
-function __createProcessMock(ipcResult: string | undefined) {
+function __listEnvelope(apis: Array<{ module: string; api: string }>) {
+  return JSON.stringify({ ok: true, value: apis });
+}
+
+function __createProcessMock(
+  ipcResult: string | undefined,
+  apis = TEST_APIS,
+  logs: Array<{ level: string; message: string }> = []
+) {
   let capturedCommand = '';
   let capturedOptions = {};
 
   return {
     mock: {
       ipc: (command: string, options: any) => {
+        if (options.args?.[0] === 'list') {
+          return Promise.resolve(__listEnvelope(apis));
+        }
+
         capturedCommand = normalizeCommand(command);
         capturedOptions = options;
+        logs.forEach((record) => options.onLog?.(record));
         return Promise.resolve(ipcResult);
       },
     },
@@ -36,10 +52,7 @@ function __createProcessMock(ipcResult: string | undefined) {
   };
 }
 
-function __createMocks(
-  ipcResult?: string | undefined,
-  globbyResult = [{ name: 'bin-test--api', path: '/test/path' }]
-) {
+function __createMocks(ipcResult?: string | undefined) {
   // Use arguments.length to detect if first parameter was explicitly passed
   const actualIpcResult = arguments.length === 0 ? '{"result": "success"}' : ipcResult;
   const processMock = __createProcessMock(actualIpcResult);
@@ -47,7 +60,6 @@ function __createMocks(
   moxxy.process.mock(processMock.mock);
   moxxy.path.mock(__createPathMock());
   moxxy.existsSync.mock(() => true);
-  moxxy.globby.mock(() => Promise.resolve(globbyResult));
   moxxy.globalThis = {
     process: { argv: ['/mock/dir/executable'] },
   };
@@ -72,9 +84,11 @@ function __testProcessOutput(ipcResult: string | undefined, expectedResult: any,
     const client = await getClient();
     const result = testData ? await client.test.api(testData) : await client.test.api();
 
-    expect(getCapturedCommand()).toBe('/test/path');
+    expect(getCapturedCommand()).toBe(GUD_PATH);
     expect(getCapturedOptions()).toEqual({
+      args: ['test', 'api'],
       data: testData ? JSON.stringify(testData) : '',
+      onLog: expect.any(Function),
     });
 
     if (expectedResult !== undefined) {
@@ -99,35 +113,33 @@ function __testErrorScenario(ipcResult: string, expectedErrorId: string) {
   };
 }
 
-function __setupBinarySearchMocks(existsPaths: string[], globbyMappings: Record<string, any[]>) {
+function __setupBinarySearchMocks(existsPaths: string[]) {
+  const capturedCommands: string[] = [];
   moxxy.existsSync.mock((pathStr: string) => {
     return existsPaths.some((path) => pathStr === path || pathStr.endsWith(path));
   });
 
-  moxxy.globby.mock((pattern: string) => {
-    for (const [patternKey, result] of Object.entries(globbyMappings)) {
-      if (pattern.includes(patternKey) || pattern === patternKey) {
-        return Promise.resolve(result);
-      }
-    }
-    return Promise.resolve([]);
-  });
-
-  moxxy.process.mock(() => ({ ipc: () => Promise.resolve('{}') }));
+  moxxy.process.mock(() => ({
+    ipc: (command: string) => {
+      capturedCommands.push(normalizeCommand(command));
+      return Promise.resolve(__listEnvelope(TEST_APIS));
+    },
+  }));
   moxxy.path.mock(__createPathMock());
   moxxy.globalThis = { process: { argv: ['/mock/dir/executable'] } };
+
+  return { getCapturedCommands: () => capturedCommands };
 }
 
-function __setupModuleTest(binaries: { name: string; path: string }[]) {
+function __setupModuleTest(listOutput: string | undefined) {
   moxxy.existsSync.mock(() => true);
-  moxxy.globby.mock(() => Promise.resolve(binaries));
-  moxxy.process.mock(() => ({ ipc: () => Promise.resolve('{}') }));
+  moxxy.process.mock(() => ({ ipc: () => Promise.resolve(listOutput) }));
   moxxy.path.mock(__createPathMock());
   moxxy.globalThis = { process: { argv: ['/mock/dir/executable'] } };
 }
 
-async function __testClientWithBinaries(binaries: { name: string; path: string }[]) {
-  __setupModuleTest(binaries);
+async function __testClientWithApis(apis: Array<{ module: string; api: string }>) {
+  __setupModuleTest(__listEnvelope(apis));
   return await rustClient();
 }
 
@@ -150,8 +162,17 @@ describe('__createDispatcher()', () => {
       const client = await getClient();
       await client.test.api();
 
-      expect(getCapturedCommand()).toBe('/test/path');
-      expect(getCapturedOptions()).toEqual({ data: '' });
+      expect(getCapturedCommand()).toBe(GUD_PATH);
+      expect(getCapturedOptions()).toEqual({ args: ['test', 'api'], data: '' });
+    });
+
+    it('passes extra arguments after the module and api', async () => {
+      const { getClient, getCapturedOptions } = __setupTest('{"ok": true, "value": null}');
+
+      const client = await getClient();
+      await client.test.api(undefined, { args: ['--schema'] });
+
+      expect(getCapturedOptions()).toEqual({ args: ['test', 'api', '--schema'], data: '' });
     });
 
     it('handles undefined process output', async () => {
@@ -201,72 +222,150 @@ describe('__createDispatcher()', () => {
       await __testProcessOutput('"hello world"', 'hello world');
     });
   });
-});
 
-describe('__getBinarySearchPaths()', () => {
-  describe('development binaries', () => {
-    it('includes development binaries if they exist', async () => {
-      __setupBinarySearchMocks(['src/rust/target/release'], {
-        'src/rust/target/release/**/bin-*--*': [{ name: 'bin-test--api', path: '/dev/path' }],
-      });
+  describe('response envelopes', () => {
+    it('unwraps the value of a successful envelope', async () => {
+      await __testProcessOutput('{"ok": true, "value": {"answer": 42}}', { answer: 42 });
+    });
 
-      const client = await rustClient();
-      expect(client.test).toBeDefined();
+    it('unwraps null values of a successful envelope', async () => {
+      await __testProcessOutput('{"ok": true, "value": null}', null);
     });
 
-    it('does not include development binaries if they do not exist', async () => {
-      __setupBinarySearchMocks(['dist/rust-binaries'], {
-        'dist/rust-binaries/bin-*--*': [{ name: 'bin-test--api', path: '/bundled/path' }],
-      });
+    it('rethrows failed envelopes as klep errors', async () => {
+      await __testErrorScenario(
+        '{"ok": false, "error": {"kind": "Argument", "code": "missing-input", "message": "Input is required", "context": null}}',
+        'missing-input'
+      );
+    });
+
+    it('preserves the error kind and context of failed envelopes', async () => {
+      const { getClient } = __setupTest(
+        '{"ok": false, "error": {"kind": "Parsing", "code": "invalid-json", "message": "bad", "context": {"line": 3}}}'
+      );
+
+      const client = await getClient();
+
+      try {
+        await client.test.api();
+        expect(true).toBe(false); // Should not reach here
+      } catch (error: any) {
+        expect(error.type).toBe(kerror.Parsing);
+        expect(error.message).toBe('bad');
+        expect(error.context).toEqual({ command: `${GUD_PATH} test api`, line: 3 });
+      }
+    });
+
+    it('attaches the error records logged by a failed call', async () => {
+      const failure = { level: 'error', message: 'Request failed', code: 'invalid-json' };
+      moxxy.process.mock(
+        __createProcessMock(
+          '{"ok": false, "error": {"kind": "Parsing", "code": "invalid-json", "message": "bad"}}',
+          TEST_APIS,
+          [{ level: 'warn', message: 'Slow' }, failure]
+        ).mock
+      );
+      moxxy.path.mock(__createPathMock());
+      moxxy.existsSync.mock(() => true);
+      moxxy.globalThis = { process: { argv: ['/mock/dir/executable'] } };
 
       const client = await rustClient();
-      expect(client.test).toBeDefined();
+
+      try {
+        await client.test.api();
+        expect(true).toBe(false); // Should not reach here
+      } catch (error: any) {
+        expect(error.context).toEqual({ command: `${GUD_PATH} test api`, logs: [failure] });
+      }
+    });
+
+    it('falls back to Unknown for unrecognized error kinds', async () => {
+      const { getClient } = __setupTest(
+        '{"ok": false, "error": {"kind": "Mystery", "code": "odd", "message": "?"}}'
+      );
+
+      const client = await getClient();
+
+      try {
+        await client.test.api();
+        expect(true).toBe(false); // Should not reach here
+      } catch (error: any) {
+        expect(error.type).toBe(kerror.Unknown);
+        expect(error.id).toBe('odd');
+      }
+    });
+
+    it('returns objects that only resemble envelopes unchanged', async () => {
+      await __testProcessOutput('{"ok": true}', { ok: true });
     });
   });
+});
 
-  describe('release binaries', () => {
-    it('include release binaries if they exist', async () => {
-      __setupBinarySearchMocks(['dist/rust-binaries'], {
-        'dist/rust-binaries/bin-*--*': [{ name: 'bin-test--api', path: '/release/path' }],
-      });
+describe('__getBinarySearchDirs()', () => {
+  describe('development binaries', () => {
+    it('uses the development dispatcher if it exists', async () => {
+      const { getCapturedCommands } = __setupBinarySearchMocks([
+        'src/rust/target/release',
+        'src/rust/target/release/gud',
+      ]);
 
       const client = await rustClient();
       expect(client.test).toBeDefined();
+      expect(getCapturedCommands()).toEqual(['src/rust/target/release/gud']);
     });
 
-    it('does not include release binaries if they do not exist', async () => {
-      __setupBinarySearchMocks(['src/rust/target/release'], {
-        'src/rust/target/release/**/bin-*--*': [{ name: 'bin-test--api', path: '/dev/path' }],
-      });
+    it('prefers the development dispatcher over distributed ones', async () => {
+      const { getCapturedCommands } = __setupBinarySearchMocks([
+        'src/rust/target/release',
+        'src/rust/target/release/gud',
+        'dist/rust-binaries',
+        'dist/rust-binaries/gud',
+      ]);
 
-      const client = await rustClient();
-      expect(client.test).toBeDefined();
+      await rustClient();
+      expect(getCapturedCommands()).toEqual(['src/rust/target/release/gud']);
     });
   });
 
-  describe('standalone', () => {
-    it('includes bundled binaries if they exist', async () => {
-      __setupBinarySearchMocks(['rust-binaries'], {
-        'rust-binaries/bin-*--*': [{ name: 'bin-test--api', path: '/standalone/path' }],
-      });
+  describe('release binaries', () => {
+    it('uses the release dispatcher if it exists', async () => {
+      const { getCapturedCommands } = __setupBinarySearchMocks([
+        'dist/rust-binaries',
+        'dist/rust-binaries/gud',
+      ]);
 
       const client = await rustClient();
       expect(client.test).toBeDefined();
+      expect(getCapturedCommands()).toEqual(['dist/rust-binaries/gud']);
     });
 
-    it('does not include bundled binaries if they do not exist', async () => {
-      __setupBinarySearchMocks(['src/rust/target/release'], {
-        'src/rust/target/release/**/bin-*--*': [{ name: 'bin-test--api', path: '/dev/path' }],
-      });
+    it('finds windows executables', async () => {
+      const { getCapturedCommands } = __setupBinarySearchMocks([
+        'dist/rust-binaries',
+        'dist/rust-binaries/gud.exe',
+      ]);
+
+      await rustClient();
+      expect(getCapturedCommands()).toEqual(['dist/rust-binaries/gud.exe']);
+    });
+  });
+
+  describe('standalone', () => {
+    it('uses the dispatcher next to the executable if it exists', async () => {
+      const { getCapturedCommands } = __setupBinarySearchMocks([
+        '/mock/dir/rust-binaries',
+        '/mock/dir/rust-binaries/gud',
+      ]);
 
       const client = await rustClient();
       expect(client.test).toBeDefined();
+      expect(getCapturedCommands()).toEqual(['/mock/dir/rust-binaries/gud']);
     });
   });
 
   describe('error handling', () => {
     it('throws a rust-no-folders-found error if no directories are included', async () => {
-      __setupBinarySearchMocks([], {});
+      __setupBinarySearchMocks([]);
 
       try {
         await rustClient();
@@ -277,143 +376,102 @@ describe('__getBinarySearchPaths()', () => {
       }
     });
 
-    it('does not throw if one or more search pathes are added', async () => {
-      __setupBinarySearchMocks(['src/rust/target/release'], {
-        'src/rust/target/release/**/bin-*--*': [{ name: 'bin-test--api', path: '/test/path' }],
-      });
+    it('throws a no-rust-binaries-found error if no directory holds the dispatcher', async () => {
+      __setupBinarySearchMocks(['src/rust/target/release']);
 
-      await expect(rustClient()).resolves.toBeDefined();
+      try {
+        await rustClient();
+        expect(true).toBe(false); // Should not reach here
+      } catch (error: any) {
+        expect(kerror.isKlepError(error)).toBe(true);
+        expect(error.id).toBe('no-rust-binaries-found');
+        expect(error.context['search-paths']).toEqual(['src/rust/target/release']);
+      }
     });
   });
 });
 
-describe('__getRustBinaries()', () => {
-  it('handles binaries when they are found', async () => {
-    const client = await __testClientWithBinaries([
-      { name: 'bin-module1--api1', path: '/path/to/bin1' },
-      { name: 'bin-module2--api2', path: '/path/to/bin2' },
-    ]);
+describe('__listApis()', () => {
+  it('asks the dispatcher for its registered apis', async () => {
+    let capturedOptions: any;
+    moxxy.existsSync.mock(() => true);
+    moxxy.process.mock(() => ({
+      ipc: (_: string, options: any) => {
+        capturedOptions = options;
+        return Promise.resolve(__listEnvelope(TEST_APIS));
+      },
+    }));
+    moxxy.path.mock(__createPathMock());
+    moxxy.globalThis = { process: { argv: ['/mock/dir/executable'] } };
 
-    expect(client.module1.api1).toBeDefined();
-    expect(client.module2.api2).toBeDefined();
+    await rustClient();
+    expect(capturedOptions).toEqual({ args: ['list'] });
   });
 
-  it('throws no-rust-binaries-found error when no binaries are found', async () => {
-    __setupModuleTest([]);
+  it('throws a no-rust-apis-found error if the dispatcher lists nothing', async () => {
+    __setupModuleTest('');
 
     try {
       await rustClient();
       expect(true).toBe(false); // Should not reach here
     } catch (error: any) {
       expect(kerror.isKlepError(error)).toBe(true);
-      expect(error.id).toBe('no-rust-binaries-found');
+      expect(error.id).toBe('no-rust-apis-found');
     }
   });
-});
-
-describe('__createModules()', () => {
-  describe('filtering', () => {
-    it('filters out files ending in .d', async () => {
-      const client = await __testClientWithBinaries([
-        { name: 'bin-test--api', path: '/path/to/bin' },
-        { name: 'bin-test--api.d', path: '/path/to/bin.d' },
-      ]);
 
-      expect(client.test.api).toBeDefined();
-      expect(Object.keys(client.test)).toHaveLength(1);
-    });
-
-    it('filters out files ending in .pdb', async () => {
-      const client = await __testClientWithBinaries([
-        { name: 'bin-test--api', path: '/path/to/bin' },
-        { name: 'bin-test--api.pdb', path: '/path/to/bin.pdb' },
-      ]);
-
-      expect(client.test.api).toBeDefined();
-      expect(Object.keys(client.test)).toHaveLength(1);
-    });
-
-    it('does not filter files if they are not debugging files', async () => {
-      const client = await __testClientWithBinaries([
-        { name: 'bin-test--api1', path: '/path/to/bin1' },
-        { name: 'bin-test--api2', path: '/path/to/bin2' },
-        { name: 'bin-test--api3.exe', path: '/path/to/bin3.exe' },
-      ]);
+  it('rethrows a failed list envelope', async () => {
+    __setupModuleTest(
+      '{"ok": false, "error": {"kind": "Unknown", "code": "broken", "message": "no", "context": null}}'
+    );
 
-      expect(Object.keys(client.test)).toHaveLength(3);
-    });
+    try {
+      await rustClient();
+      expect(true).toBe(false); // Should not reach here
+    } catch (error: any) {
+      expect(kerror.isKlepError(error)).toBe(true);
+      expect(error.id).toBe('broken');
+    }
   });
+});
 
-  describe('module creation', () => {
-    it('gracefully handles .exe extensions', async () => {
-      const client = await __testClientWithBinaries([
-        { name: 'bin-test--api.exe', path: '/path/to/bin.exe' },
-      ]);
-
-      expect(client.test.api).toBeDefined();
-    });
-
-    it('properly discovers the module from the binary name pattern', async () => {
-      const client = await __testClientWithBinaries([
-        { name: 'bin-mymodule--someapi', path: '/path/to/bin' },
-      ]);
-
-      expect(client.mymodule).toBeDefined();
-    });
+describe('__createModules()', () => {
+  it('creates a dispatcher for every listed api', async () => {
+    const client = await __testClientWithApis([
+      { module: 'module1', api: 'api1' },
+      { module: 'module2', api: 'api2' },
+    ]);
 
-    it('properly discovers the apiName from the binary name pattern', async () => {
-      const client = await __testClientWithBinaries([
-        { name: 'bin-testmodule--customapi', path: '/path/to/bin' },
-      ]);
-      expect(client.testmodule.customapi).toBeDefined();
-    });
+    expect(client.module1.api1).toBeDefined();
+    expect(client.module2.api2).toBeDefined();
+  });
 
-    it('creates a full set of modules when handed various binaries', async () => {
-      const client = await __testClientWithBinaries([
-        { name: 'bin-mod1--api1', path: '/path/to/bin1' },
-        { name: 'bin-mod1--api2', path: '/path/to/bin2' },
-        { name: 'bin-mod2--api1', path: '/path/to/bin3' },
-        { name: 'bin-mod3--special', path: '/path/to/bin4' },
-      ]);
+  it('groups apis by module', async () => {
+    const client = await __testClientWithApis([
+      { module: 'mod1', api: 'api1' },
+      { module: 'mod1', api: 'api2' },
+      { module: 'mod2', api: 'api1' },
+      { module: 'mod3', api: 'special' },
+    ]);
 
-      expect(client.mod1.api1).toBeDefined();
-      expect(client.mod1.api2).toBeDefined();
-      expect(client.mod2.api1).toBeDefined();
-      expect(client.mod3.special).toBeDefined();
-    });
+    expect(Object.keys(client.mod1)).toEqual(['api1', 'api2']);
+    expect(client.mod2.api1).toBeDefined();
+    expect(client.mod3.special).toBeDefined();
   });
 });
 
 describe('__addHelp()', () => {
   it('defines a help method on the module collection', async () => {
-    moxxy.existsSync.mock(() => true);
-    moxxy.globby.mock(() => Promise.resolve([{ name: 'bin-test--api', path: '/path/to/bin' }]));
-    moxxy.process.mock(() => ({ ipc: () => Promise.resolve('{}') }));
-    moxxy.path.mock(__createPathMock());
-    moxxy.globalThis = {
-      process: { argv: ['/mock/dir/executable'] },
-    };
-
-    const client = await rustClient();
+    const client = await __testClientWithApis(TEST_APIS);
     expect(typeof client.help).toBe('function');
   });
 
   it('provides a help string listing all of the apis available for use', async () => {
-    moxxy.existsSync.mock(() => true);
-    moxxy.globby.mock(() =>
-      Promise.resolve([
-        { name: 'bin-module1--api1', path: '/path/to/bin1' },
-        { name: 'bin-module1--api2', path: '/path/to/bin2' },
-        { name: 'bin-module2--api1', path: '/path/to/bin3' },
-      ])
-    );
-    moxxy.process.mock(() => ({ ipc: () => Promise.resolve('{}') }));
-    moxxy.path.mock(__createPathMock());
-    moxxy.globalThis = {
-      process: { argv: ['/mock/dir/executable'] },
-    };
-
-    const client = await rustClient();
+    const client = await __testClientWithApis([
+      { module: 'module1', api: 'api1' },
+      { module: 'module1', api: 'api2' },
+      { module: 'module2', api: 'api1' },
+    ]);
     const help = client.help();
 
     expect(help).toContain('Available APIs:');
@@ -426,27 +484,13 @@ describe('__addHelp()', () => {
 describe('singleton', () => {
   describe('initialization', () => {
     it('constructs a backend if one does not already exist', async () => {
-      moxxy.existsSync.mock(() => true);
-      moxxy.globby.mock(() => Promise.resolve([{ name: 'bin-test--api', path: '/path/to/bin' }]));
-      moxxy.process.mock(() => ({ ipc: () => Promise.resolve('{}') }));
-      moxxy.path.mock(__createPathMock());
-      moxxy.globalThis = {
-        process: { argv: ['/mock/dir/executable'] },
-      };
-
-      const client = await rustClient();
+      const client = await __testClientWithApis(TEST_APIS);
       expect(client).toBeDefined();
       expect(client.test.api).toBeDefined();
     });
 
     it('returns an existing backend if one already exists', async () => {
-      moxxy.existsSync.mock(() => true);
-      moxxy.globby.mock(() => Promise.resolve([{ name: 'bin-test--api', path: '/path/to/bin' }]));
-      moxxy.process.mock(() => ({ ipc: () => Promise.resolve('{}') }));
-      moxxy.path.mock(__createPathMock());
-      moxxy.globalThis = {
-        process: { argv: ['/mock/dir/executable'] },
-      };
+      __setupModuleTest(__listEnvelope(TEST_APIS));
 
       const client1 = await rustClient();
       const client2 = await rustClient();
@@ -1,6 +1,5 @@
-import process, { IpcOptions } from './process.ts';
+import process, { IpcLogRecord, IpcOptions } from './process.ts';
 import kerror from './kerror.ts';
-import { globby } from 'globby';
 import * as path from 'path';
 import { existsSync } from 'fs';
 
@@ -16,104 +15,168 @@ let __backend: RustClient | null = null;
 
 type Dispatcher = <I = undefined, O = undefined>(blob?: I, options?: IpcOptions) => Promise<O>;
 
-function __createDispatcher(binPath: string) {
-  const command = path.resolve(binPath);
+function __parseOutput(
+  output: string | undefined,
+  command: string,
+  logs: IpcLogRecord[] = []
+): unknown {
+  if (!output?.trim()) {
+    return undefined;
+  }
+
+  let parsed: unknown;
+  try {
+    parsed = JSON.parse(output);
+  } catch (e) {
+    throw kerror(kerror.Unknown, 'rust-client-json-parse-error', {
+      message: `Failed to parse JSON output: ${e instanceof Error ? e.message : 'Unknown error'}`,
+      context: {
+        output,
+        error: e instanceof Error ? e.message : 'Unknown error',
+      },
+    });
+  }
+
+  return __unwrapEnvelope(parsed, command, logs);
+}
+
+function __createDispatcher(gudPath: string, module: string, api: string) {
+  const command = path.resolve(gudPath);
   return async <I, O>(blob?: I, options: IpcOptions = {}): Promise<O> => {
     const data = blob !== undefined ? JSON.stringify(blob) : '';
+    const args = [module, api, ...(options.args ?? [])];
 
-    const output = await process.ipc(command, { ...options, data });
-
-    if (!output?.trim()) {
-      return undefined as O;
-    }
+    // Error records the call logged explain a failed envelope
+    const logs: IpcLogRecord[] = [];
+    const onLog = (record: IpcLogRecord) => {
+      options.onLog?.(record);
+      if (record.level === 'error') logs.push(record);
+    };
+    const output = await process.ipc(command, { ...options, args, data, onLog });
 
-    try {
-      return JSON.parse(output) as O;
-    } catch (e) {
-      throw kerror(kerror.Unknown, 'rust-client-json-parse-error', {
-        message: `Failed to parse JSON output: ${e instanceof Error ? e.message : 'Unknown error'}`,
-        context: {
-          output,
-          error: e instanceof Error ? e.message : 'Unknown error',
-        },
-      });
-    }
+    return __parseOutput(output, `${command} ${module} ${api}`, logs) as O;
   };
 }
 
-function __getBinarySearchPaths(): string[] {
-  const paths: string[] = [];
+type EnvelopeError = {
+  kind: string;
+  code: string;
+  message: string;
+  context?: unknown;
+};
+
+type Envelope = { ok: true; value: unknown } | { ok: false; error: EnvelopeError };
+
+function __isEnvelope(parsed: unknown): parsed is Envelope {
+  if (!parsed || typeof parsed !== 'object' || !('ok' in parsed)) {
+    return false;
+  }
+
+  return parsed.ok === true ? 'value' in parsed : parsed.ok === false && 'error' in parsed;
+}
+
+type KerrorType = (typeof kerror.type)[keyof typeof kerror.type];
 
-  // Development mode: look in the usual Rust target directory
-  const devPath = 'src/rust/target/release/**/bin-*--*';
-  if (existsSync('src/rust/target/release')) {
-    paths.push(devPath);
+function __toKerrorType(kind: string): KerrorType {
+  const known = Object.values(kerror.type) as string[];
+  return (known.includes(kind) ? kind : kerror.Unknown) as KerrorType;
+}
+
+function __toKerrorContext(command: string, context: unknown, logs: IpcLogRecord[]) {
+  const base = logs.length > 0 ? { command, logs } : { command };
+  if (context === null || context === undefined) {
+    return base;
   }
 
-  // Bundled mode: look in the distributed rust-binaries directory
-  const bundledPath = 'dist/rust-binaries/bin-*--*';
-  if (existsSync('dist/rust-binaries')) {
-    paths.push(bundledPath);
+  if (typeof context === 'object' && !Array.isArray(context)) {
+    return { ...base, ...context };
   }
 
-  // Relative to executable (for standalone distribution)
-  const executableDir = path.dirname(globalThis.process.argv[0]);
-  const relativeBundledPath = path.join(executableDir, 'rust-binaries', 'bin-*--*');
-  if (existsSync(path.join(executableDir, 'rust-binaries'))) {
-    paths.push(relativeBundledPath);
+  return { ...base, context };
+}
+
+function __unwrapEnvelope(parsed: unknown, command: string, logs: IpcLogRecord[]): unknown {
+  if (!__isEnvelope(parsed)) {
+    return parsed;
   }
 
-  if (paths.length === 0) {
+  if (parsed.ok) {
+    return parsed.value;
+  }
+
+  const { kind, code, message, context } = parsed.error;
+  throw kerror(__toKerrorType(kind), code, {
+    message,
+    context: __toKerrorContext(command, context, logs),
+  });
+}
+
+const GUD_BINARIES = ['gud', 'gud.exe'];
+
+function __getBinarySearchDirs(): string[] {
+  // Development mode, the usual Rust target directory, then the distributed
+  // rust-binaries directory, bundled or next to a standalone executable
+  const executableDir = path.dirname(globalThis.process.argv[0]);
+  const dirs = [
+    'src/rust/target/release',
+    'dist/rust-binaries',
+    path.join(executableDir, 'rust-binaries'),
+  ].filter((dir) => existsSync(dir));
+
+  if (dirs.length === 0) {
     throw kerror(kerror.Unknown, 'no-rust-folders-found', {
       message:
         'No Rust binary directories found. Ensure the project is built or binaries are distributed.',
     });
   }
 
-  return paths;
+  return dirs;
 }
 
-async function __getRustBinaries(
-  searchPaths: string[]
-): Promise<Array<{ name: string; path: string }>> {
-  const binaries: Array<{ name: string; path: string }> = [];
-  for (const searchPath of searchPaths) {
-    const foundBinaries = await globby(searchPath, { objectMode: true });
-    binaries.push(...foundBinaries);
+function __findGud(searchDirs: string[]): string {
+  for (const dir of searchDirs) {
+    for (const binary of GUD_BINARIES) {
+      const candidate = path.join(dir, binary);
+      if (existsSync(candidate)) {
+        return candidate;
+      }
+    }
   }
 
-  if (binaries.length === 0) {
-    throw kerror(kerror.Unknown, 'no-rust-binaries-found', {
-      message: 'No Rust binaries found in any search paths',
-      context: { 'search-paths': searchPaths },
+  throw kerror(kerror.Unknown, 'no-rust-binaries-found', {
+    message: 'The gud dispatcher was not found in any search paths',
+    context: { 'search-paths': searchDirs },
+  });
+}
+
+type ApiSpec = { module: string; api: string };
+
+async function __listApis(gudPath: string): Promise<ApiSpec[]> {
+  const command = path.resolve(gudPath);
+  const output = await process.ipc(command, { args: ['list'] });
+  const specs = __parseOutput(output, `${command} list`);
+
+  if (!Array.isArray(specs)) {
+    throw kerror(kerror.Unknown, 'no-rust-apis-found', {
+      message: 'The gud dispatcher did not list any APIs',
+      context: { command, output },
     });
   }
 
-  return binaries;
+  return specs as ApiSpec[];
 }
 
-async function __createModules(
-  binaries: Array<{ name: string; path: string }>
-): Promise<RustClient> {
-  return await binaries
-    .filter((entry) => {
-      // Exclude .d files (debug symbols) and .pdb files (Windows debug info)
-      return !entry.name.endsWith('.d') && !entry.name.endsWith('.pdb');
-    })
-    .reduce((modules: RustClient, entry) => {
-      // Remove .exe extension on Windows for parsing
-      const baseName = entry.name.replace(/\.exe$/, '');
-      const module = baseName.split('--')[0].split('bin-')[1];
-      if (!modules[module]) {
-        modules[module] = {};
-      }
+function __createModules(gudPath: string, specs: ApiSpec[]): RustClient {
+  return specs.reduce((modules: RustClient, { module, api }) => {
+    if (!modules[module]) {
+      modules[module] = {};
+    }
 
-      const apiName = baseName.split('--')[1];
-      const dispatcher = __createDispatcher(entry.path.toString());
-      (modules[module] as Record<string, Dispatcher>)[apiName] = dispatcher;
+    const dispatcher = __createDispatcher(gudPath, module, api);
+    (modules[module] as Record<string, Dispatcher>)[api] = dispatcher;
 
-      return modules;
-    }, {} as RustClient);
+    return modules;
+  }, {} as RustClient);
 }
 
 function __addHelp(modules: RustClient) {
@@ -141,8 +204,8 @@ function __addHelp(modules: RustClient) {
 }
 
 async function __constructor(): Promise<RustClient> {
-  const binaries = await __getRustBinaries(__getBinarySearchPaths());
-  const modules = await __createModules(binaries);
+  const gudPath = __findGud(__getBinarySearchDirs());
+  const modules = __createModules(gudPath, await __listApis(gudPath));
   return __addHelp(modules);
 }
 
@@ -1,50 +0,0 @@
-#![allow(missing_docs)]
-
-use gud_common::{debug_log, ipc_main};
-use serde::{Deserialize, Serialize};
-
-#[derive(Deserialize)]
-struct AstInput {
-  source_code: String,
-  language: Option<String>,
-}
-
-#[derive(Serialize)]
-struct ManifestOutput {
-  dependencies: Vec<String>,
-  dev_dependencies: Vec<String>,
-  scripts: Vec<String>,
-}
-
-#[allow(clippy::unnecessary_wraps)]
-fn process_ast_to_manifest(
-  input: Option<AstInput>,
-) -> Result<ManifestOutput, Box<dyn std::error::Error>> {
-  debug_log("Processing AST to manifest conversion");
-
-  if let Some(ast_input) = input {
-    debug_log(&format!(
-      "Processing {} characters of {} code",
-      ast_input.source_code.len(),
-      ast_input.language.as_deref().unwrap_or("unknown")
-    ));
-
-    // TODO: Actual AST parsing and manifest generation logic here
-    // For now, return a dummy manifest
-    Ok(ManifestOutput {
-      dependencies: vec!["serde".to_string(), "tokio".to_string()],
-      dev_dependencies: vec!["test-utils".to_string()],
-      scripts: vec!["build".to_string(), "test".to_string()],
-    })
-  } else {
-    debug_log("No input provided, returning empty manifest");
-    Ok(ManifestOutput {
-      dependencies: vec![],
-      dev_dependencies: vec![],
-      scripts: vec![],
-    })
-  }
-}
-
-// Use the macro to create a main function with IPC handling
-ipc_main!(process_ast_to_manifest);
@@ -0,0 +1,461 @@
+//! Format-independent tree for manifests, lockfiles and other data files.
+//!
+//! Every supported format is normalized into [`DataNode`]s: maps keep their
+//! entries in source order, lists their items, and scalars a typed value.
+//! Each node carries the span it was parsed from, so later stages can point
+//! back into the original file.
+
+mod ini;
+mod json;
+mod lines;
+mod toml;
+mod xml;
+mod yaml;
+
+use gud_common::{ErrorKind, GudError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::to_tree::{AstNode, Location};
+
+/// Source range of a node, end exclusive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Span {
+  /// First character of the node
+  pub start: Location,
+  /// Just past the last character of the node
+  pub end: Location,
+}
+
+/// Leaf value; formats without types of their own (XML, INI, line lists)
+/// produce strings only
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Scalar {
+  /// `null`, `~` or an empty YAML value
+  Null,
+  /// `true` or `false`
+  Bool(bool),
+  /// A number without a fraction or exponent that fits in 64 bits
+  Integer(i64),
+  /// Any other number
+  Float(f64),
+  /// Text, with quotes and escapes resolved
+  String(String),
+}
+
+impl Scalar {
+  /// Name used as the node type when converted to an [`AstNode`]
+  #[must_use]
+  pub const fn type_name(&self) -> &'static str {
+    match self {
+      Self::Null => "null",
+      Self::Bool(_) => "bool",
+      Self::Integer(_) => "integer",
+      Self::Float(_) => "float",
+      Self::String(_) => "string",
+    }
+  }
+
+  /// The value as text, `None` for null
+  #[must_use]
+  pub fn to_text(&self) -> Option<String> {
+    match self {
+      Self::Null => None,
+      Self::Bool(flag) => Some(flag.to_string()),
+      Self::Integer(n) => Some(n.to_string()),
+      Self::Float(n) => Some(n.to_string()),
+      Self::String(text) => Some(text.clone()),
+    }
+  }
+}
+
+/// One key of a map and its value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Entry {
+  /// The key, with quotes and escapes resolved
+  pub key: String,
+  /// Where the key was written
+  pub key_span: Span,
+  /// Value of the key
+  pub value: DataNode,
+}
+
+/// Node of the intermediate tree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DataNode {
+  /// Keyed entries, such as a JSON object or a TOML table
+  Map {
+    /// Entries in source order; duplicate keys are kept
+    entries: Vec<Entry>,
+    /// Where the map was written
+    span: Span,
+  },
+  /// Ordered items, such as a JSON array or a YAML sequence
+  List {
+    /// Items in source order
+    items: Vec<Self>,
+    /// Where the list was written
+    span: Span,
+  },
+  /// A leaf value
+  Scalar {
+    /// The typed value
+    value: Scalar,
+    /// Where the value was written
+    span: Span,
+  },
+}
+
+impl DataNode {
+  /// Where the node was written
+  #[must_use]
+  pub const fn span(&self) -> Span {
+    match self {
+      Self::Map { span, .. } | Self::List { span, .. } | Self::Scalar { span, .. } => *span,
+    }
+  }
+
+  pub(crate) const fn span_mut(&mut self) -> &mut Span {
+    match self {
+      Self::Map { span, .. } | Self::List { span, .. } | Self::Scalar { span, .. } => span,
+    }
+  }
+
+  /// Value of the first entry named `key`, if this is a map
+  #[must_use]
+  pub fn get(&self, key: &str) -> Option<&Self> {
+    match self {
+      Self::Map { entries, .. } => entries
+        .iter()
+        .find(|entry| entry.key == key)
+        .map(|entry| &entry.value),
+      _ => None,
+    }
+  }
+
+  /// Entries of a map, empty for anything else
+  #[must_use]
+  pub fn entries(&self) -> &[Entry] {
+    match self {
+      Self::Map { entries, .. } => entries,
+      _ => &[],
+    }
+  }
+
+  /// Items of a list, empty for anything else
+  #[must_use]
+  pub fn items(&self) -> &[Self] {
+    match self {
+      Self::List { items, .. } => items,
+      _ => &[],
+    }
+  }
+
+  /// Value of a boolean scalar
+  #[must_use]
+  pub const fn as_bool(&self) -> Option<bool> {
+    match self {
+      Self::Scalar {
+        value: Scalar::Bool(flag),
+        ..
+      } => Some(*flag),
+      _ => None,
+    }
+  }
+
+  /// Text of a string scalar
+  #[must_use]
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Self::Scalar {
+        value: Scalar::String(text),
+        ..
+      } => Some(text),
+      _ => None,
+    }
+  }
+
+  /// Plain JSON value of the node, dropping spans. Later duplicate keys
+  /// override earlier ones.
+  #[must_use]
+  pub fn to_value(&self) -> Value {
+    match self {
+      Self::Map { entries, .. } => Value::Object(
+        entries
+          .iter()
+          .map(|entry| (entry.key.clone(), entry.value.to_value()))
+          .collect(),
+      ),
+      Self::List { items, .. } => Value::Array(items.iter().map(Self::to_value).collect()),
+      Self::Scalar { value, .. } => serde_json::to_value(value).unwrap_or(Value::Null),
+    }
+  }
+
+  /// Generic view of the tree: maps become `map` nodes whose children are
+  /// `entry` nodes valued with their key, lists become `list` nodes and
+  /// scalars are named after their type
+  #[must_use]
+  pub fn to_ast(&self) -> AstNode {
+    let node = |node_type: &str, value: Option<String>, children, span: &Span| AstNode {
+      node_type: node_type.to_string(),
+      value,
+      children,
+      location: span.start,
+    };
+    match self {
+      Self::Map { entries, span } => node(
+        "map",
+        None,
+        entries
+          .iter()
+          .map(|entry| {
+            node(
+              "entry",
+              Some(entry.key.clone()),
+              vec![entry.value.to_ast()],
+              &entry.key_span,
+            )
+          })
+          .collect(),
+        span,
+      ),
+      Self::List { items, span } => {
+        node("list", None, items.iter().map(Self::to_ast).collect(), span)
+      }
+      Self::Scalar { value, span } => node(value.type_name(), value.to_text(), Vec::new(), span),
+    }
+  }
+}
+
+/// Data formats understood by [`Format::parse`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+  /// Strict JSON
+  Json,
+  /// JSON with comments and trailing commas, as in `tsconfig.json` or
+  /// `bun.lock`
+  Jsonc,
+  /// TOML 1.0
+  Toml,
+  /// The block and flow subset of YAML used by manifests
+  Yaml,
+  /// XML elements, attributes and text, such as `pom.xml`
+  Xml,
+  /// `[section]` headers with `key = value` lines, such as `setup.cfg`
+  Ini,
+  /// `key=value` files such as `.env` or Java properties
+  Properties,
+  /// One entry per line, such as `requirements.txt`
+  Lines,
+}
+
+/// Names accepted by [`Format::from_name`], one per format
+pub const FORMATS: &[&str] = &[
+  "json",
+  "jsonc",
+  "toml",
+  "yaml",
+  "xml",
+  "ini",
+  "properties",
+  "lines",
+];
+
+impl Format {
+  /// Format for a name or common file extension
+  #[must_use]
+  pub fn from_name(name: &str) -> Option<Self> {
+    Some(match name.to_ascii_lowercase().as_str() {
+      "json" => Self::Json,
+      "jsonc" | "json5" => Self::Jsonc,
+      "toml" => Self::Toml,
+      "yaml" | "yml" => Self::Yaml,
+      "xml" => Self::Xml,
+      "ini" | "cfg" => Self::Ini,
+      "properties" | "env" | "dotenv" => Self::Properties,
+      "lines" | "txt" | "requirements" => Self::Lines,
+      _ => return None,
+    })
+  }
+
+  /// Canonical name of the format, as listed in [`FORMATS`]
+  #[must_use]
+  pub const fn name(self) -> &'static str {
+    match self {
+      Self::Json => "json",
+      Self::Jsonc => "jsonc",
+      Self::Toml => "toml",
+      Self::Yaml => "yaml",
+      Self::Xml => "xml",
+      Self::Ini => "ini",
+      Self::Properties => "properties",
+      Self::Lines => "lines",
+    }
+  }
+
+  /// Human readable name used in error messages
+  const fn label(self) -> &'static str {
+    match self {
+      Self::Json => "JSON",
+      Self::Jsonc => "JSONC",
+      Self::Toml => "TOML",
+      Self::Yaml => "YAML",
+      Self::Xml => "XML",
+      Self::Ini => "INI",
+      Self::Properties => "properties file",
+      Self::Lines => "line list",
+    }
+  }
+
+  /// Parse a whole document. Syntax errors fail with a Parsing
+  /// `syntax-error` locating the problem.
+  pub fn parse(self, source: &str) -> Result<DataNode, GudError> {
+    let index = LineIndex::new(source, self);
+    match self {
+      Self::Json => json::parse(&index, false),
+      Self::Jsonc => json::parse(&index, true),
+      Self::Toml => toml::parse(&index),
+      Self::Yaml => yaml::parse(&index),
+      Self::Xml => xml::parse(&index),
+      Self::Ini => ini::parse(&index),
+      Self::Properties => lines::parse_properties(&index),
+      Self::Lines => Ok(lines::parse_lines(&index)),
+    }
+  }
+}
+
+/// Source text with the byte offset of every line, to turn offsets into
+/// [`Location`]s
+pub(crate) struct LineIndex<'a> {
+  pub source: &'a str,
+  format: Format,
+  starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+  pub fn new(source: &'a str, format: Format) -> Self {
+    let starts = std::iter::once(0)
+      .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+      .collect();
+    Self {
+      source,
+      format,
+      starts,
+    }
+  }
+
+  pub fn location(&self, offset: usize) -> Location {
+    let offset = offset.min(self.source.len());
+    let line = self.starts.partition_point(|&start| start <= offset) - 1;
+    let start = self.starts[line];
+    Location {
+      line: line + 1,
+      column: self
+        .source
+        .get(start..offset)
+        .map_or(offset - start, |prefix| prefix.chars().count())
+        + 1,
+      byte_offset: offset,
+    }
+  }
+
+  pub fn span(&self, start: usize, end: usize) -> Span {
+    Span {
+      start: self.location(start),
+      end: self.location(end),
+    }
+  }
+
+  pub fn scalar(&self, value: Scalar, start: usize, end: usize) -> DataNode {
+    DataNode::Scalar {
+      value,
+      span: self.span(start, end),
+    }
+  }
+
+  /// Syntax error at `offset`
+  pub fn error(&self, offset: usize, message: impl AsRef<str>) -> GudError {
+    let location = self.location(offset);
+    GudError::new(
+      ErrorKind::Parsing,
+      "syntax-error",
+      format!(
+        "Invalid {} at line {}, column {}: {}",
+        self.format.label(),
+        location.line,
+        location.column,
+        message.as_ref()
+      ),
+    )
+    .with_context(json!({
+      "format": self.format,
+      "line": location.line,
+      "column": location.column,
+      "byte_offset": location.byte_offset,
+    }))
+  }
+}
+
+/// Deepest nesting the recursive parsers accept
+pub(crate) const MAX_DEPTH: usize = 256;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn locations_count_lines_and_characters() {
+    let index = LineIndex::new("a\né = 1\n", Format::Toml);
+    let location = index.location(5);
+    assert_eq!((location.line, location.column), (2, 3));
+    assert_eq!(index.location(100).byte_offset, 9);
+  }
+
+  #[test]
+  fn every_format_builds_the_same_tree() {
+    let expected = json!({"name": "klep", "deps": {"zlib": "1.2"}});
+    let sources = [
+      (Format::Json, r#"{"name": "klep", "deps": {"zlib": "1.2"}}"#),
+      (Format::Toml, "name = \"klep\"\n[deps]\nzlib = \"1.2\"\n"),
+      (Format::Yaml, "name: klep\ndeps:\n  zlib: \"1.2\"\n"),
+      (
+        Format::Xml,
+        "<r><name>klep</name><deps><zlib>1.2</zlib></deps></r>",
+      ),
+      (Format::Ini, "name = klep\n[deps]\nzlib = 1.2\n"),
+    ];
+    for (format, source) in sources {
+      let mut tree = format.parse(source).unwrap().to_value();
+      if format == Format::Xml {
+        tree = tree["r"].take();
+      }
+      assert_eq!(tree, expected, "{format:?}");
+    }
+  }
+
+  #[test]
+  fn converts_to_generic_nodes() {
+    let tree = Format::Json.parse(r#"{"a": [1, null]}"#).unwrap().to_ast();
+    assert_eq!(tree.node_type, "map");
+    let entry = &tree.children[0];
+    assert_eq!(
+      (entry.node_type.as_str(), entry.value.as_deref()),
+      ("entry", Some("a"))
+    );
+    let list = &entry.children[0];
+    let types: Vec<_> = list.children.iter().map(|n| n.node_type.as_str()).collect();
+    assert_eq!(types, ["integer", "null"]);
+  }
+
+  #[test]
+  fn syntax_errors_are_located() {
+    let error = Format::Json.parse("{\n  \"a\": tru\n}").unwrap_err();
+    assert_eq!(error.code, "syntax-error");
+    assert_eq!(error.context["line"], json!(2));
+    assert_eq!(error.context["format"], json!("json"));
+  }
+}
@@ -0,0 +1,198 @@
+//! INI files such as `setup.cfg`, read the way Python's `configparser` does.
+//!
+//! Keys before the first section go to the top level; every `[section]` is a
+//! map. Values are strings, `=` and `:` both separate keys from values, and
+//! indented lines continue the previous value, so multi-line lists like
+//! `install_requires` keep one item per line. `#` and `;` start a comment at
+//! the start of a line or after whitespace, as with `configparser`'s
+//! `inline_comment_prefixes`.
+
+use gud_common::GudError;
+
+use super::{DataNode, Entry, LineIndex, Scalar, Span};
+
+/// Line being continued: which section it is in and the entry index
+struct Open {
+  section: Option<usize>,
+  entry: usize,
+}
+
+pub(super) fn parse(index: &LineIndex) -> Result<DataNode, GudError> {
+  let source = index.source;
+  let mut root = Vec::new();
+  let mut section: Option<usize> = None;
+  let mut open: Option<Open> = None;
+
+  let mut offset = 0;
+  for raw in source.split_inclusive('\n') {
+    let line_start = offset;
+    offset += raw.len();
+    let line = raw.trim_end_matches(['\n', '\r']);
+    let content = line.trim_start();
+    let indent = line.len() - content.len();
+    let start = line_start + indent;
+
+    if content.is_empty() || content.starts_with(['#', ';']) {
+      continue;
+    }
+
+    if indent > 0 {
+      if let Some(Open { section, entry }) = &open {
+        let entries = entries_mut(&mut root, *section);
+        if let DataNode::Scalar { value, span } = &mut entries[*entry].value {
+          let content = strip_comment(content).trim_end();
+          let text = match value {
+            Scalar::String(text) if !text.is_empty() => format!("{text}\n{content}"),
+            _ => content.to_string(),
+          };
+          *value = Scalar::String(text);
+          span.end = index.location(start + content.len());
+        }
+        continue;
+      }
+    }
+
+    if content.starts_with('[') {
+      let close = content
+        .find(']')
+        .ok_or_else(|| index.error(start, "expected ']'"))?;
+      let name = content[1..close].trim();
+      section = Some(open_section(
+        &mut root,
+        name,
+        index.span(start, start + close + 1),
+      ));
+      open = None;
+      continue;
+    }
+
+    let (key, value) = content
+      .find(['=', ':'])
+      .map_or((content, None), |separator| {
+        (
+          &content[..separator],
+          Some((separator, &content[separator + 1..])),
+        )
+      });
+    let key_text = key.trim_end();
+    let key_span = index.span(start, start + key_text.len());
+    let node = match value {
+      Some((separator, value)) => {
+        let trimmed = strip_comment(value).trim();
+        let value_start = start + separator + 1 + (value.len() - value.trim_start().len());
+        index.scalar(
+          Scalar::String(trimmed.to_string()),
+          value_start,
+          value_start + trimmed.len(),
+        )
+      }
+      None => index.scalar(
+        Scalar::Null,
+        key_span.end.byte_offset,
+        key_span.end.byte_offset,
+      ),
+    };
+
+    let end = node.span().end;
+    if let Some(i) = section {
+      if let DataNode::Map { span, .. } = &mut root[i].value {
+        span.end = end;
+      }
+    }
+    let entries = entries_mut(&mut root, section);
+    entries.push(Entry {
+      key: key_text.to_string(),
+      key_span,
+      value: node,
+    });
+    open = Some(Open {
+      section,
+      entry: entries.len() - 1,
+    });
+  }
+
+  Ok(DataNode::Map {
+    entries: root,
+    span: index.span(0, source.len()),
+  })
+}
+
+/// `text` up to an inline comment
+fn strip_comment(text: &str) -> &str {
+  let bytes = text.as_bytes();
+  let end = (1..bytes.len())
+    .find(|&i| matches!(bytes[i], b'#' | b';') && bytes[i - 1].is_ascii_whitespace())
+    .unwrap_or(bytes.len());
+  &text[..end]
+}
+
+/// Index of section `name` in `root`, adding it if this is its first header
+fn open_section(root: &mut Vec<Entry>, name: &str, span: Span) -> usize {
+  root
+    .iter()
+    .position(|entry| entry.key == name)
+    .unwrap_or_else(|| {
+      root.push(Entry {
+        key: name.to_string(),
+        key_span: span,
+        value: DataNode::Map {
+          entries: Vec::new(),
+          span,
+        },
+      });
+      root.len() - 1
+    })
+}
+
+fn entries_mut(root: &mut Vec<Entry>, section: Option<usize>) -> &mut Vec<Entry> {
+  match section {
+    Some(i) => match &mut root[i].value {
+      DataNode::Map { entries, .. } => entries,
+      _ => unreachable!("sections are maps"),
+    },
+    None => root,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::data::Format;
+  use serde_json::json;
+
+  #[test]
+  fn parses_sections_and_continuations() {
+    let source = "\
+; top
+name = klep
+[metadata]
+version: 1.0
+[options]
+install_requires =
+    requests>=2.0
+    click
+flag
+";
+    assert_eq!(
+      Format::Ini.parse(source).unwrap().to_value(),
+      json!({
+        "name": "klep",
+        "metadata": {"version": "1.0"},
+        "options": {"install_requires": "requests>=2.0\nclick", "flag": null},
+      })
+    );
+  }
+
+  #[test]
+  fn strips_inline_comments() {
+    let source = "a = \"q\" ; c\nb = x#y # z\nc =\n    one ; first\n    two\n";
+    assert_eq!(
+      Format::Ini.parse(source).unwrap().to_value(),
+      json!({"a": "\"q\"", "b": "x#y", "c": "one\ntwo"})
+    );
+  }
+
+  #[test]
+  fn rejects_unclosed_sections() {
+    assert!(Format::Ini.parse("[options\na = 1").is_err());
+  }
+}
@@ -0,0 +1,355 @@
+//! JSON, optionally with `//` and `/* */` comments and trailing commas.
+
+use gud_common::GudError;
+
+use super::{DataNode, Entry, LineIndex, Scalar, MAX_DEPTH};
+
+struct Parser<'a> {
+  index: &'a LineIndex<'a>,
+  bytes: &'a [u8],
+  pos: usize,
+  lenient: bool,
+  depth: usize,
+}
+
+pub(super) fn parse(index: &LineIndex, lenient: bool) -> Result<DataNode, GudError> {
+  let mut parser = Parser {
+    index,
+    bytes: index.source.as_bytes(),
+    pos: 0,
+    lenient,
+    depth: 0,
+  };
+  if parser.bytes.starts_with("\u{feff}".as_bytes()) {
+    parser.pos = 3;
+  }
+  parser.skip_space()?;
+  let node = parser.value()?;
+  parser.skip_space()?;
+  if parser.pos < parser.bytes.len() {
+    return Err(parser.error("expected the end of the document"));
+  }
+  Ok(node)
+}
+
+impl Parser<'_> {
+  fn error(&self, message: &str) -> GudError {
+    self.index.error(self.pos, message)
+  }
+
+  fn peek(&self) -> Option<u8> {
+    self.bytes.get(self.pos).copied()
+  }
+
+  fn expect(&mut self, byte: u8) -> Result<(), GudError> {
+    if self.peek() == Some(byte) {
+      self.pos += 1;
+      Ok(())
+    } else {
+      Err(self.error(&format!("expected '{}'", byte as char)))
+    }
+  }
+
+  fn skip_space(&mut self) -> Result<(), GudError> {
+    loop {
+      match self.peek() {
+        Some(b' ' | b'\t' | b'\n' | b'\r') => self.pos += 1,
+        Some(b'/') if self.lenient => match self.bytes.get(self.pos + 1) {
+          Some(b'/') => {
+            while self.peek().is_some_and(|b| b != b'\n') {
+              self.pos += 1;
+            }
+          }
+          Some(b'*') => {
+            let start = self.pos;
+            self.pos += 2;
+            while !self.bytes[self.pos..].starts_with(b"*/") {
+              if self.pos >= self.bytes.len() {
+                return Err(self.index.error(start, "unterminated comment"));
+              }
+              self.pos += 1;
+            }
+            self.pos += 2;
+          }
+          _ => return Ok(()),
+        },
+        _ => return Ok(()),
+      }
+    }
+  }
+
+  fn value(&mut self) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    match self.peek() {
+      Some(b'{') => self.nested(Self::object),
+      Some(b'[') => self.nested(Self::array),
+      Some(b'"') => {
+        let text = self.string()?;
+        Ok(self.index.scalar(Scalar::String(text), start, self.pos))
+      }
+      Some(b'-' | b'0'..=b'9') => self.number(),
+      Some(b't') => self.literal("true", Scalar::Bool(true)),
+      Some(b'f') => self.literal("false", Scalar::Bool(false)),
+      Some(b'n') => self.literal("null", Scalar::Null),
+      Some(_) => Err(self.error("expected a value")),
+      None => Err(self.error("unexpected end of the document")),
+    }
+  }
+
+  fn nested(
+    &mut self,
+    parse: fn(&mut Self) -> Result<DataNode, GudError>,
+  ) -> Result<DataNode, GudError> {
+    self.depth += 1;
+    if self.depth > MAX_DEPTH {
+      return Err(self.error("nesting is too deep"));
+    }
+    let node = parse(self);
+    self.depth -= 1;
+    node
+  }
+
+  fn literal(&mut self, word: &str, value: Scalar) -> Result<DataNode, GudError> {
+    if self.bytes[self.pos..].starts_with(word.as_bytes()) {
+      let start = self.pos;
+      self.pos += word.len();
+      Ok(self.index.scalar(value, start, self.pos))
+    } else {
+      Err(self.error("expected a value"))
+    }
+  }
+
+  /// Comma separated items up to `close`, calling `item` for each one
+  fn items(
+    &mut self,
+    close: u8,
+    mut item: impl FnMut(&mut Self) -> Result<(), GudError>,
+  ) -> Result<(), GudError> {
+    self.pos += 1;
+    self.skip_space()?;
+    if self.peek() == Some(close) {
+      self.pos += 1;
+      return Ok(());
+    }
+    loop {
+      item(self)?;
+      self.skip_space()?;
+      match self.peek() {
+        Some(b',') => {
+          self.pos += 1;
+          self.skip_space()?;
+          if self.lenient && self.peek() == Some(close) {
+            self.pos += 1;
+            return Ok(());
+          }
+        }
+        Some(b) if b == close => {
+          self.pos += 1;
+          return Ok(());
+        }
+        _ => return Err(self.error(&format!("expected ',' or '{}'", close as char))),
+      }
+    }
+  }
+
+  fn object(&mut self) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    let mut entries = Vec::new();
+    self.items(b'}', |parser| {
+      let key_start = parser.pos;
+      if parser.peek() != Some(b'"') {
+        return Err(parser.error("expected a string key"));
+      }
+      let key = parser.string()?;
+      let key_span = parser.index.span(key_start, parser.pos);
+      parser.skip_space()?;
+      parser.expect(b':')?;
+      parser.skip_space()?;
+      let value = parser.value()?;
+      entries.push(Entry {
+        key,
+        key_span,
+        value,
+      });
+      Ok(())
+    })?;
+    Ok(DataNode::Map {
+      entries,
+      span: self.index.span(start, self.pos),
+    })
+  }
+
+  fn array(&mut self) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    let mut items = Vec::new();
+    self.items(b']', |parser| {
+      items.push(parser.value()?);
+      Ok(())
+    })?;
+    Ok(DataNode::List {
+      items,
+      span: self.index.span(start, self.pos),
+    })
+  }
+
+  fn hex4(&mut self) -> Result<u32, GudError> {
+    let digits = self
+      .bytes
+      .get(self.pos..self.pos + 4)
+      .and_then(|digits| std::str::from_utf8(digits).ok())
+      .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+      .ok_or_else(|| self.error("expected four hex digits"))?;
+    self.pos += 4;
+    Ok(digits)
+  }
+
+  fn string(&mut self) -> Result<String, GudError> {
+    let start = self.pos;
+    self.pos += 1;
+    let mut text = String::new();
+    loop {
+      let run = self.pos;
+      while self
+        .peek()
+        .is_some_and(|b| b != b'"' && b != b'\\' && b >= 0x20)
+      {
+        self.pos += 1;
+      }
+      text.push_str(&self.index.source[run..self.pos]);
+      match self.peek() {
+        Some(b'"') => {
+          self.pos += 1;
+          return Ok(text);
+        }
+        Some(b'\\') => {
+          self.pos += 1;
+          let escape = self
+            .peek()
+            .ok_or_else(|| self.error("unterminated string"))?;
+          self.pos += 1;
+          match escape {
+            b'"' => text.push('"'),
+            b'\\' => text.push('\\'),
+            b'/' => text.push('/'),
+            b'b' => text.push('\u{8}'),
+            b'f' => text.push('\u{c}'),
+            b'n' => text.push('\n'),
+            b'r' => text.push('\r'),
+            b't' => text.push('\t'),
+            b'u' => {
+              let mut code = self.hex4()?;
+              if (0xd800..0xdc00).contains(&code) && self.bytes[self.pos..].starts_with(b"\\u") {
+                self.pos += 2;
+                let low = self.hex4()?;
+                code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+              }
+              text.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+            }
+            _ => {
+              self.pos -= 2;
+              return Err(self.error("invalid escape"));
+            }
+          }
+        }
+        Some(_) => return Err(self.error("control character in string")),
+        None => return Err(self.index.error(start, "unterminated string")),
+      }
+    }
+  }
+
+  fn number(&mut self) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    let digits = |parser: &mut Self| {
+      let from = parser.pos;
+      while parser.peek().is_some_and(|b| b.is_ascii_digit()) {
+        parser.pos += 1;
+      }
+      parser.pos > from
+    };
+
+    if self.peek() == Some(b'-') {
+      self.pos += 1;
+    }
+    if self.peek() == Some(b'0') {
+      self.pos += 1;
+    } else if !digits(self) {
+      return Err(self.error("expected a digit"));
+    }
+    let mut integer = true;
+    if self.peek() == Some(b'.') {
+      integer = false;
+      self.pos += 1;
+      if !digits(self) {
+        return Err(self.error("expected a digit"));
+      }
+    }
+    if matches!(self.peek(), Some(b'e' | b'E')) {
+      integer = false;
+      self.pos += 1;
+      if matches!(self.peek(), Some(b'+' | b'-')) {
+        self.pos += 1;
+      }
+      if !digits(self) {
+        return Err(self.error("expected a digit"));
+      }
+    }
+
+    let text = &self.index.source[start..self.pos];
+    let value = match text.parse::<i64>() {
+      Ok(n) if integer => Scalar::Integer(n),
+      _ => Scalar::Float(
+        text
+          .parse::<f64>()
+          .ok()
+          .filter(|n| n.is_finite())
+          .ok_or_else(|| self.index.error(start, "number is out of range"))?,
+      ),
+    };
+    Ok(self.index.scalar(value, start, self.pos))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::data::Format;
+  use serde_json::json;
+
+  #[test]
+  fn parses_values_with_spans() {
+    let tree = Format::Json
+      .parse("{\"a\": [1, -2.5e1, \"x\\u00e9\\ud83d\\ude00\"], \"b\": {}}")
+      .unwrap();
+    assert_eq!(tree.to_value(), json!({"a": [1, -25.0, "xé😀"], "b": {}}));
+
+    let b = tree.get("b").unwrap().span();
+    assert_eq!((b.start.column, b.end.column), (48, 50));
+  }
+
+  #[test]
+  fn comments_and_trailing_commas_need_jsonc() {
+    let source = "{\n  // deps\n  \"a\": [1, 2,], /* x */\n}";
+    assert!(Format::Json.parse(source).is_err());
+    assert_eq!(
+      Format::Jsonc.parse(source).unwrap().to_value(),
+      json!({"a": [1, 2]})
+    );
+  }
+
+  #[test]
+  fn rejects_malformed_documents() {
+    for source in [
+      "",
+      "[1 2]",
+      "{\"a\" 1}",
+      "01",
+      "\"a",
+      "[1] x",
+      "{'a': 1}",
+      "1e400",
+      "[-1e400]",
+    ] {
+      assert!(Format::Json.parse(source).is_err(), "{source}");
+    }
+    assert!(Format::Json.parse(&"[".repeat(1000)).is_err());
+  }
+}
@@ -0,0 +1,152 @@
+//! Line-oriented files: `key=value` properties and plain line lists.
+
+use gud_common::GudError;
+
+use super::{DataNode, Entry, LineIndex, Scalar};
+
+/// Logical lines: a trailing backslash joins a line with the next one.
+/// Yields the text and the byte range it came from.
+fn logical_lines(source: &str) -> Vec<(String, usize, usize)> {
+  let mut lines = Vec::new();
+  let mut current: Option<(String, usize)> = None;
+  let mut offset = 0;
+  for raw in source.split_inclusive('\n') {
+    let start = offset;
+    offset += raw.len();
+    let line = raw.trim_end_matches(['\n', '\r']);
+    let end = start + line.len();
+    let (text, continued) = line
+      .strip_suffix('\\')
+      .map_or((line, false), |line| (line, true));
+    let (mut joined, first) = current.take().unwrap_or_else(|| (String::new(), start));
+    joined.push_str(if joined.is_empty() {
+      text
+    } else {
+      text.trim_start()
+    });
+    if continued {
+      current = Some((joined, first));
+    } else {
+      lines.push((joined, first, end));
+    }
+  }
+  if let Some((joined, first)) = current {
+    lines.push((joined, first, source.len()));
+  }
+  lines
+}
+
+/// Offset of the first non-whitespace character of `text` at `start`
+fn content_start(text: &str, start: usize) -> usize {
+  start + (text.len() - text.trim_start().len())
+}
+
+/// `.env` and Java properties files: `key=value`, `key: value` or
+/// `export key=value`, with `#` and `!` comments. Matching quotes around a
+/// value are removed.
+pub(super) fn parse_properties(index: &LineIndex) -> Result<DataNode, GudError> {
+  let mut entries = Vec::new();
+  for (line, start, end) in logical_lines(index.source) {
+    let content = line.trim();
+    if content.is_empty() || content.starts_with(['#', '!']) {
+      continue;
+    }
+    let key_start = content_start(&line, start);
+    let content = content.strip_prefix("export ").unwrap_or(content);
+    let key_start = key_start + (line.trim().len() - content.len());
+    let Some(separator) = content.find(['=', ':']) else {
+      return Err(index.error(key_start, "expected '=' after a key"));
+    };
+    let key = content[..separator].trim_end();
+    if key.is_empty() {
+      return Err(index.error(key_start, "expected a key before the separator"));
+    }
+    let raw = content[separator + 1..].trim();
+    let value = [('"', '"'), ('\'', '\'')]
+      .iter()
+      .find_map(|(open, close)| raw.strip_prefix(*open)?.strip_suffix(*close))
+      .unwrap_or(raw);
+    let value_start = end - (line.len() - line.trim_end().len()) - raw.len();
+    entries.push(Entry {
+      key: key.to_string(),
+      key_span: index.span(key_start, key_start + key.len()),
+      value: index.scalar(
+        Scalar::String(value.to_string()),
+        value_start.max(key_start),
+        (value_start + raw.len()).min(end),
+      ),
+    });
+  }
+  Ok(DataNode::Map {
+    entries,
+    span: index.span(0, index.source.len()),
+  })
+}
+
+/// One string per line, such as `requirements.txt`. Blank lines and `#`
+/// comments, including comments after whitespace at the end of a line, are
+/// dropped.
+pub(super) fn parse_lines(index: &LineIndex) -> DataNode {
+  let mut items = Vec::new();
+  for (line, start, _) in logical_lines(index.source) {
+    let text = line
+      .match_indices('#')
+      .find(|(i, _)| *i == 0 || line[..*i].ends_with([' ', '\t']))
+      .map_or(line.as_str(), |(i, _)| &line[..i])
+      .trim();
+    if text.is_empty() {
+      continue;
+    }
+    let text_start = content_start(&line, start);
+    items.push(index.scalar(
+      Scalar::String(text.to_string()),
+      text_start,
+      text_start + text.len(),
+    ));
+  }
+  DataNode::List {
+    items,
+    span: index.span(0, index.source.len()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::data::{DataNode, Format};
+  use serde_json::json;
+
+  #[test]
+  fn parses_properties() {
+    let source = "# env\nexport API_URL=\"https://x\"\nname: klep\npath = a\\\n  b\n";
+    assert_eq!(
+      Format::Properties.parse(source).unwrap().to_value(),
+      json!({"API_URL": "https://x", "name": "klep", "path": "ab"})
+    );
+    for source in ["novalue\n", "=1\n", "a=1\n  : 2\n"] {
+      assert!(Format::Properties.parse(source).is_err(), "{source:?}");
+    }
+  }
+
+  #[test]
+  fn parses_line_lists() {
+    let source = "# deps\nrequests>=2.0  # http\n\n-r dev.txt\nurl#egg=pkg\nnumpy==1.26 \\\n  --hash=sha256:abc\n";
+    let tree = Format::Lines.parse(source).unwrap();
+    assert_eq!(
+      tree.to_value(),
+      json!([
+        "requests>=2.0",
+        "-r dev.txt",
+        "url#egg=pkg",
+        "numpy==1.26 --hash=sha256:abc"
+      ])
+    );
+    let DataNode::List { items, .. } = &tree else {
+      panic!("expected a list");
+    };
+    let span = items[0].span();
+    assert_eq!(
+      (span.start.line, span.start.column, span.end.column),
+      (2, 1, 14)
+    );
+  }
+}
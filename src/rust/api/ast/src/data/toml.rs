@@ -0,0 +1,737 @@
+//! TOML 1.0. Dates and times are kept as strings.
+
+use gud_common::GudError;
+use std::collections::HashSet;
+
+use super::{DataNode, Entry, LineIndex, Scalar, Span, MAX_DEPTH};
+use crate::to_tree::Location;
+
+/// One segment of a dotted key
+struct Key {
+  name: String,
+  start: usize,
+  end: usize,
+}
+
+struct Parser<'a> {
+  index: &'a LineIndex<'a>,
+  bytes: &'a [u8],
+  pos: usize,
+  depth: usize,
+}
+
+pub(super) fn parse(index: &LineIndex) -> Result<DataNode, GudError> {
+  let mut parser = Parser {
+    index,
+    bytes: index.source.as_bytes(),
+    pos: 0,
+    depth: 0,
+  };
+  let mut root = DataNode::Map {
+    entries: Vec::new(),
+    span: index.span(0, index.source.len()),
+  };
+  // Keys of the table that key/value pairs currently go to
+  let mut table: Vec<Key> = Vec::new();
+  let mut defined = HashSet::new();
+
+  loop {
+    parser.skip_blank_lines();
+    let Some(byte) = parser.peek() else {
+      break;
+    };
+    let start = parser.pos;
+    if byte == b'[' {
+      let array = parser.bytes.get(parser.pos + 1) == Some(&b'[');
+      parser.pos += if array { 2 } else { 1 };
+      parser.skip_space();
+      parser.depth = 0;
+      let keys = parser.key()?;
+      parser.skip_space();
+      parser.expect(if array { "]]" } else { "]" })?;
+      parser.end_of_line()?;
+      let end = parser.pos;
+      let names: Vec<_> = keys.iter().map(|key| key.name.clone()).collect();
+      if array {
+        // Sub-tables of the previous element do not clash with the new one's
+        defined.retain(|table: &Vec<String>| !table.starts_with(&names));
+      } else if !defined.insert(names) {
+        return Err(index.error(start, "table is defined twice"));
+      }
+      open_table(&mut root, &keys, array, index.span(start, end))
+        .map_err(|message| index.error(start, message))?;
+      // Pairs below the header are nested below each of its segments
+      parser.depth = keys.len();
+      table = keys;
+    } else {
+      let keys = parser.key()?;
+      parser.skip_space();
+      parser.expect("=")?;
+      parser.skip_space();
+      let value = parser.value_below(&keys)?;
+      parser.end_of_line()?;
+      let target = navigate(&mut root, &table, None).map_err(|m| index.error(start, m))?;
+      insert(index, target, keys, value).map_err(|m| index.error(start, m))?;
+    }
+  }
+  Ok(root)
+}
+
+/// Grow a table's span to cover a value ending at `end`
+const fn extend(node: &mut DataNode, end: Location) {
+  let span = node.span_mut();
+  if end.byte_offset > span.end.byte_offset {
+    span.end = end;
+  }
+}
+
+const fn entries_of(node: &mut DataNode) -> Option<&mut Vec<Entry>> {
+  match node {
+    DataNode::Map { entries, .. } => Some(entries),
+    _ => None,
+  }
+}
+
+/// Table reached by following `keys` from `node`, descending into the last
+/// table of arrays of tables. Missing tables are created with the given span,
+/// if any.
+fn navigate<'t>(
+  node: &'t mut DataNode,
+  keys: &[Key],
+  create: Option<Span>,
+) -> Result<&'t mut DataNode, String> {
+  let Some((key, rest)) = keys.split_first() else {
+    return Ok(node);
+  };
+  let entries = entries_of(node).ok_or_else(|| format!("'{}' is not a table", key.name))?;
+  let position = match entries.iter().position(|entry| entry.key == key.name) {
+    Some(position) => position,
+    None if create.is_some() => {
+      let span = create.unwrap_or_default();
+      entries.push(Entry {
+        key: key.name.clone(),
+        key_span: span,
+        value: DataNode::Map {
+          entries: Vec::new(),
+          span,
+        },
+      });
+      entries.len() - 1
+    }
+    None => return Err(format!("table '{}' does not exist", key.name)),
+  };
+  let mut child = &mut entries[position].value;
+  if let DataNode::List { items, .. } = child {
+    child = items
+      .last_mut()
+      .ok_or_else(|| format!("'{}' is not a table", key.name))?;
+  }
+  if !matches!(child, DataNode::Map { .. }) {
+    return Err(format!("'{}' is not a table", key.name));
+  }
+  navigate(child, rest, create)
+}
+
+fn open_table(root: &mut DataNode, keys: &[Key], array: bool, span: Span) -> Result<(), String> {
+  let (last, parents) = keys.split_last().ok_or("empty table name")?;
+  let parent = navigate(root, parents, Some(span))?;
+  let entries = entries_of(parent).ok_or_else(|| format!("'{}' is not a table", last.name))?;
+  let table = DataNode::Map {
+    entries: Vec::new(),
+    span,
+  };
+  match entries.iter_mut().find(|entry| entry.key == last.name) {
+    None => entries.push(Entry {
+      key: last.name.clone(),
+      key_span: span,
+      value: if array {
+        DataNode::List {
+          items: vec![table],
+          span,
+        }
+      } else {
+        table
+      },
+    }),
+    Some(Entry {
+      value: DataNode::List {
+        items,
+        span: list_span,
+      },
+      ..
+    }) if array => {
+      items.push(table);
+      list_span.end = span.end;
+    }
+    // A table created implicitly by a dotted header can be defined later
+    Some(Entry {
+      value: DataNode::Map {
+        entries,
+        span: existing,
+      },
+      key_span,
+      ..
+    }) if !array
+      && entries
+        .iter()
+        .all(|entry| matches!(entry.value, DataNode::Map { .. })) =>
+    {
+      *existing = span;
+      *key_span = span;
+    }
+    Some(_) => return Err(format!("'{}' is defined twice", last.name)),
+  }
+  Ok(())
+}
+
+/// Insert a dotted key into a table, creating intermediate tables
+fn insert(
+  index: &LineIndex,
+  table: &mut DataNode,
+  keys: Vec<Key>,
+  value: DataNode,
+) -> Result<(), String> {
+  let end = value.span().end;
+  extend(table, end);
+  let mut keys = keys.into_iter().peekable();
+  let mut node = table;
+  while let Some(key) = keys.next() {
+    let entries = entries_of(node).ok_or_else(|| format!("'{}' is not a table", key.name))?;
+    let existing = entries.iter().position(|entry| entry.key == key.name);
+    if keys.peek().is_none() {
+      if existing.is_some() {
+        return Err(format!("'{}' is defined twice", key.name));
+      }
+      entries.push(Entry {
+        key: key.name,
+        key_span: index.span(key.start, key.end),
+        value,
+      });
+      return Ok(());
+    }
+    let position = existing.unwrap_or_else(|| {
+      entries.push(Entry {
+        key: key.name.clone(),
+        key_span: index.span(key.start, key.end),
+        value: DataNode::Map {
+          entries: Vec::new(),
+          span: index.span(key.start, key.end),
+        },
+      });
+      entries.len() - 1
+    });
+    node = &mut entries[position].value;
+    if !matches!(node, DataNode::Map { .. }) {
+      return Err(format!("'{}' is not a table", key.name));
+    }
+    extend(node, end);
+  }
+  Ok(())
+}
+
+impl Parser<'_> {
+  fn peek(&self) -> Option<u8> {
+    self.bytes.get(self.pos).copied()
+  }
+
+  fn rest(&self) -> &[u8] {
+    &self.bytes[self.pos..]
+  }
+
+  fn error(&self, message: &str) -> GudError {
+    self.index.error(self.pos, message)
+  }
+
+  fn expect(&mut self, token: &str) -> Result<(), GudError> {
+    if self.rest().starts_with(token.as_bytes()) {
+      self.pos += token.len();
+      Ok(())
+    } else {
+      Err(self.error(&format!("expected '{token}'")))
+    }
+  }
+
+  fn skip_space(&mut self) {
+    while matches!(self.peek(), Some(b' ' | b'\t')) {
+      self.pos += 1;
+    }
+  }
+
+  fn skip_comment(&mut self) {
+    if self.peek() == Some(b'#') {
+      while self.peek().is_some_and(|b| b != b'\n') {
+        self.pos += 1;
+      }
+    }
+  }
+
+  fn skip_blank_lines(&mut self) {
+    loop {
+      self.skip_space();
+      self.skip_comment();
+      match self.peek() {
+        Some(b'\n') => self.pos += 1,
+        Some(b'\r') if self.bytes.get(self.pos + 1) == Some(&b'\n') => self.pos += 2,
+        _ => return,
+      }
+    }
+  }
+
+  /// Whitespace and newlines, including comments, inside arrays
+  fn skip_array_space(&mut self) {
+    loop {
+      self.skip_space();
+      self.skip_comment();
+      match self.peek() {
+        Some(b'\n' | b'\r') => self.pos += 1,
+        _ => return,
+      }
+    }
+  }
+
+  fn end_of_line(&mut self) -> Result<(), GudError> {
+    self.skip_space();
+    self.skip_comment();
+    match self.peek() {
+      None | Some(b'\n') => Ok(()),
+      Some(b'\r') if self.bytes.get(self.pos + 1) == Some(&b'\n') => Ok(()),
+      Some(_) => Err(self.error("expected the end of the line")),
+    }
+  }
+
+  fn key(&mut self) -> Result<Vec<Key>, GudError> {
+    let mut keys = Vec::new();
+    loop {
+      let start = self.pos;
+      let name = match self.peek() {
+        Some(b'"') => self.basic_string()?,
+        Some(b'\'') => self.literal_string()?,
+        _ => {
+          while self
+            .peek()
+            .is_some_and(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+          {
+            self.pos += 1;
+          }
+          if self.pos == start {
+            return Err(self.error("expected a key"));
+          }
+          self.index.source[start..self.pos].to_string()
+        }
+      };
+      keys.push(Key {
+        name,
+        start,
+        end: self.pos,
+      });
+      // Each segment is a table of its own
+      if self.depth + keys.len() > MAX_DEPTH {
+        return Err(self.index.error(start, "nesting is too deep"));
+      }
+      self.skip_space();
+      if self.peek() != Some(b'.') {
+        return Ok(keys);
+      }
+      self.pos += 1;
+      self.skip_space();
+    }
+  }
+
+  fn value(&mut self) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    let rest = self.rest();
+    let value = if rest.starts_with(b"\"\"\"") {
+      Scalar::String(self.multiline_string(b'"')?)
+    } else if rest.starts_with(b"'''") {
+      Scalar::String(self.multiline_string(b'\'')?)
+    } else {
+      match self.peek() {
+        Some(b'"') => Scalar::String(self.basic_string()?),
+        Some(b'\'') => Scalar::String(self.literal_string()?),
+        Some(b'[') => return self.nested(Self::array),
+        Some(b'{') => return self.nested(Self::inline_table),
+        Some(_) => self.bare_value()?,
+        None => return Err(self.error("expected a value")),
+      }
+    };
+    Ok(self.index.scalar(value, start, self.pos))
+  }
+
+  /// Value of the pair with the dotted key `keys`
+  fn value_below(&mut self, keys: &[Key]) -> Result<DataNode, GudError> {
+    self.depth += keys.len();
+    let value = self.value();
+    self.depth -= keys.len();
+    value
+  }
+
+  fn nested(
+    &mut self,
+    parse: fn(&mut Self) -> Result<DataNode, GudError>,
+  ) -> Result<DataNode, GudError> {
+    self.depth += 1;
+    if self.depth > MAX_DEPTH {
+      return Err(self.error("nesting is too deep"));
+    }
+    let node = parse(self);
+    self.depth -= 1;
+    node
+  }
+
+  fn array(&mut self) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    self.pos += 1;
+    let mut items = Vec::new();
+    loop {
+      self.skip_array_space();
+      if self.peek() == Some(b']') {
+        break;
+      }
+      items.push(self.value()?);
+      self.skip_array_space();
+      match self.peek() {
+        Some(b',') => self.pos += 1,
+        Some(b']') => break,
+        _ => return Err(self.error("expected ',' or ']'")),
+      }
+    }
+    self.pos += 1;
+    Ok(DataNode::List {
+      items,
+      span: self.index.span(start, self.pos),
+    })
+  }
+
+  fn inline_table(&mut self) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    self.pos += 1;
+    let mut table = DataNode::Map {
+      entries: Vec::new(),
+      span: Span::default(),
+    };
+    self.skip_space();
+    if self.peek() == Some(b'}') {
+      self.pos += 1;
+    } else {
+      loop {
+        self.skip_space();
+        let key_start = self.pos;
+        let keys = self.key()?;
+        self.expect("=")?;
+        self.skip_space();
+        let value = self.value_below(&keys)?;
+        insert(self.index, &mut table, keys, value).map_err(|m| self.index.error(key_start, m))?;
+        self.skip_space();
+        match self.peek() {
+          Some(b',') => self.pos += 1,
+          Some(b'}') => {
+            self.pos += 1;
+            break;
+          }
+          _ => return Err(self.error("expected ',' or '}'")),
+        }
+      }
+    }
+    *table.span_mut() = self.index.span(start, self.pos);
+    Ok(table)
+  }
+
+  fn escape(&mut self, text: &mut String) -> Result<(), GudError> {
+    let escape = self
+      .peek()
+      .ok_or_else(|| self.error("unterminated string"))?;
+    self.pos += 1;
+    let hex = |parser: &mut Self, len: usize| -> Result<char, GudError> {
+      let code = parser
+        .bytes
+        .get(parser.pos..parser.pos + len)
+        .and_then(|digits| std::str::from_utf8(digits).ok())
+        .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+        .and_then(char::from_u32)
+        .ok_or_else(|| parser.error("invalid unicode escape"))?;
+      parser.pos += len;
+      Ok(code)
+    };
+    let c = match escape {
+      b'b' => '\u{8}',
+      b't' => '\t',
+      b'n' => '\n',
+      b'f' => '\u{c}',
+      b'r' => '\r',
+      b'e' => '\u{1b}',
+      b'"' => '"',
+      b'\\' => '\\',
+      b'u' => hex(self, 4)?,
+      b'U' => hex(self, 8)?,
+      _ => {
+        self.pos -= 1;
+        return Err(self.error("invalid escape"));
+      }
+    };
+    text.push(c);
+    Ok(())
+  }
+
+  fn basic_string(&mut self) -> Result<String, GudError> {
+    let start = self.pos;
+    self.pos += 1;
+    let mut text = String::new();
+    loop {
+      let run = self.pos;
+      while self
+        .peek()
+        .is_some_and(|b| b != b'"' && b != b'\\' && b != b'\n')
+      {
+        self.pos += 1;
+      }
+      text.push_str(&self.index.source[run..self.pos]);
+      match self.peek() {
+        Some(b'"') => {
+          self.pos += 1;
+          return Ok(text);
+        }
+        Some(b'\\') => {
+          self.pos += 1;
+          self.escape(&mut text)?;
+        }
+        _ => return Err(self.index.error(start, "unterminated string")),
+      }
+    }
+  }
+
+  fn literal_string(&mut self) -> Result<String, GudError> {
+    let start = self.pos;
+    self.pos += 1;
+    while self.peek().is_some_and(|b| b != b'\'' && b != b'\n') {
+      self.pos += 1;
+    }
+    if self.peek() != Some(b'\'') {
+      return Err(self.index.error(start, "unterminated string"));
+    }
+    self.pos += 1;
+    Ok(self.index.source[start + 1..self.pos - 1].to_string())
+  }
+
+  fn multiline_string(&mut self, quote: u8) -> Result<String, GudError> {
+    let start = self.pos;
+    let delimiter = [quote; 3];
+    self.pos += 3;
+    // A newline right after the opening delimiter is trimmed
+    if self.rest().starts_with(b"\r\n") {
+      self.pos += 2;
+    } else if self.peek() == Some(b'\n') {
+      self.pos += 1;
+    }
+    let mut text = String::new();
+    loop {
+      if self.rest().starts_with(&delimiter) {
+        // Up to two quotes may directly precede the closing delimiter
+        let mut extra = 0;
+        while extra < 2 && self.bytes.get(self.pos + 3 + extra) == Some(&quote) {
+          extra += 1;
+        }
+        text.push_str(&self.index.source[self.pos..self.pos + extra]);
+        self.pos += 3 + extra;
+        return Ok(text);
+      }
+      match self.peek() {
+        None => return Err(self.index.error(start, "unterminated string")),
+        Some(b'\\') if quote == b'"' => {
+          self.pos += 1;
+          let after = self.pos;
+          while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+          }
+          if matches!(self.peek(), Some(b'\n' | b'\r')) {
+            // A line ending backslash trims all following whitespace
+            while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+              self.pos += 1;
+            }
+          } else {
+            self.pos = after;
+            self.escape(&mut text)?;
+          }
+        }
+        Some(_) => {
+          let c = self.index.source[self.pos..]
+            .chars()
+            .next()
+            .unwrap_or_default();
+          text.push(c);
+          self.pos += c.len_utf8();
+        }
+      }
+    }
+  }
+
+  /// Booleans, numbers, dates and times
+  fn bare_value(&mut self) -> Result<Scalar, GudError> {
+    let start = self.pos;
+    while self
+      .peek()
+      .is_some_and(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'+' | b'-' | b'.' | b':'))
+    {
+      self.pos += 1;
+    }
+    // The space between a date and a time
+    let date = &self.bytes[start..self.pos];
+    if date.len() == 10
+      && date[4] == b'-'
+      && self.peek() == Some(b' ')
+      && self.bytes.get(self.pos + 1).is_some_and(u8::is_ascii_digit)
+    {
+      self.pos += 1;
+      while self
+        .peek()
+        .is_some_and(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.' | b':'))
+      {
+        self.pos += 1;
+      }
+    }
+
+    let text = &self.index.source[start..self.pos];
+    let invalid = || self.index.error(start, format!("invalid value '{text}'"));
+    let digits = text.replace('_', "");
+    let unsigned = digits.trim_start_matches(['+', '-']);
+    let negative = digits.starts_with('-');
+    Ok(match text {
+      "" => return Err(self.error("expected a value")),
+      "true" => Scalar::Bool(true),
+      "false" => Scalar::Bool(false),
+      "inf" | "+inf" => Scalar::Float(f64::INFINITY),
+      "-inf" => Scalar::Float(f64::NEG_INFINITY),
+      "nan" | "+nan" | "-nan" => Scalar::Float(f64::NAN),
+      _ if is_datetime(text) => Scalar::String(text.to_string()),
+      _ if text.contains("__") || text.starts_with('_') || text.ends_with('_') => {
+        return Err(invalid())
+      }
+      _ if ["0x", "0o", "0b"].iter().any(|p| digits.starts_with(p)) => {
+        let radix = match &digits[..2] {
+          "0x" => 16,
+          "0o" => 8,
+          _ => 2,
+        };
+        Scalar::Integer(i64::from_str_radix(&digits[2..], radix).map_err(|_| invalid())?)
+      }
+      _ if !unsigned.bytes().all(|b| b.is_ascii_digit()) => {
+        let fraction = unsigned.split(['e', 'E']).next().unwrap_or_default();
+        if fraction.starts_with('.') || fraction.ends_with('.') || unsigned.starts_with(['e', 'E'])
+        {
+          return Err(invalid());
+        }
+        Scalar::Float(digits.parse().map_err(|_| invalid())?)
+      }
+      _ if unsigned.len() > 1 && unsigned.starts_with('0') => return Err(invalid()),
+      _ => {
+        let n: i64 = unsigned.parse().map_err(|_| invalid())?;
+        Scalar::Integer(if negative { -n } else { n })
+      }
+    })
+  }
+}
+
+/// Whether a bare value is an offset or local date-time, date or time
+fn is_datetime(text: &str) -> bool {
+  let bytes = text.as_bytes();
+  let date = bytes.len() >= 10 && bytes[4] == b'-' && bytes[7] == b'-';
+  let time = bytes.len() >= 8 && bytes[2] == b':' && bytes[5] == b':';
+  (date || time) && bytes[..4].iter().all(|b| b.is_ascii_digit() || *b == b':')
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::data::{DataNode, Format};
+  use serde_json::json;
+
+  fn parse(source: &str) -> serde_json::Value {
+    Format::Toml.parse(source).unwrap().to_value()
+  }
+
+  #[test]
+  fn builds_tables_and_arrays_of_tables() {
+    let source = r#"
+[package]
+name = "klep"   # comment
+authors = ["a", 'b',]
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+tokio.version = "1"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[[bin]]
+name = "one"
+[bin.meta]
+x = 1
+[[bin]]
+name = "two"
+[bin.meta]
+x = 2
+"#;
+    assert_eq!(
+      parse(source),
+      json!({
+        "package": {"name": "klep", "authors": ["a", "b"]},
+        "dependencies": {
+          "serde": {"version": "1.0", "features": ["derive"]},
+          "tokio": {"version": "1"},
+        },
+        "target": {"cfg(unix)": {"dependencies": {"libc": "0.2"}}},
+        "bin": [{"name": "one", "meta": {"x": 1}}, {"name": "two", "meta": {"x": 2}}],
+      })
+    );
+  }
+
+  #[test]
+  fn parses_scalars() {
+    let source = "a = 1_000\nb = 0x1F\nc = 6.5e-1\nd = true\n\
+                  e = 1979-05-27 07:32:00Z\nf = \"\"\"\n  x \\\n  y\"\"\"\ng = '''c:\\n'''\nh = -inf\n";
+    assert_eq!(
+      parse(source),
+      json!({"a": 1000, "b": 31, "c": 0.65, "d": true,
+        "e": "1979-05-27 07:32:00Z", "f": "  x y", "g": "c:\\n", "h": null})
+    );
+  }
+
+  #[test]
+  fn tables_span_their_header_and_entries() {
+    let tree = Format::Toml.parse("[a]\nx = 1\ny = 2\n\n[b]\n").unwrap();
+    let span = tree.get("a").unwrap().span();
+    assert_eq!((span.start.line, span.end.line, span.end.column), (1, 3, 6));
+    assert!(matches!(tree.get("b"), Some(DataNode::Map { entries, .. }) if entries.is_empty()));
+  }
+
+  #[test]
+  fn rejects_invalid_documents() {
+    for source in [
+      "a = ",
+      "a = 1\na = 2",
+      "[a]\n[a]",
+      "[[a]]\n[a.b]\n[a.b]",
+      "a = 01",
+      "a = \"x",
+      "a = 1 b",
+      "a = 1__0",
+    ] {
+      assert!(Format::Toml.parse(source).is_err(), "{source:?}");
+    }
+  }
+
+  #[test]
+  fn rejects_keys_nested_too_deeply() {
+    let dotted = vec!["a"; 500].join(".");
+    let half = vec!["a"; 200].join(".");
+    for source in [
+      format!("[{dotted}]\nx = 1\n"),
+      format!("{dotted} = 1\n"),
+      format!("x = {{ {dotted} = 1 }}\n"),
+      format!("[{half}]\n{half} = 1\n"),
+    ] {
+      let error = Format::Toml.parse(&source).unwrap_err();
+      assert!(error.message.contains("too deep"), "{}", error.message);
+    }
+    assert!(Format::Toml.parse(&format!("[{half}]\nx = 1\n")).is_ok());
+  }
+}
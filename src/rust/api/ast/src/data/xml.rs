@@ -0,0 +1,416 @@
+//! XML documents.
+//!
+//! The document becomes a map holding the root element. An element with
+//! neither attributes nor child elements is a string scalar of its text, or
+//! null when empty. Any other element is a map of `@attribute` entries and
+//! child elements, with repeated children grouped into a list and
+//! non-whitespace text under `#text`. Comments, processing instructions and
+//! the doctype are skipped.
+
+use gud_common::GudError;
+
+use super::{DataNode, Entry, LineIndex, Scalar, Span, MAX_DEPTH};
+
+struct Parser<'a> {
+  index: &'a LineIndex<'a>,
+  bytes: &'a [u8],
+  pos: usize,
+  depth: usize,
+}
+
+pub(super) fn parse(index: &LineIndex) -> Result<DataNode, GudError> {
+  let mut parser = Parser {
+    index,
+    bytes: index.source.as_bytes(),
+    pos: 0,
+    depth: 0,
+  };
+  if parser.bytes.starts_with("\u{feff}".as_bytes()) {
+    parser.pos = 3;
+  }
+  parser.skip_misc()?;
+  if parser.peek() != Some(b'<') {
+    return Err(parser.error("expected the root element"));
+  }
+  let start = parser.pos;
+  let (name, key_span, root) = parser.element()?;
+  parser.skip_misc()?;
+  if parser.pos < parser.bytes.len() {
+    return Err(parser.error("expected the end of the document"));
+  }
+  let span = root.span();
+  Ok(DataNode::Map {
+    entries: vec![Entry {
+      key: name,
+      key_span,
+      value: root,
+    }],
+    span: index.span(start, span.end.byte_offset),
+  })
+}
+
+const fn is_name_byte(byte: u8) -> bool {
+  byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'-' | b'.' | b':') || byte >= 0x80
+}
+
+/// Attributes and child elements, with repeated children grouped into lists
+#[derive(Default)]
+struct Children {
+  entries: Vec<Entry>,
+  grouped: Vec<bool>,
+}
+
+impl Children {
+  fn push(&mut self, key: String, key_span: Span, value: DataNode) {
+    let Some(i) = self.entries.iter().position(|entry| entry.key == key) else {
+      self.entries.push(Entry {
+        key,
+        key_span,
+        value,
+      });
+      self.grouped.push(false);
+      return;
+    };
+    let entry = &mut self.entries[i];
+    if !self.grouped[i] {
+      let first = std::mem::replace(
+        &mut entry.value,
+        DataNode::List {
+          items: Vec::new(),
+          span: Span::default(),
+        },
+      );
+      entry.value = DataNode::List {
+        span: first.span(),
+        items: vec![first],
+      };
+      self.grouped[i] = true;
+    }
+    if let DataNode::List { items, span } = &mut entry.value {
+      span.end = value.span().end;
+      items.push(value);
+    }
+  }
+}
+
+impl Parser<'_> {
+  fn peek(&self) -> Option<u8> {
+    self.bytes.get(self.pos).copied()
+  }
+
+  fn rest(&self) -> &[u8] {
+    &self.bytes[self.pos..]
+  }
+
+  fn error(&self, message: &str) -> GudError {
+    self.index.error(self.pos, message)
+  }
+
+  fn skip_space(&mut self) {
+    while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+      self.pos += 1;
+    }
+  }
+
+  /// Skip past `terminator`, failing with `what` if it never comes
+  fn skip_past(&mut self, terminator: &[u8], what: &str) -> Result<(), GudError> {
+    let start = self.pos;
+    match self
+      .rest()
+      .windows(terminator.len())
+      .position(|window| window == terminator)
+    {
+      Some(offset) => {
+        self.pos += offset + terminator.len();
+        Ok(())
+      }
+      None => Err(self.index.error(start, format!("unterminated {what}"))),
+    }
+  }
+
+  /// Skip a comment, processing instruction or doctype; returns whether
+  /// anything was skipped
+  fn skip_markup(&mut self) -> Result<bool, GudError> {
+    if self.rest().starts_with(b"<!--") {
+      self.skip_past(b"-->", "comment")?;
+    } else if self.rest().starts_with(b"<?") {
+      self.skip_past(b"?>", "processing instruction")?;
+    } else if self.rest().starts_with(b"<!DOCTYPE") {
+      let start = self.pos;
+      let mut depth = 0;
+      loop {
+        match self.peek() {
+          Some(b'[') => depth += 1,
+          Some(b']') => depth -= 1,
+          Some(b'>') if depth == 0 => break,
+          None => return Err(self.index.error(start, "unterminated doctype")),
+          _ => {}
+        }
+        self.pos += 1;
+      }
+      self.pos += 1;
+    } else {
+      return Ok(false);
+    }
+    Ok(true)
+  }
+
+  fn skip_misc(&mut self) -> Result<(), GudError> {
+    loop {
+      self.skip_space();
+      if !self.skip_markup()? {
+        return Ok(());
+      }
+    }
+  }
+
+  fn name(&mut self) -> Result<(String, usize), GudError> {
+    let start = self.pos;
+    while self.peek().is_some_and(is_name_byte) {
+      self.pos += 1;
+    }
+    if self.pos == start {
+      return Err(self.error("expected a name"));
+    }
+    Ok((self.index.source[start..self.pos].to_string(), start))
+  }
+
+  /// Decode character and entity references in `raw`, which starts at `at`
+  fn decode(&self, raw: &str, at: usize) -> Result<String, GudError> {
+    let mut text = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(amp) = rest.find('&') {
+      text.push_str(&rest[..amp]);
+      let offset = at + (raw.len() - rest.len()) + amp;
+      let semicolon = rest[amp..]
+        .find(';')
+        .ok_or_else(|| self.index.error(offset, "unterminated reference"))?;
+      let reference = &rest[amp + 1..amp + semicolon];
+      let c = match reference {
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "amp" => Some('&'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => reference
+          .strip_prefix("#x")
+          .map(|hex| u32::from_str_radix(hex, 16))
+          .or_else(|| reference.strip_prefix('#').map(str::parse))
+          .and_then(Result::ok)
+          .and_then(char::from_u32),
+      };
+      text.push(c.ok_or_else(|| {
+        self
+          .index
+          .error(offset, format!("unknown reference '&{reference};'"))
+      })?);
+      rest = &rest[amp + semicolon + 1..];
+    }
+    text.push_str(rest);
+    Ok(text)
+  }
+
+  /// `name="value"` inside a start tag
+  fn attribute(&mut self, children: &mut Children) -> Result<(), GudError> {
+    let (attribute, attribute_start) = self.name()?;
+    let attribute_span = self.index.span(attribute_start, self.pos);
+    self.skip_space();
+    if self.peek() != Some(b'=') {
+      return Err(self.error("expected '='"));
+    }
+    self.pos += 1;
+    self.skip_space();
+    let quote = self
+      .peek()
+      .filter(|b| matches!(b, b'"' | b'\''))
+      .ok_or_else(|| self.error("expected a quoted attribute value"))?;
+    let value_start = self.pos;
+    self.pos += 1;
+    self.skip_past(&[quote], "attribute value")?;
+    let raw = &self.index.source[value_start + 1..self.pos - 1];
+    let value = self.decode(raw, value_start + 1)?;
+    children.push(
+      format!("@{attribute}"),
+      attribute_span,
+      self
+        .index
+        .scalar(Scalar::String(value), value_start, self.pos),
+    );
+    Ok(())
+  }
+
+  /// Content of element `name` up to and including its end tag; returns its
+  /// text and whether it had child elements
+  fn content(
+    &mut self,
+    name: &str,
+    start: usize,
+    children: &mut Children,
+  ) -> Result<(String, bool), GudError> {
+    let mut text = String::new();
+    let mut elements = false;
+    loop {
+      let run = self.pos;
+      while self.peek().is_some_and(|b| b != b'<') {
+        self.pos += 1;
+      }
+      text.push_str(&self.decode(&self.index.source[run..self.pos], run)?);
+      if self.peek().is_none() {
+        return Err(
+          self
+            .index
+            .error(start, format!("element '{name}' is not closed")),
+        );
+      }
+      if self.rest().starts_with(b"</") {
+        self.pos += 2;
+        let (closing, closing_start) = self.name()?;
+        if closing != name {
+          return Err(self.index.error(
+            closing_start,
+            format!("expected '</{name}>', found '</{closing}>'"),
+          ));
+        }
+        self.skip_space();
+        if self.peek() != Some(b'>') {
+          return Err(self.error("expected '>'"));
+        }
+        self.pos += 1;
+        return Ok((text, elements));
+      }
+      if self.rest().starts_with(b"<![CDATA[") {
+        let data_start = self.pos + 9;
+        self.skip_past(b"]]>", "CDATA section")?;
+        text.push_str(&self.index.source[data_start..self.pos - 3]);
+      } else if !self.skip_markup()? {
+        let (child, child_span, node) = self.element()?;
+        children.push(child, child_span, node);
+        elements = true;
+      }
+    }
+  }
+
+  /// Parse an element, returning its name, the span of its name and its node
+  fn element(&mut self) -> Result<(String, Span, DataNode), GudError> {
+    self.depth += 1;
+    if self.depth > MAX_DEPTH {
+      return Err(self.error("nesting is too deep"));
+    }
+    let start = self.pos;
+    self.pos += 1;
+    let (name, name_start) = self.name()?;
+    let key_span = self.index.span(name_start, self.pos);
+
+    let mut children = Children::default();
+    let mut attributes = false;
+    loop {
+      self.skip_space();
+      match self.peek() {
+        Some(b'/') if self.rest().starts_with(b"/>") => {
+          self.pos += 2;
+          self.depth -= 1;
+          let span = self.index.span(start, self.pos);
+          let node = if attributes {
+            DataNode::Map {
+              entries: children.entries,
+              span,
+            }
+          } else {
+            DataNode::Scalar {
+              value: Scalar::Null,
+              span,
+            }
+          };
+          return Ok((name, key_span, node));
+        }
+        Some(b'>') => {
+          self.pos += 1;
+          break;
+        }
+        Some(_) => {
+          self.attribute(&mut children)?;
+          attributes = true;
+        }
+        None => return Err(self.error("unterminated tag")),
+      }
+    }
+
+    let (text, elements) = self.content(&name, start, &mut children)?;
+    self.depth -= 1;
+
+    let span = self.index.span(start, self.pos);
+    let text = text.trim();
+    let node = if attributes || elements {
+      if !text.is_empty() {
+        children.push(
+          "#text".to_string(),
+          span,
+          self
+            .index
+            .scalar(Scalar::String(text.to_string()), start, self.pos),
+        );
+      }
+      DataNode::Map {
+        entries: children.entries,
+        span,
+      }
+    } else if text.is_empty() {
+      DataNode::Scalar {
+        value: Scalar::Null,
+        span,
+      }
+    } else {
+      DataNode::Scalar {
+        value: Scalar::String(text.to_string()),
+        span,
+      }
+    };
+    Ok((name, key_span, node))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::data::Format;
+  use serde_json::json;
+
+  #[test]
+  fn maps_elements_attributes_and_text() {
+    let source = r#"<?xml version="1.0"?>
+<!-- pom -->
+<project xmlns="http://maven.apache.org/POM/4.0.0">
+  <artifactId>klep</artifactId>
+  <dependencies>
+    <dependency><groupId>junit</groupId><scope>test</scope></dependency>
+    <dependency><groupId>a &amp; b</groupId><optional/></dependency>
+  </dependencies>
+  <note lang="en">hi <![CDATA[<there>]]></note>
+</project>"#;
+    assert_eq!(
+      Format::Xml.parse(source).unwrap().to_value(),
+      json!({"project": {
+        "@xmlns": "http://maven.apache.org/POM/4.0.0",
+        "artifactId": "klep",
+        "dependencies": {"dependency": [
+          {"groupId": "junit", "scope": "test"},
+          {"groupId": "a & b", "optional": null},
+        ]},
+        "note": {"@lang": "en", "#text": "hi <there>"},
+      }})
+    );
+  }
+
+  #[test]
+  fn rejects_malformed_documents() {
+    for source in [
+      "",
+      "<a>",
+      "<a></b>",
+      "<a x=1/>",
+      "<a>&bogus;</a>",
+      "<a/><b/>",
+    ] {
+      assert!(Format::Xml.parse(source).is_err(), "{source:?}");
+    }
+  }
+}
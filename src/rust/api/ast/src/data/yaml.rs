@@ -0,0 +1,927 @@
+//! YAML 1.2, first document only.
+//!
+//! Block and flow collections, all scalar styles, anchors and aliases are
+//! supported; plain scalars are typed with the core schema. Complex `?` keys
+//! and merge keys are not interpreted.
+
+use gud_common::GudError;
+use std::collections::HashMap;
+
+use super::{DataNode, Entry, LineIndex, Scalar, MAX_DEPTH};
+
+struct Parser<'a> {
+  index: &'a LineIndex<'a>,
+  bytes: &'a [u8],
+  pos: usize,
+  depth: usize,
+  anchors: HashMap<String, DataNode>,
+}
+
+pub(super) fn parse(index: &LineIndex) -> Result<DataNode, GudError> {
+  let mut parser = Parser {
+    index,
+    bytes: index.source.as_bytes(),
+    pos: 0,
+    depth: 0,
+    anchors: HashMap::new(),
+  };
+  if parser.bytes.starts_with("\u{feff}".as_bytes()) {
+    parser.pos = 3;
+  }
+
+  parser.skip_to_content();
+  while parser.peek() == Some(b'%') && parser.column(parser.pos) == 0 {
+    parser.skip_line();
+    parser.skip_to_content();
+  }
+  if parser.at_marker(b"---") {
+    parser.pos += 3;
+  }
+  let node = parser.block_node(-1, true, false)?;
+  parser.skip_to_content();
+  if parser.peek().is_some() && !parser.at_marker(b"---") && !parser.at_marker(b"...") {
+    return Err(parser.error("expected the end of the document"));
+  }
+  Ok(node)
+}
+
+/// Type a plain scalar with the YAML 1.2 core schema
+fn resolve(text: &str) -> Scalar {
+  match text {
+    "" | "~" | "null" | "Null" | "NULL" => return Scalar::Null,
+    "true" | "True" | "TRUE" => return Scalar::Bool(true),
+    "false" | "False" | "FALSE" => return Scalar::Bool(false),
+    ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => return Scalar::Float(f64::INFINITY),
+    "-.inf" | "-.Inf" | "-.INF" => return Scalar::Float(f64::NEG_INFINITY),
+    ".nan" | ".NaN" | ".NAN" => return Scalar::Float(f64::NAN),
+    _ => {}
+  }
+  let unsigned = text.trim_start_matches(['+', '-']);
+  if let Some(octal) = text.strip_prefix("0o") {
+    if let Ok(n) = i64::from_str_radix(octal, 8) {
+      return Scalar::Integer(n);
+    }
+  } else if let Some(hex) = text.strip_prefix("0x") {
+    if let Ok(n) = i64::from_str_radix(hex, 16) {
+      return Scalar::Integer(n);
+    }
+  } else if !unsigned.is_empty() && unsigned.bytes().all(|b| b.is_ascii_digit()) {
+    if let Ok(n) = text.parse() {
+      return Scalar::Integer(n);
+    }
+  } else if is_float(unsigned) {
+    if let Ok(n) = text.parse() {
+      return Scalar::Float(n);
+    }
+  }
+  Scalar::String(text.to_string())
+}
+
+/// `(\.[0-9]+|[0-9]+(\.[0-9]*)?)([eE][-+]?[0-9]+)?` without a sign
+fn is_float(text: &str) -> bool {
+  let (mantissa, exponent) = text.split_once(['e', 'E']).unwrap_or((text, "0"));
+  let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+  let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+  let exponent = exponent.trim_start_matches(['+', '-']);
+  (!whole.is_empty() || !fraction.is_empty())
+    && digits(whole)
+    && digits(fraction)
+    && !exponent.is_empty()
+    && digits(exponent)
+}
+
+const fn is_blank(byte: Option<u8>) -> bool {
+  matches!(byte, None | Some(b' ' | b'\t' | b'\n' | b'\r'))
+}
+
+const fn is_flow_indicator(byte: u8) -> bool {
+  matches!(byte, b',' | b'[' | b']' | b'{' | b'}')
+}
+
+impl Parser<'_> {
+  fn peek(&self) -> Option<u8> {
+    self.bytes.get(self.pos).copied()
+  }
+
+  fn peek_at(&self, offset: usize) -> Option<u8> {
+    self.bytes.get(self.pos + offset).copied()
+  }
+
+  fn error(&self, message: &str) -> GudError {
+    self.index.error(self.pos, message)
+  }
+
+  fn column(&self, pos: usize) -> usize {
+    pos
+      - self.bytes[..pos]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1)
+  }
+
+  /// `---` or `...` at the start of a line
+  fn at_marker(&self, marker: &[u8]) -> bool {
+    self.column(self.pos) == 0
+      && self.bytes[self.pos..].starts_with(marker)
+      && is_blank(self.peek_at(3))
+  }
+
+  fn at_sequence_entry(&self) -> bool {
+    self.peek() == Some(b'-') && is_blank(self.peek_at(1))
+  }
+
+  /// Whether the `:` at `pos` separates a key from its value
+  fn is_value_indicator(&self, pos: usize, flow: bool) -> bool {
+    self.bytes.get(pos) == Some(&b':')
+      && match self.bytes.get(pos + 1) {
+        None | Some(b' ' | b'\t' | b'\n' | b'\r') => true,
+        Some(&b) => flow && is_flow_indicator(b),
+      }
+  }
+
+  fn skip_space(&mut self) {
+    while matches!(self.peek(), Some(b' ' | b'\t')) {
+      self.pos += 1;
+    }
+  }
+
+  fn skip_line(&mut self) {
+    while self.peek().is_some_and(|b| b != b'\n') {
+      self.pos += 1;
+    }
+  }
+
+  /// Skip whitespace, line breaks and comments up to the next content
+  fn skip_to_content(&mut self) {
+    loop {
+      self.skip_space();
+      match self.peek() {
+        Some(b'#') => self.skip_line(),
+        Some(b'\n' | b'\r') => self.pos += 1,
+        _ => return,
+      }
+    }
+  }
+
+  fn at_line_end(&self) -> bool {
+    matches!(self.peek(), None | Some(b'\n' | b'\r' | b'#'))
+  }
+
+  fn nested<T>(
+    &mut self,
+    parse: impl FnOnce(&mut Self) -> Result<T, GudError>,
+  ) -> Result<T, GudError> {
+    self.depth += 1;
+    if self.depth > MAX_DEPTH {
+      return Err(self.error("nesting is too deep"));
+    }
+    let result = parse(self);
+    self.depth -= 1;
+    result
+  }
+
+  fn null(&self, pos: usize) -> DataNode {
+    self.index.scalar(Scalar::Null, pos, pos)
+  }
+
+  /// Anchor and tag before a node
+  fn properties(&mut self) -> (Option<String>, Option<String>) {
+    let (mut anchor, mut tag) = (None, None);
+    while let Some(sigil @ (b'&' | b'!')) = self.peek() {
+      let start = self.pos;
+      while !is_blank(self.peek()) && !self.peek().is_some_and(is_flow_indicator) {
+        self.pos += 1;
+      }
+      let name = self.index.source[start + 1..self.pos].to_string();
+      if sigil == b'&' {
+        anchor = Some(name);
+      } else {
+        tag = Some(name);
+      }
+      self.skip_space();
+    }
+    (anchor, tag)
+  }
+
+  /// Finish a node: remember its anchor and apply a `!!str` tag
+  fn finish(&mut self, mut node: DataNode, anchor: Option<String>, tag: Option<&str>) -> DataNode {
+    if let (Some("!str"), DataNode::Scalar { value, .. }) = (tag, &mut node) {
+      if let Some(text) = value.to_text() {
+        *value = Scalar::String(text);
+      }
+    }
+    if let Some(anchor) = anchor {
+      self.anchors.insert(anchor, node.clone());
+    }
+    node
+  }
+
+  fn alias(&mut self) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    self.pos += 1;
+    while !is_blank(self.peek()) && !self.peek().is_some_and(is_flow_indicator) {
+      self.pos += 1;
+    }
+    let name = &self.index.source[start + 1..self.pos];
+    self
+      .anchors
+      .get(name)
+      .cloned()
+      .ok_or_else(|| self.index.error(start, format!("unknown alias '{name}'")))
+  }
+
+  /// Node following a `-`, a `key:` or the document start. `parent` is the
+  /// indentation of the enclosing collection; a mapping value may be a
+  /// sequence indented as much as its key.
+  fn block_node(
+    &mut self,
+    parent: isize,
+    compact: bool,
+    map_value: bool,
+  ) -> Result<DataNode, GudError> {
+    self.skip_space();
+    let (anchor, tag) = self.properties();
+    let node = if self.at_line_end() {
+      let empty = self.pos;
+      self.skip_to_content();
+      let column = self.column(self.pos).cast_signed();
+      if self.peek().is_none() || self.at_marker(b"---") || self.at_marker(b"...") {
+        self.pos = empty;
+        self.null(empty)
+      } else if column > parent {
+        if self.at_sequence_entry() {
+          self.nested(|parser| parser.block_sequence(column))?
+        } else {
+          self.nested(|parser| parser.inline_node(parent, true, tag.as_deref()))?
+        }
+      } else if column == parent && map_value && self.at_sequence_entry() {
+        self.nested(|parser| parser.block_sequence(column))?
+      } else {
+        self.pos = empty;
+        self.null(empty)
+      }
+    } else {
+      self.nested(|parser| parser.inline_node(parent, compact, tag.as_deref()))?
+    };
+    Ok(self.finish(node, anchor, tag.as_deref()))
+  }
+
+  /// Node starting at the current position. With `compact` set, a key here
+  /// starts a mapping indented at the key's column.
+  fn inline_node(
+    &mut self,
+    parent: isize,
+    compact: bool,
+    tag: Option<&str>,
+  ) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    match self.peek() {
+      Some(b'|' | b'>') => return self.block_scalar(parent),
+      Some(b'[' | b'{') => return self.nested(Self::flow_collection),
+      Some(b'*') => return self.alias(),
+      Some(b'-') if compact && self.at_sequence_entry() => {
+        let column = self.column(start).cast_signed();
+        return self.block_sequence(column);
+      }
+      Some(b'?') if is_blank(self.peek_at(1)) => {
+        return Err(self.error("complex keys are not supported"))
+      }
+      Some(b'@' | b'`') => return Err(self.error("reserved character")),
+      _ => {}
+    }
+
+    let quoted = matches!(self.peek(), Some(b'"' | b'\''));
+    let (text, end) = if quoted {
+      let text = self.quoted()?;
+      (text, self.pos)
+    } else {
+      let end = self.plain_line(false);
+      (self.index.source[start..end].to_string(), end)
+    };
+
+    let after = self.pos;
+    self.skip_space();
+    if self.is_value_indicator(self.pos, false) {
+      if !compact {
+        return Err(
+          self
+            .index
+            .error(self.pos, "mapping values are not allowed here"),
+        );
+      }
+      self.pos = start;
+      let column = self.column(start).cast_signed();
+      return self.block_mapping(column);
+    }
+    self.pos = after;
+
+    if quoted {
+      return Ok(self.index.scalar(Scalar::String(text), start, end));
+    }
+    let (text, end) = self.plain_continuation(parent, text, end);
+    let value = if tag == Some("!str") {
+      Scalar::String(text)
+    } else {
+      resolve(&text)
+    };
+    Ok(self.index.scalar(value, start, end))
+  }
+
+  /// Text of a plain scalar up to the end of the line, a comment or a `: `;
+  /// returns where the text ends, without trailing spaces
+  fn plain_line(&mut self, flow: bool) -> usize {
+    let start = self.pos;
+    let mut end = self.pos;
+    while let Some(byte) = self.peek() {
+      match byte {
+        b'\n' | b'\r' => break,
+        b':' if self.is_value_indicator(self.pos, flow) => break,
+        b'#' if self.pos > start && matches!(self.bytes[self.pos - 1], b' ' | b'\t') => break,
+        b if flow && is_flow_indicator(b) => break,
+        b' ' | b'\t' => self.pos += 1,
+        _ => {
+          self.pos += self.index.source[self.pos..]
+            .chars()
+            .next()
+            .map_or(1, char::len_utf8);
+          end = self.pos;
+        }
+      }
+    }
+    self.pos = end;
+    end
+  }
+
+  /// Fold the following lines indented deeper than `parent` into a plain
+  /// scalar
+  fn plain_continuation(
+    &mut self,
+    parent: isize,
+    mut text: String,
+    mut end: usize,
+  ) -> (String, usize) {
+    loop {
+      let save = self.pos;
+      self.skip_space();
+      let mut breaks = 0;
+      while matches!(self.peek(), Some(b'\n' | b'\r')) {
+        if self.peek() == Some(b'\n') {
+          breaks += 1;
+        }
+        self.pos += 1;
+        self.skip_space();
+      }
+      let line = self.pos;
+      if breaks == 0
+        || self.peek().is_none()
+        || self.peek() == Some(b'#')
+        || self.column(line).cast_signed() <= parent
+        || self.at_marker(b"---")
+        || self.at_marker(b"...")
+      {
+        self.pos = save;
+        return (text, end);
+      }
+      let line_end = self.plain_line(false);
+      if line_end == line || self.is_value_indicator(self.pos, false) {
+        self.pos = save;
+        return (text, end);
+      }
+      if breaks == 1 {
+        text.push(' ');
+      } else {
+        text.extend(std::iter::repeat_n('\n', breaks - 1));
+      }
+      text.push_str(&self.index.source[line..line_end]);
+      end = line_end;
+    }
+  }
+
+  fn block_mapping(&mut self, indent: isize) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    let mut entries = Vec::new();
+    let mut end = start;
+    loop {
+      let key_start = self.pos;
+      let key = match self.peek() {
+        Some(b'"' | b'\'') => self.quoted()?,
+        Some(b'?') if is_blank(self.peek_at(1)) => {
+          return Err(self.error("complex keys are not supported"))
+        }
+        _ => {
+          let key_end = self.plain_line(false);
+          self.index.source[key_start..key_end].to_string()
+        }
+      };
+      let key_span = self.index.span(key_start, self.pos);
+      self.skip_space();
+      if !self.is_value_indicator(self.pos, false) {
+        return Err(self.error("expected ':' after a mapping key"));
+      }
+      self.pos += 1;
+      let value = self.block_node(indent, false, true)?;
+      end = end
+        .max(value.span().end.byte_offset)
+        .max(key_span.end.byte_offset);
+      entries.push(Entry {
+        key,
+        key_span,
+        value,
+      });
+
+      let save = self.pos;
+      self.skip_to_content();
+      let column = self.column(self.pos).cast_signed();
+      if self.peek().is_none()
+        || self.at_marker(b"---")
+        || self.at_marker(b"...")
+        || column < indent
+      {
+        self.pos = save;
+        break;
+      }
+      if column > indent || self.at_sequence_entry() {
+        return Err(self.error("bad indentation of a mapping entry"));
+      }
+    }
+    Ok(DataNode::Map {
+      entries,
+      span: self.index.span(start, end),
+    })
+  }
+
+  fn block_sequence(&mut self, indent: isize) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    let mut items = Vec::new();
+    let mut end = start + 1;
+    loop {
+      self.pos += 1;
+      let item = self.block_node(indent, true, false)?;
+      end = end.max(item.span().end.byte_offset);
+      items.push(item);
+
+      let save = self.pos;
+      self.skip_to_content();
+      let column = self.column(self.pos).cast_signed();
+      if self.peek().is_none()
+        || self.at_marker(b"---")
+        || self.at_marker(b"...")
+        || column < indent
+        || (column == indent && !self.at_sequence_entry())
+      {
+        self.pos = save;
+        break;
+      }
+      if column > indent {
+        return Err(self.error("bad indentation of a sequence entry"));
+      }
+    }
+    Ok(DataNode::List {
+      items,
+      span: self.index.span(start, end),
+    })
+  }
+
+  /// `|` and `>` scalars with their chomping and indentation indicators
+  fn block_scalar(&mut self, parent: isize) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    let folded = self.peek() == Some(b'>');
+    self.pos += 1;
+    let (mut chomp, mut explicit) = (None, None);
+    for _ in 0..2 {
+      match self.peek() {
+        Some(b @ (b'-' | b'+')) if chomp.is_none() => chomp = Some(b),
+        Some(b @ b'1'..=b'9') if explicit.is_none() => explicit = Some(usize::from(b - b'0')),
+        _ => break,
+      }
+      self.pos += 1;
+    }
+    self.skip_space();
+    if self.peek() == Some(b'#') {
+      self.skip_line();
+    }
+    if !matches!(self.peek(), None | Some(b'\n' | b'\r')) {
+      return Err(self.error("expected the end of the line after a block scalar indicator"));
+    }
+
+    if self.peek() == Some(b'\r') {
+      self.pos += 1;
+    }
+
+    // Content must be indented deeper than the parent collection
+    let base = usize::try_from(parent + 1).unwrap_or(0);
+    let mut indent = explicit.map(|n| usize::try_from(parent).unwrap_or(0) + n);
+    let mut lines: Vec<&str> = Vec::new();
+    let mut end = self.pos;
+    let mut cursor = self.pos;
+    while cursor < self.bytes.len() {
+      let line_start = cursor + 1;
+      // A final line break ends the last line rather than starting another
+      if line_start >= self.bytes.len() {
+        break;
+      }
+      let line_end = self.bytes[line_start..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(self.bytes.len(), |i| line_start + i);
+      let line = self.index.source[line_start..line_end].trim_end_matches('\r');
+      let spaces = line.len() - line.trim_start_matches(' ').len();
+      if line.trim().is_empty() {
+        lines.push("");
+        cursor = line_end;
+        continue;
+      }
+      let content = *indent.get_or_insert(spaces);
+      let marker = spaces == 0 && (line.starts_with("---") || line.starts_with("..."));
+      if spaces < content || content < base || marker {
+        break;
+      }
+      lines.push(&line[content..]);
+      end = line_start + line.len();
+      cursor = line_end;
+    }
+
+    let trailing = lines
+      .iter()
+      .rev()
+      .take_while(|line| line.is_empty())
+      .count();
+    let body = &lines[..lines.len() - trailing];
+    let mut text = if folded { fold(body) } else { body.join("\n") };
+    if !body.is_empty() {
+      match chomp {
+        Some(b'-') => {}
+        Some(_) => text.push_str(&"\n".repeat(trailing + 1)),
+        None => text.push('\n'),
+      }
+    }
+    self.pos = end;
+    Ok(self.index.scalar(Scalar::String(text), start, end))
+  }
+
+  fn flow_collection(&mut self) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    let sequence = self.peek() == Some(b'[');
+    let close = if sequence { b']' } else { b'}' };
+    self.pos += 1;
+    let mut entries = Vec::new();
+    let mut items = Vec::new();
+    loop {
+      self.skip_to_content();
+      if self.peek() == Some(close) {
+        break;
+      }
+      let key_start = self.pos;
+      let key = self.flow_node()?;
+      // A `:` may follow a quoted or flow collection key directly, as in JSON
+      let json_key = matches!(
+        self.bytes.get(self.pos.wrapping_sub(1)),
+        Some(b'"' | b'\'' | b']' | b'}')
+      );
+      self.skip_to_content();
+      if self.is_value_indicator(self.pos, true) || (json_key && self.peek() == Some(b':')) {
+        self.pos += 1;
+        self.skip_to_content();
+        let value = if matches!(self.peek(), Some(b',') | None) || self.peek() == Some(close) {
+          self.null(self.pos)
+        } else {
+          self.flow_node()?
+        };
+        let entry = Entry {
+          key: key_text(&key),
+          key_span: key.span(),
+          value,
+        };
+        if sequence {
+          let span = self.index.span(key_start, self.pos);
+          items.push(DataNode::Map {
+            entries: vec![entry],
+            span,
+          });
+        } else {
+          entries.push(entry);
+        }
+      } else if sequence {
+        items.push(key);
+      } else {
+        entries.push(Entry {
+          key: key_text(&key),
+          key_span: key.span(),
+          value: self.null(self.pos),
+        });
+      }
+      self.skip_to_content();
+      match self.peek() {
+        Some(b',') => self.pos += 1,
+        Some(b) if b == close => break,
+        _ => return Err(self.error(&format!("expected ',' or '{}'", close as char))),
+      }
+    }
+    self.pos += 1;
+    let span = self.index.span(start, self.pos);
+    Ok(if sequence {
+      DataNode::List { items, span }
+    } else {
+      DataNode::Map { entries, span }
+    })
+  }
+
+  fn flow_node(&mut self) -> Result<DataNode, GudError> {
+    let (anchor, tag) = self.properties();
+    let start = self.pos;
+    let node = match self.peek() {
+      Some(b'[' | b'{') => self.nested(Self::flow_collection)?,
+      Some(b'*') => self.alias()?,
+      Some(b'"' | b'\'') => {
+        let text = self.quoted()?;
+        self.index.scalar(Scalar::String(text), start, self.pos)
+      }
+      Some(_) => {
+        let end = self.plain_line(true);
+        if end == start {
+          return Err(self.error("expected a value"));
+        }
+        self
+          .index
+          .scalar(resolve(&self.index.source[start..end]), start, end)
+      }
+      None => return Err(self.error("unterminated flow collection")),
+    };
+    Ok(self.finish(node, anchor, tag.as_deref()))
+  }
+
+  /// Single or double quoted scalar, folding line breaks
+  fn quoted(&mut self) -> Result<String, GudError> {
+    let start = self.pos;
+    let quote = self.bytes[self.pos];
+    self.pos += 1;
+    let mut text = String::new();
+    loop {
+      match self.peek() {
+        None => return Err(self.index.error(start, "unterminated string")),
+        Some(b'\'') if quote == b'\'' => {
+          if self.peek_at(1) == Some(b'\'') {
+            text.push('\'');
+            self.pos += 2;
+          } else {
+            self.pos += 1;
+            return Ok(text);
+          }
+        }
+        Some(b'"') if quote == b'"' => {
+          self.pos += 1;
+          return Ok(text);
+        }
+        Some(b'\\') if quote == b'"' => {
+          self.pos += 1;
+          if matches!(self.peek(), Some(b'\n' | b'\r')) {
+            // An escaped line break joins the lines without a space
+            self.skip_to_line_content();
+          } else {
+            self.escape(&mut text)?;
+          }
+        }
+        Some(b'\n' | b'\r') => {
+          let trimmed = text.trim_end_matches([' ', '\t']).len();
+          text.truncate(trimmed);
+          let breaks = self.skip_to_line_content();
+          if breaks > 1 {
+            text.extend(std::iter::repeat_n('\n', breaks - 1));
+          } else {
+            text.push(' ');
+          }
+        }
+        Some(_) => {
+          let c = self.index.source[self.pos..]
+            .chars()
+            .next()
+            .unwrap_or_default();
+          text.push(c);
+          self.pos += c.len_utf8();
+        }
+      }
+    }
+  }
+
+  /// Skip line breaks and indentation, returning the number of line breaks
+  fn skip_to_line_content(&mut self) -> usize {
+    let mut breaks = 0;
+    loop {
+      match self.peek() {
+        Some(b'\n') => {
+          breaks += 1;
+          self.pos += 1;
+        }
+        Some(b' ' | b'\t' | b'\r') => self.pos += 1,
+        _ => return breaks,
+      }
+    }
+  }
+
+  fn escape(&mut self, text: &mut String) -> Result<(), GudError> {
+    let escape = self
+      .peek()
+      .ok_or_else(|| self.error("unterminated string"))?;
+    self.pos += 1;
+    let hex = |parser: &mut Self, len: usize| -> Result<char, GudError> {
+      let c = parser
+        .bytes
+        .get(parser.pos..parser.pos + len)
+        .and_then(|digits| std::str::from_utf8(digits).ok())
+        .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+        .and_then(char::from_u32)
+        .ok_or_else(|| parser.error("invalid escape"))?;
+      parser.pos += len;
+      Ok(c)
+    };
+    let c = match escape {
+      b'0' => '\0',
+      b'a' => '\u{7}',
+      b'b' => '\u{8}',
+      b't' | b'\t' => '\t',
+      b'n' => '\n',
+      b'v' => '\u{b}',
+      b'f' => '\u{c}',
+      b'r' => '\r',
+      b'e' => '\u{1b}',
+      b' ' => ' ',
+      b'"' => '"',
+      b'/' => '/',
+      b'\\' => '\\',
+      b'N' => '\u{85}',
+      b'_' => '\u{a0}',
+      b'L' => '\u{2028}',
+      b'P' => '\u{2029}',
+      b'x' => hex(self, 2)?,
+      b'u' => hex(self, 4)?,
+      b'U' => hex(self, 8)?,
+      _ => {
+        self.pos -= 1;
+        return Err(self.error("invalid escape"));
+      }
+    };
+    text.push(c);
+    Ok(())
+  }
+}
+
+/// Fold the lines of a `>` scalar: line breaks between lines of text become
+/// spaces, except around empty or more indented lines
+fn fold(lines: &[&str]) -> String {
+  let indented = |line: &str| line.starts_with([' ', '\t']);
+  let mut text = String::new();
+  for (i, line) in lines.iter().enumerate() {
+    if i > 0 {
+      let previous = lines[i - 1];
+      if line.is_empty() {
+        text.push('\n');
+        continue;
+      }
+      if !previous.is_empty() {
+        text.push(if indented(previous) || indented(line) {
+          '\n'
+        } else {
+          ' '
+        });
+      }
+    }
+    text.push_str(line);
+  }
+  text
+}
+
+/// Text of a node used as a flow mapping key
+fn key_text(node: &DataNode) -> String {
+  match node {
+    DataNode::Scalar { value, .. } => value.to_text().unwrap_or_default(),
+    _ => serde_json::to_string(&node.to_value()).unwrap_or_default(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::data::Format;
+  use serde_json::{json, Value};
+
+  fn parse(source: &str) -> Value {
+    Format::Yaml.parse(source).unwrap().to_value()
+  }
+
+  #[test]
+  fn parses_block_collections() {
+    let source = "\
+# pubspec
+name: klep
+version: 1.10.0
+dependencies:
+  http: ^1.2.0   # comment
+  path:
+    git: https://example.com/path.git
+dev:
+- lints
+- test: [1, 2]
+  only: true
+empty:
+";
+    assert_eq!(
+      parse(source),
+      json!({
+        "name": "klep",
+        "version": "1.10.0",
+        "dependencies": {"http": "^1.2.0", "path": {"git": "https://example.com/path.git"}},
+        "dev": ["lints", {"test": [1, 2], "only": true}],
+        "empty": null,
+      })
+    );
+  }
+
+  #[test]
+  fn types_plain_scalars_with_the_core_schema() {
+    assert_eq!(
+      parse("[1, -2, 0x1f, 1.5, .inf, true, ~, 'yes', 1.2.3, \"07\"]"),
+      json!([1, -2, 31, 1.5, null, true, null, "yes", "1.2.3", "07"])
+    );
+    assert_eq!(
+      parse("a: !!str 1.10\nb: 1.10"),
+      json!({"a": "1.10", "b": 1.1})
+    );
+  }
+
+  #[test]
+  fn parses_scalar_styles() {
+    let source = "\
+literal: |
+  a
+   b
+
+folded: >-
+  one
+  two
+
+  three
+plain: first
+  second
+quoted: \"x\\ty\n  z\"
+single: 'it''s'
+";
+    assert_eq!(
+      parse(source),
+      json!({
+        "literal": "a\n b\n",
+        "folded": "one two\nthree",
+        "plain": "first second",
+        "quoted": "x\ty z",
+        "single": "it's",
+      })
+    );
+  }
+
+  #[test]
+  fn keeps_exactly_the_trailing_line_breaks() {
+    assert_eq!(parse("b: |+\n  z\n\n"), json!({"b": "z\n\n"}));
+    assert_eq!(parse("b: |+\n  z\n"), json!({"b": "z\n"}));
+    assert_eq!(parse("b: |+\n  z"), json!({"b": "z\n"}));
+    assert_eq!(parse("b: |\n  z\n\n"), json!({"b": "z\n"}));
+  }
+
+  #[test]
+  fn resolves_aliases_and_flow_mappings() {
+    let source = "base: &base {a: 1, b: [x, y]}\ncopy: *base\n---\nignored: true\n";
+    assert_eq!(
+      parse(source),
+      json!({"base": {"a": 1, "b": ["x", "y"]}, "copy": {"a": 1, "b": ["x", "y"]}})
+    );
+  }
+
+  #[test]
+  fn reads_json_style_flow_mappings() {
+    assert_eq!(
+      parse(r#"{"a":1, 'b':[2], "c" :{"d":null}}"#),
+      json!({"a": 1, "b": [2], "c": {"d": null}})
+    );
+    // Plain keys still need a space after the colon
+    assert_eq!(parse("{ a:1 }"), json!({"a:1": null}));
+  }
+
+  #[test]
+  fn spans_cover_nodes() {
+    let tree = Format::Yaml.parse("a:\n  - x\n  - yy\nb: 1\n").unwrap();
+    let list = tree.get("a").unwrap().span();
+    assert_eq!((list.start.line, list.start.column), (2, 3));
+    assert_eq!((list.end.line, list.end.column), (3, 7));
+  }
+
+  #[test]
+  fn rejects_invalid_documents() {
+    for source in [
+      "a: b: c",
+      "a: 1\n  b: 2",
+      "- a\n b: 1",
+      "a: \"x",
+      "a: *missing",
+      "[1, 2",
+    ] {
+      assert!(Format::Yaml.parse(source).is_err(), "{source:?}");
+    }
+  }
+}
@@ -0,0 +1,564 @@
+//! Tree edit distance between [`AstNode`] trees.
+//!
+//! Zhang and Shasha's algorithm finds the cheapest way to turn one ordered
+//! tree into another by inserting, deleting and relabeling nodes. Deleting
+//! a node hands its children to its parent; inserting one adopts a run of
+//! its parent's children. What each operation costs is up to a [`Costs`]
+//! model, so that, say, keys spelled `devDependencies` and
+//! `dev-dependencies` can be made free to rename.
+//!
+//! Comparing trees of `n` and `m` nodes takes up to `n²m²` steps, so each
+//! tree holds at most [`MAX_NODES`] and the comparison stops once its
+//! request is cancelled.
+
+use gud_common::{log, CallContext, ErrorKind, GudError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeSet;
+
+use crate::fingerprint::normalize;
+use crate::to_tree::{AstNode, Location};
+
+/// Most nodes either tree may have
+pub const MAX_NODES: usize = 1000;
+
+/// Prices of the edit operations
+pub trait Costs {
+  /// Cost of inserting `node` into the source tree
+  fn insert(&self, _node: &AstNode) -> f64 {
+    1.0
+  }
+
+  /// Cost of deleting `node` from the source tree
+  fn delete(&self, _node: &AstNode) -> f64 {
+    1.0
+  }
+
+  /// Cost of turning `from` into `to`, which must be zero when they are
+  /// the same node and at most deleting one and inserting the other
+  fn relabel(&self, from: &AstNode, to: &AstNode) -> f64;
+}
+
+/// Built-in relabeling costs; nodes of different types always cost 1
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CostModel {
+  /// Nodes are the same when their values are
+  #[default]
+  Exact,
+  /// Values are ignored, leaving only the shape of the trees
+  Structure,
+  /// Values cost by how far apart they are as text, once lowercased and
+  /// without `-` and `_`
+  Fuzzy,
+}
+
+impl Costs for CostModel {
+  fn relabel(&self, from: &AstNode, to: &AstNode) -> f64 {
+    if from.node_type != to.node_type {
+      return 1.0;
+    }
+    match (self, &from.value, &to.value) {
+      (Self::Fuzzy, Some(a), Some(b)) => text_distance(&normalize(a), &normalize(b)),
+      (Self::Structure, _, _) | (Self::Exact | Self::Fuzzy, None, None) => 0.0,
+      (Self::Exact, a, b) if a == b => 0.0,
+      _ => 1.0,
+    }
+  }
+}
+
+/// Levenshtein distance between two strings over the length of the longer
+fn text_distance(a: &str, b: &str) -> f64 {
+  let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+  let longest = a.len().max(b.len());
+  if longest == 0 {
+    return 0.0;
+  }
+  let mut row: Vec<usize> = (0..=b.len()).collect();
+  for (i, x) in a.iter().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+    for (j, y) in b.iter().enumerate() {
+      let substituted = diagonal + usize::from(x != y);
+      diagonal = row[j + 1];
+      row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+    }
+  }
+  row[b.len()] as f64 / longest as f64
+}
+
+/// A node of one of the compared trees
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct NodeRef {
+  /// Indexes of the children leading from the root to the node
+  pub path: Vec<usize>,
+  /// Type of the node
+  pub node_type: String,
+  /// Source text of a leaf
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub value: Option<String>,
+  /// Where the node starts in its source
+  pub location: Location,
+}
+
+impl NodeRef {
+  /// Reference to `node`, found at `path` below the root
+  #[must_use]
+  pub fn new(node: &AstNode, path: &[usize]) -> Self {
+    Self {
+      path: path.to_vec(),
+      node_type: node.node_type.clone(),
+      value: node.value.clone(),
+      location: node.location,
+    }
+  }
+}
+
+/// One step of an edit script
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum EditOperation {
+  /// Turn a source node into a target node with another type or value
+  Relabel {
+    /// Node of the source tree
+    from: NodeRef,
+    /// Node of the target tree it becomes
+    to: NodeRef,
+    /// Price under the cost model
+    cost: f64,
+  },
+  /// Remove a source node, moving its children up to its parent
+  Delete {
+    /// Node of the source tree
+    node: NodeRef,
+    /// Price under the cost model
+    cost: f64,
+  },
+  /// Add a target node
+  Insert {
+    /// Node of the target tree
+    node: NodeRef,
+    /// Price under the cost model
+    cost: f64,
+  },
+}
+
+/// Nodes of a tree in postorder, with the leftmost leaf below each
+struct Postorder<'a> {
+  nodes: Vec<&'a AstNode>,
+  paths: Vec<Vec<usize>>,
+  leftmost: Vec<usize>,
+}
+
+impl<'a> Postorder<'a> {
+  fn new(root: &'a AstNode) -> Self {
+    let mut postorder = Self {
+      nodes: Vec::new(),
+      paths: Vec::new(),
+      leftmost: Vec::new(),
+    };
+    postorder.visit(root, &mut Vec::new());
+    postorder
+  }
+
+  /// Number `node` and its descendants, returning its leftmost leaf
+  fn visit(&mut self, node: &'a AstNode, path: &mut Vec<usize>) -> usize {
+    let mut leftmost = None;
+    for (index, child) in node.children.iter().enumerate() {
+      path.push(index);
+      let leaf = self.visit(child, path);
+      path.pop();
+      leftmost.get_or_insert(leaf);
+    }
+    let leftmost = leftmost.unwrap_or(self.nodes.len());
+    self.nodes.push(node);
+    self.paths.push(path.clone());
+    self.leftmost.push(leftmost);
+    leftmost
+  }
+
+  /// Nodes with no ancestor sharing their leftmost leaf, in postorder
+  fn keyroots(&self) -> Vec<usize> {
+    let mut seen = BTreeSet::new();
+    let mut keyroots: Vec<usize> = (0..self.nodes.len())
+      .rev()
+      .filter(|&node| seen.insert(self.leftmost[node]))
+      .collect();
+    keyroots.reverse();
+    keyroots
+  }
+
+  fn node(&self, index: usize) -> NodeRef {
+    NodeRef::new(self.nodes[index], &self.paths[index])
+  }
+}
+
+/// Whether two sums of the same costs came out equal
+fn same(a: f64, b: f64) -> bool {
+  (a - b).abs() <= f64::EPSILON * a.abs().max(b.abs()).max(1.0)
+}
+
+struct ZhangShasha<'a, C> {
+  source: Postorder<'a>,
+  target: Postorder<'a>,
+  costs: &'a C,
+  /// Distance between the subtrees under each pair of nodes
+  trees: Vec<Vec<f64>>,
+}
+
+impl<'a, C: Costs> ZhangShasha<'a, C> {
+  fn new(
+    source: &'a AstNode,
+    target: &'a AstNode,
+    costs: &'a C,
+    ctx: &CallContext,
+  ) -> Result<Self, GudError> {
+    let (source, target) = (Postorder::new(source), Postorder::new(target));
+    for (tree, nodes) in [("source", &source.nodes), ("target", &target.nodes)] {
+      if nodes.len() > MAX_NODES {
+        return Err(
+          GudError::new(
+            ErrorKind::Argument,
+            "tree-too-large",
+            format!(
+              "The {tree} tree has {} nodes, more than the {MAX_NODES} that can be compared",
+              nodes.len()
+            ),
+          )
+          .with_context(json!({ "tree": tree, "nodes": nodes.len(), "max": MAX_NODES })),
+        );
+      }
+    }
+    let trees = vec![vec![0.0; target.nodes.len()]; source.nodes.len()];
+    let mut zhang_shasha = Self {
+      source,
+      target,
+      costs,
+      trees,
+    };
+    for &i in &zhang_shasha.source.keyroots() {
+      for &j in &zhang_shasha.target.keyroots() {
+        ctx.check()?;
+        zhang_shasha.forest(i, j);
+      }
+    }
+    Ok(zhang_shasha)
+  }
+
+  fn distance(&self) -> f64 {
+    self.trees[self.source.nodes.len() - 1][self.target.nodes.len() - 1]
+  }
+
+  /// Distances between the forests of the leftmost-leaf runs ending under
+  /// `i` and `j`, recording those between whole subtrees as it goes. Row
+  /// and column 0 are the empty forest.
+  fn forest(&mut self, i: usize, j: usize) -> Vec<Vec<f64>> {
+    let (source, target, costs) = (&self.source, &self.target, self.costs);
+    let (first_i, first_j) = (source.leftmost[i], target.leftmost[j]);
+    let mut forest = vec![vec![0.0; j - first_j + 2]; i - first_i + 2];
+    for x in first_i..=i {
+      let dx = x - first_i + 1;
+      forest[dx][0] = forest[dx - 1][0] + costs.delete(source.nodes[x]);
+    }
+    for y in first_j..=j {
+      let dy = y - first_j + 1;
+      forest[0][dy] = forest[0][dy - 1] + costs.insert(target.nodes[y]);
+    }
+    for x in first_i..=i {
+      for y in first_j..=j {
+        let (dx, dy) = (x - first_i + 1, y - first_j + 1);
+        let edited = (forest[dx - 1][dy] + costs.delete(source.nodes[x]))
+          .min(forest[dx][dy - 1] + costs.insert(target.nodes[y]));
+        forest[dx][dy] = if source.leftmost[x] == first_i && target.leftmost[y] == first_j {
+          let distance =
+            edited.min(forest[dx - 1][dy - 1] + costs.relabel(source.nodes[x], target.nodes[y]));
+          self.trees[x][y] = distance;
+          distance
+        } else {
+          let (lx, ly) = (source.leftmost[x] - first_i, target.leftmost[y] - first_j);
+          edited.min(forest[lx][ly] + self.trees[x][y])
+        };
+      }
+    }
+    forest
+  }
+
+  /// Walk the distances back into the operations that add up to them
+  fn script(&mut self, ctx: &CallContext) -> Result<Vec<EditOperation>, GudError> {
+    let mut relabels = Vec::new();
+    let mut deletes = Vec::new();
+    let mut inserts = Vec::new();
+    let mut pending = vec![(self.source.nodes.len() - 1, self.target.nodes.len() - 1)];
+
+    while let Some((i, j)) = pending.pop() {
+      ctx.check()?;
+      let forest = self.forest(i, j);
+      let (source, target, costs) = (&self.source, &self.target, self.costs);
+      let (first_i, first_j) = (source.leftmost[i], target.leftmost[j]);
+      let (mut dx, mut dy) = (i - first_i + 1, j - first_j + 1);
+      while dx > 0 || dy > 0 {
+        let (x, y) = (
+          (first_i + dx).wrapping_sub(1),
+          (first_j + dy).wrapping_sub(1),
+        );
+        let here = forest[dx][dy];
+        if dx > 0 && dy > 0 {
+          if source.leftmost[x] == first_i && target.leftmost[y] == first_j {
+            let cost = costs.relabel(source.nodes[x], target.nodes[y]);
+            if same(here, forest[dx - 1][dy - 1] + cost) {
+              if cost > 0.0 {
+                relabels.push((x, y, cost));
+              }
+              (dx, dy) = (dx - 1, dy - 1);
+              continue;
+            }
+          } else {
+            let (lx, ly) = (source.leftmost[x] - first_i, target.leftmost[y] - first_j);
+            if same(here, forest[lx][ly] + self.trees[x][y]) {
+              pending.push((x, y));
+              (dx, dy) = (lx, ly);
+              continue;
+            }
+          }
+        }
+        let cost = if dx > 0 {
+          costs.delete(source.nodes[x])
+        } else {
+          f64::INFINITY
+        };
+        if dx > 0 && same(here, forest[dx - 1][dy] + cost) {
+          deletes.push((x, cost));
+          dx -= 1;
+        } else {
+          inserts.push((y, costs.insert(target.nodes[y])));
+          dy -= 1;
+        }
+      }
+    }
+
+    relabels.sort_by_key(|&(x, _, _)| x);
+    deletes.sort_by_key(|&(x, _)| x);
+    inserts.sort_by_key(|&(y, _)| y);
+    let relabels = relabels
+      .into_iter()
+      .map(|(x, y, cost)| EditOperation::Relabel {
+        from: self.source.node(x),
+        to: self.target.node(y),
+        cost,
+      });
+    let deletes = deletes.into_iter().map(|(x, cost)| EditOperation::Delete {
+      node: self.source.node(x),
+      cost,
+    });
+    let inserts = inserts.into_iter().map(|(y, cost)| EditOperation::Insert {
+      node: self.target.node(y),
+      cost,
+    });
+    Ok(relabels.chain(deletes).chain(inserts).collect())
+  }
+}
+
+/// Cost of the cheapest edit script turning `source` into `target`
+pub fn edit_distance(
+  source: &AstNode,
+  target: &AstNode,
+  costs: &impl Costs,
+  ctx: &CallContext,
+) -> Result<f64, GudError> {
+  Ok(ZhangShasha::new(source, target, costs, ctx)?.distance())
+}
+
+/// The cheapest edit script turning `source` into `target`: relabelings,
+/// then deletions, then insertions, each in postorder
+pub fn edit_script(
+  source: &AstNode,
+  target: &AstNode,
+  costs: &impl Costs,
+  ctx: &CallContext,
+) -> Result<(f64, Vec<EditOperation>), GudError> {
+  let mut zhang_shasha = ZhangShasha::new(source, target, costs, ctx)?;
+  Ok((zhang_shasha.distance(), zhang_shasha.script(ctx)?))
+}
+
+/// Cost of deleting every node of `tree`, or inserting it with `insert`
+fn whole(tree: &AstNode, cost: &dyn Fn(&AstNode) -> f64) -> f64 {
+  cost(tree)
+    + tree
+      .children
+      .iter()
+      .map(|child| whole(child, cost))
+      .sum::<f64>()
+}
+
+/// Input of `ast/edit_distance`
+#[derive(Deserialize, JsonSchema)]
+pub struct EditDistanceInput {
+  /// Tree edited from
+  pub source: AstNode,
+  /// Tree edited into
+  pub target: AstNode,
+  /// How relabeling a node is priced; `exact` by default
+  pub costs: Option<CostModel>,
+  /// Whether to list the edit operations, which is the default
+  pub operations: Option<bool>,
+}
+
+/// Output of `ast/edit_distance`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct EditDistanceOutput {
+  /// Total cost of the cheapest edit script
+  pub distance: f64,
+  /// 1 less the distance over the cost of deleting the whole source and
+  /// inserting the whole target: 1 for equal trees, 0 for nothing shared
+  pub similarity: f64,
+  /// The cheapest edit script, unless it was not asked for
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub operations: Option<Vec<EditOperation>>,
+}
+
+/// Compare two trees by the cost of editing one into the other
+#[allow(clippy::needless_pass_by_value)]
+pub fn tree_edit_distance(
+  input: EditDistanceInput,
+  ctx: &CallContext,
+) -> Result<EditDistanceOutput, Box<dyn std::error::Error>> {
+  let costs = input.costs.unwrap_or_default();
+  log::debug(&format!("Computing {costs:?} tree edit distance"));
+  let (distance, operations) = if input.operations.unwrap_or(true) {
+    let (distance, operations) = edit_script(&input.source, &input.target, &costs, ctx)?;
+    (distance, Some(operations))
+  } else {
+    (
+      edit_distance(&input.source, &input.target, &costs, ctx)?,
+      None,
+    )
+  };
+  let most = whole(&input.source, &|node| costs.delete(node))
+    + whole(&input.target, &|node| costs.insert(node));
+  Ok(EditDistanceOutput {
+    distance,
+    similarity: 1.0 - distance / most,
+    operations,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::data::Format;
+  use gud_common::CancellationToken;
+
+  fn tree(format: Format, source: &str) -> AstNode {
+    format.parse(source).unwrap().to_ast()
+  }
+
+  fn node(node_type: &str, value: &str, children: Vec<AstNode>) -> AstNode {
+    AstNode {
+      node_type: node_type.to_string(),
+      value: (!value.is_empty()).then(|| value.to_string()),
+      children,
+      location: Location::default(),
+    }
+  }
+
+  #[test]
+  fn matches_the_classic_example() {
+    // f(d(a, c(b)), e) and f(c(d(a, b)), e), from Zhang and Shasha's paper
+    let leaf = |label| node(label, "", Vec::new());
+    let source = node(
+      "f",
+      "",
+      vec![
+        node("d", "", vec![leaf("a"), node("c", "", vec![leaf("b")])]),
+        leaf("e"),
+      ],
+    );
+    let target = node(
+      "f",
+      "",
+      vec![
+        node("c", "", vec![node("d", "", vec![leaf("a"), leaf("b")])]),
+        leaf("e"),
+      ],
+    );
+    let (distance, operations) =
+      edit_script(&source, &target, &CostModel::Exact, &CallContext::new()).unwrap();
+    assert!((distance - 2.0).abs() < f64::EPSILON);
+    let total: f64 = operations
+      .iter()
+      .map(|operation| match operation {
+        EditOperation::Relabel { cost, .. }
+        | EditOperation::Delete { cost, .. }
+        | EditOperation::Insert { cost, .. } => cost,
+      })
+      .sum();
+    assert!((total - distance).abs() < f64::EPSILON, "{operations:?}");
+  }
+
+  #[test]
+  fn relabel_costs_are_pluggable() {
+    let npm = tree(Format::Json, r#"{"devDependencies": {"jest": "^29"}}"#);
+    let other = tree(Format::Yaml, "dev-dependencies:\n  jest: ^29\n");
+    let ctx = CallContext::new();
+    let distance = |costs| edit_distance(&npm, &other, &costs, &ctx).unwrap();
+    assert!((distance(CostModel::Exact) - 1.0).abs() < f64::EPSILON);
+    assert!(distance(CostModel::Fuzzy).abs() < f64::EPSILON);
+
+    let renamed = tree(Format::Yaml, "devDeps:\n  vitest: ^1\n");
+    assert!(distance(CostModel::Structure).abs() < f64::EPSILON);
+    let fuzzy = edit_distance(&npm, &renamed, &CostModel::Fuzzy, &ctx).unwrap();
+    let exact = edit_distance(&npm, &renamed, &CostModel::Exact, &ctx).unwrap();
+    assert!(fuzzy > 0.0 && fuzzy < exact);
+  }
+
+  #[test]
+  fn scripts_name_the_nodes_they_edit() {
+    let output = tree_edit_distance(
+      EditDistanceInput {
+        source: tree(Format::Json, r#"{"name": "a", "version": "1.0.0"}"#),
+        target: tree(
+          Format::Json,
+          r#"{"name": "a", "version": "1.1.0", "private": true}"#,
+        ),
+        costs: None,
+        operations: None,
+      },
+      &CallContext::new(),
+    )
+    .unwrap();
+    assert!((output.distance - 3.0).abs() < f64::EPSILON);
+    let operations = serde_json::to_value(output.operations.unwrap()).unwrap();
+    assert_eq!(operations[0]["op"], "relabel");
+    assert_eq!(operations[0]["from"]["path"], serde_json::json!([1, 0]));
+    assert_eq!(operations[0]["to"]["value"], "1.1.0");
+    assert_eq!(operations[1]["op"], "insert");
+    assert_eq!(operations[1]["node"]["path"], serde_json::json!([2, 0]));
+    assert_eq!(operations[2]["node"]["value"], "private");
+    assert!(output.similarity > 0.5 && output.similarity < 1.0);
+  }
+
+  #[test]
+  fn refuses_trees_too_large_to_compare() {
+    let wide = node(
+      "list",
+      "",
+      (0..MAX_NODES)
+        .map(|_| node("bool", "true", Vec::new()))
+        .collect(),
+    );
+    let error = edit_distance(&wide, &wide, &CostModel::Exact, &CallContext::new()).unwrap_err();
+    assert_eq!(error.code, "tree-too-large");
+    assert_eq!(error.context["nodes"], MAX_NODES + 1);
+  }
+
+  #[test]
+  fn stops_comparing_once_cancelled() {
+    let source = tree(Format::Json, r#"{"a": [1, 2]}"#);
+    let token = CancellationToken::new();
+    token.cancel();
+    let ctx = CallContext::new().with_cancellation(token);
+    let error = edit_distance(&source, &source, &CostModel::Exact, &ctx).unwrap_err();
+    assert_eq!(error.code, "cancelled");
+  }
+}
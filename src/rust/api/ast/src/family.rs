@@ -0,0 +1,697 @@
+//! Families of documents that share a structure.
+//!
+//! Documents are compared through their [`Fingerprint`]s. A corpus is
+//! clustered bottom-up with average linkage: the two families whose members
+//! are most alike on average merge, until no pair reaches the threshold. A
+//! new document belongs to the family whose members it is most like on
+//! average.
+//!
+//! Without a corpus, documents are matched against exemplars of the
+//! manifests `to_manifest` can extract, one family per manifest, so that a
+//! manifest it does not recognize still gets a best guess.
+
+use gud_common::{log, CallContext, ErrorKind, GudError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::data::{DataNode, Format, FORMATS};
+use crate::fingerprint::Fingerprint;
+use crate::to_manifest::ManifestKind;
+
+/// Average similarity two families need to be merged into one
+pub const DEFAULT_THRESHOLD: f64 = 0.6;
+
+/// Most documents a corpus may hold, as clustering takes cubic time
+pub const MAX_DOCUMENTS: usize = 1000;
+
+/// Known manifests `nearest_family` compares against without a corpus
+const EXEMPLARS: &[(ManifestKind, &str)] = &[
+  (
+    ManifestKind::PackageJson,
+    include_str!("family/exemplars/package.json"),
+  ),
+  (
+    ManifestKind::PackageJson,
+    include_str!("family/exemplars/package-app.json"),
+  ),
+  (
+    ManifestKind::ComposerJson,
+    include_str!("family/exemplars/composer.json"),
+  ),
+  (
+    ManifestKind::CargoToml,
+    include_str!("family/exemplars/Cargo.toml"),
+  ),
+  (
+    ManifestKind::CargoToml,
+    include_str!("family/exemplars/Cargo-workspace.toml"),
+  ),
+  (
+    ManifestKind::Pyproject,
+    include_str!("family/exemplars/pyproject.toml"),
+  ),
+  (
+    ManifestKind::Pyproject,
+    include_str!("family/exemplars/pyproject-poetry.toml"),
+  ),
+  (
+    ManifestKind::Requirements,
+    include_str!("family/exemplars/requirements.txt"),
+  ),
+];
+
+/// A data file to fingerprint
+#[derive(Deserialize, JsonSchema)]
+pub struct Document {
+  /// Name to report the document by; defaults to its path
+  pub name: Option<String>,
+  /// Contents of the file
+  pub source_code: String,
+  /// Data format such as `json` or `yaml`, or a manifest name such as
+  /// `Cargo.toml`
+  pub language: Option<String>,
+  /// Path or file name, used for the format when `language` is absent
+  pub path: Option<String>,
+  /// Family the document is known to belong to, such as `package.json`,
+  /// used to label the clusters it ends up in
+  pub label: Option<String>,
+}
+
+impl Document {
+  fn name(&self, index: usize) -> String {
+    self
+      .name
+      .clone()
+      .or_else(|| self.path.clone())
+      .unwrap_or_else(|| format!("#{index}"))
+  }
+
+  fn fingerprint(&self) -> Result<Fingerprint, GudError> {
+    let tree = parse_document(
+      &self.source_code,
+      self.language.as_deref(),
+      self.path.as_deref(),
+    )?;
+    Ok(Fingerprint::of(&tree))
+  }
+}
+
+/// Parse a document in the data format [`data_format`] finds for it
+pub(crate) fn parse_document(
+  source: &str,
+  language: Option<&str>,
+  path: Option<&str>,
+) -> Result<DataNode, GudError> {
+  let format = data_format(language, path).ok_or_else(|| {
+    GudError::new(
+      ErrorKind::Argument,
+      "unknown-format",
+      format!(
+        "Cannot tell the data format of '{}'",
+        path.or(language).unwrap_or("the document")
+      ),
+    )
+    .with_context(json!({
+      "language": language,
+      "path": path,
+      "supported": FORMATS,
+    }))
+  })?;
+  format.parse(source)
+}
+
+/// Data format named by `language`, either directly or through the
+/// manifest it names, else the one of the file at `path`
+pub(crate) fn data_format(language: Option<&str>, path: Option<&str>) -> Option<Format> {
+  let of_manifest = |manifest: Option<ManifestKind>| manifest.and_then(ManifestKind::format);
+  language
+    .and_then(|language| {
+      Format::from_name(language).or_else(|| of_manifest(ManifestKind::from_name(language)))
+    })
+    .or_else(|| {
+      let path = path?;
+      of_manifest(ManifestKind::from_path(path)).or_else(|| {
+        std::path::Path::new(path)
+          .extension()
+          .and_then(|extension| Format::from_name(&extension.to_string_lossy()))
+      })
+    })
+}
+
+/// Round a score to four decimals for reporting
+fn round(score: f64) -> f64 {
+  (score * 10_000.0).round() / 10_000.0
+}
+
+/// Cluster fingerprints with average linkage
+///
+/// Families merge until no two are on average at least `threshold` alike.
+/// They are lists of indexes into `prints`, in order of their first member.
+/// Clustering fails once `ctx` is cancelled.
+pub fn cluster(
+  prints: &[Fingerprint],
+  threshold: f64,
+  ctx: &CallContext,
+) -> Result<Vec<Vec<usize>>, GudError> {
+  let mut similarity = Vec::with_capacity(prints.len());
+  for a in prints {
+    ctx.check()?;
+    similarity.push(prints.iter().map(|b| a.similarity(b)).collect::<Vec<_>>());
+  }
+  let mut families: Vec<Vec<usize>> = (0..prints.len()).map(|i| vec![i]).collect();
+
+  loop {
+    ctx.check()?;
+    let mut best: Option<(f64, usize, usize)> = None;
+    for a in 0..families.len() {
+      for b in a + 1..families.len() {
+        let linkage = linkage(&similarity, &families[a], &families[b]);
+        if best.is_none_or(|(score, _, _)| linkage > score) {
+          best = Some((linkage, a, b));
+        }
+      }
+    }
+    let Some((score, a, b)) = best.filter(|(score, _, _)| *score >= threshold) else {
+      break;
+    };
+    log::debug(&format!("Merging families {a} and {b} at {score:.3}"));
+    let merged = families.remove(b);
+    families[a].extend(merged);
+  }
+  for family in &mut families {
+    family.sort_unstable();
+  }
+  Ok(families)
+}
+
+/// Mean similarity between the members of two families
+fn linkage(similarity: &[Vec<f64>], a: &[usize], b: &[usize]) -> f64 {
+  let total: f64 = a
+    .iter()
+    .flat_map(|&i| b.iter().map(move |&j| similarity[i][j]))
+    .sum();
+  total / (a.len() * b.len()) as f64
+}
+
+/// Mean similarity of `print` to a family's members
+fn affinity(print: &Fingerprint, members: &[&Fingerprint]) -> f64 {
+  let total: f64 = members.iter().map(|member| print.similarity(member)).sum();
+  total / members.len().max(1) as f64
+}
+
+/// A cluster of documents
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct Family {
+  /// Position of the family in the clustering output
+  pub id: usize,
+  /// Most common label among its members, if any has one
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub label: Option<String>,
+  /// Names of its documents, in input order
+  pub members: Vec<String>,
+  /// Member most like the others
+  pub exemplar: String,
+  /// Mean similarity between its members; 1 for a single member
+  pub cohesion: f64,
+}
+
+/// How alike a document is to one family
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct Candidate {
+  /// Id of the family
+  pub family: usize,
+  /// Label of the family
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub label: Option<String>,
+  /// Mean similarity to the family's members
+  pub similarity: f64,
+}
+
+/// The family a document is nearest to
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct FamilyMatch {
+  /// Id of the nearest family
+  pub family: usize,
+  /// Label of the nearest family
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub label: Option<String>,
+  /// Mean similarity to the nearest family's members
+  pub similarity: f64,
+  /// The similarity weighted by its share of the two best: a close
+  /// runner-up lowers it, no runner-up leaves it as is
+  pub confidence: f64,
+  /// Every family, nearest first
+  pub candidates: Vec<Candidate>,
+}
+
+/// Find the family nearest to `print` among families given by their
+/// labels and members' fingerprints
+#[must_use]
+pub fn nearest(
+  print: &Fingerprint,
+  families: &[(Option<String>, Vec<&Fingerprint>)],
+) -> Option<FamilyMatch> {
+  let mut candidates: Vec<Candidate> = families
+    .iter()
+    .enumerate()
+    .map(|(family, (label, members))| Candidate {
+      family,
+      label: label.clone(),
+      similarity: affinity(print, members),
+    })
+    .collect();
+  candidates.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+
+  let best = candidates.first()?.clone();
+  let runner_up = candidates
+    .get(1)
+    .map_or(0.0, |candidate| candidate.similarity);
+  let confidence = if best.similarity > 0.0 {
+    best.similarity * best.similarity / (best.similarity + runner_up)
+  } else {
+    0.0
+  };
+  for candidate in &mut candidates {
+    candidate.similarity = round(candidate.similarity);
+  }
+  Some(FamilyMatch {
+    family: best.family,
+    label: best.label,
+    similarity: round(best.similarity),
+    confidence: round(confidence),
+    candidates,
+  })
+}
+
+/// Fingerprints of the exemplars, grouped into one family per manifest
+fn exemplar_families() -> &'static [(ManifestKind, Vec<Fingerprint>)] {
+  static FAMILIES: OnceLock<Vec<(ManifestKind, Vec<Fingerprint>)>> = OnceLock::new();
+  FAMILIES.get_or_init(|| {
+    let mut families: Vec<(ManifestKind, Vec<Fingerprint>)> = Vec::new();
+    for (manifest, source) in EXEMPLARS {
+      let Some(tree) = manifest
+        .format()
+        .and_then(|format| format.parse(source).ok())
+      else {
+        continue;
+      };
+      let print = Fingerprint::of(&tree);
+      match families.iter_mut().find(|(kind, _)| kind == manifest) {
+        Some((_, prints)) => prints.push(print),
+        None => families.push((*manifest, vec![print])),
+      }
+    }
+    families
+  })
+}
+
+/// The known manifest whose exemplars `tree` is most like
+#[must_use]
+pub fn nearest_manifest(tree: &DataNode) -> Option<(ManifestKind, FamilyMatch)> {
+  nearest_exemplar(&Fingerprint::of(tree))
+}
+
+fn nearest_exemplar(print: &Fingerprint) -> Option<(ManifestKind, FamilyMatch)> {
+  let families = exemplar_families();
+  let labelled: Vec<(Option<String>, Vec<&Fingerprint>)> = families
+    .iter()
+    .map(|(manifest, prints)| (Some(manifest.name().to_string()), prints.iter().collect()))
+    .collect();
+  let found = nearest(print, &labelled)?;
+  Some((families[found.family].0, found))
+}
+
+/// Describe the families `cluster` found among `documents`
+fn describe(
+  documents: &[Document],
+  prints: &[Fingerprint],
+  families: &[Vec<usize>],
+) -> Vec<Family> {
+  families
+    .iter()
+    .enumerate()
+    .map(|(id, members)| {
+      // Mean similarity of each member to the others
+      let closeness: Vec<f64> = members
+        .iter()
+        .map(|&i| {
+          let others: Vec<&Fingerprint> = members
+            .iter()
+            .filter(|&&j| j != i)
+            .map(|&j| &prints[j])
+            .collect();
+          if others.is_empty() {
+            1.0
+          } else {
+            affinity(&prints[i], &others)
+          }
+        })
+        .collect();
+      let exemplar = (0..members.len())
+        .max_by(|&a, &b| closeness[a].total_cmp(&closeness[b]).then(b.cmp(&a)))
+        .unwrap_or_default();
+
+      let mut labels: BTreeMap<&str, usize> = BTreeMap::new();
+      for &i in members {
+        if let Some(label) = &documents[i].label {
+          *labels.entry(label).or_default() += 1;
+        }
+      }
+      let label = labels
+        .iter()
+        .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+        .map(|(label, _)| (*label).to_string());
+
+      Family {
+        id,
+        label,
+        members: members.iter().map(|&i| documents[i].name(i)).collect(),
+        exemplar: documents[members[exemplar]].name(members[exemplar]),
+        cohesion: round(closeness.iter().sum::<f64>() / closeness.len() as f64),
+      }
+    })
+    .collect()
+}
+
+fn check_threshold(threshold: Option<f64>) -> Result<f64, GudError> {
+  let threshold = threshold.unwrap_or(DEFAULT_THRESHOLD);
+  if (0.0..=1.0).contains(&threshold) {
+    Ok(threshold)
+  } else {
+    Err(
+      GudError::new(
+        ErrorKind::Argument,
+        "invalid-threshold",
+        format!("The threshold must be between 0 and 1, not {threshold}"),
+      )
+      .with_context(json!({ "threshold": threshold })),
+    )
+  }
+}
+
+/// Input of `ast/cluster`
+#[derive(Deserialize, JsonSchema)]
+pub struct ClusterInput {
+  /// Documents to group into families
+  pub documents: Vec<Document>,
+  /// Average similarity two families need to merge, 0.6 by default
+  pub threshold: Option<f64>,
+}
+
+/// Output of `ast/cluster`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ClusterOutput {
+  /// Every family, ordered by their first member
+  pub families: Vec<Family>,
+}
+
+/// Input of `ast/nearest_family`
+#[derive(Deserialize, JsonSchema)]
+pub struct NearestFamilyInput {
+  /// Document to place
+  pub document: Document,
+  /// Documents to cluster into the families to choose from; the known
+  /// manifests when absent
+  pub corpus: Option<Vec<Document>>,
+  /// Threshold for clustering `corpus`, 0.6 by default
+  pub threshold: Option<f64>,
+}
+
+/// Fingerprint one document
+#[allow(clippy::needless_pass_by_value)]
+pub fn fingerprint_document(input: Document) -> Result<Fingerprint, Box<dyn std::error::Error>> {
+  Ok(input.fingerprint()?)
+}
+
+/// Fingerprints of a corpus, which must hold at most [`MAX_DOCUMENTS`]
+fn fingerprint_corpus(
+  documents: &[Document],
+  ctx: &CallContext,
+) -> Result<Vec<Fingerprint>, GudError> {
+  if documents.len() > MAX_DOCUMENTS {
+    return Err(
+      GudError::new(
+        ErrorKind::Argument,
+        "too-many-documents",
+        format!(
+          "A corpus holds at most {MAX_DOCUMENTS} documents, not {}",
+          documents.len()
+        ),
+      )
+      .with_context(json!({ "documents": documents.len(), "max": MAX_DOCUMENTS })),
+    );
+  }
+  documents
+    .iter()
+    .map(|document| {
+      ctx.check()?;
+      document.fingerprint()
+    })
+    .collect()
+}
+
+/// Cluster a corpus of documents into families
+#[allow(clippy::needless_pass_by_value)]
+pub fn cluster_documents(
+  input: ClusterInput,
+  ctx: &CallContext,
+) -> Result<ClusterOutput, Box<dyn std::error::Error>> {
+  let threshold = check_threshold(input.threshold)?;
+  let prints = fingerprint_corpus(&input.documents, ctx)?;
+  log::debug(&format!(
+    "Clustering {} documents at {threshold}",
+    prints.len()
+  ));
+  let families = cluster(&prints, threshold, ctx)?;
+  Ok(ClusterOutput {
+    families: describe(&input.documents, &prints, &families),
+  })
+}
+
+/// Assign a document to its nearest family, among the clusters of a
+/// corpus or the known manifests
+#[allow(clippy::needless_pass_by_value)]
+pub fn nearest_family(
+  input: NearestFamilyInput,
+  ctx: &CallContext,
+) -> Result<FamilyMatch, Box<dyn std::error::Error>> {
+  let print = input.document.fingerprint()?;
+  let threshold = check_threshold(input.threshold)?;
+  let empty = || {
+    GudError::new(
+      ErrorKind::Argument,
+      "empty-corpus",
+      "There is no family to compare the document with",
+    )
+  };
+
+  let Some(corpus) = input.corpus else {
+    let (_, found) = nearest_exemplar(&print).ok_or_else(empty)?;
+    return Ok(found);
+  };
+
+  let prints = fingerprint_corpus(&corpus, ctx)?;
+  let families = cluster(&prints, threshold, ctx)?;
+  let labelled: Vec<(Option<String>, Vec<&Fingerprint>)> = describe(&corpus, &prints, &families)
+    .into_iter()
+    .zip(&families)
+    .map(|(family, members)| (family.label, members.iter().map(|&i| &prints[i]).collect()))
+    .collect();
+  Ok(nearest(&print, &labelled).ok_or_else(empty)?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use gud_common::CancellationToken;
+
+  fn document(name: &str, language: &str, source: &str, label: Option<&str>) -> Document {
+    Document {
+      name: Some(name.to_string()),
+      source_code: source.to_string(),
+      language: Some(language.to_string()),
+      path: None,
+      label: label.map(str::to_string),
+    }
+  }
+
+  fn corpus() -> Vec<Document> {
+    vec![
+      document(
+        "a.json",
+        "json",
+        r#"{"name": "a", "version": "1.0.0", "dependencies": {"x": "^1"}}"#,
+        Some("npm"),
+      ),
+      document(
+        "b.json",
+        "json",
+        r#"{"name": "b", "version": "2.0.0", "dependencies": {"y": "^2", "z": "~3"}, "devDependencies": {"t": "*"}}"#,
+        Some("npm"),
+      ),
+      document(
+        "c.yaml",
+        "yaml",
+        "name: c\nversion: 0.1.0\ndependencies:\n  w: ^4\n",
+        None,
+      ),
+      document("d.txt", "txt", "flask>=3\nrequests\n", Some("pip")),
+      document("e.txt", "txt", "numpy==1.26\n", None),
+    ]
+  }
+
+  #[test]
+  fn clusters_documents_by_structure() {
+    let output = cluster_documents(
+      ClusterInput {
+        documents: corpus(),
+        threshold: None,
+      },
+      &CallContext::new(),
+    )
+    .unwrap();
+    let families: Vec<_> = output
+      .families
+      .iter()
+      .map(|family| (family.label.as_deref(), family.members.clone()))
+      .collect();
+    assert_eq!(
+      families,
+      [
+        (
+          Some("npm"),
+          vec![
+            "a.json".to_string(),
+            "b.json".to_string(),
+            "c.yaml".to_string()
+          ]
+        ),
+        (Some("pip"), vec!["d.txt".to_string(), "e.txt".to_string()]),
+      ]
+    );
+    assert!(output
+      .families
+      .iter()
+      .all(|family| family.cohesion >= DEFAULT_THRESHOLD));
+  }
+
+  #[test]
+  fn assigns_documents_to_the_nearest_cluster() {
+    let found = nearest_family(
+      NearestFamilyInput {
+        document: document(
+          "new.toml",
+          "toml",
+          "name = \"n\"\nversion = \"1\"\n[dependencies]\nq = \"^1\"\n",
+          None,
+        ),
+        corpus: Some(corpus()),
+        threshold: None,
+      },
+      &CallContext::new(),
+    )
+    .unwrap();
+    assert_eq!(found.label.as_deref(), Some("npm"));
+    assert!(found.confidence > 0.4, "{found:?}");
+    assert!(found.confidence <= found.similarity);
+    assert_eq!(found.candidates.len(), 2);
+  }
+
+  #[test]
+  fn guesses_known_manifests_without_a_corpus() {
+    let guess = |language, source| {
+      nearest_family(
+        NearestFamilyInput {
+          document: document("doc", language, source, None),
+          corpus: None,
+          threshold: None,
+        },
+        &CallContext::new(),
+      )
+      .unwrap()
+      .label
+    };
+    assert_eq!(
+      guess("yaml", "name: app\nversion: 1.0.0\nscripts:\n  test: jest\ndependencies:\n  lodash: ^4\ndevDependencies:\n  jest: ^29\n").as_deref(),
+      Some("package.json")
+    );
+    assert_eq!(
+      guess(
+        "json",
+        r#"{"package": {"name": "x", "version": "0.1.0", "edition": "2021", "license": "MIT"},
+            "dependencies": {"serde": {"version": "1", "features": ["derive"]}, "log": "0.4"},
+            "dev-dependencies": {"insta": "1"}}"#
+      )
+      .as_deref(),
+      Some("Cargo.toml")
+    );
+    assert_eq!(
+      guess("txt", "pandas\nscipy>=1.12\n").as_deref(),
+      Some("requirements.txt")
+    );
+  }
+
+  #[test]
+  fn reports_documents_in_unknown_formats() {
+    let error = GudError::from_boxed(
+      fingerprint_document(Document {
+        name: None,
+        source_code: String::new(),
+        language: None,
+        path: Some("deps".to_string()),
+        label: None,
+      })
+      .unwrap_err(),
+    );
+    assert_eq!(error.code, "unknown-format");
+    let error = GudError::from_boxed(
+      cluster_documents(
+        ClusterInput {
+          documents: Vec::new(),
+          threshold: Some(1.5),
+        },
+        &CallContext::new(),
+      )
+      .unwrap_err(),
+    );
+    assert_eq!(error.code, "invalid-threshold");
+  }
+
+  #[test]
+  fn refuses_oversized_corpora() {
+    let documents = (0..=MAX_DOCUMENTS)
+      .map(|_| document("d.txt", "txt", "flask\n", None))
+      .collect();
+    let error = GudError::from_boxed(
+      cluster_documents(
+        ClusterInput {
+          documents,
+          threshold: None,
+        },
+        &CallContext::new(),
+      )
+      .unwrap_err(),
+    );
+    assert_eq!(error.code, "too-many-documents");
+  }
+
+  #[test]
+  fn stops_clustering_once_cancelled() {
+    let token = CancellationToken::new();
+    token.cancel();
+    let error = GudError::from_boxed(
+      cluster_documents(
+        ClusterInput {
+          documents: corpus(),
+          threshold: None,
+        },
+        &CallContext::new().with_cancellation(token),
+      )
+      .unwrap_err(),
+    );
+    assert_eq!(error.code, "cancelled");
+  }
+}
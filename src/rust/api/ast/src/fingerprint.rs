@@ -0,0 +1,264 @@
+//! Structural fingerprints of intermediate trees.
+//!
+//! A fingerprint keeps what a tree looks like and drops what it says: the
+//! paths of its upper keys, how many nodes sit at each depth, how many
+//! children its maps and lists have, and the shapes of its subtrees. Two
+//! manifests of the same kind share most of these even when they list
+//! entirely different dependencies, whatever format they are written in.
+
+use gud_std::canonical::digest;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::data::DataNode;
+
+/// How many map keys deep key paths go; deeper keys are mostly the names
+/// of dependencies, scripts and other user content
+pub const KEY_PATH_DEPTH: usize = 2;
+
+/// Weights of each part of the fingerprint in [`Fingerprint::similarity`]
+const KEY_PATH_WEIGHT: f64 = 0.5;
+const SHAPE_WEIGHT: f64 = 0.3;
+const DEPTH_WEIGHT: f64 = 0.1;
+const ARITY_WEIGHT: f64 = 0.1;
+
+/// Hex digits kept from the digest of a subtree shape
+const SHAPE_DIGITS: usize = 16;
+
+/// Shape of a data tree, independent of its values
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Fingerprint {
+  /// Paths of the keys down to [`KEY_PATH_DEPTH`], joined by `.` and with
+  /// `[]` for list items. Keys are lowercased without `-` and `_`, so
+  /// `devDependencies` and `dev-dependencies` are the same key.
+  pub key_paths: BTreeSet<String>,
+  /// Number of nodes at each depth, the root being at depth 0
+  pub depths: Vec<usize>,
+  /// Number of maps and lists by their count of children, in buckets of
+  /// powers of two: none, 1, 2-3, 4-7 and so on
+  pub arities: Vec<usize>,
+  /// How many subtrees have each shape, by digest. A shape is the kind of
+  /// a node and the distinct shapes of its children, ignoring keys, values
+  /// and how many children share a shape.
+  pub shapes: BTreeMap<String, usize>,
+}
+
+impl Fingerprint {
+  /// Fingerprint of `tree`
+  #[must_use]
+  pub fn of(tree: &DataNode) -> Self {
+    let mut fingerprint = Self::default();
+    fingerprint.visit(tree, 0, Some(""));
+    fingerprint
+  }
+
+  /// Record `node` and its children, returning its shape. `path` is the
+  /// key path of `node` while it is still short enough to keep.
+  fn visit(&mut self, node: &DataNode, depth: usize, path: Option<&str>) -> String {
+    if self.depths.len() <= depth {
+      self.depths.resize(depth + 1, 0);
+    }
+    self.depths[depth] += 1;
+
+    let (open, children): (&str, Vec<String>) = match node {
+      DataNode::Scalar { value, .. } => return value.type_name().to_string(),
+      DataNode::Map { entries, .. } => {
+        let children = entries
+          .iter()
+          .map(|entry| {
+            let child = path
+              .filter(|path| key_depth(path) < KEY_PATH_DEPTH)
+              .map(|path| {
+                let key = normalize(&entry.key);
+                if path.is_empty() {
+                  key
+                } else {
+                  format!("{path}.{key}")
+                }
+              });
+            if let Some(child) = &child {
+              self.key_paths.insert(child.clone());
+            }
+            self.visit(&entry.value, depth + 1, child.as_deref())
+          })
+          .collect();
+        ("map", children)
+      }
+      DataNode::List { items, .. } => {
+        let child = path.map(|path| format!("{path}[]"));
+        let children = items
+          .iter()
+          .map(|item| self.visit(item, depth + 1, child.as_deref()))
+          .collect();
+        ("list", children)
+      }
+    };
+
+    let bucket = usize::BITS - children.len().leading_zeros();
+    let bucket = bucket as usize;
+    if self.arities.len() <= bucket {
+      self.arities.resize(bucket + 1, 0);
+    }
+    self.arities[bucket] += 1;
+
+    let children: BTreeSet<String> = children.into_iter().collect();
+    let text = format!(
+      "{open}({})",
+      children.into_iter().collect::<Vec<_>>().join(",")
+    );
+    let hash = digest(&Value::String(text));
+    let shape = hash
+      .rsplit(':')
+      .next()
+      .unwrap_or(&hash)
+      .chars()
+      .take(SHAPE_DIGITS)
+      .collect::<String>();
+    *self.shapes.entry(shape.clone()).or_default() += 1;
+    shape
+  }
+
+  /// How alike two fingerprints are, from 0 for nothing in common to 1 for
+  /// the same structure
+  #[must_use]
+  pub fn similarity(&self, other: &Self) -> f64 {
+    ARITY_WEIGHT.mul_add(
+      histogram_overlap(&self.arities, &other.arities),
+      DEPTH_WEIGHT.mul_add(
+        histogram_overlap(&self.depths, &other.depths),
+        KEY_PATH_WEIGHT.mul_add(
+          key_path_overlap(&self.key_paths, &other.key_paths),
+          SHAPE_WEIGHT * shape_overlap(&self.shapes, &other.shapes),
+        ),
+      ),
+    )
+  }
+}
+
+/// Key as compared across ecosystems
+pub(crate) fn normalize(key: &str) -> String {
+  key
+    .chars()
+    .filter(|c| !matches!(c, '-' | '_'))
+    .flat_map(char::to_lowercase)
+    .collect()
+}
+
+/// Number of keys in a key path
+fn key_depth(path: &str) -> usize {
+  path
+    .split('.')
+    .filter(|segment| !segment.trim_end_matches("[]").is_empty())
+    .count()
+}
+
+/// Mean of the shares of `a` and of `b` that they have in common. Unlike
+/// the Jaccard index this does not sink when one side is much larger, as
+/// when a short manifest is compared with a thorough one. Two empty sides
+/// are alike.
+fn overlap(shared: f64, a: f64, b: f64) -> f64 {
+  match (a > 0.0, b > 0.0) {
+    (false, false) => 1.0,
+    (true, true) => f64::midpoint(shared / a, shared / b),
+    _ => 0.0,
+  }
+}
+
+/// Overlap of two key path sets, where a path counts less the deeper it is
+fn key_path_overlap(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+  let weight = |path: &String| 1.0 / key_depth(path).max(1) as f64;
+  overlap(
+    a.intersection(b).map(weight).sum(),
+    a.iter().map(weight).sum(),
+    b.iter().map(weight).sum(),
+  )
+}
+
+/// Overlap of two multisets of shapes
+fn shape_overlap(a: &BTreeMap<String, usize>, b: &BTreeMap<String, usize>) -> f64 {
+  let shared: usize = a
+    .iter()
+    .map(|(shape, &count)| count.min(b.get(shape).copied().unwrap_or(0)))
+    .sum();
+  overlap(
+    shared as f64,
+    a.values().sum::<usize>() as f64,
+    b.values().sum::<usize>() as f64,
+  )
+}
+
+/// Overlap of two histograms once each is scaled to a total of 1
+fn histogram_overlap(a: &[usize], b: &[usize]) -> f64 {
+  let (total_a, total_b) = (a.iter().sum::<usize>(), b.iter().sum::<usize>());
+  if total_a == 0 || total_b == 0 {
+    return if total_a == total_b { 1.0 } else { 0.0 };
+  }
+  (0..a.len().max(b.len()))
+    .map(|i| {
+      let x = a.get(i).copied().unwrap_or(0) as f64 / total_a as f64;
+      let y = b.get(i).copied().unwrap_or(0) as f64 / total_b as f64;
+      x.min(y)
+    })
+    .sum()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::data::Format;
+
+  fn fingerprint(format: Format, source: &str) -> Fingerprint {
+    Fingerprint::of(&format.parse(source).unwrap())
+  }
+
+  #[test]
+  fn records_key_paths_depths_and_arities() {
+    let print = fingerprint(
+      Format::Json,
+      r#"{"name": "a", "devDependencies": {"x": "1", "y": {"version": "2"}}, "files": ["a", "b"]}"#,
+    );
+    assert_eq!(
+      print
+        .key_paths
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>(),
+      [
+        "devdependencies",
+        "devdependencies.x",
+        "devdependencies.y",
+        "files",
+        "name"
+      ]
+    );
+    assert_eq!(print.depths, [1, 3, 4, 1]);
+    // One map with a child, two maps and a list with two or three
+    assert_eq!(print.arities, [0, 1, 3]);
+  }
+
+  #[test]
+  fn same_structure_in_another_format_is_alike() {
+    let json = fingerprint(
+      Format::Json,
+      r#"{"name": "a", "dependencies": {"left-pad": "^1.0"}}"#,
+    );
+    let yaml = fingerprint(Format::Yaml, "name: b\ndependencies:\n  lodash: ^4.0\n");
+    let toml = fingerprint(
+      Format::Toml,
+      "[package]\nname = \"c\"\nedition = \"2021\"\n\n[dev-dependencies]\nserde = { version = \"1\" }\n",
+    );
+    assert!((json.similarity(&json) - 1.0).abs() < f64::EPSILON);
+    assert!(json.similarity(&yaml) > 0.7, "{}", json.similarity(&yaml));
+    assert!(json.similarity(&yaml) > json.similarity(&toml));
+  }
+
+  #[test]
+  fn trees_without_keys_compare_by_shape() {
+    let a = fingerprint(Format::Lines, "requests>=2\nflask\n");
+    let b = fingerprint(Format::Lines, "numpy\n");
+    assert!(a.key_paths.is_empty());
+    assert!(a.similarity(&b) > 0.6, "{}", a.similarity(&b));
+  }
+}
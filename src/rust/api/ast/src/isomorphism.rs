@@ -0,0 +1,263 @@
+//! Subtree isomorphism between [`AstNode`] trees.
+//!
+//! Two subtrees are isomorphic when their nodes pair up one for one, each
+//! pair being the same under a [`Costs`] model, that is free to relabel.
+//! Children pair up in order for source code, or in any order for data,
+//! where the order of a map's keys means nothing; lists are then
+//! unordered too.
+//!
+//! Matching pairs the subtrees two trees have in common, largest first,
+//! so what is left over is what an edit would have to change.
+
+use gud_common::log;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::edit_distance::{CostModel, Costs, NodeRef};
+use crate::to_tree::AstNode;
+
+/// Whether `a` and `b` are the same subtree under `costs`, with their
+/// children in the same order if `ordered`
+#[must_use]
+pub fn isomorphic(a: &AstNode, b: &AstNode, ordered: bool, costs: &impl Costs) -> bool {
+  if a.children.len() != b.children.len() || costs.relabel(a, b) > 0.0 {
+    return false;
+  }
+  if ordered {
+    return a
+      .children
+      .iter()
+      .zip(&b.children)
+      .all(|(x, y)| isomorphic(x, y, ordered, costs));
+  }
+  // Isomorphism is an equivalence, so any child that fits will do
+  let mut paired = vec![false; b.children.len()];
+  a.children.iter().all(|x| {
+    let found = b
+      .children
+      .iter()
+      .enumerate()
+      .position(|(index, y)| !paired[index] && isomorphic(x, y, ordered, costs));
+    found.map(|index| paired[index] = true).is_some()
+  })
+}
+
+/// Nodes of a tree in preorder, with their paths and subtree sizes
+fn subtrees<'a>(
+  node: &'a AstNode,
+  path: &mut Vec<usize>,
+  out: &mut Vec<(&'a AstNode, Vec<usize>, usize)>,
+) -> usize {
+  let at = out.len();
+  out.push((node, path.clone(), 0));
+  let mut size = 1;
+  for (index, child) in node.children.iter().enumerate() {
+    path.push(index);
+    size += subtrees(child, path, out);
+    path.pop();
+  }
+  out[at].2 = size;
+  size
+}
+
+fn preorder(root: &AstNode) -> Vec<(&AstNode, Vec<usize>, usize)> {
+  let mut nodes = Vec::new();
+  subtrees(root, &mut Vec::new(), &mut nodes);
+  nodes
+}
+
+/// Whether `path` lies within one of the `taken` subtrees
+fn within(taken: &[Vec<usize>], path: &[usize]) -> bool {
+  taken.iter().any(|root| path.starts_with(root))
+}
+
+/// Nodes of `tree` whose subtrees are isomorphic to `pattern`, in preorder
+#[must_use]
+pub fn occurrences(
+  pattern: &AstNode,
+  tree: &AstNode,
+  ordered: bool,
+  costs: &impl Costs,
+) -> Vec<NodeRef> {
+  let size = preorder(pattern).len();
+  preorder(tree)
+    .into_iter()
+    .filter(|(node, _, count)| *count == size && isomorphic(pattern, node, ordered, costs))
+    .map(|(node, path, _)| NodeRef::new(node, &path))
+    .collect()
+}
+
+/// A subtree found in both trees
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct SubtreeMatch {
+  /// Root of the subtree in the source tree
+  pub source: NodeRef,
+  /// Root of the subtree in the target tree
+  pub target: NodeRef,
+  /// Number of nodes in the subtree
+  pub size: usize,
+}
+
+/// Pair the largest subtrees `source` and `target` have in common, each
+/// node being in one pair at most. Subtrees smaller than `min_size` are
+/// left unpaired.
+#[must_use]
+pub fn common_subtrees(
+  source: &AstNode,
+  target: &AstNode,
+  ordered: bool,
+  min_size: usize,
+  costs: &impl Costs,
+) -> Vec<SubtreeMatch> {
+  let mut sources = preorder(source);
+  // Largest first, then in preorder
+  sources.sort_by_key(|&(_, _, size)| std::cmp::Reverse(size));
+  let targets = preorder(target);
+
+  let mut matches = Vec::new();
+  let (mut taken_sources, mut taken_targets) = (Vec::new(), Vec::new());
+  for (node, path, size) in sources {
+    // Subtrees are visited largest first, so an ancestor of a paired
+    // subtree is too large to pair with anything left
+    if size < min_size || within(&taken_sources, &path) {
+      continue;
+    }
+    let Some((other, other_path, _)) = targets.iter().find(|(other, other_path, count)| {
+      *count == size
+        && !within(&taken_targets, other_path)
+        && isomorphic(node, other, ordered, costs)
+    }) else {
+      continue;
+    };
+    matches.push(SubtreeMatch {
+      source: NodeRef::new(node, &path),
+      target: NodeRef::new(other, other_path),
+      size,
+    });
+    taken_sources.push(path);
+    taken_targets.push(other_path.clone());
+  }
+  matches
+}
+
+/// Input of `ast/match_subtrees`
+#[derive(Deserialize, JsonSchema)]
+pub struct MatchSubtreesInput {
+  /// Tree whose subtrees are looked for
+  pub source: AstNode,
+  /// Tree searched for them
+  pub target: AstNode,
+  /// Whether children must be in the same order, as in source code; off by
+  /// default, as for data
+  pub ordered: Option<bool>,
+  /// When two nodes are the same; `exact` by default
+  pub costs: Option<CostModel>,
+  /// Smallest subtree worth pairing, 1 by default
+  pub min_size: Option<usize>,
+}
+
+/// Output of `ast/match_subtrees`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct MatchSubtreesOutput {
+  /// Where the whole source tree occurs within the target
+  pub occurrences: Vec<NodeRef>,
+  /// Subtrees in common, largest first
+  pub matches: Vec<SubtreeMatch>,
+  /// Share of the source's nodes within `matches`
+  pub coverage: f64,
+}
+
+/// Find where one tree occurs in another and what subtrees they share
+#[allow(clippy::needless_pass_by_value)]
+pub fn match_subtrees(
+  input: MatchSubtreesInput,
+) -> Result<MatchSubtreesOutput, Box<dyn std::error::Error>> {
+  let costs = input.costs.unwrap_or_default();
+  let ordered = input.ordered.unwrap_or(false);
+  log::debug(&format!(
+    "Matching subtrees with {costs:?} labels, ordered: {ordered}"
+  ));
+  let matches = common_subtrees(
+    &input.source,
+    &input.target,
+    ordered,
+    input.min_size.unwrap_or(1),
+    &costs,
+  );
+  let covered: usize = matches.iter().map(|found| found.size).sum();
+  Ok(MatchSubtreesOutput {
+    occurrences: occurrences(&input.source, &input.target, ordered, &costs),
+    coverage: covered as f64 / preorder(&input.source).len() as f64,
+    matches,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::data::Format;
+
+  fn tree(format: Format, source: &str) -> AstNode {
+    format.parse(source).unwrap().to_ast()
+  }
+
+  #[test]
+  fn maps_match_in_any_order_unless_ordered() {
+    let a = tree(Format::Json, r#"{"a": 1, "b": [true, "x"]}"#);
+    let b = tree(Format::Yaml, "b:\n  - true\n  - x\na: 1\n");
+    assert!(isomorphic(&a, &b, false, &CostModel::Exact));
+    assert!(!isomorphic(&a, &b, true, &CostModel::Exact));
+    let c = tree(Format::Json, r#"{"a": 1, "b": ["y", true]}"#);
+    assert!(!isomorphic(&a, &c, false, &CostModel::Exact));
+    assert!(isomorphic(&a, &c, false, &CostModel::Structure));
+  }
+
+  #[test]
+  fn finds_every_occurrence_of_a_pattern() {
+    let pattern = tree(Format::Json, r#"{"version": "1.0"}"#);
+    let found = occurrences(
+      &pattern,
+      &tree(
+        Format::Json,
+        r#"{"a": {"version": "2.0"}, "b": {"version": "1.0"}, "c": [{"version": "1"}]}"#,
+      ),
+      false,
+      &CostModel::Structure,
+    );
+    let paths: Vec<_> = found.iter().map(|node| node.path.clone()).collect();
+    assert_eq!(paths, [vec![0, 0], vec![1, 0], vec![2, 0, 0]]);
+  }
+
+  #[test]
+  fn pairs_the_largest_common_subtrees() {
+    let output = match_subtrees(MatchSubtreesInput {
+      source: tree(
+        Format::Json,
+        r#"{"dependencies": {"a": "^1", "b": "^2"}, "name": "x"}"#,
+      ),
+      target: tree(
+        Format::Toml,
+        "name = \"y\"\n[tool.deps]\nb = \"^2\"\na = \"^1\"\n",
+      ),
+      ordered: None,
+      costs: None,
+      min_size: Some(2),
+    })
+    .unwrap();
+    let pairs: Vec<_> = output
+      .matches
+      .iter()
+      .map(|found| {
+        (
+          found.source.path.clone(),
+          found.target.path.clone(),
+          found.size,
+        )
+      })
+      .collect();
+    // The dependency map moved under another key; the names differ
+    assert_eq!(pairs, [(vec![0, 0], vec![1, 0, 0, 0], 5)]);
+    assert!(output.occurrences.is_empty());
+    assert!((output.coverage - 5.0 / 9.0).abs() < 1e-9);
+  }
+}
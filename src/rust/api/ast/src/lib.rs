@@ -2,3 +2,112 @@
 //!
 //! This crate provides parsing and AST manipulation functionality
 //! for working with code and configuration structures.
+
+// Scores divide counts of nodes, keys, characters and documents, which
+// inputs are far too small to bring anywhere near 2^52
+#![allow(clippy::cast_precision_loss)]
+
+use gud_common::{Handler, Registry};
+
+pub mod data;
+pub mod edit_distance;
+pub mod family;
+pub mod fingerprint;
+pub mod isomorphism;
+pub mod planner;
+pub mod to_keepfile;
+pub mod to_manifest;
+pub mod to_tree;
+
+/// Name under which this crate's APIs are registered
+pub const MODULE: &str = "ast";
+
+/// Register every `ast` API with the given registry
+pub fn register(registry: &mut Registry) {
+  let version = env!("CARGO_PKG_VERSION");
+  registry
+    .register(Handler::required_input(
+      MODULE,
+      "to_tree",
+      version,
+      to_tree::parse_to_ast_tree,
+    ))
+    .register(Handler::new(
+      MODULE,
+      "to_manifest",
+      version,
+      to_manifest::process_ast_to_manifest,
+    ))
+    .register(Handler::required_input(
+      MODULE,
+      "to_keepfile",
+      version,
+      to_keepfile::parse_to_keepfile,
+    ))
+    .register(Handler::required_input(
+      MODULE,
+      "fingerprint",
+      version,
+      family::fingerprint_document,
+    ))
+    .register(Handler::required_input_with_context(
+      MODULE,
+      "cluster",
+      version,
+      family::cluster_documents,
+    ))
+    .register(Handler::required_input_with_context(
+      MODULE,
+      "nearest_family",
+      version,
+      family::nearest_family,
+    ))
+    .register(Handler::required_input_with_context(
+      MODULE,
+      "edit_distance",
+      version,
+      edit_distance::tree_edit_distance,
+    ))
+    .register(Handler::required_input(
+      MODULE,
+      "match_subtrees",
+      version,
+      isomorphism::match_subtrees,
+    ))
+    .register(Handler::required_input(
+      MODULE,
+      "plan_translation",
+      version,
+      planner::plan_translation,
+    ))
+    .register(Handler::required_input(
+      MODULE,
+      "apply_plan",
+      version,
+      planner::apply_plan,
+    ))
+    .register(Handler::required_input(
+      MODULE,
+      "check_rules",
+      version,
+      to_manifest::rules::check_rules,
+    ));
+}
+
+gud_common::export_c_abi!(
+  register,
+  ast,
+  [
+    to_tree,
+    to_manifest,
+    to_keepfile,
+    fingerprint,
+    cluster,
+    nearest_family,
+    edit_distance,
+    match_subtrees,
+    plan_translation,
+    apply_plan,
+    check_rules
+  ]
+);
@@ -0,0 +1,954 @@
+//! Plans translating a manifest's intermediate tree into the universal
+//! shape of K-space.
+//!
+//! The universal shape is that of `klep.deps`, as [`DepsFile`] holds it:
+//! the root is a map whose `dependencies`, and `devDependencies` for dev
+//! ones, map each dependency's name to its [`KlepDependency`] entry:
+//!
+//! ```text
+//! {"dependencies": {"lodash": {"url": "", "version": "^4.17.0", "extract": "all"}},
+//!  "devDependencies": {"jest": {"url": "", "version": "^29.0.0", "extract": "all"}}}
+//! ```
+//!
+//! [`DepsFile`]: crate::to_manifest::DepsFile
+//!
+//! The planner first finds the blocks of dependencies in the tree, where
+//! the manifest's family is known to keep them or under keys that look
+//! like it. It then searches, cheapest first, through the ways of folding
+//! each block in: how to split a list of requirement strings, which blocks
+//! become `dependencies` and `devDependencies` and which are merged into
+//! them, and whether a block that only looks like dependencies is left out.
+//! A sequence of [`Operation`]s is a plan once applying it satisfies every
+//! constraint of the universal shape.
+
+pub mod operation;
+
+use gud_common::{log, ErrorKind, GudError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::data::DataNode;
+use crate::family::{nearest_manifest, parse_document};
+use crate::fingerprint::normalize;
+use crate::to_manifest::{
+  DependencyKind, KlepDependency, ManifestKind, GUESS_CONFIDENCE, MANIFESTS,
+};
+use operation::{display, is_entry, kind_name, Operation, RequirementSyntax, Shape};
+
+/// Key the universal shape keeps dependencies under
+pub const DEPENDENCIES: &str = "dependencies";
+
+/// Key the universal shape keeps dev dependencies under
+pub const DEV_DEPENDENCIES: &str = "devDependencies";
+
+/// Plans returned unless the input asks for another number
+pub const DEFAULT_LIMIT: usize = 3;
+
+/// Cost of one operation
+const STEP_COST: u32 = 2;
+/// Extra cost of splitting requirements in a syntax the family does not
+/// write them in
+const FOREIGN_SYNTAX_COST: u32 = 1;
+/// Cost of leaving out a block that only looks like dependencies, more
+/// than folding it in usually is
+const SKIP_COST: u32 = 10;
+/// Partial plans explored before the search gives up on finding more
+const MAX_STATES: usize = 10_000;
+
+/// Blocks of dependencies each family keeps, as key paths where `*`
+/// stands for any key
+const fn known_blocks(
+  family: ManifestKind,
+) -> &'static [(&'static [&'static str], DependencyKind)] {
+  use DependencyKind::{Build, Dev, Normal, Optional, Peer};
+  match family {
+    ManifestKind::PackageJson => &[
+      (&["dependencies"], Normal),
+      (&["devDependencies"], Dev),
+      (&["peerDependencies"], Peer),
+      (&["optionalDependencies"], Optional),
+    ],
+    ManifestKind::CargoToml => &[
+      (&["dependencies"], Normal),
+      (&["dev-dependencies"], Dev),
+      (&["build-dependencies"], Build),
+      (&["workspace", "dependencies"], Normal),
+      (&["target", "*", "dependencies"], Normal),
+      (&["target", "*", "dev-dependencies"], Dev),
+      (&["target", "*", "build-dependencies"], Build),
+    ],
+    ManifestKind::Pyproject => &[
+      (&["project", "dependencies"], Normal),
+      (&["project", "optional-dependencies", "*"], Optional),
+      (&["tool", "poetry", "dependencies"], Normal),
+      (&["tool", "poetry", "dev-dependencies"], Dev),
+      (&["tool", "poetry", "group", "*", "dependencies"], Dev),
+    ],
+    ManifestKind::Requirements => &[(&[], Normal)],
+    ManifestKind::ComposerJson => &[(&["require"], Normal), (&["require-dev"], Dev)],
+    ManifestKind::GoMod | ManifestKind::Gemfile | ManifestKind::Rockspec => &[],
+  }
+}
+
+/// Key the universal shape keeps dependencies of `kind` under
+const fn target(kind: DependencyKind) -> &'static str {
+  match kind {
+    DependencyKind::Dev => DEV_DEPENDENCIES,
+    _ => DEPENDENCIES,
+  }
+}
+
+/// Syntax the family writes requirement strings in
+const fn native_syntax(family: Option<ManifestKind>) -> Option<RequirementSyntax> {
+  match family {
+    Some(ManifestKind::PackageJson) => Some(RequirementSyntax::Npm),
+    Some(ManifestKind::Requirements | ManifestKind::Pyproject) => Some(RequirementSyntax::Pep508),
+    _ => None,
+  }
+}
+
+/// Kind of dependencies a key path holds, if its last key looks like it
+/// names a block of them
+fn looks_like_block(path: &[String]) -> Option<DependencyKind> {
+  let key = normalize(path.last()?);
+  let named = key.ends_with("dependencies") || key.ends_with("deps") || key.starts_with("require");
+  if !named {
+    return None;
+  }
+  let segments: Vec<String> = path.iter().map(|segment| normalize(segment)).collect();
+  let any = |words: &[&str]| {
+    segments
+      .iter()
+      .any(|segment| words.iter().any(|word| segment.contains(word)))
+  };
+  Some(if any(&["dev", "test"]) {
+    DependencyKind::Dev
+  } else if any(&["peer"]) {
+    DependencyKind::Peer
+  } else if any(&["optional", "extra"]) {
+    DependencyKind::Optional
+  } else if any(&["build"]) {
+    DependencyKind::Build
+  } else {
+    DependencyKind::Normal
+  })
+}
+
+/// A node of the tree holding dependencies
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Block {
+  /// Keys leading from the root to the node
+  pub path: Vec<String>,
+  /// Kind of the dependencies it holds
+  pub kind: DependencyKind,
+  /// Whether the family is known to keep dependencies there, rather than
+  /// the key only looking like it
+  pub known: bool,
+  /// Line the node starts on
+  pub line: usize,
+}
+
+fn node_at<'a>(tree: &'a DataNode, path: &[String]) -> Option<&'a DataNode> {
+  path.iter().try_fold(tree, |node, key| node.get(key))
+}
+
+/// Call `found` with each node whose key path matches `pattern`
+pub(crate) fn matching<'a>(
+  node: &'a DataNode,
+  pattern: &[&str],
+  path: &mut Vec<String>,
+  found: &mut dyn FnMut(&[String], &'a DataNode),
+) {
+  let Some((key, rest)) = pattern.split_first() else {
+    found(path, node);
+    return;
+  };
+  for entry in node.entries() {
+    if *key == "*" || entry.key == *key {
+      path.push(entry.key.clone());
+      matching(&entry.value, rest, path, found);
+      path.pop();
+    }
+  }
+}
+
+/// Maps and lists under keys that look like blocks, down to a few levels.
+/// A map of lists, such as extras, is a block per list. Nodes within a block
+/// already found are passed over, and nodes around one are only looked
+/// through, so that no block is found twice.
+fn look(node: &DataNode, path: &mut Vec<String>, blocks: &mut Vec<Block>) {
+  const DEPTH: usize = 4;
+  for entry in node.entries() {
+    path.push(entry.key.clone());
+    let is_container = matches!(entry.value, DataNode::Map { .. } | DataNode::List { .. });
+    let groups = !entry.value.entries().is_empty()
+      && entry
+        .value
+        .entries()
+        .iter()
+        .all(|group| matches!(group.value, DataNode::List { .. }));
+    let around = blocks.iter().any(|block| block.path.starts_with(path));
+    match looks_like_block(path) {
+      _ if blocks.iter().any(|block| path.starts_with(&block.path)) => {}
+      Some(kind) if groups && !around => {
+        for group in entry.value.entries() {
+          path.push(group.key.clone());
+          blocks.push(Block {
+            path: path.clone(),
+            kind,
+            known: false,
+            line: group.key_span.start.line,
+          });
+          path.pop();
+        }
+      }
+      Some(kind) if is_container && !around => blocks.push(Block {
+        path: path.clone(),
+        kind,
+        known: false,
+        line: entry.key_span.start.line,
+      }),
+      _ if path.len() < DEPTH => look(&entry.value, path, blocks),
+      _ => {}
+    }
+    path.pop();
+  }
+}
+
+/// Blocks of dependencies in `tree`: where `family` keeps them, then
+/// wherever else keys look like they name some, in the order of the file
+#[must_use]
+pub fn find_blocks(tree: &DataNode, family: Option<ManifestKind>) -> Vec<Block> {
+  let mut blocks = Vec::new();
+  for (pattern, kind) in family.map_or(&[][..], known_blocks) {
+    matching(tree, pattern, &mut Vec::new(), &mut |path, node| {
+      if matches!(node, DataNode::Map { .. } | DataNode::List { .. }) {
+        blocks.push(Block {
+          path: path.to_vec(),
+          kind: *kind,
+          known: true,
+          line: node.span().start.line,
+        });
+      }
+    });
+  }
+  if family.is_none() && matches!(tree, DataNode::List { .. }) {
+    blocks.push(Block {
+      path: Vec::new(),
+      kind: DependencyKind::Normal,
+      known: false,
+      line: tree.span().start.line,
+    });
+  }
+  if !blocks.iter().any(|block| block.path.is_empty()) {
+    look(tree, &mut Vec::new(), &mut blocks);
+  }
+  blocks.sort_by_key(|block| (block.line, block.path.clone()));
+  blocks
+}
+
+/// Ways `tree` falls short of the universal shape, including `blocks` it
+/// still holds outside `dependencies` and `devDependencies`
+#[must_use]
+pub fn violations(tree: &DataNode, blocks: &[&Block]) -> Vec<String> {
+  let mut violations = Vec::new();
+  if !matches!(tree, DataNode::Map { .. }) {
+    violations.push("The root is not a map".to_string());
+  }
+  for (key, required) in [(DEPENDENCIES, true), (DEV_DEPENDENCIES, false)] {
+    match tree.get(key) {
+      None if required => violations.push(format!("There is no `{key}` map")),
+      None => {}
+      Some(DataNode::Map { entries, .. }) => {
+        for entry in entries {
+          if let Err(error) = serde_json::from_value::<KlepDependency>(entry.value.to_value()) {
+            violations.push(format!(
+              "`{key}.{}` is not a `klep.deps` entry: {error}",
+              entry.key
+            ));
+          }
+        }
+      }
+      Some(_) => violations.push(format!("`{key}` is not a map")),
+    }
+  }
+  for block in blocks {
+    // A root block is left as the map around `dependencies`
+    let folded = block.path.is_empty() || block.path == [target(block.kind)];
+    if !folded && node_at(tree, &block.path).is_some() {
+      violations.push(format!(
+        "`{}` still holds dependencies",
+        display(&block.path)
+      ));
+    }
+  }
+  violations
+}
+
+/// An operation and why the plan makes it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Step {
+  /// What the step does to the tree
+  #[serde(flatten)]
+  pub operation: Operation,
+  /// Why the plan makes it, in a sentence
+  pub explanation: String,
+}
+
+/// A sequence of operations turning the tree into the universal shape
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Plan {
+  /// 1 for the cheapest plan
+  pub rank: usize,
+  /// Two points per operation, more for guesses and left-out blocks
+  pub cost: u32,
+  /// Operations in the order they apply
+  pub steps: Vec<Step>,
+  /// What the plan does and what it leaves out, in prose
+  pub explanation: String,
+  /// The tree once the plan is applied
+  pub result: Value,
+}
+
+impl Plan {
+  /// The plan as Markdown for a person to review
+  #[must_use]
+  pub fn review(&self) -> String {
+    let steps = self
+      .steps
+      .iter()
+      .enumerate()
+      .map(|(number, step)| format!("{}. {}\n", number + 1, step.explanation))
+      .collect::<Vec<_>>()
+      .concat();
+    format!(
+      "## Plan {} (cost {})\n\n{}\n\n{steps}",
+      self.rank, self.cost, self.explanation
+    )
+  }
+}
+
+/// A plan in the making
+#[derive(Clone)]
+struct Partial {
+  tree: DataNode,
+  steps: Vec<Step>,
+  /// Index of the next block to fold in
+  next: usize,
+  /// Blocks left out
+  skipped: Vec<usize>,
+}
+
+const fn syntax_name(syntax: RequirementSyntax) -> &'static str {
+  match syntax {
+    RequirementSyntax::Pep508 => "PEP 508 requirements like `requests>=2.31`",
+    RequirementSyntax::Npm => "npm specs like `lodash@^4.17.0`",
+    RequirementSyntax::Spaced => "a name and a version apart, like `rack 3.0.9`",
+  }
+}
+
+struct Planner<'a> {
+  blocks: &'a [Block],
+  family: Option<ManifestKind>,
+}
+
+impl Planner<'_> {
+  /// Ways of bringing the block's node to a map of versions or records,
+  /// with their extra cost
+  fn reshapings(&self, block: &Block, node: &DataNode) -> Vec<(Vec<Step>, u32)> {
+    let at = format!("`{}` (line {})", display(&block.path), block.line);
+    let path = block.path.clone();
+    let key_by_name = Step {
+      operation: Operation::Coerce {
+        path: path.clone(),
+        to: Shape::Map,
+      },
+      explanation: format!("Key the records of {at} by their names"),
+    };
+    match node {
+      DataNode::Map { .. } => vec![(Vec::new(), 0)],
+      DataNode::List { items, .. } if items.iter().all(|item| item.as_str().is_some()) => {
+        let native = native_syntax(self.family);
+        let mut syntaxes = vec![
+          RequirementSyntax::Pep508,
+          RequirementSyntax::Npm,
+          RequirementSyntax::Spaced,
+        ];
+        syntaxes.sort_by_key(|syntax| Some(*syntax) != native);
+        syntaxes
+          .into_iter()
+          .filter(|syntax| {
+            // The node stands in for the whole tree here
+            let split = Operation::Split {
+              path: Vec::new(),
+              syntax: *syntax,
+            };
+            split.apply(&mut node.clone()).is_ok()
+          })
+          .map(|syntax| {
+            let split = Step {
+              operation: Operation::Split {
+                path: path.clone(),
+                syntax,
+              },
+              explanation: format!("Read the requirements in {at} as {}", syntax_name(syntax)),
+            };
+            let extra = if Some(syntax) == native {
+              0
+            } else {
+              FOREIGN_SYNTAX_COST
+            };
+            (vec![split, key_by_name.clone()], extra)
+          })
+          .collect()
+      }
+      DataNode::List { items, .. }
+        if items
+          .iter()
+          .all(|item| item.get("name").and_then(DataNode::as_str).is_some()) =>
+      {
+        vec![(vec![key_by_name], 0)]
+      }
+      _ => Vec::new(),
+    }
+  }
+
+  /// Step turning the block's versions or records into `klep.deps`
+  /// entries, unless they already are
+  fn entries(block: &Block, node: &DataNode) -> Option<Step> {
+    match node {
+      DataNode::Map { entries, .. } if entries.iter().all(|entry| is_entry(&entry.value)) => None,
+      _ => Some(Step {
+        operation: Operation::Coerce {
+          path: block.path.clone(),
+          to: Shape::Entries,
+        },
+        explanation: format!(
+          "Turn each dependency in `{}` (line {}) into a `klep.deps` entry",
+          display(&block.path),
+          block.line
+        ),
+      }),
+    }
+  }
+
+  /// Step putting the block's entries under `dependencies`, or
+  /// `devDependencies` for dev ones
+  fn placement(block: &Block, tree: &DataNode) -> Option<Step> {
+    let key = target(block.kind);
+    let target = vec![key.to_string()];
+    let name = display(&block.path);
+    let kind = kind_name(block.kind);
+    if block.path == target {
+      return None;
+    }
+    if tree.get(key).is_some() {
+      return Some(Step {
+        operation: Operation::Merge {
+          from: block.path.clone(),
+          into: target,
+        },
+        explanation: format!("`{name}` holds {kind} dependencies: merge it into `{key}`"),
+      });
+    }
+    let operation = match block.path.as_slice() {
+      [_] => Operation::Rename {
+        path: block.path.clone(),
+        to: key.to_string(),
+      },
+      _ => Operation::Move {
+        from: block.path.clone(),
+        to: target,
+      },
+    };
+    let verb = if matches!(operation, Operation::Rename { .. }) {
+      "rename"
+    } else {
+      "move"
+    };
+    Some(Step {
+      operation,
+      explanation: format!("`{name}` holds {kind} dependencies: {verb} it to `{key}`"),
+    })
+  }
+
+  /// Plans continuing `partial` with each way of folding in its next
+  /// block, with their costs
+  fn expand(&self, partial: &Partial) -> Vec<(Partial, u32)> {
+    let block = &self.blocks[partial.next];
+    let mut next = Vec::new();
+    if !block.known {
+      let mut skipped = partial.clone();
+      skipped.next += 1;
+      skipped.skipped.push(partial.next);
+      next.push((skipped, SKIP_COST));
+    }
+    let Some(node) = node_at(&partial.tree, &block.path) else {
+      return next;
+    };
+    'reshaping: for (steps, extra) in self.reshapings(block, node) {
+      let mut child = partial.clone();
+      for step in steps {
+        if step.operation.apply(&mut child.tree).is_err() {
+          continue 'reshaping;
+        }
+        child.steps.push(step);
+      }
+      let reshaped = node_at(&child.tree, &block.path).and_then(|node| Self::entries(block, node));
+      if let Some(step) = reshaped {
+        if step.operation.apply(&mut child.tree).is_err() {
+          continue;
+        }
+        child.steps.push(step);
+      }
+      if let Some(step) = Self::placement(block, &child.tree) {
+        if step.operation.apply(&mut child.tree).is_err() {
+          continue;
+        }
+        child.steps.push(step);
+      }
+      child.next += 1;
+      let steps = u32::try_from(child.steps.len() - partial.steps.len()).unwrap_or(u32::MAX);
+      next.push((child, steps * STEP_COST + extra));
+    }
+    next
+  }
+
+  fn explain(&self, partial: &Partial) -> String {
+    let folded = self.blocks.len() - partial.skipped.len();
+    let family = self.family.map_or_else(
+      || "an unknown manifest".to_string(),
+      |family| format!("a {}", family.name()),
+    );
+    let skipped = partial
+      .skipped
+      .iter()
+      .map(|&index| {
+        let block = &self.blocks[index];
+        format!(
+          "; leaves out `{}` (line {}), whose key only looks like it holds dependencies",
+          display(&block.path),
+          block.line
+        )
+      })
+      .collect::<Vec<_>>()
+      .concat();
+    format!(
+      "Folds {folded} block{} of dependencies of {family} into `klep.deps` entries in {} step{}{skipped}",
+      if folded == 1 { "" } else { "s" },
+      partial.steps.len(),
+      if partial.steps.len() == 1 { "" } else { "s" },
+    )
+  }
+
+  /// Search the plans cheapest first, keeping up to `limit` that satisfy
+  /// the universal shape and give different results
+  fn search(&self, tree: &DataNode, limit: usize) -> Vec<Plan> {
+    let required: Vec<&Block> = self.blocks.iter().filter(|block| block.known).collect();
+    let mut partials = vec![Some(Partial {
+      tree: tree.clone(),
+      steps: Vec::new(),
+      next: 0,
+      skipped: Vec::new(),
+    })];
+    let mut queue = BinaryHeap::from([Reverse((0, 0))]);
+    let mut plans: Vec<Plan> = Vec::new();
+
+    while let Some(Reverse((cost, index))) = queue.pop() {
+      if plans.len() >= limit || partials.len() > MAX_STATES {
+        break;
+      }
+      let Some(partial) = partials[index].take() else {
+        continue;
+      };
+      if partial.next < self.blocks.len() {
+        for (child, step_cost) in self.expand(&partial) {
+          queue.push(Reverse((cost + step_cost, partials.len())));
+          partials.push(Some(child));
+        }
+        continue;
+      }
+      if !violations(&partial.tree, &required).is_empty() {
+        continue;
+      }
+      let result = partial.tree.to_value();
+      if plans.iter().any(|plan| plan.result == result) {
+        continue;
+      }
+      plans.push(Plan {
+        rank: plans.len() + 1,
+        cost,
+        explanation: self.explain(&partial),
+        steps: partial.steps,
+        result,
+      });
+    }
+    log::debug(&format!(
+      "Explored {} partial plans for {} plans",
+      partials.len(),
+      plans.len()
+    ));
+    plans
+  }
+}
+
+/// Order blocks are folded in: normal dependencies first, so that one of
+/// them becomes `dependencies`, then blocks the family is known to keep
+fn fold_order(blocks: &mut [Block]) {
+  blocks.sort_by_key(|block| {
+    (
+      block.kind != DependencyKind::Normal,
+      !block.known,
+      block.path != [DEPENDENCIES],
+      block.line,
+    )
+  });
+}
+
+/// Input of `ast/plan_translation`
+#[derive(Deserialize, JsonSchema)]
+pub struct PlanInput {
+  /// Contents of the manifest
+  pub source_code: String,
+  /// Data format, or manifest name such as `Cargo.toml`
+  pub language: Option<String>,
+  /// Path or file name, used for the format when `language` is absent
+  pub path: Option<String>,
+  /// Manifest family the document belongs to, such as `package.json`;
+  /// taken from `language` or `path` when absent, else guessed from its
+  /// structure
+  pub family: Option<String>,
+  /// Number of plans to return, 3 by default
+  pub limit: Option<usize>,
+}
+
+/// Output of `ast/plan_translation`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PlanOutput {
+  /// Manifest family the plans were made for
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub family: Option<ManifestKind>,
+  /// Whether `family` was guessed from the structure of the document
+  pub guessed: bool,
+  /// Nodes found holding dependencies
+  pub blocks: Vec<Block>,
+  /// Cheapest first
+  pub plans: Vec<Plan>,
+  /// Every plan as one Markdown document for review
+  pub review: String,
+}
+
+/// Family named by the input, its language or its path, else the known
+/// manifest the tree looks most like, if confidently
+fn family_of(input: &PlanInput, tree: &DataNode) -> Result<(Option<ManifestKind>, bool), GudError> {
+  let Some(name) = &input.family else {
+    let named = input
+      .language
+      .as_deref()
+      .and_then(ManifestKind::from_name)
+      .or_else(|| input.path.as_deref().and_then(ManifestKind::from_path));
+    if named.is_some() {
+      return Ok((named, false));
+    }
+    return Ok(
+      nearest_manifest(tree)
+        .filter(|(_, found)| found.confidence >= GUESS_CONFIDENCE)
+        .map_or((None, false), |(family, _)| (Some(family), true)),
+    );
+  };
+  let family = ManifestKind::from_name(name).ok_or_else(|| {
+    GudError::new(
+      ErrorKind::Argument,
+      "unknown-family",
+      format!("There is no manifest family named '{name}'"),
+    )
+    .with_context(json!({ "family": name, "supported": MANIFESTS }))
+  })?;
+  Ok((Some(family), false))
+}
+
+/// Plan the translation of a manifest into the universal shape
+#[allow(clippy::needless_pass_by_value)]
+pub fn plan_translation(input: PlanInput) -> Result<PlanOutput, Box<dyn std::error::Error>> {
+  let tree = parse_document(
+    &input.source_code,
+    input.language.as_deref(),
+    input.path.as_deref(),
+  )?;
+  let (family, guessed) = family_of(&input, &tree)?;
+  let mut blocks = find_blocks(&tree, family);
+  log::debug(&format!(
+    "Planning the translation of {} blocks of dependencies",
+    blocks.len()
+  ));
+  let output_blocks = blocks.clone();
+  fold_order(&mut blocks);
+  let planner = Planner {
+    blocks: &blocks,
+    family,
+  };
+  let plans = planner.search(&tree, input.limit.unwrap_or(DEFAULT_LIMIT));
+
+  let title = family.map_or("an unknown manifest", ManifestKind::name);
+  let review = std::iter::once(format!("# Translating {title}\n"))
+    .chain(plans.iter().map(Plan::review))
+    .collect::<Vec<_>>()
+    .join("\n");
+  Ok(PlanOutput {
+    family,
+    guessed,
+    blocks: output_blocks,
+    plans,
+    review,
+  })
+}
+
+/// Input of `ast/apply_plan`
+#[derive(Deserialize, JsonSchema)]
+pub struct ApplyPlanInput {
+  /// Contents of the manifest
+  pub source_code: String,
+  /// Data format, or manifest name such as `Cargo.toml`
+  pub language: Option<String>,
+  /// Path or file name, used for the format when `language` is absent
+  pub path: Option<String>,
+  /// Operations of a plan, as `plan_translation` returns them or as a
+  /// reviewer edited them
+  pub operations: Vec<Operation>,
+}
+
+/// Output of `ast/apply_plan`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ApplyPlanOutput {
+  /// The tree once the operations are applied
+  pub result: Value,
+  /// Ways the result falls short of the universal shape; empty when it
+  /// has it
+  pub violations: Vec<String>,
+}
+
+/// Apply a plan's operations to a manifest
+#[allow(clippy::needless_pass_by_value)]
+pub fn apply_plan(input: ApplyPlanInput) -> Result<ApplyPlanOutput, Box<dyn std::error::Error>> {
+  let mut tree = parse_document(
+    &input.source_code,
+    input.language.as_deref(),
+    input.path.as_deref(),
+  )?;
+  for operation in &input.operations {
+    operation.apply(&mut tree)?;
+  }
+  Ok(ApplyPlanOutput {
+    violations: violations(&tree, &[]),
+    result: tree.to_value(),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::to_manifest::DepsFile;
+
+  fn plan(source: &str, language: &str, family: Option<&str>) -> PlanOutput {
+    plan_translation(PlanInput {
+      source_code: source.to_string(),
+      language: Some(language.to_string()),
+      path: None,
+      family: family.map(str::to_string),
+      limit: None,
+    })
+    .unwrap()
+  }
+
+  fn operations(plan: &Plan) -> Vec<String> {
+    plan
+      .steps
+      .iter()
+      .map(|step| {
+        serde_json::to_value(&step.operation).unwrap()["op"]
+          .as_str()
+          .unwrap()
+          .to_string()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn folds_npm_blocks_into_dependencies() {
+    let output = plan(
+      r#"{"name": "app", "dependencies": {"lodash": "^4.17.0"}, "devDependencies": {"jest": "^29.0.0"}, "peerDependencies": {"react": ">=18"}}"#,
+      "json",
+      None,
+    );
+    assert_eq!(output.family, Some(ManifestKind::PackageJson));
+    assert!(output.guessed);
+    let best = &output.plans[0];
+    assert_eq!(operations(best), ["coerce", "coerce", "coerce", "merge"]);
+    assert_eq!(
+      best.result[DEPENDENCIES],
+      json!({
+        "lodash": {"url": "", "version": "^4.17.0", "extract": "all"},
+        "react": {"url": "", "version": ">=18", "extract": "all"},
+      })
+    );
+    assert_eq!(
+      best.result[DEV_DEPENDENCIES],
+      json!({"jest": {"url": "", "version": "^29.0.0", "extract": "all"}})
+    );
+    assert_eq!(best.result["name"], "app");
+    assert!(
+      best.steps[3].explanation.contains("peer dependencies"),
+      "{:?}",
+      best.steps
+    );
+    let deps_file: DepsFile = serde_json::from_value(best.result.clone()).unwrap();
+    assert_eq!(deps_file.dev_dependencies.len(), 1);
+    assert!(output.review.contains("## Plan 1"));
+  }
+
+  #[test]
+  fn splits_requirement_lists_in_the_family_syntax() {
+    let output = plan(
+      "[project]\nname = \"svc\"\ndependencies = [\"httpx>=0.27\", \"rich\"]\n\n[project.optional-dependencies]\ntest = [\"pytest>=8\"]\n",
+      "pyproject.toml",
+      None,
+    );
+    assert_eq!(output.family, Some(ManifestKind::Pyproject));
+    assert!(!output.guessed);
+    let best = &output.plans[0];
+    assert_eq!(
+      operations(best),
+      ["split", "coerce", "coerce", "move", "split", "coerce", "coerce", "merge"]
+    );
+    assert_eq!(
+      best.result[DEPENDENCIES],
+      json!({
+        "httpx": {"url": "", "version": ">=0.27", "extract": "all"},
+        "rich": {"url": "", "extract": "all"},
+        "pytest": {"url": "", "version": ">=8", "extract": "all"},
+      })
+    );
+    // Neither npm specs nor spaced requirements can read `httpx>=0.27`
+    assert_eq!(output.plans.len(), 1);
+
+    let output = plan("-r base.txt\nflask>=3\n", "requirements.txt", None);
+    assert_eq!(
+      operations(&output.plans[0]),
+      ["split", "coerce", "coerce", "move"]
+    );
+    assert_eq!(
+      output.plans[0].result,
+      json!({DEPENDENCIES: {"flask": {"url": "", "version": ">=3", "extract": "all"}}})
+    );
+  }
+
+  #[test]
+  fn finds_each_block_once() {
+    let output = plan(
+      "[project]\nname = \"svc\"\ndependencies = [\"httpx>=0.27\"]\n\n[project.optional-dependencies]\ndev = [\"pytest>=8\"]\ndocs = [\"mkdocs\"]\n",
+      "pyproject.toml",
+      None,
+    );
+    let paths: Vec<_> = output
+      .blocks
+      .iter()
+      .map(|block| (display(&block.path), block.known))
+      .collect();
+    assert_eq!(
+      paths,
+      [
+        ("project.dependencies".to_string(), true),
+        ("project.optional-dependencies.dev".to_string(), true),
+        ("project.optional-dependencies.docs".to_string(), true),
+      ]
+    );
+    let best = &output.plans[0];
+    assert!(
+      !best.explanation.contains("leaves out"),
+      "{}",
+      best.explanation
+    );
+    let steps = u32::try_from(best.steps.len()).unwrap();
+    assert_eq!(best.cost, steps * STEP_COST);
+    assert_eq!(
+      best.result[DEPENDENCIES],
+      json!({
+        "httpx": {"url": "", "version": ">=0.27", "extract": "all"},
+        "pytest": {"url": "", "version": ">=8", "extract": "all"},
+        "mkdocs": {"url": "", "extract": "all"},
+      })
+    );
+  }
+
+  #[test]
+  fn ranks_leaving_out_blocks_that_only_look_like_dependencies() {
+    let output = plan(
+      "deps:\n  left-pad: ^1.0\nbuild_deps:\n  - make 4.3\nextras:\n  docs: true\n",
+      "yaml",
+      None,
+    );
+    assert_eq!(output.family, None);
+    assert_eq!(output.blocks.len(), 2);
+    let best = &output.plans[0];
+    assert_eq!(
+      operations(best),
+      ["coerce", "rename", "split", "coerce", "coerce", "merge"]
+    );
+    assert_eq!(
+      best.result[DEPENDENCIES]["make"],
+      json!({"url": "", "version": "4.3", "extract": "all"})
+    );
+    assert!(output
+      .plans
+      .iter()
+      .any(|plan| plan.explanation.contains("leaves out `build_deps`")));
+    assert!(output
+      .plans
+      .windows(2)
+      .all(|pair| pair[0].cost <= pair[1].cost));
+  }
+
+  #[test]
+  fn applies_exported_plans() {
+    let source =
+      r#"{"require": {"monolog/monolog": "^3.0"}, "require-dev": {"phpunit/phpunit": "^11"}}"#;
+    let output = plan(source, "json", Some("composer"));
+    let exported = serde_json::to_value(&output.plans[0].steps).unwrap();
+    let applied = apply_plan(ApplyPlanInput {
+      source_code: source.to_string(),
+      language: Some("json".to_string()),
+      path: None,
+      operations: serde_json::from_value(exported).unwrap(),
+    })
+    .unwrap();
+    assert_eq!(applied.result, output.plans[0].result);
+    assert!(applied.violations.is_empty());
+
+    let untouched = apply_plan(ApplyPlanInput {
+      source_code: source.to_string(),
+      language: Some("json".to_string()),
+      path: None,
+      operations: Vec::new(),
+    })
+    .unwrap();
+    assert_eq!(untouched.violations, ["There is no `dependencies` map"]);
+  }
+
+  #[test]
+  fn rejects_unknown_families() {
+    let error = GudError::from_boxed(
+      plan_translation(PlanInput {
+        source_code: "{}".to_string(),
+        language: Some("json".to_string()),
+        path: None,
+        family: Some("maven".to_string()),
+        limit: None,
+      })
+      .unwrap_err(),
+    );
+    assert_eq!(error.code, "unknown-family");
+  }
+}
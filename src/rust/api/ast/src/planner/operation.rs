@@ -0,0 +1,582 @@
+//! Tree operations a plan is made of, and how they apply to an
+//! intermediate tree.
+//!
+//! Operations address nodes by key path from the root, the root itself
+//! being `[]`. Nodes they create take the span of the node they come from,
+//! so the result still points back into the manifest.
+
+use gud_common::{ErrorKind, GudError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::data::{DataNode, Entry, Scalar, Span};
+use crate::to_manifest::{DependencyKind, KlepDependency};
+
+/// One step of a plan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+  /// Rename the key at `path` to `to`
+  Rename {
+    /// Keys leading to the entry
+    path: Vec<String>,
+    /// New key
+    to: String,
+  },
+  /// Move the node at `from` to `to`, creating maps on the way
+  Move {
+    /// Keys leading to the node
+    from: Vec<String>,
+    /// Keys leading to where it goes
+    to: Vec<String>,
+  },
+  /// Add the entries of the map at `from` to the map at `into`, creating
+  /// it if needed, and remove `from`. Names already in `into` keep their
+  /// entry.
+  Merge {
+    /// Keys leading to the map merged in
+    from: Vec<String>,
+    /// Keys leading to the map merged into
+    into: Vec<String>,
+  },
+  /// Split each requirement string in the list at `path` into a record of
+  /// its `name` and `version`
+  Split {
+    /// Keys leading to the list
+    path: Vec<String>,
+    /// How the strings name their package and version
+    syntax: RequirementSyntax,
+  },
+  /// Reshape the node at `path`
+  Coerce {
+    /// Keys leading to the node
+    path: Vec<String>,
+    /// Shape it is given
+    to: Shape,
+  },
+}
+
+/// How a requirement string names its package and version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RequirementSyntax {
+  /// `requests[socks]>=2.31; python_version > "3.8"`, as pip writes them;
+  /// lines of pip options such as `-r base.txt` are dropped
+  Pep508,
+  /// `lodash@^4.17.0` or `@types/node@20`
+  Npm,
+  /// `rack 3.0.9`, the name then the version after whitespace
+  Spaced,
+}
+
+/// What [`Operation::Coerce`] turns a node into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Shape {
+  /// A map of names to versions or records becomes a map of names to
+  /// `klep.deps` entries. An entry keeps the record's `version` and takes
+  /// its `url` from `git`, `url` or `path`, else leaves it empty, as klep
+  /// cannot fetch from a registry; it extracts everything.
+  Entries,
+  /// A list of records with a `name` becomes a map from their names to the
+  /// rest of each record
+  Map,
+}
+
+/// Key path as written in explanations, `<root>` for the root
+#[must_use]
+pub fn display(path: &[String]) -> String {
+  if path.is_empty() {
+    "<root>".to_string()
+  } else {
+    path.join(".")
+  }
+}
+
+fn failure(operation: &Operation, message: impl Into<String>) -> GudError {
+  GudError::new(ErrorKind::Argument, "invalid-operation", message)
+    .with_context(json!({ "operation": operation }))
+}
+
+/// Name of a dependency kind as explanations give it
+#[must_use]
+pub const fn kind_name(kind: DependencyKind) -> &'static str {
+  match kind {
+    DependencyKind::Normal => "normal",
+    DependencyKind::Dev => "dev",
+    DependencyKind::Build => "build",
+    DependencyKind::Optional => "optional",
+    DependencyKind::Peer => "peer",
+    DependencyKind::Indirect => "indirect",
+  }
+}
+
+fn scalar(text: &str, span: Span) -> DataNode {
+  DataNode::Scalar {
+    value: Scalar::String(text.to_string()),
+    span,
+  }
+}
+
+fn entry(key: &str, value: DataNode) -> Entry {
+  Entry {
+    key: key.to_string(),
+    key_span: value.span(),
+    value,
+  }
+}
+
+/// Node at `path`, following the first entry of each key
+fn find_mut<'a>(tree: &'a mut DataNode, path: &[String]) -> Option<&'a mut DataNode> {
+  let Some((key, rest)) = path.split_first() else {
+    return Some(tree);
+  };
+  match tree {
+    DataNode::Map { entries, .. } => entries
+      .iter_mut()
+      .find(|entry| entry.key == *key)
+      .and_then(|entry| find_mut(&mut entry.value, rest)),
+    _ => None,
+  }
+}
+
+/// Detach the node at `path`; the root is replaced by an empty map
+fn take(tree: &mut DataNode, path: &[String]) -> Option<DataNode> {
+  let Some((key, parent)) = path.split_last() else {
+    let span = tree.span();
+    return Some(std::mem::replace(
+      tree,
+      DataNode::Map {
+        entries: Vec::new(),
+        span,
+      },
+    ));
+  };
+  match find_mut(tree, parent)? {
+    DataNode::Map { entries, .. } => {
+      let at = entries.iter().position(|entry| entry.key == *key)?;
+      Some(entries.remove(at).value)
+    }
+    _ => None,
+  }
+}
+
+/// Attach `node` at `path`, creating the maps on the way. Fails if
+/// something already is there or a node on the way is not a map.
+fn put(tree: &mut DataNode, path: &[String], node: DataNode) -> Result<(), String> {
+  let Some((key, rest)) = path.split_first() else {
+    return Err("cannot replace the root".to_string());
+  };
+  let span = node.span();
+  let DataNode::Map { entries, .. } = tree else {
+    return Err(format!("cannot add '{key}' to something other than a map"));
+  };
+  let at = match entries.iter().position(|entry| entry.key == *key) {
+    Some(_) if rest.is_empty() => return Err(format!("'{key}' already exists")),
+    Some(at) => at,
+    None if rest.is_empty() => {
+      entries.push(entry(key, node));
+      return Ok(());
+    }
+    None => {
+      entries.push(entry(
+        key,
+        DataNode::Map {
+          entries: Vec::new(),
+          span,
+        },
+      ));
+      entries.len() - 1
+    }
+  };
+  put(&mut entries[at].value, rest, node)
+}
+
+/// Name and version of a requirement string; `Ok(None)` for lines that
+/// are not requirements
+fn split(text: &str, syntax: RequirementSyntax) -> Result<Option<(&str, &str)>, ()> {
+  let text = text.trim();
+  let valid = |name: &str, extra: &str| {
+    !name.is_empty()
+      && name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c) || extra.contains(c))
+  };
+  match syntax {
+    RequirementSyntax::Pep508 => {
+      if text.starts_with('-') {
+        return Ok(None);
+      }
+      let requirement = text.split(';').next().unwrap_or_default().trim();
+      let end = requirement
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "-_.".contains(c)))
+        .unwrap_or(requirement.len());
+      let (name, mut rest) = requirement.split_at(end);
+      if rest.starts_with('[') {
+        rest = rest.split_once(']').ok_or(())?.1;
+      }
+      let rest = rest.trim();
+      let operator = rest.is_empty() || rest.starts_with(['<', '>', '=', '!', '~', '(', '@']);
+      if valid(name, "") && operator {
+        Ok(Some((name, rest)))
+      } else {
+        Err(())
+      }
+    }
+    RequirementSyntax::Npm => {
+      let (name, version) = text
+        .rfind('@')
+        .filter(|&at| at > 0)
+        .map_or((text, ""), |at| (&text[..at], &text[at + 1..]));
+      if valid(name, "@/") && !version.contains(char::is_whitespace) {
+        Ok(Some((name, version)))
+      } else {
+        Err(())
+      }
+    }
+    RequirementSyntax::Spaced => {
+      let (name, version) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+      if valid(name, "/") {
+        Ok(Some((name, version.trim())))
+      } else {
+        Err(())
+      }
+    }
+  }
+}
+
+/// Whether `node` already is a `klep.deps` entry
+pub(crate) fn is_entry(node: &DataNode) -> bool {
+  matches!(node, DataNode::Map { .. })
+    && serde_json::from_value::<KlepDependency>(node.to_value()).is_ok()
+}
+
+/// Text of a version written as a scalar; `None` for flags and nulls
+fn version_text(value: &Scalar) -> Option<String> {
+  match value {
+    Scalar::String(text) => Some(text.clone()),
+    Scalar::Integer(number) => Some(number.to_string()),
+    Scalar::Float(number) => Some(number.to_string()),
+    Scalar::Bool(_) | Scalar::Null => None,
+  }
+}
+
+/// `klep.deps` entry of a dependency from the value it is listed with
+fn klep_entry(value: DataNode) -> Result<DataNode, String> {
+  if is_entry(&value) {
+    return Ok(value);
+  }
+  let span = value.span();
+  let (url, version) = match &value {
+    DataNode::Scalar { value, .. } => (String::new(), version_text(value)),
+    DataNode::Map { .. } => {
+      let url = ["git", "url", "path"]
+        .iter()
+        .find_map(|key| value.get(key).and_then(DataNode::as_str))
+        .unwrap_or_default();
+      let version = match value.get("version") {
+        Some(DataNode::Scalar { value, .. }) => version_text(value),
+        _ => None,
+      };
+      (url.to_string(), version)
+    }
+    DataNode::List { .. } => return Err("a list cannot be a dependency".to_string()),
+  };
+  let mut entries = vec![entry("url", scalar(&url, span))];
+  if let Some(version) = version {
+    entries.push(entry("version", scalar(&version, span)));
+  }
+  entries.push(entry("extract", scalar("all", span)));
+  Ok(DataNode::Map { entries, span })
+}
+
+/// Move the entries of the map at `from` into the map at `into`
+fn merge(tree: &mut DataNode, from: &[String], into: &[String]) -> Result<(), String> {
+  let DataNode::Map { entries, .. } =
+    take(tree, from).ok_or_else(|| format!("nothing at '{}'", display(from)))?
+  else {
+    return Err(format!("'{}' is not a map", display(from)));
+  };
+  if find_mut(tree, into).is_none() {
+    let span = tree.span();
+    put(
+      tree,
+      into,
+      DataNode::Map {
+        entries: Vec::new(),
+        span,
+      },
+    )?;
+  }
+  let Some(DataNode::Map {
+    entries: existing, ..
+  }) = find_mut(tree, into)
+  else {
+    return Err(format!("'{}' is not a map", display(into)));
+  };
+  for Entry {
+    key,
+    key_span,
+    value,
+  } in entries
+  {
+    if existing.iter().any(|entry| entry.key == key) {
+      continue;
+    }
+    existing.push(Entry {
+      key,
+      key_span,
+      value,
+    });
+  }
+  Ok(())
+}
+
+/// Reshape the node at `path` into `to`
+fn coerce(tree: &mut DataNode, path: &[String], to: Shape) -> Result<(), String> {
+  let node = find_mut(tree, path).ok_or_else(|| format!("nothing at '{}'", display(path)))?;
+  let span = node.span();
+  let coerced = match (
+    to,
+    std::mem::replace(
+      node,
+      DataNode::List {
+        items: Vec::new(),
+        span,
+      },
+    ),
+  ) {
+    (Shape::Entries, DataNode::Map { entries, span }) => DataNode::Map {
+      entries: entries
+        .into_iter()
+        .map(|entry| {
+          Ok(Entry {
+            value: klep_entry(entry.value)?,
+            ..entry
+          })
+        })
+        .collect::<Result<_, String>>()?,
+      span,
+    },
+    (Shape::Map, DataNode::List { items, span }) => DataNode::Map {
+      entries: items
+        .into_iter()
+        .map(|item| {
+          let DataNode::Map { mut entries, .. } = item else {
+            return Err("only records can be keyed by name".to_string());
+          };
+          let at = entries
+            .iter()
+            .position(|entry| entry.key == "name")
+            .ok_or_else(|| "a record has no name".to_string())?;
+          let name = entries.remove(at);
+          let key = name
+            .value
+            .as_str()
+            .ok_or_else(|| "a name is not a string".to_string())?
+            .to_string();
+          Ok(Entry {
+            key,
+            key_span: name.value.span(),
+            value: DataNode::Map {
+              entries,
+              span: name.key_span,
+            },
+          })
+        })
+        .collect::<Result<_, String>>()?,
+      span,
+    },
+    (Shape::Entries, _) => return Err(format!("'{}' is not a map", display(path))),
+    (Shape::Map, _) => return Err(format!("'{}' is not a list", display(path))),
+  };
+  *node = coerced;
+  Ok(())
+}
+
+impl Operation {
+  /// Apply the operation to `tree`, which is left as it was on failure
+  pub fn apply(&self, tree: &mut DataNode) -> Result<(), GudError> {
+    let mut changed = tree.clone();
+    self
+      .apply_to(&mut changed)
+      .map_err(|message| failure(self, message))?;
+    *tree = changed;
+    Ok(())
+  }
+
+  fn apply_to(&self, tree: &mut DataNode) -> Result<(), String> {
+    let missing = |path: &[String]| format!("nothing at '{}'", display(path));
+    match self {
+      Self::Rename { path, to } => {
+        let (key, parent) = path
+          .split_last()
+          .ok_or_else(|| "the root has no key".to_string())?;
+        let Some(DataNode::Map { entries, .. }) = find_mut(tree, parent) else {
+          return Err(missing(path));
+        };
+        if entries.iter().any(|entry| entry.key == *to) {
+          return Err(format!("'{to}' already exists"));
+        }
+        let entry = entries
+          .iter_mut()
+          .find(|entry| entry.key == *key)
+          .ok_or_else(|| missing(path))?;
+        entry.key.clone_from(to);
+      }
+      Self::Move { from, to } => {
+        if to.starts_with(from) && !from.is_empty() {
+          return Err("cannot move a node into itself".to_string());
+        }
+        let node = take(tree, from).ok_or_else(|| missing(from))?;
+        put(tree, to, node)?;
+      }
+      Self::Merge { from, into } => {
+        merge(tree, from, into)?;
+      }
+      Self::Split { path, syntax } => {
+        let Some(DataNode::List { items, .. }) = find_mut(tree, path) else {
+          return Err(format!("'{}' is not a list", display(path)));
+        };
+        let mut records = Vec::new();
+        for item in items.drain(..) {
+          let span = item.span();
+          let text = item
+            .as_str()
+            .ok_or_else(|| "only strings can be split".to_string())?;
+          let Some((name, version)) = split(text, *syntax)
+            .map_err(|()| format!("'{text}' is not a {syntax:?} requirement"))?
+          else {
+            continue;
+          };
+          let mut fields = vec![entry("name", scalar(name, span))];
+          if !version.is_empty() {
+            fields.push(entry("version", scalar(version, span)));
+          }
+          records.push(DataNode::Map {
+            entries: fields,
+            span,
+          });
+        }
+        *items = records;
+      }
+      Self::Coerce { path, to } => {
+        coerce(tree, path, *to)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::data::Format;
+
+  fn path(path: &str) -> Vec<String> {
+    path
+      .split('.')
+      .filter(|key| !key.is_empty())
+      .map(str::to_string)
+      .collect()
+  }
+
+  fn apply(format: Format, source: &str, operations: &[Operation]) -> serde_json::Value {
+    let mut tree = format.parse(source).unwrap();
+    for operation in operations {
+      operation.apply(&mut tree).unwrap();
+    }
+    tree.to_value()
+  }
+
+  #[test]
+  fn reshapes_requirement_lists() {
+    let result = apply(
+      Format::Lines,
+      "-r base.txt\nrequests[socks]>=2.31 ; python_version > '3.8'\nflask\n",
+      &[
+        Operation::Split {
+          path: Vec::new(),
+          syntax: RequirementSyntax::Pep508,
+        },
+        Operation::Coerce {
+          path: Vec::new(),
+          to: Shape::Map,
+        },
+        Operation::Coerce {
+          path: Vec::new(),
+          to: Shape::Entries,
+        },
+        Operation::Move {
+          from: Vec::new(),
+          to: path("dependencies"),
+        },
+      ],
+    );
+    assert_eq!(
+      result,
+      json!({"dependencies": {
+        "requests": {"url": "", "version": ">=2.31", "extract": "all"},
+        "flask": {"url": "", "extract": "all"},
+      }})
+    );
+  }
+
+  #[test]
+  fn merges_klep_entries() {
+    let result = apply(
+      Format::Toml,
+      "[dependencies]\nserde = \"1\"\nlocal = { path = \"../local\" }\n[dev-dependencies]\nserde = \"1.0.200\"\ninsta = { version = \"1\", features = [\"json\"] }\n",
+      &[
+        Operation::Coerce {
+          path: path("dependencies"),
+          to: Shape::Entries,
+        },
+        Operation::Coerce {
+          path: path("dev-dependencies"),
+          to: Shape::Entries,
+        },
+        Operation::Merge {
+          from: path("dev-dependencies"),
+          into: path("dependencies"),
+        },
+      ],
+    );
+    assert_eq!(
+      result,
+      json!({"dependencies": {
+        "serde": {"url": "", "version": "1", "extract": "all"},
+        "local": {"url": "../local", "extract": "all"},
+        "insta": {"url": "", "version": "1", "extract": "all"},
+      }})
+    );
+  }
+
+  #[test]
+  fn failed_operations_leave_the_tree_alone() {
+    let mut tree = Format::Json
+      .parse(r#"{"a": {"b": 1}, "c": ["x y"]}"#)
+      .unwrap();
+    let before = tree.clone();
+    let rename = Operation::Rename {
+      path: path("a"),
+      to: "c".to_string(),
+    };
+    assert_eq!(
+      rename.apply(&mut tree).unwrap_err().code,
+      "invalid-operation"
+    );
+    let split = Operation::Split {
+      path: path("c"),
+      syntax: RequirementSyntax::Npm,
+    };
+    assert!(split.apply(&mut tree).is_err());
+    let into_itself = Operation::Move {
+      from: path("a"),
+      to: path("a.b.c"),
+    };
+    assert!(into_itself.apply(&mut tree).is_err());
+    assert_eq!(tree, before);
+  }
+}
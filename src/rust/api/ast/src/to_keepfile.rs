@@ -0,0 +1,552 @@
+//! Resolved dependency graphs from the lockfiles of known ecosystems.
+//!
+//! Manifests only record constraints; lockfiles record what those
+//! constraints resolved to. Each reader lists the package versions a
+//! lockfile pins, what every one of them requires and, when the lockfile
+//! keeps them, the project's own dependencies as universal [`Dependency`]
+//! records.
+//!
+//! The output follows `klep.keep`: one entry per pinned package with the
+//! ranges requested of it and its resolution, whose requirements point at
+//! the versions they were resolved to.
+
+mod bun;
+mod cargo;
+mod go;
+mod npm;
+mod poetry;
+mod ruby;
+
+use gud_common::{log, ErrorKind, GudError};
+use gud_std::version::Version;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::data::{DataNode, Entry, Format, Span};
+use crate::to_manifest::{Dependency, DependencyKind, ExtractRule, ManifestKind, Source};
+
+/// Input of `ast/to_keepfile`
+#[derive(Deserialize, JsonSchema)]
+pub struct LockfileInput {
+  /// Contents of the lockfile
+  pub source_code: String,
+  /// Lockfile name such as `Cargo.lock` or `bun`, or a data format (`json`,
+  /// `toml`) to detect the lockfile from its contents
+  pub language: Option<String>,
+  /// Path or file name of the lockfile
+  pub path: Option<String>,
+}
+
+/// Lockfile formats with a reader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum LockfileKind {
+  /// npm, lockfile versions 2 and 3
+  #[serde(rename = "package-lock.json")]
+  PackageLock,
+  /// Bun's text lockfile
+  #[serde(rename = "bun.lock")]
+  BunLock,
+  /// Cargo
+  #[serde(rename = "Cargo.lock")]
+  CargoLock,
+  /// Poetry
+  #[serde(rename = "poetry.lock")]
+  PoetryLock,
+  /// Go module checksums
+  #[serde(rename = "go.sum")]
+  GoSum,
+  /// Bundler
+  #[serde(rename = "Gemfile.lock")]
+  GemfileLock,
+}
+
+/// Every lockfile name accepted as `language`
+pub const LOCKFILES: &[&str] = &[
+  "package-lock.json",
+  "bun.lock",
+  "Cargo.lock",
+  "poetry.lock",
+  "go.sum",
+  "Gemfile.lock",
+];
+
+impl LockfileKind {
+  /// Look up a lockfile by file name or ecosystem name, ignoring case
+  #[must_use]
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name.to_ascii_lowercase().as_str() {
+      "package-lock.json" | "npm-shrinkwrap.json" | "npm" => Some(Self::PackageLock),
+      "bun.lock" | "bun" => Some(Self::BunLock),
+      "cargo.lock" | "cargo" => Some(Self::CargoLock),
+      "poetry.lock" | "poetry" => Some(Self::PoetryLock),
+      "go.sum" | "go" => Some(Self::GoSum),
+      "gemfile.lock" | "gems.locked" | "bundler" => Some(Self::GemfileLock),
+      _ => None,
+    }
+  }
+
+  /// Recognize a lockfile from the file name at the end of `path`
+  #[must_use]
+  pub fn from_path(path: &str) -> Option<Self> {
+    let file = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    if file.contains('.') {
+      Self::from_name(file)
+    } else {
+      None
+    }
+  }
+
+  /// Guess the lockfile from a document in a generic data `format`
+  fn sniff(format: Format, source: &str) -> Option<Self> {
+    match format {
+      Format::Json | Format::Jsonc => {
+        let tree = Format::Jsonc.parse(source).ok()?;
+        tree.get("lockfileVersion")?;
+        Some(if tree.get("workspaces").is_some() {
+          Self::BunLock
+        } else {
+          Self::PackageLock
+        })
+      }
+      Format::Toml => {
+        let tree = format.parse(source).ok()?;
+        if tree
+          .get("metadata")
+          .is_some_and(|metadata| metadata.get("content-hash").is_some())
+        {
+          Some(Self::PoetryLock)
+        } else if tree.get("package").is_some() {
+          Some(Self::CargoLock)
+        } else {
+          None
+        }
+      }
+      _ => None,
+    }
+  }
+
+  /// File name of the lockfile, as listed in [`LOCKFILES`]
+  #[must_use]
+  pub const fn name(self) -> &'static str {
+    match self {
+      Self::PackageLock => "package-lock.json",
+      Self::BunLock => "bun.lock",
+      Self::CargoLock => "Cargo.lock",
+      Self::PoetryLock => "poetry.lock",
+      Self::GoSum => "go.sum",
+      Self::GemfileLock => "Gemfile.lock",
+    }
+  }
+
+  /// Manifest of the ecosystem the lockfile belongs to, which decides how
+  /// its URLs and constraints are read
+  #[must_use]
+  pub const fn manifest(self) -> ManifestKind {
+    match self {
+      Self::PackageLock | Self::BunLock => ManifestKind::PackageJson,
+      Self::CargoLock => ManifestKind::CargoToml,
+      Self::PoetryLock => ManifestKind::Pyproject,
+      Self::GoSum => ManifestKind::GoMod,
+      Self::GemfileLock => ManifestKind::Gemfile,
+    }
+  }
+
+  /// Read the lockfile from its source text
+  pub fn read(self, source: &str) -> Result<KeepfileOutput, GudError> {
+    let lock = match self {
+      Self::PackageLock => npm::read(&Format::Json.parse(source)?)?,
+      // bun writes trailing commas
+      Self::BunLock => bun::read(&Format::Jsonc.parse(source)?)?,
+      Self::CargoLock => cargo::read(&Format::Toml.parse(source)?)?,
+      Self::PoetryLock => poetry::read(&Format::Toml.parse(source)?)?,
+      Self::GoSum => go::read(source)?,
+      Self::GemfileLock => ruby::read(source)?,
+    };
+    Ok(lock.into_output(self))
+  }
+}
+
+/// A package version pinned by a lockfile
+struct Locked {
+  name: String,
+  version: String,
+  source: Source,
+  /// What the package depends on, each with the version the lockfile pins
+  /// it to when it says which
+  requires: Vec<(Dependency, Option<String>)>,
+  span: Span,
+}
+
+impl Locked {
+  fn new(name: impl Into<String>, version: impl Into<String>, source: Source, span: Span) -> Self {
+    Self {
+      name: name.into(),
+      version: version.into(),
+      source,
+      requires: Vec::new(),
+      span,
+    }
+  }
+}
+
+/// Everything a reader finds in a lockfile
+#[derive(Default)]
+struct Lock {
+  /// The project's own dependencies
+  roots: Vec<Dependency>,
+  packages: Vec<Locked>,
+}
+
+impl Lock {
+  fn into_output(mut self, kind: LockfileKind) -> KeepfileOutput {
+    let manifest = kind.manifest();
+    let mut roots = std::mem::take(&mut self.roots);
+    for root in &mut roots {
+      manifest.complete(root);
+    }
+
+    // Every range asked of each name, by the project or by other packages
+    let mut requests: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let edges = self
+      .packages
+      .iter()
+      .flat_map(|package| package.requires.iter().map(|(dependency, _)| dependency));
+    for dependency in roots.iter().chain(edges) {
+      if let Some(range) = dependency.version.as_deref() {
+        let ranges = requests.entry(&dependency.name).or_default();
+        if !ranges.contains(&range) {
+          ranges.push(range);
+        }
+      }
+    }
+
+    let keepfile = self
+      .packages
+      .iter()
+      .map(|package| {
+        let version = Version::parse(&package.version).ok();
+        // Ranges this version cannot satisfy were meant for another copy
+        let requested = requests
+          .get(package.name.as_str())
+          .into_iter()
+          .flatten()
+          .filter(|range| match (manifest.parse_constraint(range), &version) {
+            (Some(constraint), Some(version)) => constraint.matches(version),
+            _ => true,
+          })
+          .map(|range| RequestedVersion {
+            version: (*range).to_string(),
+          })
+          .collect();
+        let requires = package
+          .requires
+          .iter()
+          .map(|(dependency, pinned)| RequiredDependency {
+            name: dependency.name.clone(),
+            version: pinned
+              .clone()
+              .or_else(|| self.pin(manifest, dependency))
+              .or_else(|| dependency.version.clone())
+              .unwrap_or_else(|| "*".to_string()),
+          })
+          .collect();
+
+        let mut dependency =
+          Dependency::new(&package.name, None, DependencyKind::Normal, package.span);
+        dependency.source = package.source.clone();
+        ResolvedDependency {
+          name: package.name.clone(),
+          url: manifest.url_of(&dependency),
+          source: package.source.clone(),
+          requested,
+          resolved: ResolvedVersion {
+            version: package.version.clone(),
+            extract: ExtractRule::ALL,
+            requires,
+          },
+          span: package.span,
+        }
+      })
+      .collect();
+
+    KeepfileOutput {
+      lockfile: kind,
+      roots,
+      keepfile,
+    }
+  }
+
+  /// Version of `dependency` when the lockfile does not say which it uses:
+  /// the only one locked, or else the first its range accepts
+  fn pin(&self, manifest: ManifestKind, dependency: &Dependency) -> Option<String> {
+    let candidates: Vec<&Locked> = self
+      .packages
+      .iter()
+      .filter(|package| package.name == dependency.name)
+      .collect();
+    if let [only] = candidates.as_slice() {
+      return Some(only.version.clone());
+    }
+    let constraint = manifest.parse_constraint(dependency.version.as_deref()?)?;
+    candidates
+      .into_iter()
+      .find(|package| {
+        Version::parse(&package.version).is_ok_and(|version| constraint.matches(&version))
+      })
+      .map(|package| package.version.clone())
+  }
+}
+
+/// A range asked of a package, as `klep.keep` records it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct RequestedVersion {
+  /// The range as written by whoever asked for the package
+  pub version: String,
+}
+
+/// A requirement of a resolved package and the version it resolved to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct RequiredDependency {
+  /// Name of the required package
+  pub name: String,
+  /// The pinned version, or the range as written when nothing pins it
+  pub version: String,
+}
+
+/// The version a package resolved to and what it requires
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ResolvedVersion {
+  /// The pinned version
+  pub version: String,
+  /// What to take from the fetched package
+  pub extract: ExtractRule,
+  /// Dependencies of this version
+  pub requires: Vec<RequiredDependency>,
+}
+
+/// One pinned package as `klep.keep` records it, with where it is fetched
+/// from and its entry in the lockfile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ResolvedDependency {
+  /// Name of the package
+  pub name: String,
+  /// Where the package is fetched from
+  pub url: String,
+  /// Kind of place `url` points to
+  pub source: Source,
+  /// Ranges the package was asked for with
+  pub requested: Vec<RequestedVersion>,
+  /// What the ranges resolved to
+  pub resolved: ResolvedVersion,
+  /// The package's entry in the lockfile
+  pub span: Span,
+}
+
+/// Output of `ast/to_keepfile`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct KeepfileOutput {
+  /// Lockfile the resolutions were read from
+  pub lockfile: LockfileKind,
+  /// Dependencies of the project itself, when the lockfile records them
+  pub roots: Vec<Dependency>,
+  /// The entries of `klep.keep`
+  pub keepfile: Vec<ResolvedDependency>,
+}
+
+/// Lockfile whose structure does not match its format
+fn invalid(kind: LockfileKind, span: Span, message: impl AsRef<str>) -> GudError {
+  GudError::new(
+    ErrorKind::Parsing,
+    "invalid-lockfile",
+    format!(
+      "Invalid {} at line {}, column {}: {}",
+      kind.name(),
+      span.start.line,
+      span.start.column,
+      message.as_ref()
+    ),
+  )
+  .with_context(json!({
+    "lockfile": kind,
+    "line": span.start.line,
+    "column": span.start.column,
+  }))
+}
+
+/// String value of `node`, failing unless it is a string
+fn expect_str<'a>(kind: LockfileKind, node: &'a DataNode, what: &str) -> Result<&'a str, GudError> {
+  node.as_str().ok_or_else(|| {
+    invalid(
+      kind,
+      node.span(),
+      format!("expected '{what}' to be a string"),
+    )
+  })
+}
+
+/// String value of the field `key` of `node`, failing unless it is there
+fn require_str<'a>(kind: LockfileKind, node: &'a DataNode, key: &str) -> Result<&'a str, GudError> {
+  node
+    .get(key)
+    .ok_or_else(|| invalid(kind, node.span(), format!("expected a '{key}'")))
+    .and_then(|value| expect_str(kind, value, key))
+}
+
+/// Entries of the table `node`, failing unless it is a map
+fn expect_map<'a>(
+  kind: LockfileKind,
+  node: &'a DataNode,
+  what: &str,
+) -> Result<&'a [Entry], GudError> {
+  match node {
+    DataNode::Map { entries, .. } => Ok(entries),
+    _ => Err(invalid(
+      kind,
+      node.span(),
+      format!("expected {what} to be a table"),
+    )),
+  }
+}
+
+/// Pick the reader for `input` from its language, then its path, then its
+/// contents
+fn detect(input: &LockfileInput) -> Result<LockfileKind, GudError> {
+  let language = input.language.as_deref();
+  language
+    .and_then(LockfileKind::from_name)
+    .or_else(|| input.path.as_deref().and_then(LockfileKind::from_path))
+    .or_else(|| {
+      language
+        .and_then(Format::from_name)
+        .and_then(|format| LockfileKind::sniff(format, &input.source_code))
+    })
+    .ok_or_else(|| {
+      GudError::new(
+        ErrorKind::Argument,
+        "unknown-lockfile",
+        format!(
+          "Cannot tell which lockfile '{}' is",
+          input.path.as_deref().or(language).unwrap_or("the input")
+        ),
+      )
+      .with_context(json!({
+        "language": language,
+        "path": input.path,
+        "supported": LOCKFILES,
+      }))
+    })
+}
+
+/// Read the resolved dependency graph of a lockfile as `klep.keep` entries
+#[allow(clippy::needless_pass_by_value)]
+pub fn parse_to_keepfile(
+  input: LockfileInput,
+) -> Result<KeepfileOutput, Box<dyn std::error::Error>> {
+  let kind = detect(&input)?;
+  log::debug(&format!(
+    "Reading {} resolutions from {} characters",
+    kind.name(),
+    input.source_code.len()
+  ));
+  Ok(kind.read(&input.source_code)?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::Value;
+
+  /// Keepfile entries as `name@version` with their requests and
+  /// requirements, leaving out URLs, sources and spans
+  pub(super) fn summary(output: &KeepfileOutput) -> Value {
+    output
+      .keepfile
+      .iter()
+      .map(|entry| {
+        json!({
+          "package": format!("{}@{}", entry.name, entry.resolved.version),
+          "requested": entry.requested.iter().map(|request| &request.version).collect::<Vec<_>>(),
+          "requires": entry
+            .resolved
+            .requires
+            .iter()
+            .map(|required| format!("{}@{}", required.name, required.version))
+            .collect::<Vec<_>>(),
+        })
+      })
+      .collect()
+  }
+
+  fn input(source: &str, language: Option<&str>, path: Option<&str>) -> LockfileInput {
+    LockfileInput {
+      source_code: source.to_string(),
+      language: language.map(str::to_string),
+      path: path.map(str::to_string),
+    }
+  }
+
+  #[test]
+  fn detects_lockfiles_by_name_path_and_contents() {
+    let detect = |language, path, source| detect(&input(source, language, path)).ok();
+    assert_eq!(detect(Some("bun"), None, ""), Some(LockfileKind::BunLock));
+    assert_eq!(
+      detect(None, Some("web/npm-shrinkwrap.json"), ""),
+      Some(LockfileKind::PackageLock)
+    );
+    assert_eq!(
+      detect(None, Some("Gemfile.lock"), ""),
+      Some(LockfileKind::GemfileLock)
+    );
+    assert_eq!(
+      detect(
+        Some("json"),
+        None,
+        r#"{"lockfileVersion": 3, "packages": {}}"#
+      ),
+      Some(LockfileKind::PackageLock)
+    );
+    assert_eq!(
+      detect(Some("toml"), None, "[metadata]\ncontent-hash = \"x\""),
+      Some(LockfileKind::PoetryLock)
+    );
+    assert_eq!(detect(Some("json"), None, "{}"), None);
+    assert_eq!(detect(None, Some("Cargo.toml"), ""), None);
+  }
+
+  #[test]
+  fn reports_unknown_lockfiles() {
+    let error =
+      GudError::from_boxed(parse_to_keepfile(input("", None, Some("yarn.lock"))).unwrap_err());
+    assert_eq!(error.code, "unknown-lockfile");
+    assert_eq!(error.kind, ErrorKind::Argument);
+  }
+
+  #[test]
+  fn requests_and_pins_come_from_the_whole_graph() {
+    let output = LockfileKind::PackageLock
+      .read(
+        r#"{
+  "lockfileVersion": 3,
+  "packages": {
+    "": {"dependencies": {"a": "^1.0.0", "b": "^1.0.0"}},
+    "node_modules/a": {"version": "1.2.0", "dependencies": {"b": "^2.0.0"}},
+    "node_modules/a/node_modules/b": {"version": "2.1.0"},
+    "node_modules/b": {"version": "1.4.0"}
+  }
+}"#,
+      )
+      .unwrap();
+    assert_eq!(
+      summary(&output),
+      json!([
+        {"package": "a@1.2.0", "requested": ["^1.0.0"], "requires": ["b@2.1.0"]},
+        {"package": "b@2.1.0", "requested": ["^2.0.0"], "requires": []},
+        {"package": "b@1.4.0", "requested": ["^1.0.0"], "requires": []},
+      ])
+    );
+    assert_eq!(output.roots.len(), 2);
+    assert_eq!(output.keepfile[0].url, "https://registry.npmjs.org/a");
+  }
+}
@@ -0,0 +1,176 @@
+//! Bun's text lockfile, `bun.lock`.
+//!
+//! Each entry of `packages` is a list starting with the package's
+//! `name@spec`, followed for registry packages by the tarball URL (empty
+//! for the default registry), then a table of the package's own
+//! dependencies. A key `parent/name` is the copy of `name` installed for
+//! `parent`. The project's dependencies are in the `""` workspace.
+
+use gud_common::GudError;
+
+use super::{expect_map, expect_str, invalid, Lock, Locked, LockfileKind};
+use crate::data::{DataNode, Entry};
+use crate::to_manifest::{entry_span, npm, DependencyKind, ManifestKind, Source};
+
+const KIND: LockfileKind = LockfileKind::BunLock;
+
+pub(super) fn read(tree: &DataNode) -> Result<Lock, GudError> {
+  expect_map(KIND, tree, "the lockfile")?;
+  let mut lock = Lock::default();
+  if let Some(root) = tree
+    .get("workspaces")
+    .and_then(|workspaces| workspaces.get(""))
+  {
+    lock.roots = ManifestKind::PackageJson.extract_tree(root)?.dependencies;
+  }
+  let Some(packages) = tree.get("packages") else {
+    return Ok(lock);
+  };
+
+  let mut found = Vec::new();
+  for entry in expect_map(KIND, packages, "packages")? {
+    if let Some(locked) = package(entry)? {
+      found.push((entry, locked));
+    }
+  }
+  let installed: Vec<(&str, String)> = found
+    .iter()
+    .map(|(entry, locked)| (entry.key.as_str(), locked.version.clone()))
+    .collect();
+
+  for (entry, mut locked) in found {
+    // The table of the package's own dependencies
+    let meta = entry
+      .value
+      .items()
+      .iter()
+      .find(|item| matches!(item, DataNode::Map { .. }));
+    if let Some(meta) = meta {
+      for dependency in ManifestKind::PackageJson.extract_tree(meta)?.dependencies {
+        let pinned = resolve(&installed, &entry.key, &dependency.name);
+        locked.requires.push((dependency, pinned));
+      }
+    }
+    lock.packages.push(locked);
+  }
+  Ok(lock)
+}
+
+/// The package an entry of `packages` pins; workspace members are not
+/// packages of their own
+fn package(entry: &Entry) -> Result<Option<Locked>, GudError> {
+  let items = entry.value.items();
+  let Some(first) = items.first() else {
+    return Err(invalid(
+      KIND,
+      entry.value.span(),
+      format!(
+        "expected '{}' to be a list starting with its name and version",
+        entry.key
+      ),
+    ));
+  };
+  let ident = expect_str(KIND, first, &entry.key)?;
+  // Scoped names start with their own `@`
+  let at = ident
+    .get(1..)
+    .and_then(|rest| rest.find('@'))
+    .map_or(ident.len(), |at| at + 1);
+  let (name, spec) = (&ident[..at], ident.get(at + 1..).unwrap_or_default());
+  if spec.starts_with("workspace:") {
+    return Ok(None);
+  }
+
+  let dependency = npm::dependency(name, spec, DependencyKind::Normal, entry_span(entry));
+  let mut source = dependency.source;
+  let version = match &source {
+    Source::Git {
+      reference: Some(reference),
+      ..
+    } => reference.clone(),
+    _ => dependency.version.unwrap_or_else(|| spec.to_string()),
+  };
+  if let Some(tarball) = items
+    .get(1)
+    .and_then(DataNode::as_str)
+    .filter(|tarball| !tarball.is_empty() && source == Source::REGISTRY)
+  {
+    source = Source::Url {
+      url: tarball.to_string(),
+    };
+  }
+  Ok(Some(Locked::new(
+    dependency.name,
+    version,
+    source,
+    entry_span(entry),
+  )))
+}
+
+/// Version of `name` as seen from the package at `key`: `key/name`, then
+/// the same under each shorter prefix of `key`, then the top-level copy
+fn resolve(installed: &[(&str, String)], key: &str, name: &str) -> Option<String> {
+  let find = |candidate: &str| {
+    installed
+      .iter()
+      .find(|(key, _)| *key == candidate)
+      .map(|(_, version)| version.clone())
+  };
+  let mut prefix = key;
+  loop {
+    if let Some(version) = find(&format!("{prefix}/{name}")) {
+      return Some(version);
+    }
+    match prefix.rfind('/') {
+      Some(slash) => prefix = &prefix[..slash],
+      None => return find(name),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_keepfile::tests::summary;
+  use crate::to_keepfile::LockfileKind;
+  use serde_json::json;
+
+  #[test]
+  fn reads_packages_and_nested_copies() {
+    let output = LockfileKind::BunLock
+      .read(
+        r#"{
+  "lockfileVersion": 1,
+  "workspaces": {
+    "": {
+      "name": "app",
+      "dependencies": {"@scope/a": "^1.0.0", "b": "^1.0.0"},
+      "devDependencies": {"tool": "github:org/tool#v2"},
+    },
+    "packages/lib": {"name": "lib"},
+  },
+  "packages": {
+    "@scope/a": ["@scope/a@1.1.0", "", {"dependencies": {"b": "^2.0.0"}}, "sha512-a"],
+    "@scope/a/b": ["b@2.3.0", "", {}, "sha512-b2"],
+    "b": ["b@1.0.5", "https://npm.example.com/b/-/b-1.0.5.tgz", {}, "sha512-b1"],
+    "lib": ["lib@workspace:packages/lib"],
+    "tool": ["tool@github:org/tool#v2", {}, "org-tool-v2"],
+  },
+}"#,
+      )
+      .unwrap();
+    assert_eq!(
+      summary(&output),
+      json!([
+        {"package": "@scope/a@1.1.0", "requested": ["^1.0.0"], "requires": ["b@2.3.0"]},
+        {"package": "b@2.3.0", "requested": ["^2.0.0"], "requires": []},
+        {"package": "b@1.0.5", "requested": ["^1.0.0"], "requires": []},
+        {"package": "tool@v2", "requested": [], "requires": []},
+      ])
+    );
+    assert_eq!(
+      output.keepfile[2].url,
+      "https://npm.example.com/b/-/b-1.0.5.tgz"
+    );
+    assert_eq!(output.roots.len(), 3);
+  }
+}
@@ -0,0 +1,179 @@
+//! Cargo's `Cargo.lock`.
+//!
+//! Every `[[package]]` names its `source`: a registry index, a git URL
+//! whose fragment is the locked commit, or nothing for packages on disk
+//! such as workspace members. A dependency is written `name`, or
+//! `name version` and then `name version (source)` when the name alone is
+//! ambiguous. The lockfile keeps no ranges, so nothing is requested; the
+//! project's dependencies are those its packages on disk have outside the
+//! workspace, each listed once.
+
+use gud_common::GudError;
+use std::collections::HashSet;
+
+use super::{expect_map, expect_str, invalid, require_str, Lock, Locked, LockfileKind};
+use crate::data::DataNode;
+use crate::to_manifest::{Dependency, DependencyKind, Source};
+
+const KIND: LockfileKind = LockfileKind::CargoLock;
+
+/// Indexes of crates.io, which are the default registry
+const CRATES_IO: &[&str] = &[
+  "registry+https://github.com/rust-lang/crates.io-index",
+  "sparse+https://index.crates.io/",
+];
+
+pub(super) fn read(tree: &DataNode) -> Result<Lock, GudError> {
+  expect_map(KIND, tree, "the lockfile")?;
+  let mut lock = Lock::default();
+  let Some(packages) = tree.get("package") else {
+    return Ok(lock);
+  };
+  let DataNode::List { items, .. } = packages else {
+    return Err(invalid(
+      KIND,
+      packages.span(),
+      "expected [[package]] tables",
+    ));
+  };
+
+  for package in items {
+    expect_map(KIND, package, "a [[package]]")?;
+    let source = match package.get("source") {
+      Some(source) => source_of(expect_str(KIND, source, "source")?),
+      None => Source::Workspace,
+    };
+    let on_disk = source == Source::Workspace;
+    let span = package.span();
+    let mut locked = Locked::new(
+      require_str(KIND, package, "name")?,
+      require_str(KIND, package, "version")?,
+      source,
+      span,
+    );
+
+    for item in package.get("dependencies").map_or(&[][..], DataNode::items) {
+      let text = expect_str(KIND, item, "dependencies")?;
+      let mut words = text.split_whitespace();
+      let name = words.next().unwrap_or_default();
+      let version = words.next().map(str::to_string);
+      let dependency = Dependency::new(name, None, DependencyKind::Normal, item.span());
+      if on_disk {
+        lock.roots.push(dependency.clone());
+      }
+      locked.requires.push((dependency, version));
+    }
+    lock.packages.push(locked);
+  }
+
+  let members: HashSet<&str> = lock
+    .packages
+    .iter()
+    .filter(|package| package.source == Source::Workspace)
+    .map(|package| package.name.as_str())
+    .collect();
+  let mut seen = HashSet::new();
+  lock
+    .roots
+    .retain(|root| !members.contains(root.name.as_str()) && seen.insert(root.name.clone()));
+  Ok(lock)
+}
+
+/// `registry+<index>`, `sparse+<index>` or `git+<url>?<query>#<commit>`
+fn source_of(source: &str) -> Source {
+  if CRATES_IO.contains(&source) {
+    return Source::REGISTRY;
+  }
+  match source.split_once('+') {
+    Some(("git", url)) => {
+      let (url, commit) = url.split_once('#').unwrap_or((url, ""));
+      Source::Git {
+        url: url.split('?').next().unwrap_or(url).to_string(),
+        reference: (!commit.is_empty()).then(|| commit.to_string()),
+      }
+    }
+    Some((_, index)) => Source::Registry {
+      registry: Some(index.to_string()),
+    },
+    None => Source::Url {
+      url: source.to_string(),
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_keepfile::tests::summary;
+  use crate::to_keepfile::LockfileKind;
+  use crate::to_manifest::Source;
+  use serde_json::json;
+
+  #[test]
+  fn reads_packages_and_disambiguated_dependencies() {
+    let output = LockfileKind::CargoLock
+      .read(
+        r#"# This file is automatically @generated by Cargo.
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "app-core",
+ "serde",
+ "syn 2.0.66",
+ "tool",
+]
+
+[[package]]
+name = "app-core"
+version = "0.1.0"
+dependencies = ["serde", "tool"]
+
+[[package]]
+name = "serde"
+version = "1.0.203"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "7253ab4de971e72fb7be983802300c30b5a7f0c2e56fab8abfc6a214307c0094"
+dependencies = ["syn 1.0.109"]
+
+[[package]]
+name = "syn"
+version = "1.0.109"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "syn"
+version = "2.0.66"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "tool"
+version = "0.3.0"
+source = "git+https://github.com/org/tool?branch=main#0123abcd"
+"#,
+      )
+      .unwrap();
+    assert_eq!(
+      summary(&output),
+      json!([
+        {"package": "app@0.1.0", "requested": [], "requires": ["app-core@0.1.0", "serde@1.0.203", "syn@2.0.66", "tool@0.3.0"]},
+        {"package": "app-core@0.1.0", "requested": [], "requires": ["serde@1.0.203", "tool@0.3.0"]},
+        {"package": "serde@1.0.203", "requested": [], "requires": ["syn@1.0.109"]},
+        {"package": "syn@1.0.109", "requested": [], "requires": []},
+        {"package": "syn@2.0.66", "requested": [], "requires": []},
+        {"package": "tool@0.3.0", "requested": [], "requires": []},
+      ])
+    );
+    assert_eq!(
+      output.keepfile[5].source,
+      Source::Git {
+        url: "https://github.com/org/tool".to_string(),
+        reference: Some("0123abcd".to_string()),
+      }
+    );
+    assert_eq!(output.keepfile[2].url, "https://crates.io/crates/serde");
+    let roots: Vec<_> = output.roots.iter().map(|root| root.name.as_str()).collect();
+    assert_eq!(roots, ["serde", "syn", "tool"]);
+  }
+}
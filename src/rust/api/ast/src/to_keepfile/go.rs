@@ -0,0 +1,83 @@
+//! Go modules' `go.sum`.
+//!
+//! Each line is `module version hash`, where a version ending in `/go.mod`
+//! only checksums that module's `go.mod`. Modules whose contents are
+//! checksummed are the ones the build uses. `go.sum` does not say which
+//! module requires which; that lives in the modules' own `go.mod` files.
+
+use gud_common::GudError;
+
+use super::{invalid, Lock, Locked, LockfileKind};
+use crate::data::{Format, LineIndex};
+use crate::to_manifest::Source;
+
+const KIND: LockfileKind = LockfileKind::GoSum;
+
+pub(super) fn read(source: &str) -> Result<Lock, GudError> {
+  let index = LineIndex::new(source, Format::Lines);
+  let mut lock = Lock::default();
+
+  let mut offset = 0;
+  for raw in source.split_inclusive('\n') {
+    let start = offset + (raw.len() - raw.trim_start().len());
+    offset += raw.len();
+    let line = raw.trim();
+    if line.is_empty() {
+      continue;
+    }
+    let span = index.span(start, start + line.len());
+    let [module, version, _hash] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+      return Err(invalid(KIND, span, "expected '<module> <version> <hash>'"));
+    };
+    if version.ends_with("/go.mod")
+      || lock
+        .packages
+        .iter()
+        .any(|locked| locked.name == module && locked.version == version)
+    {
+      continue;
+    }
+    lock
+      .packages
+      .push(Locked::new(module, version, Source::REGISTRY, span));
+  }
+  Ok(lock)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_keepfile::tests::summary;
+  use crate::to_keepfile::LockfileKind;
+  use serde_json::json;
+
+  #[test]
+  fn reads_checksummed_modules() {
+    let output = LockfileKind::GoSum
+      .read(
+        "\
+github.com/spf13/cobra v1.8.0 h1:7aJaZx1B85qltLMc546zn58BxxfZdR/W22ej9CFoEf0=
+github.com/spf13/cobra v1.8.0/go.mod h1:WXLWApfZ71AjXPya3WOlMsY9yMs7YeiHhFVlvLyhcho=
+golang.org/x/sys v0.15.0/go.mod h1:/VUhepiaJMQUp4+oa/7Zr1D23ma6VTLIYjOOTFZPUcA=
+golang.org/x/sys v0.20.0 h1:Od9JTbYCk261bKm4M/mw7AklTlFYIa0bIp9BgSm1S8Y=
+",
+      )
+      .unwrap();
+    assert_eq!(
+      summary(&output),
+      json!([
+        {"package": "github.com/spf13/cobra@v1.8.0", "requested": [], "requires": []},
+        {"package": "golang.org/x/sys@v0.20.0", "requested": [], "requires": []},
+      ])
+    );
+    assert_eq!(output.keepfile[0].url, "https://github.com/spf13/cobra");
+  }
+
+  #[test]
+  fn rejects_malformed_lines() {
+    let error = LockfileKind::GoSum
+      .read("golang.org/x/sys v0.20.0 h1:x=\ngolang.org/x/text\n")
+      .unwrap_err();
+    assert_eq!(error.code, "invalid-lockfile");
+    assert_eq!(error.context["line"], 2);
+  }
+}
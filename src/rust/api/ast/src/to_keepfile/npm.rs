@@ -0,0 +1,253 @@
+//! npm's `package-lock.json` and `npm-shrinkwrap.json`.
+//!
+//! Lockfile versions 2 and 3 list every installed package under `packages`,
+//! keyed by its path in `node_modules`, and a requirement resolves to the
+//! nearest copy up that path as Node would find it. Workspace folders and
+//! the links to them are not installed packages and are skipped. Version 1
+//! lockfiles only have the `dependencies` tree, nested the same way.
+
+use gud_common::GudError;
+use std::collections::BTreeMap;
+
+use super::{expect_map, expect_str, require_str, Lock, Locked, LockfileKind};
+use crate::data::{DataNode, Entry};
+use crate::to_manifest::{
+  entry_span, git_source, Dependency, DependencyKind, ManifestKind, Source,
+};
+
+const KIND: LockfileKind = LockfileKind::PackageLock;
+
+const MODULES: &str = "node_modules/";
+
+pub(super) fn read(tree: &DataNode) -> Result<Lock, GudError> {
+  expect_map(KIND, tree, "the lockfile")?;
+  let mut lock = Lock::default();
+  if let Some(packages) = tree.get("packages") {
+    read_packages(expect_map(KIND, packages, "packages")?, &mut lock)?;
+  } else if let Some(dependencies) = tree.get("dependencies") {
+    let dependencies = expect_map(KIND, dependencies, "dependencies")?;
+    read_nested(dependencies, &mut Vec::new(), &mut lock)?;
+  }
+  Ok(lock)
+}
+
+/// Where a package was fetched from, going by its `resolved` field
+fn source_of(resolved: Option<&str>) -> Source {
+  match resolved {
+    Some(url) if url.starts_with("git+") || url.starts_with("git://") => git_source(url),
+    Some(url) => url
+      .strip_prefix("file:")
+      .map_or(Source::REGISTRY, |path| Source::Path {
+        path: path.to_string(),
+      }),
+    None => Source::REGISTRY,
+  }
+}
+
+fn read_packages(entries: &[Entry], lock: &mut Lock) -> Result<(), GudError> {
+  let mut installed = BTreeMap::new();
+  for entry in entries {
+    if let Some(version) = entry.value.get("version").and_then(DataNode::as_str) {
+      installed.insert(entry.key.as_str(), version);
+    }
+  }
+
+  for entry in entries {
+    let package = &entry.value;
+    expect_map(KIND, package, &entry.key)?;
+    if entry.key.is_empty() {
+      lock.roots = ManifestKind::PackageJson
+        .extract_tree(package)?
+        .dependencies;
+      continue;
+    }
+    let is_link = package.get("link").and_then(DataNode::as_bool) == Some(true);
+    let Some(at) = entry.key.rfind(MODULES).filter(|_| !is_link) else {
+      continue;
+    };
+
+    // An aliased package is installed under its alias but names itself
+    let name = match package.get("name") {
+      Some(name) => expect_str(KIND, name, "name")?,
+      None => &entry.key[at + MODULES.len()..],
+    };
+    let resolved = package
+      .get("resolved")
+      .map(|resolved| expect_str(KIND, resolved, "resolved"))
+      .transpose()?;
+    let mut locked = Locked::new(
+      name,
+      require_str(KIND, package, "version")?,
+      source_of(resolved),
+      entry_span(entry),
+    );
+    for dependency in ManifestKind::PackageJson
+      .extract_tree(package)?
+      .dependencies
+    {
+      let pinned = resolve(&installed, &entry.key, &dependency.name).map(str::to_string);
+      locked.requires.push((dependency, pinned));
+    }
+    lock.packages.push(locked);
+  }
+  Ok(())
+}
+
+/// Version installed where Node looks for `name` from the package at
+/// `path`: its own `node_modules`, then each enclosing one
+fn resolve<'a>(installed: &BTreeMap<&str, &'a str>, path: &str, name: &str) -> Option<&'a str> {
+  let mut base = path;
+  loop {
+    let candidate = if base.is_empty() {
+      format!("{MODULES}{name}")
+    } else {
+      format!("{base}/{MODULES}{name}")
+    };
+    if let Some(version) = installed.get(candidate.as_str()) {
+      return Some(version);
+    }
+    if base.is_empty() {
+      return None;
+    }
+    base = base
+      .rfind(&format!("/{MODULES}"))
+      .map_or("", |at| &base[..at]);
+  }
+}
+
+/// Version 1: each package's `dependencies` are the copies installed
+/// inside it, and `requires` maps the names it needs to their ranges
+fn read_nested<'a>(
+  dependencies: &'a [Entry],
+  scopes: &mut Vec<&'a [Entry]>,
+  lock: &mut Lock,
+) -> Result<(), GudError> {
+  scopes.push(dependencies);
+  for entry in dependencies {
+    let package = &entry.value;
+    expect_map(KIND, package, &entry.key)?;
+    let nested = match package.get("dependencies") {
+      Some(nested) => expect_map(KIND, nested, "dependencies")?,
+      None => &[],
+    };
+
+    // Git dependencies record their URL as the version
+    let version = require_str(KIND, package, "version")?;
+    let (source, version) = match source_of(Some(version)) {
+      Source::Git {
+        url,
+        reference: Some(reference),
+      } => (
+        Source::Git {
+          url,
+          reference: Some(reference.clone()),
+        },
+        reference,
+      ),
+      Source::REGISTRY => {
+        let resolved = package.get("resolved").and_then(DataNode::as_str);
+        (source_of(resolved), version.to_string())
+      }
+      source => (source, version.to_string()),
+    };
+    let mut locked = Locked::new(&entry.key, version, source, entry_span(entry));
+
+    if let Some(requires) = package.get("requires") {
+      for requirement in expect_map(KIND, requires, "requires")? {
+        let range = expect_str(KIND, &requirement.value, &requirement.key)?;
+        let pinned = std::iter::once(nested)
+          .chain(scopes.iter().rev().copied())
+          .find_map(|scope| {
+            scope
+              .iter()
+              .find(|installed| installed.key == requirement.key)
+          })
+          .and_then(|installed| installed.value.get("version"))
+          .and_then(DataNode::as_str)
+          .map(str::to_string);
+        let dependency = Dependency::new(
+          &requirement.key,
+          Some(range.to_string()),
+          DependencyKind::Normal,
+          entry_span(requirement),
+        );
+        locked.requires.push((dependency, pinned));
+      }
+    }
+    lock.packages.push(locked);
+    read_nested(nested, scopes, lock)?;
+  }
+  scopes.pop();
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_keepfile::tests::summary;
+  use crate::to_keepfile::LockfileKind;
+  use crate::to_manifest::Source;
+  use serde_json::json;
+
+  #[test]
+  fn reads_installed_packages_and_roots() {
+    let output = LockfileKind::PackageLock
+      .read(
+        r#"{
+  "name": "app",
+  "lockfileVersion": 3,
+  "packages": {
+    "": {"name": "app", "dependencies": {"left": "npm:pad@^1.3.0", "tool": "github:org/tool#v1"}, "devDependencies": {"lib": "*"}},
+    "node_modules/left": {"name": "pad", "version": "1.3.1", "resolved": "https://registry.npmjs.org/pad/-/pad-1.3.1.tgz"},
+    "node_modules/tool": {"version": "1.0.0", "resolved": "git+ssh://git@github.com/org/tool.git#0123abc", "peerDependencies": {"pad": "1.x"}},
+    "node_modules/lib": {"resolved": "packages/lib", "link": true},
+    "packages/lib": {"name": "lib", "version": "0.1.0"}
+  }
+}"#,
+      )
+      .unwrap();
+    assert_eq!(
+      summary(&output),
+      json!([
+        {"package": "pad@1.3.1", "requested": ["^1.3.0", "1.x"], "requires": []},
+        {"package": "tool@1.0.0", "requested": [], "requires": ["pad@1.3.1"]},
+      ])
+    );
+    assert_eq!(
+      output.keepfile[1].source,
+      Source::Git {
+        url: "ssh://git@github.com/org/tool.git".to_string(),
+        reference: Some("0123abc".to_string()),
+      }
+    );
+    let roots: Vec<_> = output.roots.iter().map(|root| root.name.as_str()).collect();
+    assert_eq!(roots, ["pad", "tool", "lib"]);
+  }
+
+  #[test]
+  fn reads_version_1_trees() {
+    let output = LockfileKind::PackageLock
+      .read(
+        r#"{
+  "lockfileVersion": 1,
+  "requires": true,
+  "dependencies": {
+    "a": {"version": "1.0.0", "requires": {"b": "^2.0.0", "c": "~1.1.0"}, "dependencies": {
+      "b": {"version": "2.0.1"}
+    }},
+    "b": {"version": "1.0.0"},
+    "c": {"version": "1.1.4"}
+  }
+}"#,
+      )
+      .unwrap();
+    assert_eq!(
+      summary(&output),
+      json!([
+        {"package": "a@1.0.0", "requested": [], "requires": ["b@2.0.1", "c@1.1.4"]},
+        {"package": "b@2.0.1", "requested": ["^2.0.0"], "requires": []},
+        {"package": "b@1.0.0", "requested": [], "requires": []},
+        {"package": "c@1.1.4", "requested": ["~1.1.0"], "requires": []},
+      ])
+    );
+  }
+}
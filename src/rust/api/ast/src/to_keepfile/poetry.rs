@@ -0,0 +1,200 @@
+//! Poetry's `poetry.lock`.
+//!
+//! Every `[[package]]` has its `[package.dependencies]` with the ranges it
+//! asks for, written as a string, a table with a `version`, or a list of
+//! such tables for different markers. `[package.source]` says where a
+//! package not on `PyPI` comes from. Names are compared in their normalized
+//! form, lowercase with runs of `-`, `_` and `.` as one `-`. The project's
+//! own dependencies live in `pyproject.toml`, not here.
+
+use gud_common::GudError;
+
+use super::{expect_map, expect_str, invalid, require_str, Lock, Locked, LockfileKind};
+use crate::data::DataNode;
+use crate::to_manifest::{entry_span, Dependency, DependencyKind, Source};
+
+const KIND: LockfileKind = LockfileKind::PoetryLock;
+
+pub(super) fn read(tree: &DataNode) -> Result<Lock, GudError> {
+  expect_map(KIND, tree, "the lockfile")?;
+  let mut lock = Lock::default();
+  let Some(packages) = tree.get("package") else {
+    return Ok(lock);
+  };
+  let DataNode::List { items, .. } = packages else {
+    return Err(invalid(
+      KIND,
+      packages.span(),
+      "expected [[package]] tables",
+    ));
+  };
+
+  for package in items {
+    expect_map(KIND, package, "a [[package]]")?;
+    let mut locked = Locked::new(
+      normalize(require_str(KIND, package, "name")?),
+      require_str(KIND, package, "version")?,
+      match package.get("source") {
+        Some(source) => source_of(source)?,
+        None => Source::REGISTRY,
+      },
+      package.span(),
+    );
+    if let Some(dependencies) = package.get("dependencies") {
+      for entry in expect_map(KIND, dependencies, "[package.dependencies]")? {
+        let (version, optional) = requirement(&entry.value, &entry.key)?;
+        let kind = if optional {
+          DependencyKind::Optional
+        } else {
+          DependencyKind::Normal
+        };
+        let dependency = Dependency::new(normalize(&entry.key), version, kind, entry_span(entry));
+        locked.requires.push((dependency, None));
+      }
+    }
+    lock.packages.push(locked);
+  }
+  Ok(lock)
+}
+
+/// Name as PEP 503 normalizes it
+fn normalize(name: &str) -> String {
+  let mut normalized = String::with_capacity(name.len());
+  for c in name.chars() {
+    if matches!(c, '-' | '_' | '.') {
+      if !normalized.ends_with('-') {
+        normalized.push('-');
+      }
+    } else {
+      normalized.push(c.to_ascii_lowercase());
+    }
+  }
+  normalized
+}
+
+/// Range and optionality of one dependency; alternatives for different
+/// markers are merged into one range
+fn requirement(spec: &DataNode, name: &str) -> Result<(Option<String>, bool), GudError> {
+  match spec {
+    DataNode::Scalar { .. } => Ok((Some(expect_str(KIND, spec, name)?.to_string()), false)),
+    DataNode::Map { .. } => {
+      let version = spec
+        .get("version")
+        .map(|version| expect_str(KIND, version, "version"))
+        .transpose()?;
+      let optional = spec.get("optional").and_then(DataNode::as_bool) == Some(true);
+      Ok((version.map(str::to_string), optional))
+    }
+    DataNode::List { items, .. } => {
+      let mut ranges = Vec::new();
+      let mut optional = true;
+      for item in items {
+        let (version, item_optional) = requirement(item, name)?;
+        ranges.extend(version);
+        optional &= item_optional;
+      }
+      Ok(((!ranges.is_empty()).then(|| ranges.join(" || ")), optional))
+    }
+  }
+}
+
+/// `[package.source]`: a git repository at its `resolved_reference`, a
+/// directory, a file or URL, or a named alternative index
+fn source_of(source: &DataNode) -> Result<Source, GudError> {
+  let url = require_str(KIND, source, "url")?.to_string();
+  let field = |key| {
+    source
+      .get(key)
+      .and_then(DataNode::as_str)
+      .map(str::to_string)
+  };
+  Ok(match require_str(KIND, source, "type")? {
+    "git" => Source::Git {
+      url,
+      reference: field("resolved_reference").or_else(|| field("reference")),
+    },
+    "directory" | "file" => Source::Path { path: url },
+    "url" => Source::Url { url },
+    _ => Source::Registry {
+      registry: Some(url),
+    },
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_keepfile::tests::summary;
+  use crate::to_keepfile::LockfileKind;
+  use crate::to_manifest::Source;
+  use serde_json::json;
+
+  #[test]
+  fn reads_packages_with_their_ranges() {
+    let output = LockfileKind::PoetryLock
+      .read(
+        r#"
+[[package]]
+name = "requests"
+version = "2.31.0"
+optional = false
+python-versions = ">=3.7"
+files = [{file = "requests-2.31.0.tar.gz", hash = "sha256:942c5a"}]
+
+[package.dependencies]
+certifi = ">=2017.4.17"
+charset-normalizer = ">=2,<4"
+urllib3 = {version = ">=1.21.1,<3", optional = true}
+
+[[package]]
+name = "Charset_Normalizer"
+version = "3.3.2"
+
+[[package]]
+name = "urllib3"
+version = "2.2.1"
+
+[[package]]
+name = "certifi"
+version = "2024.2.2"
+
+[[package]]
+name = "tool"
+version = "0.1.0"
+
+[package.dependencies]
+numpy = [
+  {version = ">=1.26", markers = "python_version >= \"3.12\""},
+  {version = ">=1.22", markers = "python_version < \"3.12\""},
+]
+
+[package.source]
+type = "git"
+url = "https://github.com/org/tool.git"
+reference = "main"
+resolved_reference = "0123abcd"
+
+[metadata]
+lock-version = "2.0"
+content-hash = "abc"
+"#,
+      )
+      .unwrap();
+    assert_eq!(
+      summary(&output),
+      json!([
+        {"package": "requests@2.31.0", "requested": [], "requires": ["certifi@2024.2.2", "charset-normalizer@3.3.2", "urllib3@2.2.1"]},
+        {"package": "charset-normalizer@3.3.2", "requested": [">=2,<4"], "requires": []},
+        {"package": "urllib3@2.2.1", "requested": [">=1.21.1,<3"], "requires": []},
+        {"package": "certifi@2024.2.2", "requested": [">=2017.4.17"], "requires": []},
+        {"package": "tool@0.1.0", "requested": [], "requires": ["numpy@>=1.26 || >=1.22"]},
+      ])
+    );
+    assert_eq!(
+      output.keepfile[4].source,
+      Source::Git {
+        url: "https://github.com/org/tool.git".to_string(),
+        reference: Some("0123abcd".to_string()),
+      }
+    );
+  }
+}
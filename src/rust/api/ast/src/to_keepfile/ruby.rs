@@ -0,0 +1,246 @@
+//! Bundler's `Gemfile.lock`.
+//!
+//! `GEM`, `GIT` and `PATH` sections give their `remote` and, for git, the
+//! locked `revision`, then list their `specs:`: each gem indented by four
+//! spaces as `name (version)`, its requirements by six as
+//! `name (ranges)`. A version may carry a platform after `-`, and every
+//! platform's copy is listed. `DEPENDENCIES` holds the Gemfile's own gems,
+//! marked with `!` when they come from a git or path section.
+
+use gud_common::GudError;
+
+use super::{invalid, Lock, Locked, LockfileKind};
+use crate::data::{Format, LineIndex, Span};
+use crate::to_manifest::{Dependency, DependencyKind, Source};
+
+const KIND: LockfileKind = LockfileKind::GemfileLock;
+
+const RUBYGEMS: &str = "https://rubygems.org/";
+
+/// `name (ranges)` or `name`
+fn spec(text: &str) -> (&str, Option<&str>) {
+  text
+    .split_once(" (")
+    .map_or((text, None), |(name, ranges)| {
+      (name, Some(ranges.trim_end_matches(')')))
+    })
+}
+
+/// Where the gems of a `GEM`, `GIT` or `PATH` section come from, once
+/// its fields are read
+fn source_of(section: &str, fields: &[(&str, &str)], span: Span) -> Result<Source, GudError> {
+  let field = |key| {
+    fields
+      .iter()
+      .find(|(name, _)| *name == key)
+      .map(|(_, value)| (*value).to_string())
+  };
+  let remote =
+    field("remote").ok_or_else(|| invalid(KIND, span, "expected a 'remote' before 'specs:'"))?;
+  Ok(match section {
+    "GIT" => Source::Git {
+      url: remote,
+      reference: field("revision"),
+    },
+    "PATH" => Source::Path { path: remote },
+    _ if remote == RUBYGEMS => Source::REGISTRY,
+    _ => Source::Registry {
+      registry: Some(remote),
+    },
+  })
+}
+
+/// Lock the gem of a `name (version)` line, unless another platform's copy
+/// already did, and give the index of its entry
+fn gem(lock: &mut Lock, text: &str, source: &Source, span: Span) -> usize {
+  let (name, version) = spec(text);
+  // The release without the platform, as in `1.16.0-x86_64-linux`
+  let version = version
+    .unwrap_or_default()
+    .split('-')
+    .next()
+    .unwrap_or_default();
+  lock
+    .packages
+    .iter()
+    .position(|locked| locked.name == name && locked.version == version)
+    .unwrap_or_else(|| {
+      lock
+        .packages
+        .push(Locked::new(name, version, source.clone(), span));
+      lock.packages.len() - 1
+    })
+}
+
+/// Add the requirement on a `name (ranges)` line, once across platforms
+fn require(locked: &mut Locked, text: &str, span: Span) {
+  let (name, ranges) = spec(text);
+  if locked
+    .requires
+    .iter()
+    .all(|(required, _)| required.name != name)
+  {
+    let dependency = Dependency::new(
+      name,
+      ranges.map(str::to_string),
+      DependencyKind::Normal,
+      span,
+    );
+    locked.requires.push((dependency, None));
+  }
+}
+
+pub(super) fn read(source: &str) -> Result<Lock, GudError> {
+  let index = LineIndex::new(source, Format::Lines);
+  let mut lock = Lock::default();
+  let mut section = "";
+  let mut fields = Vec::new();
+  let mut specs: Option<Source> = None;
+  // Gem receiving the requirements that follow
+  let mut current: Option<usize> = None;
+  // Roots marked `!`, which take the source of their locked gem
+  let mut elsewhere = Vec::new();
+
+  let mut offset = 0;
+  for raw in source.split_inclusive('\n') {
+    let line = raw.trim_end();
+    let text = line.trim_start();
+    let indent = line.len() - text.len();
+    let start = offset + indent;
+    offset += raw.len();
+    if text.is_empty() {
+      continue;
+    }
+    let span = index.span(start, start + text.len());
+
+    match (section, indent) {
+      (_, 0) => {
+        section = text;
+        fields.clear();
+        specs = None;
+        current = None;
+      }
+      ("DEPENDENCIES", 2) => {
+        let (name, ranges) = spec(text);
+        let (name, bang) = name
+          .strip_suffix('!')
+          .map_or((name, false), |name| (name, true));
+        if bang {
+          elsewhere.push(lock.roots.len());
+        }
+        lock.roots.push(Dependency::new(
+          name,
+          ranges.map(str::to_string),
+          DependencyKind::Normal,
+          span,
+        ));
+      }
+      ("GEM" | "GIT" | "PATH", 2) => {
+        if text == "specs:" {
+          specs = Some(source_of(section, &fields, span)?);
+        } else if let Some((key, value)) = text.split_once(": ") {
+          fields.push((key, value));
+        }
+      }
+      ("GEM" | "GIT" | "PATH", 4) => {
+        let Some(source) = &specs else {
+          return Err(invalid(KIND, span, "expected 'specs:' before the gems"));
+        };
+        current = Some(gem(&mut lock, text, source, span));
+      }
+      ("GEM" | "GIT" | "PATH", 6) => {
+        let Some(locked) = current.map(|current| &mut lock.packages[current]) else {
+          return Err(invalid(
+            KIND,
+            span,
+            "expected a gem before its requirements",
+          ));
+        };
+        require(locked, text, span);
+      }
+      // `PLATFORMS`, `RUBY VERSION`, `BUNDLED WITH` and others
+      _ => {}
+    }
+  }
+
+  for root in elsewhere {
+    let dependency = &mut lock.roots[root];
+    if let Some(locked) = lock
+      .packages
+      .iter()
+      .find(|locked| locked.name == dependency.name)
+    {
+      dependency.source = locked.source.clone();
+    }
+  }
+  Ok(lock)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_keepfile::tests::summary;
+  use crate::to_keepfile::LockfileKind;
+  use crate::to_manifest::Source;
+  use serde_json::json;
+
+  const LOCKFILE: &str = "\
+GIT
+  remote: https://github.com/org/tool.git
+  revision: 0123abcd
+  branch: main
+  specs:
+    tool (0.3.0)
+      rack (>= 2.0)
+
+GEM
+  remote: https://rubygems.org/
+  specs:
+    nokogiri (1.16.0-arm64-darwin)
+      racc (~> 1.4)
+    nokogiri (1.16.0-x86_64-linux)
+      racc (~> 1.4)
+    rack (3.0.9)
+    racc (1.7.3)
+
+PLATFORMS
+  arm64-darwin
+  x86_64-linux
+
+DEPENDENCIES
+  nokogiri (~> 1.16)
+  tool!
+
+BUNDLED WITH
+   2.5.3
+";
+
+  #[test]
+  fn reads_specs_and_dependencies() {
+    let output = LockfileKind::GemfileLock.read(LOCKFILE).unwrap();
+    assert_eq!(
+      summary(&output),
+      json!([
+        {"package": "tool@0.3.0", "requested": [], "requires": ["rack@3.0.9"]},
+        {"package": "nokogiri@1.16.0", "requested": ["~> 1.16"], "requires": ["racc@1.7.3"]},
+        {"package": "rack@3.0.9", "requested": [">= 2.0"], "requires": []},
+        {"package": "racc@1.7.3", "requested": ["~> 1.4"], "requires": []},
+      ])
+    );
+    let tool = Source::Git {
+      url: "https://github.com/org/tool.git".to_string(),
+      reference: Some("0123abcd".to_string()),
+    };
+    assert_eq!(output.keepfile[0].source, tool);
+    assert_eq!(output.roots[1].source, tool);
+    assert_eq!(output.keepfile[1].url, "https://rubygems.org/gems/nokogiri");
+  }
+
+  #[test]
+  fn rejects_requirements_outside_a_gem() {
+    let error = LockfileKind::GemfileLock
+      .read("GEM\n  remote: https://rubygems.org/\n  specs:\n      rack (>= 2.0)\n")
+      .unwrap_err();
+    assert_eq!(error.code, "invalid-lockfile");
+    assert_eq!(error.context["line"], 4);
+  }
+}
@@ -0,0 +1,976 @@
+//! Dependency extraction from the manifests of known ecosystems.
+//!
+//! Each extractor reads one manifest format deterministically and reports
+//! the package's own name and version, its dependencies with their raw
+//! constraints, where they come from and how they are used, and the names
+//! of its scripts. Runtime requirements such as `python`, `php` or `lua`
+//! are not dependencies and are left out.
+//!
+//! Every dependency becomes the same [`Dependency`] record whatever its
+//! ecosystem, with its constraint normalized by `gud_std` and the span of
+//! its declaration. [`ManifestOutput::deps_file`] holds the same
+//! dependencies in the shape of `klep.deps`.
+//!
+//! A manifest that is none of these but is written in a known data format
+//! is read as the known manifest it is structurally nearest to, when that
+//! match is confident enough; see [`crate::family`]. In-house formats can
+//! be taught with rule files instead, which are tried ahead of that
+//! inference; see [`rules`].
+
+mod cargo;
+mod composer;
+mod go;
+pub(crate) mod npm;
+mod python;
+mod rockspec;
+mod ruby;
+pub mod rules;
+
+use gud_common::{log, ErrorKind, GudError};
+use gud_std::version::Constraint;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::data::{DataNode, Entry, Format, Span};
+use crate::family::{data_format, nearest_manifest, FamilyMatch};
+use rules::{RuleFile, Rules};
+
+/// Input of `ast/to_manifest`
+#[derive(Deserialize, JsonSchema)]
+pub struct AstInput {
+  /// Contents of the manifest
+  pub source_code: String,
+  /// Manifest name such as `package.json` or `cargo`, or a data format
+  /// (`json`, `toml`, `txt`) to detect the manifest from its contents
+  pub language: Option<String>,
+  /// Path or file name of the manifest
+  pub path: Option<String>,
+  /// Rule files for in-house manifest formats, each checked against its
+  /// samples before use
+  #[serde(default)]
+  pub rules: Vec<RuleFile>,
+}
+
+/// Manifest formats with an extractor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ManifestKind {
+  /// npm, Bun and Yarn
+  #[serde(rename = "package.json")]
+  PackageJson,
+  /// Cargo
+  #[serde(rename = "Cargo.toml")]
+  CargoToml,
+  /// PEP 621 and Poetry
+  #[serde(rename = "pyproject.toml")]
+  Pyproject,
+  /// pip
+  #[serde(rename = "requirements.txt")]
+  Requirements,
+  /// Go modules
+  #[serde(rename = "go.mod")]
+  GoMod,
+  /// Bundler
+  #[serde(rename = "Gemfile")]
+  Gemfile,
+  /// Composer
+  #[serde(rename = "composer.json")]
+  ComposerJson,
+  /// Lua rocks, such as `foo-1.0-1.rockspec`
+  #[serde(rename = "rockspec")]
+  Rockspec,
+}
+
+/// Confidence a guessed manifest needs for its dependencies to be read
+pub const GUESS_CONFIDENCE: f64 = 0.3;
+
+/// Every manifest name accepted as `language`
+pub const MANIFESTS: &[&str] = &[
+  "package.json",
+  "Cargo.toml",
+  "pyproject.toml",
+  "requirements.txt",
+  "go.mod",
+  "Gemfile",
+  "composer.json",
+  "rockspec",
+];
+
+impl ManifestKind {
+  /// Look up a manifest by file name or ecosystem name, ignoring case
+  #[must_use]
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name.to_ascii_lowercase().as_str() {
+      "package.json" | "npm" | "node" => Some(Self::PackageJson),
+      "cargo.toml" | "cargo" => Some(Self::CargoToml),
+      "pyproject.toml" | "pyproject" | "poetry" => Some(Self::Pyproject),
+      "requirements.txt" | "requirements" | "pip" => Some(Self::Requirements),
+      "go.mod" | "gomod" | "go" => Some(Self::GoMod),
+      "gemfile" | "gems.rb" | "bundler" => Some(Self::Gemfile),
+      "composer.json" | "composer" => Some(Self::ComposerJson),
+      "rockspec" | "luarocks" => Some(Self::Rockspec),
+      _ => None,
+    }
+  }
+
+  /// Recognize a manifest from the file name at the end of `path`
+  #[must_use]
+  pub fn from_path(path: &str) -> Option<Self> {
+    let file = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    let lower = file.to_ascii_lowercase();
+    let extension = std::path::Path::new(&lower).extension();
+    if extension.is_some_and(|extension| extension == "rockspec") {
+      Some(Self::Rockspec)
+    } else if lower.starts_with("requirements")
+      && extension.is_some_and(|extension| extension == "txt")
+    {
+      Some(Self::Requirements)
+    } else if lower.contains('.') || lower == "gemfile" {
+      Self::from_name(&lower)
+    } else {
+      None
+    }
+  }
+
+  /// Guess the manifest from a document in a generic data `format`
+  fn sniff(format: Format, source: &str) -> Option<Self> {
+    match format {
+      Format::Json | Format::Jsonc => {
+        let tree = format.parse(source).ok()?;
+        let composer = ["require", "require-dev"]
+          .iter()
+          .any(|key| tree.get(key).is_some());
+        Some(if composer && tree.get("dependencies").is_none() {
+          Self::ComposerJson
+        } else {
+          Self::PackageJson
+        })
+      }
+      Format::Toml => {
+        let tree = format.parse(source).ok()?;
+        if tree.get("package").is_some() || tree.get("workspace").is_some() {
+          Some(Self::CargoToml)
+        } else if tree.get("project").is_some() || tree.get("tool").is_some() {
+          Some(Self::Pyproject)
+        } else {
+          None
+        }
+      }
+      Format::Lines => Some(Self::Requirements),
+      _ => None,
+    }
+  }
+
+  /// File name of the manifest, as listed in [`MANIFESTS`]
+  #[must_use]
+  pub const fn name(self) -> &'static str {
+    match self {
+      Self::PackageJson => "package.json",
+      Self::CargoToml => "Cargo.toml",
+      Self::Pyproject => "pyproject.toml",
+      Self::Requirements => "requirements.txt",
+      Self::GoMod => "go.mod",
+      Self::Gemfile => "Gemfile",
+      Self::ComposerJson => "composer.json",
+      Self::Rockspec => "rockspec",
+    }
+  }
+
+  /// Data format the manifest is written in, if it is one
+  #[must_use]
+  pub const fn format(self) -> Option<Format> {
+    match self {
+      Self::PackageJson | Self::ComposerJson => Some(Format::Json),
+      Self::CargoToml | Self::Pyproject => Some(Format::Toml),
+      Self::Requirements => Some(Format::Lines),
+      Self::GoMod | Self::Gemfile | Self::Rockspec => None,
+    }
+  }
+
+  /// Extract the manifest from its source text
+  pub fn extract(self, source: &str) -> Result<ManifestOutput, GudError> {
+    let mut manifest = self.empty();
+    match self {
+      Self::PackageJson | Self::ComposerJson => {
+        return self.extract_tree(&Format::Json.parse(source)?);
+      }
+      Self::CargoToml | Self::Pyproject => return self.extract_tree(&Format::Toml.parse(source)?),
+      Self::Requirements => return self.extract_tree(&Format::Lines.parse(source)?),
+      Self::GoMod => go::extract(source, &mut manifest)?,
+      Self::Gemfile => ruby::extract(source, &mut manifest)?,
+      Self::Rockspec => rockspec::extract(source, &mut manifest)?,
+    }
+    Ok(self.finish(manifest))
+  }
+
+  /// Extract a manifest already parsed into the intermediate tree, such as
+  /// a package entry of a lockfile. Manifests that are not a data format
+  /// give no dependencies.
+  pub(crate) fn extract_tree(self, tree: &DataNode) -> Result<ManifestOutput, GudError> {
+    let mut manifest = self.empty();
+    match self {
+      Self::PackageJson => npm::extract(tree, &mut manifest)?,
+      Self::CargoToml => cargo::extract(tree, &mut manifest)?,
+      Self::Pyproject => python::extract_pyproject(tree, &mut manifest)?,
+      Self::Requirements => python::extract_requirements(tree, &mut manifest),
+      Self::ComposerJson => composer::extract(tree, &mut manifest)?,
+      Self::GoMod | Self::Gemfile | Self::Rockspec => {}
+    }
+    Ok(self.finish(manifest))
+  }
+
+  fn empty(self) -> ManifestOutput {
+    ManifestOutput {
+      manifest: Some(self),
+      ..ManifestOutput::default()
+    }
+  }
+
+  fn finish(self, mut manifest: ManifestOutput) -> ManifestOutput {
+    for dependency in &mut manifest.dependencies {
+      self.complete(dependency);
+    }
+    manifest.deps_file = DepsFile::new(&manifest.dependencies);
+    manifest
+  }
+
+  /// Fill in the URL and normalized constraint of `dependency`
+  pub(crate) fn complete(self, dependency: &mut Dependency) {
+    dependency.url = self.url_of(dependency);
+    dependency.constraint = dependency
+      .version
+      .as_deref()
+      .and_then(|version| self.parse_constraint(version));
+  }
+
+  /// Where the ecosystem's registry publishes a package
+  fn registry_url(self, name: &str) -> String {
+    match self {
+      Self::PackageJson => format!("https://registry.npmjs.org/{name}"),
+      Self::CargoToml => format!("https://crates.io/crates/{name}"),
+      Self::Pyproject | Self::Requirements => format!("https://pypi.org/project/{name}/"),
+      Self::GoMod => format!("https://{name}"),
+      Self::Gemfile => format!("https://rubygems.org/gems/{name}"),
+      Self::ComposerJson => format!("https://packagist.org/packages/{name}"),
+      Self::Rockspec => format!("https://luarocks.org/search?q={name}"),
+    }
+  }
+
+  pub(crate) fn url_of(self, dependency: &Dependency) -> String {
+    match &dependency.source {
+      Source::Registry { registry: None } => self.registry_url(&dependency.name),
+      Source::Registry {
+        registry: Some(registry),
+      } if registry.contains("://") => {
+        format!("{}/{}", registry.trim_end_matches('/'), dependency.name)
+      }
+      Source::Registry {
+        registry: Some(registry),
+      } => format!("{registry}:{}", dependency.name),
+      Source::Url { url } if self == Self::GoMod && !url.contains("://") => {
+        format!("https://{url}")
+      }
+      Source::Git { url, .. } | Source::Url { url } => url.clone(),
+      Source::Path { path } => path.clone(),
+      Source::Workspace => format!("workspace:{}", dependency.name),
+    }
+  }
+
+  /// Normalize a raw requirement with `gud_std`, after rewriting the
+  /// ecosystem's own operators into npm's syntax. Requirements it cannot
+  /// read, such as git references or environment markers, give `None`.
+  pub(crate) fn parse_constraint(self, raw: &str) -> Option<Constraint> {
+    if self == Self::GoMod {
+      // Minimal version selection: the version is a lower bound
+      return Constraint::parse(&format!(">={raw}")).ok();
+    }
+    if self == Self::PackageJson {
+      return Constraint::parse(raw).ok();
+    }
+
+    let raw = raw.split(" as ").next().unwrap_or(raw);
+    let alternatives = raw
+      .split("||")
+      .flat_map(|alternative| alternative.split('|'))
+      .map(|alternative| {
+        comparators(alternative)
+          .into_iter()
+          .map(|(op, version)| self.rewrite(op, version))
+          .collect::<Option<Vec<_>>>()
+          .map(|comparators| comparators.join(" "))
+      })
+      .collect::<Option<Vec<_>>>()?;
+    Constraint::parse(&alternatives.join(" || ")).ok()
+  }
+
+  /// One comparator in npm's syntax, or `None` when it has no upper bound
+  /// that can be written
+  fn rewrite(self, op: &str, version: &str) -> Option<String> {
+    let version = version.split('@').next().unwrap_or(version);
+    let version = version.strip_suffix(".*").unwrap_or(version);
+    let version = match self {
+      // Rock revisions such as the `-1` of `3.0-1`
+      Self::Rockspec => version
+        .rsplit_once('-')
+        .filter(|(_, revision)| revision.chars().all(|c| c.is_ascii_digit()))
+        .map_or(version, |(version, _)| version),
+      _ => version,
+    };
+    Some(match (self, op) {
+      (_, "~>" | "~=") | (Self::ComposerJson, "~") => pessimistic(version)?,
+      (_, "===") => format!("={version}"),
+      (Self::CargoToml, "") if version != "*" => format!("^{version}"),
+      _ => format!("{op}{version}"),
+    })
+  }
+}
+
+/// Operators and versions of one alternative, separated by commas or
+/// whitespace; an operator may be followed by spaces, as in `~> 1.2`
+fn comparators(alternative: &str) -> Vec<(&str, &str)> {
+  let separators = |c: char| c == ',' || c.is_whitespace();
+  let mut comparators = Vec::new();
+  let mut rest = alternative.trim_start_matches(separators);
+  while !rest.is_empty() {
+    let op_end = rest
+      .find(|c: char| !matches!(c, '<' | '>' | '=' | '!' | '~' | '^'))
+      .unwrap_or(rest.len());
+    let after = rest[op_end..].trim_start();
+    let end = after.find(separators).unwrap_or(after.len());
+    comparators.push((&rest[..op_end], &after[..end]));
+    rest = after[end..].trim_start_matches(separators);
+  }
+  comparators
+}
+
+/// Bundler's `~> 1.2.3`, also pip's `~=` and Composer's `~`: at least the
+/// version, below the next release of its second to last component. `None`
+/// when that component is already the largest number we can represent.
+fn pessimistic(version: &str) -> Option<String> {
+  let release = version.split(['-', '+']).next().unwrap_or(version);
+  let mut parts: Vec<u64> = release
+    .split('.')
+    .map_while(|part| part.parse().ok())
+    .collect();
+  if parts.len() > 1 {
+    parts.pop();
+  }
+  let Some(last) = parts.last_mut() else {
+    return Some(format!("={version}"));
+  };
+  *last = last.checked_add(1)?;
+  let upper: Vec<String> = parts.iter().map(u64::to_string).collect();
+  Some(format!(">={version} <{}", upper.join(".")))
+}
+
+/// How a dependency is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyKind {
+  /// Needed to use the package
+  Normal,
+  /// Needed only to develop or test it
+  Dev,
+  /// Needed only to build it
+  Build,
+  /// Needed only for a feature or extra
+  Optional,
+  /// Expected to be provided by the package's user
+  Peer,
+  /// Pinned only for another dependency, like go.mod's `// indirect`
+  Indirect,
+}
+
+/// Where a dependency is fetched from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Source {
+  /// The ecosystem's package registry, or a named alternative one
+  Registry {
+    /// Name or URL of an alternative registry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    registry: Option<String>,
+  },
+  /// A git repository, optionally at a branch, tag or commit
+  Git {
+    /// URL of the repository
+    url: String,
+    /// Branch, tag or commit to check out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reference: Option<String>,
+  },
+  /// A directory on disk, relative to the manifest
+  Path {
+    /// The directory, as written
+    path: String,
+  },
+  /// An archive URL
+  Url {
+    /// URL of the archive
+    url: String,
+  },
+  /// Inherited from the enclosing workspace
+  Workspace,
+}
+
+impl Source {
+  pub(crate) const REGISTRY: Self = Self::Registry { registry: None };
+}
+
+/// Marker for the `"all"` extract rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Everything {
+  /// `"all"`
+  #[serde(rename = "all")]
+  All,
+}
+
+/// Which folders of a dependency to extract, as in `klep.deps`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ExtractRule {
+  /// The whole dependency, written `"all"`
+  All(Everything),
+  /// Folders of the dependency, each mapped to where it goes
+  Folders(BTreeMap<String, String>),
+}
+
+impl ExtractRule {
+  /// The whole dependency
+  pub const ALL: Self = Self::All(Everything::All);
+
+  /// Only the folder `folder`, kept under the same name
+  #[must_use]
+  pub fn folder(folder: &str) -> Self {
+    Self::Folders(BTreeMap::from([(folder.to_string(), folder.to_string())]))
+  }
+}
+
+/// One dependency in a form shared by every ecosystem
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Dependency {
+  /// Name of the package
+  pub name: String,
+  /// Where to fetch it from: a git or archive URL, a local path, the
+  /// package's page in its registry, or `workspace:<name>`
+  pub url: String,
+  /// Kind of place `url` points to
+  pub source: Source,
+  /// Version requirement exactly as written in the manifest
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+  /// `version` normalized by `gud_std`, absent when it cannot read it
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub constraint: Option<Constraint>,
+  /// How the package uses it
+  pub kind: DependencyKind,
+  /// Feature, extra, group or target the dependency belongs to
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub group: Option<String>,
+  /// Folders to extract from it
+  pub extract: ExtractRule,
+  /// Declaration of the dependency in the manifest
+  pub span: Span,
+}
+
+impl Dependency {
+  pub(crate) fn new(
+    name: impl Into<String>,
+    version: Option<String>,
+    kind: DependencyKind,
+    span: Span,
+  ) -> Self {
+    Self {
+      name: name.into(),
+      url: String::new(),
+      source: Source::REGISTRY,
+      version,
+      constraint: None,
+      kind,
+      group: None,
+      extract: ExtractRule::ALL,
+      span,
+    }
+  }
+
+  /// The `klep.deps` entry for the dependency. Git dependencies are pinned
+  /// to their branch, tag or commit when they name one. klep cannot fetch
+  /// from a package registry, so registry and workspace dependencies are
+  /// left without a URL.
+  #[must_use]
+  pub fn to_klep(&self) -> KlepDependency {
+    let reference = match &self.source {
+      Source::Git { reference, .. } => reference.clone(),
+      _ => None,
+    };
+    let url = match &self.source {
+      Source::Registry { .. } | Source::Workspace => String::new(),
+      _ => self.url.clone(),
+    };
+    KlepDependency {
+      url,
+      version: reference.or_else(|| self.version.clone()),
+      extract: self.extract.clone(),
+    }
+  }
+}
+
+/// A dependency as `klep.deps` records it. Entries go to the deps file's
+/// `dependencyFolder`, so none is given a `folder` of its own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct KlepDependency {
+  /// Where klep fetches the dependency from; empty when it only comes from a
+  /// package registry
+  pub url: String,
+  /// Version requirement, or the git reference to check out
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+  /// Folders to extract from it
+  pub extract: ExtractRule,
+}
+
+/// The dependencies of a manifest in the shape of `klep.deps`; dev
+/// dependencies are kept apart and the first declaration of a name wins
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DepsFile {
+  /// Every dependency other than dev ones, by name
+  pub dependencies: BTreeMap<String, KlepDependency>,
+  /// Dev dependencies, by name
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub dev_dependencies: BTreeMap<String, KlepDependency>,
+}
+
+impl DepsFile {
+  /// Gather `dependencies` by name
+  #[must_use]
+  pub fn new(dependencies: &[Dependency]) -> Self {
+    let mut deps_file = Self::default();
+    for dependency in dependencies {
+      let table = if dependency.kind == DependencyKind::Dev {
+        &mut deps_file.dev_dependencies
+      } else {
+        &mut deps_file.dependencies
+      };
+      table
+        .entry(dependency.name.clone())
+        .or_insert_with(|| dependency.to_klep());
+    }
+    deps_file
+  }
+}
+
+/// Output of `ast/to_manifest`
+#[derive(Debug, Default, Serialize, JsonSchema)]
+pub struct ManifestOutput {
+  /// Manifest the dependencies were extracted from; absent without input
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub manifest: Option<ManifestKind>,
+  /// Name of the package itself
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  /// Version of the package itself
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+  /// Every dependency, in declaration order
+  pub dependencies: Vec<Dependency>,
+  /// Names of the scripts or tasks the manifest defines
+  pub scripts: Vec<String>,
+  /// `dependencies` as `klep.deps` would list them
+  pub deps_file: DepsFile,
+  /// Known manifest the input looked most like when it was not recognized
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub family: Option<FamilyMatch>,
+  /// Rule set the dependencies were read with, for in-house formats
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub rules: Option<String>,
+}
+
+/// Span of a whole `key = value` entry
+pub(crate) const fn entry_span(entry: &Entry) -> Span {
+  Span {
+    start: entry.key_span.start,
+    end: entry.value.span().end,
+  }
+}
+
+/// Git source from a URL that may name a branch, tag or commit after `#`
+pub(crate) fn git_source(url: &str) -> Source {
+  let url = url.strip_prefix("git+").unwrap_or(url);
+  let (url, reference) = url.split_once('#').unwrap_or((url, ""));
+  Source::Git {
+    url: url.to_string(),
+    reference: (!reference.is_empty()).then(|| reference.to_string()),
+  }
+}
+
+/// `git = "url"` with an optional `rev`, `tag` or `branch`, as Cargo and
+/// Poetry write it
+fn git_table(kind: ManifestKind, spec: &DataNode) -> Result<Option<Source>, GudError> {
+  Ok(str_field(kind, spec, "git")?.map(|url| {
+    Source::Git {
+      url,
+      reference: ["rev", "tag", "branch"]
+        .iter()
+        .find_map(|key| spec.get(key).and_then(DataNode::as_str))
+        .map(str::to_string),
+    }
+  }))
+}
+
+/// Manifest whose structure does not match its format, such as a
+/// dependency table that is a list
+fn invalid(kind: ManifestKind, span: Span, message: impl AsRef<str>) -> GudError {
+  GudError::new(
+    ErrorKind::Parsing,
+    "invalid-manifest",
+    format!(
+      "Invalid {} at line {}, column {}: {}",
+      kind.name(),
+      span.start.line,
+      span.start.column,
+      message.as_ref()
+    ),
+  )
+  .with_context(json!({
+    "manifest": kind,
+    "line": span.start.line,
+    "column": span.start.column,
+  }))
+}
+
+/// String value of `node`, failing unless it is a string
+fn expect_str(kind: ManifestKind, node: &DataNode, what: &str) -> Result<String, GudError> {
+  node.as_str().map(str::to_string).ok_or_else(|| {
+    invalid(
+      kind,
+      node.span(),
+      format!("expected '{what}' to be a string"),
+    )
+  })
+}
+
+/// String value of an optional field `key` of `node`
+fn str_field(kind: ManifestKind, node: &DataNode, key: &str) -> Result<Option<String>, GudError> {
+  node
+    .get(key)
+    .map(|value| expect_str(kind, value, key))
+    .transpose()
+}
+
+/// Entries of the table `node`, failing unless it is a map
+fn expect_map<'a>(
+  kind: ManifestKind,
+  node: &'a DataNode,
+  what: &str,
+) -> Result<&'a [Entry], GudError> {
+  match node {
+    DataNode::Map { entries, .. } => Ok(entries),
+    _ => Err(invalid(
+      kind,
+      node.span(),
+      format!("expected {what} to be a table"),
+    )),
+  }
+}
+
+/// Pick the extractor for `input` from its language, then its path, then
+/// its contents
+fn detect(input: &AstInput) -> Result<ManifestKind, GudError> {
+  let language = input.language.as_deref();
+  language
+    .and_then(ManifestKind::from_name)
+    .or_else(|| input.path.as_deref().and_then(ManifestKind::from_path))
+    .or_else(|| {
+      language
+        .and_then(Format::from_name)
+        .and_then(|format| ManifestKind::sniff(format, &input.source_code))
+    })
+    .ok_or_else(|| {
+      GudError::new(
+        ErrorKind::Argument,
+        "unknown-manifest",
+        format!(
+          "Cannot tell which manifest '{}' is",
+          input.path.as_deref().or(language).unwrap_or("the input")
+        ),
+      )
+      .with_context(json!({
+        "language": language,
+        "path": input.path,
+        "supported": MANIFESTS,
+      }))
+    })
+}
+
+/// Extract the dependencies of a manifest; no input gives an empty manifest
+pub fn process_ast_to_manifest(
+  input: Option<AstInput>,
+) -> Result<ManifestOutput, Box<dyn std::error::Error>> {
+  log::debug("Processing AST to manifest conversion");
+
+  let Some(ast_input) = input else {
+    log::debug("No input provided, returning empty manifest");
+    return Ok(ManifestOutput::default());
+  };
+
+  let rules = ast_input
+    .rules
+    .iter()
+    .map(Rules::load)
+    .collect::<Result<Vec<_>, _>>()?;
+  let (source, language, path) = (
+    ast_input.source_code.as_str(),
+    ast_input.language.as_deref(),
+    ast_input.path.as_deref(),
+  );
+  if let Some(rules) = rules.iter().find(|rules| rules.claims(language, path)) {
+    log::debug(&format!(
+      "Reading the manifest with rule set '{}'",
+      rules.name()
+    ));
+    return Ok(rules.extract(source, language, path)?.0);
+  }
+
+  let kind = match detect(&ast_input) {
+    Ok(kind) => kind,
+    Err(error) => {
+      if let Some(manifest) = rules
+        .iter()
+        .find_map(|rules| rules.recognize(source, language, path))
+      {
+        return Ok(manifest);
+      }
+      return guess(&ast_input).ok_or(error).map_err(Into::into);
+    }
+  };
+  log::debug(&format!(
+    "Extracting {} dependencies from {} characters",
+    kind.name(),
+    ast_input.source_code.len()
+  ));
+  Ok(kind.extract(&ast_input.source_code)?)
+}
+
+/// Best guess at an unrecognized manifest in a known data format: the
+/// known manifest it looks most like, and its dependencies when the match
+/// is confident enough to read it that way
+fn guess(input: &AstInput) -> Option<ManifestOutput> {
+  let format = data_format(input.language.as_deref(), input.path.as_deref())?;
+  let tree = format.parse(&input.source_code).ok()?;
+  let (kind, found) = nearest_manifest(&tree)?;
+  log::debug(&format!(
+    "Unrecognized manifest looks like {} with confidence {}",
+    kind.name(),
+    found.confidence
+  ));
+  let mut output = if found.confidence >= GUESS_CONFIDENCE {
+    kind.extract_tree(&tree).unwrap_or_default()
+  } else {
+    ManifestOutput::default()
+  };
+  output.family = Some(found);
+  Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::Value;
+
+  /// Dependencies without the fields every extractor fills the same way
+  pub(super) fn summary(dependencies: &[Dependency]) -> Value {
+    let mut value = serde_json::to_value(dependencies).unwrap();
+    for dependency in value.as_array_mut().unwrap() {
+      let fields = dependency.as_object_mut().unwrap();
+      for key in ["url", "constraint", "extract", "span"] {
+        fields.remove(key);
+      }
+    }
+    value
+  }
+
+  fn input(source: &str, language: Option<&str>, path: Option<&str>) -> AstInput {
+    AstInput {
+      source_code: source.to_string(),
+      language: language.map(str::to_string),
+      path: path.map(str::to_string),
+      rules: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn detects_manifests_by_name_path_and_contents() {
+    let detect = |language, path, source| detect(&input(source, language, path)).ok();
+    assert_eq!(
+      detect(Some("cargo"), None, ""),
+      Some(ManifestKind::CargoToml)
+    );
+    assert_eq!(
+      detect(None, Some("web/package.json"), "{}"),
+      Some(ManifestKind::PackageJson)
+    );
+    assert_eq!(
+      detect(None, Some("requirements-dev.txt"), ""),
+      Some(ManifestKind::Requirements)
+    );
+    assert_eq!(
+      detect(None, Some("klep-1.0-1.rockspec"), ""),
+      Some(ManifestKind::Rockspec)
+    );
+    assert_eq!(
+      detect(Some("json"), None, r#"{"require": {"a/b": "^1"}}"#),
+      Some(ManifestKind::ComposerJson)
+    );
+    assert_eq!(
+      detect(Some("toml"), None, "[tool.poetry]\nname = \"x\""),
+      Some(ManifestKind::Pyproject)
+    );
+    assert_eq!(detect(Some("toml"), None, "[deps]"), None);
+    assert_eq!(detect(None, Some("README"), ""), None);
+  }
+
+  #[test]
+  fn reports_unknown_manifests() {
+    let error = GudError::from_boxed(
+      process_ast_to_manifest(Some(input("[deps]", None, Some("deps")))).unwrap_err(),
+    );
+    assert_eq!(error.code, "unknown-manifest");
+    assert_eq!(error.kind, ErrorKind::Argument);
+  }
+
+  #[test]
+  fn guesses_unknown_manifests_in_known_formats() {
+    let manifest = process_ast_to_manifest(Some(input(
+      "name: app\nversion: 1.0.0\ndependencies:\n  lodash: ^4.17.0\ndevDependencies:\n  jest: ^29.0.0\n",
+      None,
+      Some("package.yaml"),
+    )))
+    .unwrap();
+    assert_eq!(manifest.manifest, Some(ManifestKind::PackageJson));
+    let family = manifest.family.unwrap();
+    assert_eq!(family.label.as_deref(), Some("package.json"));
+    assert!(family.confidence >= GUESS_CONFIDENCE, "{family:?}");
+    assert_eq!(
+      summary(&manifest.dependencies),
+      serde_json::json!([
+        {"name": "lodash", "version": "^4.17.0", "kind": "normal", "source": {"type": "registry"}},
+        {"name": "jest", "version": "^29.0.0", "kind": "dev", "source": {"type": "registry"}},
+      ])
+    );
+
+    // Too unlike any manifest to read, but still reported
+    let manifest = process_ast_to_manifest(Some(input("[deps]", Some("toml"), None))).unwrap();
+    assert!(manifest.manifest.is_none() && manifest.dependencies.is_empty());
+    assert!(manifest.family.unwrap().confidence < GUESS_CONFIDENCE);
+  }
+
+  #[test]
+  fn rules_are_tried_ahead_of_inference() {
+    let rules = RuleFile {
+      source_code: r#"{"name": "kit", "files": ["kit.json"], "rules": [{"path": "dependencies", "kind": "optional"}]}"#.to_string(),
+      language: Some("json".to_string()),
+      path: None,
+    };
+    let read = |source: &str, path: &str| {
+      let mut input = input(source, None, Some(path));
+      input.rules = vec![rules.clone()];
+      process_ast_to_manifest(Some(input)).unwrap()
+    };
+
+    // Claimed by its file name, ahead of what it looks like
+    let manifest = read(r#"{"dependencies": {"lodash": "^4"}}"#, "kit.json");
+    assert_eq!(manifest.rules.as_deref(), Some("kit"));
+    assert!(manifest.manifest.is_none() && manifest.family.is_none());
+    assert_eq!(manifest.dependencies[0].kind, DependencyKind::Optional);
+
+    // Known manifests are still read by their own extractor
+    let manifest = read(r#"{"dependencies": {"lodash": "^4"}}"#, "package.json");
+    assert_eq!(manifest.manifest, Some(ManifestKind::PackageJson));
+    assert_eq!(manifest.dependencies[0].kind, DependencyKind::Normal);
+
+    // Unknown ones the rules find dependencies in are theirs
+    let manifest = read(r#"{"dependencies": {"a": "1"}}"#, "other.json");
+    assert_eq!(manifest.rules.as_deref(), Some("kit"));
+  }
+
+  #[test]
+  fn reports_misshapen_manifests() {
+    let error = GudError::from_boxed(
+      process_ast_to_manifest(Some(input(
+        "{\n  \"dependencies\": [\"a\"]\n}",
+        Some("package.json"),
+        None,
+      )))
+      .unwrap_err(),
+    );
+    assert_eq!(error.code, "invalid-manifest");
+    assert_eq!(error.context["line"], 2);
+  }
+
+  #[test]
+  fn no_input_is_an_empty_manifest() {
+    let manifest = process_ast_to_manifest(None).unwrap();
+    assert!(manifest.manifest.is_none() && manifest.dependencies.is_empty());
+  }
+
+  #[test]
+  fn normalizes_constraints_per_ecosystem() {
+    let parse = |kind: ManifestKind, raw: &str| kind.parse_constraint(raw);
+    let npm = |raw: &str| Constraint::parse(raw).ok();
+    assert_eq!(parse(ManifestKind::CargoToml, "1.2"), npm("^1.2"));
+    assert_eq!(parse(ManifestKind::CargoToml, "=1.2.3"), npm("1.2.3"));
+    assert_eq!(
+      parse(ManifestKind::Gemfile, "~> 1.2, >= 1.2.3"),
+      npm(">=1.2.3 <2")
+    );
+    assert_eq!(
+      parse(ManifestKind::Requirements, "~=1.4.2"),
+      npm(">=1.4.2 <1.5")
+    );
+    assert_eq!(parse(ManifestKind::Requirements, "==1.4.*"), npm("1.4"));
+    assert_eq!(
+      parse(ManifestKind::ComposerJson, "~1.2 | ^3.0@dev"),
+      npm(">=1.2 <2 || ^3.0")
+    );
+    assert_eq!(
+      parse(ManifestKind::Rockspec, ">= 3.0-1, < 4"),
+      npm(">=3.0 <4")
+    );
+    assert_eq!(parse(ManifestKind::GoMod, "v1.8.0"), npm(">=1.8.0"));
+
+    // No next release to bound by, so the requirement stays unparsed
+    assert_eq!(
+      parse(ManifestKind::Gemfile, "~> 18446744073709551615"),
+      None
+    );
+    assert_eq!(
+      parse(ManifestKind::Requirements, "~=1.18446744073709551615.0"),
+      None
+    );
+    let manifest = ManifestKind::Gemfile
+      .extract("gem \"x\", \"~> 18446744073709551615\"\n")
+      .unwrap();
+    assert_eq!(manifest.dependencies[0].constraint, None);
+  }
+
+  #[test]
+  fn maps_dependencies_onto_klep_deps() {
+    let manifest = ManifestKind::Requirements
+      .extract(
+        "requests>=2.0\n-e git+https://github.com/org/mono.git@v2#egg=tool&subdirectory=py/tool\n",
+      )
+      .unwrap();
+    let tool = &manifest.dependencies[1];
+    assert_eq!(tool.url, "https://github.com/org/mono.git");
+    assert_eq!(tool.span.start.line, 2);
+    assert_eq!(
+      serde_json::to_value(&manifest.deps_file).unwrap(),
+      json!({"dependencies": {
+        "requests": {"url": "", "version": ">=2.0", "extract": "all"},
+        "tool": {"url": "https://github.com/org/mono.git", "version": "v2", "extract": {"py/tool": "py/tool"}},
+      }})
+    );
+  }
+}
@@ -0,0 +1,174 @@
+//! Cargo's `Cargo.toml`.
+
+use gud_common::GudError;
+
+use super::{
+  entry_span, expect_map, git_table, invalid, str_field, Dependency, DependencyKind, ManifestKind,
+  ManifestOutput, Source,
+};
+use crate::data::{DataNode, Entry};
+
+const KIND: ManifestKind = ManifestKind::CargoToml;
+
+const SECTIONS: &[(&str, DependencyKind)] = &[
+  ("dependencies", DependencyKind::Normal),
+  ("dev-dependencies", DependencyKind::Dev),
+  ("dev_dependencies", DependencyKind::Dev),
+  ("build-dependencies", DependencyKind::Build),
+  ("build_dependencies", DependencyKind::Build),
+];
+
+pub(super) fn extract(tree: &DataNode, manifest: &mut ManifestOutput) -> Result<(), GudError> {
+  if let Some(package) = tree.get("package") {
+    manifest.name = str_field(KIND, package, "name")?;
+    // `version.workspace = true` leaves the version to the workspace
+    manifest.version = package
+      .get("version")
+      .and_then(DataNode::as_str)
+      .map(str::to_string);
+  }
+
+  sections(tree, None, manifest)?;
+  if let Some(targets) = tree.get("target") {
+    for target in expect_map(KIND, targets, "target")? {
+      sections(&target.value, Some(&target.key), manifest)?;
+    }
+  }
+  if let Some(table) = tree
+    .get("workspace")
+    .and_then(|workspace| workspace.get("dependencies"))
+  {
+    for entry in expect_map(KIND, table, "workspace.dependencies")? {
+      let mut dependency = dependency(entry, DependencyKind::Normal)?;
+      dependency.group = Some("workspace".to_string());
+      manifest.dependencies.push(dependency);
+    }
+  }
+  Ok(())
+}
+
+/// Dependency tables of the manifest or of one `[target.<cfg>]`
+fn sections(
+  table: &DataNode,
+  target: Option<&str>,
+  manifest: &mut ManifestOutput,
+) -> Result<(), GudError> {
+  for (section, kind) in SECTIONS {
+    let Some(dependencies) = table.get(section) else {
+      continue;
+    };
+    for entry in expect_map(KIND, dependencies, section)? {
+      let mut dependency = dependency(entry, *kind)?;
+      dependency.group = target.map(str::to_string);
+      manifest.dependencies.push(dependency);
+    }
+  }
+  Ok(())
+}
+
+/// `name = "1.0"` or `name = { version = "1.0", ... }`
+fn dependency(entry: &Entry, kind: DependencyKind) -> Result<Dependency, GudError> {
+  if let Some(version) = entry.value.as_str() {
+    return Ok(Dependency::new(
+      &entry.key,
+      Some(version.to_string()),
+      kind,
+      entry_span(entry),
+    ));
+  }
+  let spec = &entry.value;
+  if !matches!(spec, DataNode::Map { .. }) {
+    return Err(invalid(
+      KIND,
+      spec.span(),
+      format!(
+        "expected dependency '{}' to be a version or a table",
+        entry.key
+      ),
+    ));
+  }
+
+  let name = str_field(KIND, spec, "package")?.unwrap_or_else(|| entry.key.clone());
+  let mut dependency = Dependency::new(
+    name,
+    str_field(KIND, spec, "version")?,
+    kind,
+    entry_span(entry),
+  );
+  if spec.get("optional").and_then(DataNode::as_bool) == Some(true)
+    && kind == DependencyKind::Normal
+  {
+    dependency.kind = DependencyKind::Optional;
+  }
+
+  if let Some(source) = git_table(KIND, spec)? {
+    dependency.source = source;
+  } else if let Some(path) = str_field(KIND, spec, "path")? {
+    dependency.source = Source::Path { path };
+  } else if spec.get("workspace").and_then(DataNode::as_bool) == Some(true) {
+    dependency.source = Source::Workspace;
+  } else if let Some(registry) = str_field(KIND, spec, "registry")? {
+    dependency.source = Source::Registry {
+      registry: Some(registry),
+    };
+  }
+  Ok(dependency)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_manifest::tests::summary;
+  use crate::to_manifest::ManifestKind;
+  use serde_json::json;
+
+  #[test]
+  fn extracts_tables_targets_and_sources() {
+    let manifest = ManifestKind::CargoToml
+      .extract(
+        r#"
+[package]
+name = "gud"
+version.workspace = true
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+json = { package = "serde_json", version = "1", optional = true }
+common = { path = "../common" }
+ts = { git = "https://github.com/tree-sitter/tree-sitter", tag = "v0.25.0" }
+log.workspace = true
+
+[dev-dependencies]
+tempfile = "3"
+
+[build-dependencies.cc]
+version = "1.0"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+"#,
+      )
+      .unwrap();
+    assert_eq!(manifest.name.as_deref(), Some("gud"));
+    assert_eq!(manifest.version, None);
+    assert_eq!(
+      summary(&manifest.dependencies),
+      json!([
+        {"name": "serde", "version": "1.0", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "serde_json", "version": "1", "source": {"type": "registry"}, "kind": "optional"},
+        {"name": "common", "source": {"type": "path", "path": "../common"}, "kind": "normal"},
+        {"name": "ts", "source": {"type": "git", "url": "https://github.com/tree-sitter/tree-sitter", "reference": "v0.25.0"}, "kind": "normal"},
+        {"name": "log", "source": {"type": "workspace"}, "kind": "normal"},
+        {"name": "tempfile", "version": "3", "source": {"type": "registry"}, "kind": "dev"},
+        {"name": "cc", "version": "1.0", "source": {"type": "registry"}, "kind": "build"},
+        {"name": "libc", "version": "0.2", "source": {"type": "registry"}, "kind": "normal", "group": "cfg(unix)"},
+      ])
+    );
+  }
+
+  #[test]
+  fn rejects_misshapen_dependencies() {
+    assert!(ManifestKind::CargoToml
+      .extract("[dependencies]\nserde = 1")
+      .is_err());
+  }
+}
@@ -0,0 +1,97 @@
+//! PHP Composer `composer.json`.
+
+use gud_common::GudError;
+
+use super::{
+  entry_span, expect_map, expect_str, str_field, Dependency, DependencyKind, ManifestKind,
+  ManifestOutput,
+};
+use crate::data::DataNode;
+
+const KIND: ManifestKind = ManifestKind::ComposerJson;
+
+const SECTIONS: &[(&str, DependencyKind)] = &[
+  ("require", DependencyKind::Normal),
+  ("require-dev", DependencyKind::Dev),
+];
+
+/// Platform requirements: the PHP runtime, its extensions and libraries and
+/// Composer itself
+fn is_platform(name: &str) -> bool {
+  matches!(
+    name,
+    "php" | "php-64bit" | "php-ipv6" | "php-zts" | "php-debug" | "hhvm"
+  ) || name.starts_with("composer")
+    || name.starts_with("ext-")
+    || name.starts_with("lib-")
+}
+
+pub(super) fn extract(tree: &DataNode, manifest: &mut ManifestOutput) -> Result<(), GudError> {
+  expect_map(KIND, tree, "the manifest")?;
+  manifest.name = str_field(KIND, tree, "name")?;
+  manifest.version = str_field(KIND, tree, "version")?;
+
+  for (section, kind) in SECTIONS {
+    let Some(table) = tree.get(section) else {
+      continue;
+    };
+    for entry in expect_map(KIND, table, section)? {
+      if is_platform(&entry.key) {
+        continue;
+      }
+      let constraint = expect_str(KIND, &entry.value, &entry.key)?;
+      manifest.dependencies.push(Dependency::new(
+        &entry.key,
+        Some(constraint),
+        *kind,
+        entry_span(entry),
+      ));
+    }
+  }
+
+  if let Some(scripts) = tree.get("scripts") {
+    manifest.scripts = expect_map(KIND, scripts, "scripts")?
+      .iter()
+      .map(|entry| entry.key.clone())
+      .collect();
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_manifest::{DependencyKind, ManifestKind};
+
+  #[test]
+  fn skips_platform_requirements() {
+    let manifest = ManifestKind::ComposerJson
+      .extract(
+        r#"{
+  "name": "klep/site",
+  "require": {"php": ">=8.1", "ext-json": "*", "monolog/monolog": "^3.0"},
+  "require-dev": {"phpunit/phpunit": "^10.5"},
+  "scripts": {"test": "phpunit"}
+}"#,
+      )
+      .unwrap();
+    let dependencies: Vec<_> = manifest
+      .dependencies
+      .iter()
+      .map(|dependency| {
+        (
+          dependency.name.as_str(),
+          dependency.version.as_deref(),
+          dependency.kind,
+        )
+      })
+      .collect();
+    assert_eq!(
+      dependencies,
+      [
+        ("monolog/monolog", Some("^3.0"), DependencyKind::Normal),
+        ("phpunit/phpunit", Some("^10.5"), DependencyKind::Dev),
+      ]
+    );
+    assert_eq!(manifest.scripts, ["test"]);
+  }
+}
@@ -0,0 +1,198 @@
+//! Go modules' `go.mod`.
+//!
+//! `require` directives become dependencies, `// indirect` ones of the
+//! indirect kind. `replace` directives are applied afterwards: a local
+//! replacement becomes a path source and a module replacement an URL source
+//! at the replacement's version.
+
+use gud_common::GudError;
+
+use super::{invalid, Dependency, DependencyKind, ManifestKind, ManifestOutput, Source};
+use crate::data::{Format, LineIndex};
+
+const KIND: ManifestKind = ManifestKind::GoMod;
+
+/// `old [version] => new [version]`
+struct Replace {
+  module: String,
+  version: Option<String>,
+  target: String,
+  target_version: Option<String>,
+}
+
+/// Split a directive's arguments into words, unquoting `"..."` and
+/// `` `...` `` strings
+fn words(text: &str) -> Vec<String> {
+  let mut words = Vec::new();
+  let mut rest = text.trim();
+  while !rest.is_empty() {
+    let (word, tail) = match rest.chars().next() {
+      Some(quote @ ('"' | '`')) => rest[1..]
+        .find(quote)
+        .map_or((&rest[1..], ""), |end| (&rest[1..=end], &rest[end + 2..])),
+      _ => rest
+        .find(char::is_whitespace)
+        .map_or((rest, ""), |end| (&rest[..end], &rest[end..])),
+    };
+    words.push(word.to_string());
+    rest = tail.trim_start();
+  }
+  words
+}
+
+pub(super) fn extract(source: &str, manifest: &mut ManifestOutput) -> Result<(), GudError> {
+  let index = LineIndex::new(source, Format::Lines);
+  let mut replaces = Vec::new();
+  // Verb of the open `verb (` block, and where it starts and ends
+  let mut block: Option<(String, usize, usize)> = None;
+
+  let mut offset = 0;
+  for raw in source.split_inclusive('\n') {
+    let start = offset + (raw.len() - raw.trim_start().len());
+    offset += raw.len();
+    let (code, comment) = raw.split_once("//").unwrap_or((raw, ""));
+    let code = code.trim();
+    if code.is_empty() {
+      continue;
+    }
+
+    let (verb, arguments) = match &block {
+      Some(_) if code == ")" => {
+        block = None;
+        continue;
+      }
+      Some((verb, ..)) => (verb.clone(), code),
+      None => {
+        let (verb, arguments) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+        if arguments.trim() == "(" {
+          block = Some((verb.to_string(), start, start + code.len()));
+          continue;
+        }
+        (verb.to_string(), arguments)
+      }
+    };
+
+    let words = words(arguments);
+    let error = |message: &str| invalid(KIND, index.span(start, start + code.len()), message);
+    match verb.as_str() {
+      "module" => manifest.name = words.into_iter().next(),
+      "require" => {
+        let [module, version] = words.as_slice() else {
+          return Err(error("expected 'require <module> <version>'"));
+        };
+        let kind = if comment.trim() == "indirect" {
+          DependencyKind::Indirect
+        } else {
+          DependencyKind::Normal
+        };
+        manifest.dependencies.push(Dependency::new(
+          module,
+          Some(version.clone()),
+          kind,
+          index.span(start, start + code.len()),
+        ));
+      }
+      "replace" => {
+        let arrow = words
+          .iter()
+          .position(|word| word == "=>")
+          .filter(|&arrow| (1..=2).contains(&arrow) && (1..=2).contains(&(words.len() - arrow - 1)))
+          .ok_or_else(|| error("expected 'replace <module> [version] => <target> [version]'"))?;
+        replaces.push(Replace {
+          module: words[0].clone(),
+          version: words.get(1).filter(|_| arrow == 2).cloned(),
+          target: words[arrow + 1].clone(),
+          target_version: words.get(arrow + 2).cloned(),
+        });
+      }
+      // `go`, `toolchain`, `exclude`, `retract` and `godebug`
+      _ => {}
+    }
+  }
+
+  if let Some((verb, start, end)) = block {
+    return Err(invalid(
+      KIND,
+      index.span(start, end),
+      format!("the '{verb} (' block is never closed"),
+    ));
+  }
+
+  for replace in replaces {
+    for dependency in &mut manifest.dependencies {
+      if dependency.name != replace.module
+        || replace.version.is_some() && dependency.version != replace.version
+      {
+        continue;
+      }
+      if replace.target.starts_with("./")
+        || replace.target.starts_with("../")
+        || replace.target.starts_with('/')
+      {
+        dependency.source = Source::Path {
+          path: replace.target.clone(),
+        };
+        dependency.version = None;
+      } else {
+        dependency.source = Source::Url {
+          url: replace.target.clone(),
+        };
+        dependency.version.clone_from(&replace.target_version);
+      }
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_manifest::tests::summary;
+  use crate::to_manifest::ManifestKind;
+  use serde_json::json;
+
+  #[test]
+  fn extracts_requires_and_applies_replaces() {
+    let source = "\
+module github.com/klep/gud
+
+go 1.22
+
+require github.com/spf13/cobra v1.8.0
+
+require (
+\tgolang.org/x/sys v0.20.0 // indirect
+\tgithub.com/klep/common v0.1.0
+\t\"github.com/old/lib\" v1.0.0
+)
+
+replace github.com/klep/common => ../common
+replace github.com/old/lib v1.0.0 => github.com/new/lib v1.1.0
+";
+    let manifest = ManifestKind::GoMod.extract(source).unwrap();
+    assert_eq!(manifest.name.as_deref(), Some("github.com/klep/gud"));
+    assert_eq!(
+      summary(&manifest.dependencies),
+      json!([
+        {"name": "github.com/spf13/cobra", "version": "v1.8.0", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "golang.org/x/sys", "version": "v0.20.0", "source": {"type": "registry"}, "kind": "indirect"},
+        {"name": "github.com/klep/common", "source": {"type": "path", "path": "../common"}, "kind": "normal"},
+        {"name": "github.com/old/lib", "version": "v1.1.0", "source": {"type": "url", "url": "github.com/new/lib"}, "kind": "normal"},
+      ])
+    );
+  }
+
+  #[test]
+  fn rejects_malformed_directives() {
+    let error = ManifestKind::GoMod
+      .extract("module m\n\nrequire (\n  a\n)\n")
+      .unwrap_err();
+    assert_eq!(error.code, "invalid-manifest");
+    assert_eq!(error.context["line"], 4);
+
+    let error = ManifestKind::GoMod
+      .extract("module m\n\nrequire (\n  a v1.0.0\n")
+      .unwrap_err();
+    assert_eq!(error.code, "invalid-manifest");
+    assert_eq!(error.context["line"], 3);
+  }
+}
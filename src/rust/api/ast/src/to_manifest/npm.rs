@@ -0,0 +1,144 @@
+//! npm `package.json`.
+
+use gud_common::GudError;
+
+use super::{
+  entry_span, expect_map, expect_str, git_source, str_field, Dependency, DependencyKind,
+  ManifestKind, ManifestOutput, Source,
+};
+use crate::data::{DataNode, Span};
+
+const KIND: ManifestKind = ManifestKind::PackageJson;
+
+const SECTIONS: &[(&str, DependencyKind)] = &[
+  ("dependencies", DependencyKind::Normal),
+  ("devDependencies", DependencyKind::Dev),
+  ("peerDependencies", DependencyKind::Peer),
+  ("optionalDependencies", DependencyKind::Optional),
+];
+
+pub(super) fn extract(tree: &DataNode, manifest: &mut ManifestOutput) -> Result<(), GudError> {
+  expect_map(KIND, tree, "the manifest")?;
+  manifest.name = str_field(KIND, tree, "name")?;
+  manifest.version = str_field(KIND, tree, "version")?;
+
+  for (section, kind) in SECTIONS {
+    let Some(table) = tree.get(section) else {
+      continue;
+    };
+    for entry in expect_map(KIND, table, section)? {
+      let spec = expect_str(KIND, &entry.value, &entry.key)?;
+      manifest
+        .dependencies
+        .push(dependency(&entry.key, &spec, *kind, entry_span(entry)));
+    }
+  }
+
+  if let Some(scripts) = tree.get("scripts") {
+    manifest.scripts = expect_map(KIND, scripts, "scripts")?
+      .iter()
+      .map(|entry| entry.key.clone())
+      .collect();
+  }
+  Ok(())
+}
+
+/// Interpret the version spec `spec` of dependency `name`
+pub fn dependency(name: &str, spec: &str, kind: DependencyKind, span: Span) -> Dependency {
+  let spec = spec.trim();
+  let mut dependency = Dependency::new(name, None, kind, span);
+
+  if let Some(alias) = spec.strip_prefix("npm:") {
+    // `npm:real-name@range`; scoped names start with their own `@`
+    let split = alias[1..].find('@').map(|at| at + 1);
+    let (real, range) = split.map_or((alias, ""), |at| (&alias[..at], &alias[at + 1..]));
+    dependency.name = real.to_string();
+    dependency.version = (!range.is_empty()).then(|| range.to_string());
+  } else if let Some(range) = spec.strip_prefix("workspace:") {
+    dependency.source = Source::Workspace;
+    dependency.version = Some(range.to_string());
+  } else if let Some(path) = spec
+    .strip_prefix("file:")
+    .or_else(|| spec.strip_prefix("link:"))
+    .or_else(|| spec.starts_with(['.', '/', '~']).then_some(spec))
+  {
+    dependency.source = Source::Path {
+      path: path.to_string(),
+    };
+  } else if spec.starts_with("git+") || spec.starts_with("git://") || spec.starts_with("git@") {
+    dependency.source = git_source(spec);
+  } else if spec.starts_with("http://") || spec.starts_with("https://") {
+    dependency.source = Source::Url {
+      url: spec.to_string(),
+    };
+  } else if let Some(source) = hosted(spec) {
+    dependency.source = source;
+  } else if !spec.is_empty() {
+    dependency.version = Some(spec.to_string());
+  }
+  dependency
+}
+
+/// `github:user/repo#ref`, `gitlab:`, `bitbucket:` and the bare
+/// `user/repo` GitHub shorthand
+fn hosted(spec: &str) -> Option<Source> {
+  let (host, repository) = match spec.split_once(':') {
+    Some(("github", rest)) => ("github.com", rest),
+    Some(("gitlab", rest)) => ("gitlab.com", rest),
+    Some(("bitbucket", rest)) => ("bitbucket.org", rest),
+    None
+      if !spec.starts_with('@')
+        && spec.split('#').next()?.split('/').count() == 2
+        && !spec.contains(char::is_whitespace) =>
+    {
+      ("github.com", spec)
+    }
+    _ => return None,
+  };
+  let (repository, reference) = repository
+    .split_once('#')
+    .map_or((repository, None), |(repository, reference)| {
+      (repository, Some(reference))
+    });
+  Some(git_source(&format!(
+    "https://{host}/{repository}.git{}",
+    reference.map_or(String::new(), |reference| format!("#{reference}"))
+  )))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_manifest::tests::summary;
+  use crate::to_manifest::ManifestKind;
+  use serde_json::json;
+
+  #[test]
+  fn extracts_every_section() {
+    let manifest = ManifestKind::PackageJson
+      .extract(
+        r#"{
+  "name": "klep", "version": "1.2.0",
+  "scripts": {"build": "tsc", "test": "bun test"},
+  "dependencies": {"lodash": "^4.17.21", "left": "npm:@scope/pad@~1.3", "mine": "file:../mine"},
+  "devDependencies": {"ts": "github:microsoft/TypeScript#v5.4.0", "util": "workspace:*"},
+  "peerDependencies": {"react": ">=18"},
+  "optionalDependencies": {"fsevents": "git+https://github.com/fsevents/fsevents.git#main"}
+}"#,
+      )
+      .unwrap();
+    assert_eq!(manifest.name.as_deref(), Some("klep"));
+    assert_eq!(manifest.scripts, ["build", "test"]);
+    assert_eq!(
+      summary(&manifest.dependencies),
+      json!([
+        {"name": "lodash", "version": "^4.17.21", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "@scope/pad", "version": "~1.3", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "mine", "source": {"type": "path", "path": "../mine"}, "kind": "normal"},
+        {"name": "ts", "source": {"type": "git", "url": "https://github.com/microsoft/TypeScript.git", "reference": "v5.4.0"}, "kind": "dev"},
+        {"name": "util", "version": "*", "source": {"type": "workspace"}, "kind": "dev"},
+        {"name": "react", "version": ">=18", "source": {"type": "registry"}, "kind": "peer"},
+        {"name": "fsevents", "source": {"type": "git", "url": "https://github.com/fsevents/fsevents.git", "reference": "main"}, "kind": "optional"},
+      ])
+    );
+  }
+}
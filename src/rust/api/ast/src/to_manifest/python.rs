@@ -0,0 +1,427 @@
+//! Python `pyproject.toml` (PEP 621, PEP 735 and Poetry) and pip
+//! `requirements.txt`.
+
+use gud_common::GudError;
+
+use super::{
+  entry_span, expect_map, expect_str, git_source, git_table, invalid, str_field, Dependency,
+  DependencyKind, ExtractRule, ManifestKind, ManifestOutput, Source,
+};
+use crate::data::{DataNode, Entry, Span};
+
+const KIND: ManifestKind = ManifestKind::Pyproject;
+
+/// Parse a PEP 508 requirement such as
+/// `requests[socks]>=2.0; python_version>"3.8"` or
+/// `pkg @ git+https://host/pkg.git@v1`. Environment markers are dropped.
+fn requirement(text: &str, kind: DependencyKind, span: Span) -> Option<Dependency> {
+  let text = text.split(';').next().unwrap_or(text).trim();
+  let end = text
+    .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')))
+    .unwrap_or(text.len());
+  if end == 0 {
+    return None;
+  }
+  let mut dependency = Dependency::new(&text[..end], None, kind, span);
+
+  let mut rest = text[end..].trim_start();
+  if rest.starts_with('[') {
+    rest = rest
+      .find(']')
+      .map_or("", |close| rest[close + 1..].trim_start());
+  }
+  if let Some(url) = rest.strip_prefix('@') {
+    direct_url(&mut dependency, url.trim());
+  } else {
+    let constraint = rest.trim_start_matches('(').trim_end_matches(')').trim();
+    dependency.version = (!constraint.is_empty()).then(|| constraint.to_string());
+  }
+  Some(dependency)
+}
+
+/// Source of a direct reference: VCS URLs keep their `@reference`, local
+/// files become paths and anything else is an archive. A `subdirectory`
+/// in the URL's fragment becomes the extract rule.
+fn direct_url(dependency: &mut Dependency, url: &str) {
+  let (url, fragment) = url.split_once('#').unwrap_or((url, ""));
+  if let Some(folder) = fragment
+    .split('&')
+    .find_map(|parameter| parameter.strip_prefix("subdirectory="))
+  {
+    dependency.extract = ExtractRule::folder(folder);
+  }
+  dependency.source = if url.starts_with("git+") {
+    // The reference follows the last `@` after the host
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let after_host = rest.find('/').unwrap_or(rest.len());
+    rest[after_host..].rfind('@').map_or_else(
+      || git_source(url),
+      |at| {
+        git_source(&format!(
+          "{scheme}://{}#{}",
+          &rest[..after_host + at],
+          &rest[after_host + at + 1..]
+        ))
+      },
+    )
+  } else if let Some(path) = url.strip_prefix("file://") {
+    Source::Path {
+      path: path.to_string(),
+    }
+  } else if url.starts_with(['.', '/']) {
+    Source::Path {
+      path: url.to_string(),
+    }
+  } else {
+    Source::Url {
+      url: url.to_string(),
+    }
+  };
+}
+
+/// A `requirements.txt` line naming a URL or path, with the project name in
+/// its `#egg=` fragment or, failing that, its last path segment. Paths such
+/// as `.` name no project other than the one being read, so they are left
+/// out.
+fn direct_reference(target: &str, span: Span) -> Option<Dependency> {
+  // Extras of a local project, as in `./pkg[dev]`
+  let target = match target.find('[') {
+    Some(open) if target.ends_with(']') && !target.contains("://") => &target[..open],
+    _ => target,
+  };
+  let name = target.split_once("#egg=").map_or_else(
+    || {
+      let path = target.split(['#', '?']).next().unwrap_or(target);
+      let last = path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(path);
+      last
+        .split('@')
+        .next()
+        .unwrap_or(last)
+        .trim_end_matches(".git")
+    },
+    |(_, egg)| egg.split('&').next().unwrap_or(egg),
+  );
+  if name.chars().all(|c| c == '.') {
+    return None;
+  }
+  let mut dependency = Dependency::new(name, None, DependencyKind::Normal, span);
+  direct_url(&mut dependency, target);
+  Some(dependency)
+}
+
+pub(super) fn extract_requirements(tree: &DataNode, manifest: &mut ManifestOutput) {
+  for item in tree.items() {
+    let Some(line) = item.as_str() else {
+      continue;
+    };
+    let span = item.span();
+    // Per-requirement options such as `--hash` follow the requirement
+    let line = line.split(" --").next().unwrap_or(line).trim();
+    let editable = line
+      .strip_prefix("-e")
+      .or_else(|| line.strip_prefix("--editable"))
+      .map(|target| target.trim_start_matches([' ', '=']));
+    let dependency = match editable {
+      Some(target) => direct_reference(target, span),
+      // `-r other.txt`, `--index-url` and other global options
+      None if line.starts_with('-') => None,
+      None if line.contains("://") && !line.contains(" @ ") || line.starts_with(['.', '/']) => {
+        direct_reference(line, span)
+      }
+      None => requirement(line, DependencyKind::Normal, span),
+    };
+    manifest.dependencies.extend(dependency);
+  }
+}
+
+/// PEP 508 strings of a list, tagged with `group`
+fn requirements(
+  list: &DataNode,
+  what: &str,
+  kind: DependencyKind,
+  group: Option<&str>,
+  manifest: &mut ManifestOutput,
+) -> Result<(), GudError> {
+  let DataNode::List { items, .. } = list else {
+    return Err(invalid(
+      KIND,
+      list.span(),
+      format!("expected {what} to be a list"),
+    ));
+  };
+  for item in items {
+    // PEP 735 groups may include other groups with `{include-group = ...}`
+    if matches!(item, DataNode::Map { .. }) {
+      continue;
+    }
+    let text = expect_str(KIND, item, what)?;
+    let mut dependency = requirement(&text, kind, item.span())
+      .ok_or_else(|| invalid(KIND, item.span(), format!("'{text}' is not a requirement")))?;
+    dependency.group = group.map(str::to_string);
+    manifest.dependencies.push(dependency);
+  }
+  Ok(())
+}
+
+pub(super) fn extract_pyproject(
+  tree: &DataNode,
+  manifest: &mut ManifestOutput,
+) -> Result<(), GudError> {
+  if let Some(requires) = tree
+    .get("build-system")
+    .and_then(|build| build.get("requires"))
+  {
+    requirements(
+      requires,
+      "build-system.requires",
+      DependencyKind::Build,
+      None,
+      manifest,
+    )?;
+  }
+
+  if let Some(project) = tree.get("project") {
+    manifest.name = str_field(KIND, project, "name")?;
+    manifest.version = str_field(KIND, project, "version")?;
+    if let Some(list) = project.get("dependencies") {
+      requirements(list, "dependencies", DependencyKind::Normal, None, manifest)?;
+    }
+    if let Some(extras) = project.get("optional-dependencies") {
+      for extra in expect_map(KIND, extras, "optional-dependencies")? {
+        requirements(
+          &extra.value,
+          &extra.key,
+          DependencyKind::Optional,
+          Some(&extra.key),
+          manifest,
+        )?;
+      }
+    }
+    scripts(project, manifest)?;
+  }
+
+  if let Some(groups) = tree.get("dependency-groups") {
+    for group in expect_map(KIND, groups, "dependency-groups")? {
+      requirements(
+        &group.value,
+        &group.key,
+        DependencyKind::Dev,
+        Some(&group.key),
+        manifest,
+      )?;
+    }
+  }
+
+  if let Some(poetry) = tree.get("tool").and_then(|tool| tool.get("poetry")) {
+    poetry_section(poetry, manifest)?;
+  }
+  Ok(())
+}
+
+fn scripts(table: &DataNode, manifest: &mut ManifestOutput) -> Result<(), GudError> {
+  if let Some(scripts) = table.get("scripts") {
+    manifest.scripts.extend(
+      expect_map(KIND, scripts, "scripts")?
+        .iter()
+        .map(|entry| entry.key.clone()),
+    );
+  }
+  Ok(())
+}
+
+/// `[tool.poetry]`, which only names the package when `[project]` does not
+fn poetry_section(poetry: &DataNode, manifest: &mut ManifestOutput) -> Result<(), GudError> {
+  if manifest.name.is_none() {
+    manifest.name = str_field(KIND, poetry, "name")?;
+  }
+  if manifest.version.is_none() {
+    manifest.version = str_field(KIND, poetry, "version")?;
+  }
+
+  let mut tables = vec![];
+  if let Some(table) = poetry.get("dependencies") {
+    tables.push((table, DependencyKind::Normal, None));
+  }
+  if let Some(table) = poetry.get("dev-dependencies") {
+    tables.push((table, DependencyKind::Dev, None));
+  }
+  if let Some(groups) = poetry.get("group") {
+    for group in expect_map(KIND, groups, "group")? {
+      if let Some(table) = group.value.get("dependencies") {
+        tables.push((table, DependencyKind::Dev, Some(group.key.as_str())));
+      }
+    }
+  }
+
+  for (table, kind, group) in tables {
+    for entry in expect_map(KIND, table, "dependencies")? {
+      if entry.key == "python" {
+        continue;
+      }
+      // A list holds alternatives for different environments
+      let specs = match &entry.value {
+        DataNode::List { items, .. } => items.iter().map(|item| (item, item.span())).collect(),
+        spec => vec![(spec, entry_span(entry))],
+      };
+      for (spec, span) in specs {
+        let mut dependency = poetry_dependency(entry, spec, kind, span)?;
+        dependency.group = group.map(str::to_string);
+        manifest.dependencies.push(dependency);
+      }
+    }
+  }
+  scripts(poetry, manifest)
+}
+
+/// `name = "^1.0"` or `name = { version = "^1.0", ... }`
+fn poetry_dependency(
+  entry: &Entry,
+  spec: &DataNode,
+  kind: DependencyKind,
+  span: Span,
+) -> Result<Dependency, GudError> {
+  if let Some(version) = spec.as_str() {
+    return Ok(Dependency::new(
+      &entry.key,
+      Some(version.to_string()),
+      kind,
+      span,
+    ));
+  }
+  if !matches!(spec, DataNode::Map { .. }) {
+    return Err(invalid(
+      KIND,
+      spec.span(),
+      format!(
+        "expected dependency '{}' to be a version or a table",
+        entry.key
+      ),
+    ));
+  }
+
+  let mut dependency = Dependency::new(&entry.key, str_field(KIND, spec, "version")?, kind, span);
+  if spec.get("optional").and_then(DataNode::as_bool) == Some(true)
+    && kind == DependencyKind::Normal
+  {
+    dependency.kind = DependencyKind::Optional;
+  }
+  if let Some(source) = git_table(KIND, spec)? {
+    dependency.source = source;
+  } else if let Some(path) = str_field(KIND, spec, "path")? {
+    dependency.source = Source::Path { path };
+  } else if let Some(url) = str_field(KIND, spec, "url")? {
+    dependency.source = Source::Url { url };
+  } else if let Some(registry) = str_field(KIND, spec, "source")? {
+    dependency.source = Source::Registry {
+      registry: Some(registry),
+    };
+  }
+  if let Some(folder) = str_field(KIND, spec, "subdirectory")? {
+    dependency.extract = ExtractRule::folder(&folder);
+  }
+  Ok(dependency)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_manifest::tests::summary;
+  use crate::to_manifest::ManifestKind;
+  use serde_json::json;
+
+  fn dependencies(kind: ManifestKind, source: &str) -> serde_json::Value {
+    summary(&kind.extract(source).unwrap().dependencies)
+  }
+
+  #[test]
+  fn extracts_requirement_lines() {
+    let source = "\
+-r base.txt
+--index-url https://pypi.org/simple
+requests[socks]>=2.31,<3 ; python_version >= \"3.8\"
+flask
+numpy==1.26.4 --hash=sha256:abc
+-e git+https://github.com/org/tool.git@v1.2#egg=tool
+-e .
+-e .[dev]
+./vendor/local_pkg
+../sibling[extra]
+pkg @ https://example.com/pkg-1.0.tar.gz
+";
+    assert_eq!(
+      dependencies(ManifestKind::Requirements, source),
+      json!([
+        {"name": "requests", "version": ">=2.31,<3", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "flask", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "numpy", "version": "==1.26.4", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "tool", "source": {"type": "git", "url": "https://github.com/org/tool.git", "reference": "v1.2"}, "kind": "normal"},
+        {"name": "local_pkg", "source": {"type": "path", "path": "./vendor/local_pkg"}, "kind": "normal"},
+        {"name": "sibling", "source": {"type": "path", "path": "../sibling"}, "kind": "normal"},
+        {"name": "pkg", "source": {"type": "url", "url": "https://example.com/pkg-1.0.tar.gz"}, "kind": "normal"},
+      ])
+    );
+  }
+
+  #[test]
+  fn extracts_pep_621_and_735_tables() {
+    let source = r#"
+[build-system]
+requires = ["hatchling>=1.18"]
+
+[project]
+name = "klep"
+version = "0.1.0"
+dependencies = ["httpx (>=0.27)", "rich"]
+
+[project.optional-dependencies]
+yaml = ["pyyaml>=6"]
+
+[project.scripts]
+klep = "klep.cli:main"
+
+[dependency-groups]
+test = ["pytest>=8", {include-group = "lint"}]
+"#;
+    let manifest = ManifestKind::Pyproject.extract(source).unwrap();
+    assert_eq!(manifest.name.as_deref(), Some("klep"));
+    assert_eq!(manifest.scripts, ["klep"]);
+    assert_eq!(
+      summary(&manifest.dependencies),
+      json!([
+        {"name": "hatchling", "version": ">=1.18", "source": {"type": "registry"}, "kind": "build"},
+        {"name": "httpx", "version": ">=0.27", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "rich", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "pyyaml", "version": ">=6", "source": {"type": "registry"}, "kind": "optional", "group": "yaml"},
+        {"name": "pytest", "version": ">=8", "source": {"type": "registry"}, "kind": "dev", "group": "test"},
+      ])
+    );
+  }
+
+  #[test]
+  fn extracts_poetry_tables() {
+    let source = r#"
+[tool.poetry]
+name = "klep"
+version = "0.2.0"
+
+[tool.poetry.dependencies]
+python = "^3.10"
+requests = "^2.31"
+lib = { git = "https://github.com/org/lib.git", branch = "main", optional = true }
+
+[tool.poetry.group.test.dependencies]
+pytest = { version = "^8.0", source = "internal" }
+"#;
+    assert_eq!(
+      dependencies(ManifestKind::Pyproject, source),
+      json!([
+        {"name": "requests", "version": "^2.31", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "lib", "source": {"type": "git", "url": "https://github.com/org/lib.git", "reference": "main"}, "kind": "optional"},
+        {"name": "pytest", "version": "^8.0", "source": {"type": "registry", "registry": "internal"}, "kind": "dev", "group": "test"},
+      ])
+    );
+  }
+}
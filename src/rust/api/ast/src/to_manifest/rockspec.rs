@@ -0,0 +1,435 @@
+//! Lua rock specifications, `.rockspec` files.
+//!
+//! A rockspec is a Lua script of global assignments. Their values, strings,
+//! numbers, booleans and tables, are read into [`DataNode`]s; any other
+//! expression becomes null. Dependencies are strings like
+//! `"luasocket >= 3.0, < 4"`.
+
+use gud_common::GudError;
+
+use super::{expect_str, invalid, Dependency, DependencyKind, ManifestKind, ManifestOutput};
+use crate::data::{DataNode, Entry, Format, LineIndex, Scalar, MAX_DEPTH};
+
+const KIND: ManifestKind = ManifestKind::Rockspec;
+
+const SECTIONS: &[(&str, DependencyKind)] = &[
+  ("dependencies", DependencyKind::Normal),
+  ("build_dependencies", DependencyKind::Build),
+  ("test_dependencies", DependencyKind::Dev),
+];
+
+struct Parser<'a> {
+  index: &'a LineIndex<'a>,
+  bytes: &'a [u8],
+  pos: usize,
+  depth: usize,
+}
+
+pub(super) fn extract(source: &str, manifest: &mut ManifestOutput) -> Result<(), GudError> {
+  let index = LineIndex::new(source, Format::Lines);
+  let tree = Parser {
+    index: &index,
+    bytes: source.as_bytes(),
+    pos: 0,
+    depth: 0,
+  }
+  .chunk()?;
+
+  manifest.name = tree
+    .get("package")
+    .and_then(DataNode::as_str)
+    .map(str::to_string);
+  manifest.version = tree
+    .get("version")
+    .and_then(DataNode::as_str)
+    .map(str::to_string);
+  for (section, kind) in SECTIONS {
+    let Some(list) = tree.get(section) else {
+      continue;
+    };
+    for item in list.items() {
+      let text = expect_str(KIND, item, section)?;
+      let text = text.trim();
+      let (name, constraint) = text
+        .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '=' | '~'))
+        .map_or((text, ""), |end| (&text[..end], text[end..].trim()));
+      if name.is_empty() {
+        return Err(invalid(
+          KIND,
+          item.span(),
+          format!("'{text}' is not a dependency"),
+        ));
+      }
+      if name == "lua" {
+        continue;
+      }
+      manifest.dependencies.push(Dependency::new(
+        name,
+        (!constraint.is_empty()).then(|| constraint.to_string()),
+        *kind,
+        item.span(),
+      ));
+    }
+  }
+  Ok(())
+}
+
+impl Parser<'_> {
+  fn error(&self, message: &str) -> GudError {
+    invalid(KIND, self.index.span(self.pos, self.pos), message)
+  }
+
+  fn peek(&self) -> Option<u8> {
+    self.bytes.get(self.pos).copied()
+  }
+
+  fn rest(&self) -> &[u8] {
+    &self.bytes[self.pos..]
+  }
+
+  /// Level of a long bracket `[[` or `[==[` at the cursor
+  fn long_bracket(&self) -> Option<usize> {
+    let rest = self.rest().strip_prefix(b"[")?;
+    let level = rest.iter().take_while(|&&b| b == b'=').count();
+    (rest.get(level) == Some(&b'[')).then_some(level)
+  }
+
+  /// Skip a long bracket of `level` at the cursor, returning its contents
+  fn long_string(&mut self, level: usize) -> Result<String, GudError> {
+    let start = self.pos;
+    self.pos += level + 2;
+    let close = format!("]{}]", "=".repeat(level));
+    let end = self
+      .rest()
+      .windows(close.len())
+      .position(|window| window == close.as_bytes())
+      .ok_or_else(|| {
+        invalid(
+          KIND,
+          self.index.span(start, start),
+          "unterminated long string",
+        )
+      })?;
+    let text = &self.index.source[self.pos..self.pos + end];
+    self.pos += end + close.len();
+    // A newline right after the opening bracket is not part of the string
+    Ok(text.strip_prefix('\n').unwrap_or(text).to_string())
+  }
+
+  fn skip_space(&mut self) -> Result<(), GudError> {
+    loop {
+      match self.peek() {
+        Some(b' ' | b'\t' | b'\n' | b'\r' | b';') => self.pos += 1,
+        Some(b'-') if self.rest().starts_with(b"--") => {
+          self.pos += 2;
+          if let Some(level) = self.long_bracket() {
+            self.long_string(level)?;
+          } else {
+            while self.peek().is_some_and(|b| b != b'\n') {
+              self.pos += 1;
+            }
+          }
+        }
+        _ => return Ok(()),
+      }
+    }
+  }
+
+  fn name(&mut self) -> Option<String> {
+    let start = self.pos;
+    while self
+      .peek()
+      .is_some_and(|b| b.is_ascii_alphanumeric() || b == b'_')
+    {
+      self.pos += 1;
+    }
+    (self.pos > start && !self.bytes[start].is_ascii_digit())
+      .then(|| self.index.source[start..self.pos].to_string())
+  }
+
+  /// Top-level `name = value` assignments
+  fn chunk(&mut self) -> Result<DataNode, GudError> {
+    let mut entries = Vec::new();
+    self.skip_space()?;
+    while self.pos < self.bytes.len() {
+      let key_start = self.pos;
+      let key = self
+        .name()
+        .ok_or_else(|| self.error("expected an assignment"))?;
+      let key_span = self.index.span(key_start, self.pos);
+      self.skip_space()?;
+      if self.peek() != Some(b'=') {
+        return Err(self.error("expected '='"));
+      }
+      self.pos += 1;
+      self.skip_space()?;
+      let value = self.expression()?;
+      entries.push(Entry {
+        key,
+        key_span,
+        value,
+      });
+      self.skip_space()?;
+    }
+    Ok(DataNode::Map {
+      entries,
+      span: self.index.span(0, self.bytes.len()),
+    })
+  }
+
+  /// Values joined with `..`; anything but strings makes the result null
+  fn expression(&mut self) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    let mut node = self.primary()?;
+    self.skip_space()?;
+    while self.rest().starts_with(b"..") {
+      self.pos += 2;
+      self.skip_space()?;
+      let next = self.primary()?;
+      let text = node
+        .as_str()
+        .zip(next.as_str())
+        .map(|(a, b)| format!("{a}{b}"));
+      node = self
+        .index
+        .scalar(text.map_or(Scalar::Null, Scalar::String), start, self.pos);
+      self.skip_space()?;
+    }
+    Ok(node)
+  }
+
+  fn primary(&mut self) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    match self.peek() {
+      Some(b'{') => {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+          return Err(self.error("nesting is too deep"));
+        }
+        let table = self.table();
+        self.depth -= 1;
+        table
+      }
+      Some(quote @ (b'"' | b'\'')) => {
+        let text = self.string(quote)?;
+        Ok(self.index.scalar(Scalar::String(text), start, self.pos))
+      }
+      Some(b'[') if self.long_bracket().is_some() => {
+        let text = self.long_string(self.long_bracket().unwrap_or(0))?;
+        Ok(self.index.scalar(Scalar::String(text), start, self.pos))
+      }
+      Some(b'0'..=b'9' | b'-' | b'.') => {
+        while self
+          .peek()
+          .is_some_and(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'+'))
+        {
+          self.pos += 1;
+        }
+        let text = &self.index.source[start..self.pos];
+        let value = text.parse().map_or_else(
+          |_| text.parse().map_or(Scalar::Null, Scalar::Float),
+          Scalar::Integer,
+        );
+        Ok(self.index.scalar(value, start, self.pos))
+      }
+      _ => {
+        let name = self.name().ok_or_else(|| self.error("expected a value"))?;
+        let value = match name.as_str() {
+          "true" => Scalar::Bool(true),
+          "false" => Scalar::Bool(false),
+          _ => Scalar::Null,
+        };
+        // Skip field accesses and calls such as `os.getenv("X")`
+        while let Some(b'.' | b'(' | b':') = self.peek() {
+          if self.rest().starts_with(b"..") {
+            break;
+          }
+          if self.peek() == Some(b'(') {
+            while self.peek().is_some_and(|b| b != b')') {
+              self.pos += 1;
+            }
+          }
+          self.pos += 1;
+          self.name();
+        }
+        Ok(self.index.scalar(value, start, self.pos))
+      }
+    }
+  }
+
+  fn string(&mut self, quote: u8) -> Result<String, GudError> {
+    let start = self.pos;
+    self.pos += 1;
+    let mut text = Vec::new();
+    loop {
+      match self.peek() {
+        Some(b) if b == quote => {
+          self.pos += 1;
+          return Ok(String::from_utf8_lossy(&text).into_owned());
+        }
+        Some(b'\\') => {
+          self.pos += 1;
+          let escaped = self
+            .peek()
+            .ok_or_else(|| invalid(KIND, self.index.span(start, start), "unterminated string"))?;
+          text.push(match escaped {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b => b,
+          });
+          self.pos += 1;
+        }
+        Some(b'\n') | None => {
+          return Err(invalid(
+            KIND,
+            self.index.span(start, start),
+            "unterminated string",
+          ))
+        }
+        Some(b) => {
+          text.push(b);
+          self.pos += 1;
+        }
+      }
+    }
+  }
+
+  /// `{ value, key = value, ["key"] = value }`: a list when every field is
+  /// positional, otherwise a map with positional fields keyed by position
+  fn table(&mut self) -> Result<DataNode, GudError> {
+    let start = self.pos;
+    self.pos += 1;
+    let mut entries = Vec::new();
+    let mut keyed = false;
+    loop {
+      self.skip_space()?;
+      match self.peek() {
+        Some(b'}') => break,
+        None => {
+          return Err(invalid(
+            KIND,
+            self.index.span(start, start),
+            "unterminated table",
+          ))
+        }
+        _ => {}
+      }
+
+      let key_start = self.pos;
+      let key = if self.peek() == Some(b'[') && self.long_bracket().is_none() {
+        self.pos += 1;
+        self.skip_space()?;
+        let key = self.expression()?;
+        if self.peek() != Some(b']') {
+          return Err(self.error("expected ']'"));
+        }
+        self.pos += 1;
+        Some(
+          key
+            .as_str()
+            .map_or_else(|| key.to_value().to_string(), str::to_string),
+        )
+      } else {
+        let mark = self.pos;
+        let name = self.name();
+        self.skip_space()?;
+        if name.is_some()
+          && self.peek() == Some(b'=')
+          && self.bytes.get(self.pos + 1) != Some(&b'=')
+        {
+          name
+        } else {
+          self.pos = mark;
+          None
+        }
+      };
+      let key_span = self.index.span(key_start, self.pos);
+      if key.is_some() {
+        self.skip_space()?;
+        if self.peek() != Some(b'=') {
+          return Err(self.error("expected '='"));
+        }
+        self.pos += 1;
+        self.skip_space()?;
+      }
+
+      let value = self.expression()?;
+      keyed |= key.is_some();
+      entries.push(Entry {
+        key: key.unwrap_or_else(|| (entries.len() + 1).to_string()),
+        key_span,
+        value,
+      });
+
+      self.skip_space()?;
+      match self.peek() {
+        Some(b',') => self.pos += 1,
+        Some(b'}') => {}
+        _ => return Err(self.error("expected ',' or '}'")),
+      }
+    }
+    self.pos += 1;
+    let span = self.index.span(start, self.pos);
+    Ok(if keyed {
+      DataNode::Map { entries, span }
+    } else {
+      DataNode::List {
+        items: entries.into_iter().map(|entry| entry.value).collect(),
+        span,
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_manifest::tests::summary;
+  use crate::to_manifest::ManifestKind;
+  use serde_json::json;
+
+  #[test]
+  fn extracts_dependency_strings() {
+    let source = r#"
+-- klep rock
+package = "klep"
+version = "1.0-1"
+source = {
+   url = "git+https://github.com/klep/klep.git",
+   tag = "v" .. "1.0",
+}
+description = { summary = [[Package
+manager]], license = "MIT" }
+dependencies = {
+   "lua >= 5.1, < 5.5",
+   "luasocket >= 3.0",
+   'penlight',
+}
+test_dependencies = { "busted ~> 2" }
+build = { type = "builtin", modules = { ["klep.init"] = "src/init.lua" } }
+"#;
+    let manifest = ManifestKind::Rockspec.extract(source).unwrap();
+    assert_eq!(manifest.name.as_deref(), Some("klep"));
+    assert_eq!(manifest.version.as_deref(), Some("1.0-1"));
+    assert_eq!(
+      summary(&manifest.dependencies),
+      json!([
+        {"name": "luasocket", "version": ">= 3.0", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "penlight", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "busted", "version": "~> 2", "source": {"type": "registry"}, "kind": "dev"},
+      ])
+    );
+  }
+
+  #[test]
+  fn rejects_malformed_tables() {
+    for source in [
+      "dependencies = { \"a\"",
+      "package = \"x",
+      "= 1",
+      "a = { b = }",
+    ] {
+      assert!(ManifestKind::Rockspec.extract(source).is_err(), "{source}");
+    }
+  }
+}
@@ -0,0 +1,381 @@
+//! Bundler's `Gemfile`.
+//!
+//! Gemfiles are Ruby, so only the Bundler DSL is read: `gem` lines with
+//! their versions and options, and the `group`, `source`, `git`, `github`,
+//! `path` and `platforms` blocks around them. Any other statement is
+//! skipped, keeping track of the blocks it opens so `end` closes the right
+//! one. A line ending in a comma continues on the next one, and `if` or
+//! `unless` modifiers after a statement are left out.
+
+use gud_common::GudError;
+
+use super::{
+  git_source, invalid, Dependency, DependencyKind, ManifestKind, ManifestOutput, Source,
+};
+use crate::data::{Format, LineIndex, Span};
+
+const KIND: ManifestKind = ManifestKind::Gemfile;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Word(String),
+  Symbol(String),
+  Str(String),
+  /// `key:` of a keyword argument, or `:key =>`
+  Key(String),
+  Open,
+  Close,
+  Comma,
+}
+
+/// Tokens of one line, up to its comment
+fn tokens(line: &str) -> Vec<Token> {
+  let mut tokens = Vec::new();
+  let mut chars = line.char_indices().peekable();
+  while let Some((_, c)) = chars.next() {
+    match c {
+      '#' => break,
+      quote @ ('"' | '\'') => {
+        let mut text = String::new();
+        while let Some((_, c)) = chars.next() {
+          match c {
+            '\\' => text.extend(chars.next().map(|(_, c)| c)),
+            c if c == quote => break,
+            c => text.push(c),
+          }
+        }
+        tokens.push(Token::Str(text));
+      }
+      ':'
+        if chars
+          .peek()
+          .is_some_and(|(_, c)| c.is_alphanumeric() || *c == '_') =>
+      {
+        let symbol = word(&mut chars);
+        tokens.push(Token::Symbol(symbol));
+      }
+      '=' if chars.peek().is_some_and(|(_, c)| *c == '>') => {
+        chars.next();
+        if let Some(Token::Symbol(key) | Token::Str(key)) = tokens.pop() {
+          tokens.push(Token::Key(key));
+        }
+      }
+      '[' | '(' => tokens.push(Token::Open),
+      ']' | ')' => tokens.push(Token::Close),
+      ',' => tokens.push(Token::Comma),
+      c if c.is_alphanumeric() || c == '_' => {
+        let mut text = c.to_string();
+        text.push_str(&word(&mut chars));
+        if chars.peek().is_some_and(|(_, c)| *c == ':') {
+          chars.next();
+          tokens.push(Token::Key(text));
+        } else {
+          tokens.push(Token::Word(text));
+        }
+      }
+      _ => {}
+    }
+  }
+  tokens
+}
+
+fn word(chars: &mut std::iter::Peekable<std::str::CharIndices>) -> String {
+  let mut text = String::new();
+  while let Some(&(_, c)) = chars.peek() {
+    if !(c.is_alphanumeric() || matches!(c, '_' | '?' | '!')) {
+      break;
+    }
+    text.push(c);
+    chars.next();
+  }
+  text
+}
+
+/// Value of a keyword argument: a string, a symbol, a word such as `true`
+/// or a bracketed list of those
+fn option_value(tokens: &[Token]) -> Vec<String> {
+  let text = |token: &Token| match token {
+    Token::Str(text) | Token::Symbol(text) | Token::Word(text) => Some(text.clone()),
+    _ => None,
+  };
+  match tokens.first() {
+    Some(Token::Open) => tokens[1..]
+      .iter()
+      .take_while(|token| **token != Token::Close)
+      .filter_map(text)
+      .collect(),
+    Some(token) => text(token).into_iter().collect(),
+    None => Vec::new(),
+  }
+}
+
+/// Positional arguments and keyword options of a statement
+struct Call {
+  arguments: Vec<String>,
+  options: Vec<(String, Vec<String>)>,
+}
+
+impl Call {
+  fn parse(tokens: &[Token]) -> Self {
+    let mut call = Self {
+      arguments: Vec::new(),
+      options: Vec::new(),
+    };
+    for (i, token) in tokens.iter().enumerate() {
+      match token {
+        Token::Word(word) if word == "if" || word == "unless" => break,
+        Token::Key(key) => call
+          .options
+          .push((key.clone(), option_value(&tokens[i + 1..]))),
+        Token::Str(text) | Token::Symbol(text) if call.options.is_empty() => {
+          call.arguments.push(text.clone());
+        }
+        _ => {}
+      }
+    }
+    call
+  }
+
+  fn option(&self, key: &str) -> Option<&[String]> {
+    self
+      .options
+      .iter()
+      .find(|(name, _)| name == key)
+      .map(|(_, values)| values.as_slice())
+  }
+
+  fn first(&self, key: &str) -> Option<String> {
+    self.option(key).and_then(|values| values.first()).cloned()
+  }
+
+  /// Source named by `git:`, `github:`, `path:` or `source:`
+  fn source(&self) -> Option<Source> {
+    let reference = ["ref", "tag", "branch"]
+      .iter()
+      .find_map(|key| self.first(key));
+    let git = |url: String| match git_source(&url) {
+      Source::Git {
+        url,
+        reference: own,
+      } => Source::Git {
+        url,
+        reference: reference.clone().or(own),
+      },
+      source => source,
+    };
+    self
+      .first("git")
+      .map(&git)
+      .or_else(|| {
+        self
+          .first("github")
+          .map(|repository| git(format!("https://github.com/{repository}.git")))
+      })
+      .or_else(|| self.first("path").map(|path| Source::Path { path }))
+      .or_else(|| {
+        self.first("source").map(|registry| Source::Registry {
+          registry: Some(registry),
+        })
+      })
+  }
+}
+
+/// Block opened by a `do`
+enum Frame {
+  Group { groups: Vec<String>, optional: bool },
+  Source(Source),
+  Other,
+}
+
+const OPENERS: &[&str] = &[
+  "if", "unless", "case", "while", "until", "begin", "def", "class", "module",
+];
+
+pub(super) fn extract(source: &str, manifest: &mut ManifestOutput) -> Result<(), GudError> {
+  let index = LineIndex::new(source, Format::Lines);
+  let mut frames: Vec<Frame> = Vec::new();
+
+  let mut offset = 0;
+  let mut lines = source.split_inclusive('\n');
+  while let Some(raw) = lines.next() {
+    let start = offset + (raw.len() - raw.trim_start().len());
+    offset += raw.len();
+    let mut end = start + raw.trim().len();
+    let mut tokens = tokens(raw);
+    while tokens.last() == Some(&Token::Comma) {
+      let Some(next) = lines.next() else {
+        break;
+      };
+      end = offset + next.trim_end().len();
+      offset += next.len();
+      tokens.extend(self::tokens(next));
+    }
+    let Some(Token::Word(verb)) = tokens.first() else {
+      continue;
+    };
+    let opens = tokens
+      .iter()
+      .any(|token| *token == Token::Word("do".to_string()));
+    let call = Call::parse(&tokens[1..]);
+
+    let frame = match verb.as_str() {
+      "end" => {
+        frames.pop();
+        continue;
+      }
+      "gem" => {
+        let Some(name) = call.arguments.first() else {
+          return Err(invalid(KIND, index.span(start, end), "expected a gem name"));
+        };
+        manifest
+          .dependencies
+          .push(gem(name, &call, &frames, index.span(start, end)));
+        None
+      }
+      "group" => Some(Frame::Group {
+        optional: call.first("optional").as_deref() == Some("true"),
+        groups: call.arguments,
+      }),
+      "source" => call.arguments.first().map(|registry| {
+        Frame::Source(Source::Registry {
+          registry: Some(registry.clone()),
+        })
+      }),
+      "git" | "github" | "path" => {
+        let mut block = Call {
+          arguments: Vec::new(),
+          options: call.options,
+        };
+        block.options.insert(0, (verb.clone(), call.arguments));
+        block.source().map(Frame::Source)
+      }
+      verb if OPENERS.contains(&verb) => {
+        frames.push(Frame::Other);
+        continue;
+      }
+      _ => None,
+    };
+    if opens {
+      frames.push(frame.unwrap_or(Frame::Other));
+    }
+  }
+  Ok(())
+}
+
+/// `gem "name", "~> 1.0", group: :test, git: "..."`
+fn gem(name: &str, call: &Call, frames: &[Frame], span: Span) -> Dependency {
+  let constraints = &call.arguments[1..];
+  let mut dependency = Dependency::new(
+    name,
+    (!constraints.is_empty()).then(|| constraints.join(", ")),
+    DependencyKind::Normal,
+    span,
+  );
+
+  let mut groups = Vec::new();
+  let mut optional = call.first("optional").as_deref() == Some("true");
+  for frame in frames {
+    match frame {
+      Frame::Group {
+        groups: block,
+        optional: block_optional,
+      } => {
+        groups.extend(block.iter().cloned());
+        optional |= block_optional;
+      }
+      Frame::Source(source) => dependency.source = source.clone(),
+      Frame::Other => {}
+    }
+  }
+  for key in ["group", "groups"] {
+    groups.extend(call.option(key).unwrap_or_default().iter().cloned());
+  }
+  if let Some(source) = call.source() {
+    dependency.source = source;
+  }
+
+  if optional {
+    dependency.kind = DependencyKind::Optional;
+  } else if groups
+    .iter()
+    .any(|group| group == "development" || group == "test")
+  {
+    dependency.kind = DependencyKind::Dev;
+  }
+  if !groups.is_empty() {
+    dependency.group = Some(groups.join(","));
+  }
+  dependency
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::to_manifest::tests::summary;
+  use crate::to_manifest::ManifestKind;
+  use serde_json::json;
+
+  #[test]
+  fn extracts_gems_in_blocks() {
+    let source = r#"
+source "https://rubygems.org"
+ruby "3.3.0"
+
+gem "rails", "~> 7.1", ">= 7.1.3" # framework
+gem 'pg', require: false
+gem "tool", git: "https://github.com/org/tool.git", branch: "main"
+
+group :development, :test do
+  gem "rspec-rails", groups: [:ci]
+  if ENV["CI"]
+    gem "simplecov"
+  end
+end
+
+source "https://gems.example.com" do
+  gem "internal"
+end
+
+gem "local", :path => "../local"
+"#;
+    let manifest = ManifestKind::Gemfile.extract(source).unwrap();
+    assert_eq!(
+      summary(&manifest.dependencies),
+      json!([
+        {"name": "rails", "version": "~> 7.1, >= 7.1.3", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "pg", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "tool", "source": {"type": "git", "url": "https://github.com/org/tool.git", "reference": "main"}, "kind": "normal"},
+        {"name": "rspec-rails", "source": {"type": "registry"}, "kind": "dev", "group": "development,test,ci"},
+        {"name": "simplecov", "source": {"type": "registry"}, "kind": "dev", "group": "development,test"},
+        {"name": "internal", "source": {"type": "registry", "registry": "https://gems.example.com"}, "kind": "normal"},
+        {"name": "local", "source": {"type": "path", "path": "../local"}, "kind": "normal"},
+      ])
+    );
+  }
+
+  #[test]
+  fn leaves_out_statement_modifiers() {
+    let source = "gem 'a', '>= 1' if ENV['X']\ngem 'b', require: false unless windows?\n";
+    let manifest = ManifestKind::Gemfile.extract(source).unwrap();
+    assert_eq!(
+      summary(&manifest.dependencies),
+      json!([
+        {"name": "a", "version": ">= 1", "source": {"type": "registry"}, "kind": "normal"},
+        {"name": "b", "source": {"type": "registry"}, "kind": "normal"},
+      ])
+    );
+  }
+
+  #[test]
+  fn joins_lines_ending_in_a_comma() {
+    let source = "gem 'x',\n  git: 'https://example.com/x.git', # fork\n  tag: 'v1'\ngem 'y'\n";
+    let manifest = ManifestKind::Gemfile.extract(source).unwrap();
+    assert_eq!(
+      summary(&manifest.dependencies),
+      json!([
+        {"name": "x", "source": {"type": "git", "url": "https://example.com/x.git", "reference": "v1"}, "kind": "normal"},
+        {"name": "y", "source": {"type": "registry"}, "kind": "normal"},
+      ])
+    );
+    assert_eq!(manifest.dependencies[0].span.end.line, 3);
+  }
+}
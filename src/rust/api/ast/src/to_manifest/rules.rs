@@ -0,0 +1,831 @@
+//! User-authored rules for reading in-house manifest formats.
+//!
+//! A rule file teaches `to_manifest` a format it has no extractor for. It
+//! is written in any data format and declares where the dependencies are,
+//! how each one's fields are read and what kind they are:
+//!
+//! ```toml
+//! name = "acme"
+//! files = ["*.acme.yaml"]
+//! ecosystem = "npm"
+//!
+//! [package]
+//! name = "project.id"
+//!
+//! [[rules]]
+//! path = "modules.*.needs"
+//! pattern = '^(?P<name>[\w./-]+)(?:\s+(?P<version>.+))?$'
+//! kind = "build"
+//!
+//! [[samples]]
+//! path = "app.acme.yaml"
+//! source_code = "modules:\n  web:\n    needs:\n      - lodash ^4.17.0\n"
+//! expect = [{ name = "lodash", version = "^4.17.0", kind = "build" }]
+//! ```
+//!
+//! Each rule's `path` is a key path of nodes holding dependencies, `*`
+//! matching any key and naming the dependency's group. A map holds them
+//! by name, a list one per item. Fields are read from the key paths in
+//! `fields`, then from the named groups of `pattern` matched against a
+//! string spec, then from a record's own `name` and `version`, or the
+//! string spec itself. Samples are checked whenever the rules are loaded,
+//! so a rule file that stops reading them fails loudly.
+
+use gud_common::{log, ErrorKind, GudError};
+use gud_std::version::Constraint;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use super::{
+  entry_span, git_source, Dependency, DependencyKind, DepsFile, ManifestKind, ManifestOutput,
+  Source, MANIFESTS,
+};
+use crate::data::{DataNode, Format, Scalar, Span, FORMATS};
+use crate::family::parse_document;
+use crate::planner::matching;
+use crate::planner::operation::kind_name;
+
+/// Named groups a rule's pattern may capture
+const CAPTURES: &[&str] = &["name", "version", "kind", "url", "group"];
+
+/// A rule file, as `to_manifest` and `check_rules` take it
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct RuleFile {
+  /// Contents of the rule file
+  pub source_code: String,
+  /// Data format of the rule file, else taken from its path
+  pub language: Option<String>,
+  /// Path or file name of the rule file
+  pub path: Option<String>,
+}
+
+/// How to read one in-house manifest format
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSet {
+  /// Name of the format, which `language` may give to pick these rules
+  pub name: String,
+  /// File names the format is kept in, where `*` matches anything
+  #[serde(default)]
+  pub files: Vec<String>,
+  /// Data format of the manifests, else taken from their path
+  pub format: Option<String>,
+  /// Manifest whose registry and version syntax the dependencies follow,
+  /// such as `npm` or `cargo`
+  pub ecosystem: Option<String>,
+  /// URL of a registry dependency, with `{name}` standing for its name
+  pub registry: Option<String>,
+  /// Where the package's own name and version are
+  #[serde(default)]
+  pub package: PackageRule,
+  /// Blocks of dependencies, read in order
+  pub rules: Vec<Rule>,
+  /// Manifests the rules are checked against before use
+  #[serde(default)]
+  pub samples: Vec<Sample>,
+}
+
+/// Key paths of the package's own name and version
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PackageRule {
+  /// Dotted key path of the package's name
+  pub name: Option<String>,
+  /// Dotted key path of the package's version
+  pub version: Option<String>,
+}
+
+/// Where a block of dependencies is and how to read it
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+  /// Dotted key path of the nodes holding dependencies; `*` matches any
+  /// key, the first one naming the group, and an empty path is the root
+  pub path: String,
+  /// Where the fields are within record specs
+  #[serde(default)]
+  pub fields: Fields,
+  /// Regular expression matched against string specs, whose groups
+  /// `name`, `version`, `kind`, `url` and `group` fill those fields
+  pub pattern: Option<String>,
+  /// Kind of the dependencies unless they name one, `normal` by default
+  pub kind: Option<DependencyKind>,
+  /// Kinds the values of a `kind` field stand for, such as `test = "dev"`
+  #[serde(default)]
+  pub kinds: BTreeMap<String, DependencyKind>,
+}
+
+/// Key paths within a dependency's record of its universal fields
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Fields {
+  /// Name of the dependency
+  pub name: Option<String>,
+  /// Version requirement
+  pub version: Option<String>,
+  /// Kind, read through the rule's `kinds`
+  pub kind: Option<String>,
+  /// Where the dependency is fetched from
+  pub url: Option<String>,
+  /// Feature, extra or group it belongs to
+  pub group: Option<String>,
+}
+
+/// A manifest the rules must read
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Sample {
+  /// Path or file name of the sample
+  pub path: Option<String>,
+  /// Contents of the sample
+  pub source_code: String,
+  /// Dependencies the rules must read from it; with none listed, the
+  /// sample only has to be read without errors
+  #[serde(default)]
+  pub expect: Vec<Expected>,
+}
+
+/// A dependency a sample must give, compared on the fields it lists
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Expected {
+  /// Name of the dependency
+  pub name: String,
+  /// Version requirement as written
+  pub version: Option<String>,
+  /// How it is used
+  pub kind: Option<DependencyKind>,
+}
+
+/// Rule file that cannot be used
+fn invalid_rules(name: &str, message: impl Into<String>) -> GudError {
+  GudError::new(ErrorKind::Argument, "invalid-rules", message)
+    .with_context(json!({ "rules": name }))
+}
+
+fn key_path(path: &str) -> Vec<String> {
+  path
+    .split('.')
+    .filter(|key| !key.is_empty())
+    .map(str::to_string)
+    .collect()
+}
+
+fn lookup<'a>(node: &'a DataNode, path: &str) -> Option<&'a DataNode> {
+  key_path(path)
+    .iter()
+    .try_fold(node, |node, key| node.get(key))
+}
+
+/// A scalar as text; booleans and nulls have none
+fn text(node: &DataNode) -> Option<String> {
+  match node {
+    DataNode::Scalar {
+      value: Scalar::String(text),
+      ..
+    } => Some(text.clone()),
+    DataNode::Scalar {
+      value: Scalar::Integer(number),
+      ..
+    } => Some(number.to_string()),
+    DataNode::Scalar {
+      value: Scalar::Float(number),
+      ..
+    } => Some(number.to_string()),
+    _ => None,
+  }
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any run of
+/// characters, ignoring case
+fn glob(pattern: &str, name: &str) -> bool {
+  let (pattern, name) = (pattern.to_ascii_lowercase(), name.to_ascii_lowercase());
+  let mut parts = pattern.split('*');
+  let first = parts.next().unwrap_or_default();
+  let Some(mut rest) = name.strip_prefix(first) else {
+    return false;
+  };
+  let parts: Vec<&str> = parts.collect();
+  let Some((last, middle)) = parts.split_last() else {
+    return rest.is_empty();
+  };
+  for part in middle {
+    let Some(at) = rest.find(part) else {
+      return false;
+    };
+    rest = &rest[at + part.len()..];
+  }
+  rest.ends_with(last)
+}
+
+/// Source of a dependency from where its rules say it comes from
+fn source_of(url: &str) -> Source {
+  if url.starts_with("git+")
+    || url.contains(".git#")
+    || std::path::Path::new(url)
+      .extension()
+      .is_some_and(|extension| extension.eq_ignore_ascii_case("git"))
+  {
+    git_source(url)
+  } else if url.starts_with(['.', '/']) {
+    Source::Path {
+      path: url.to_string(),
+    }
+  } else {
+    Source::Url {
+      url: url.to_string(),
+    }
+  }
+}
+
+struct CompiledRule {
+  rule: Rule,
+  path: Vec<String>,
+  pattern: Option<Regex>,
+}
+
+/// A rule set ready to read manifests
+pub struct Rules {
+  set: RuleSet,
+  ecosystem: Option<ManifestKind>,
+  compiled: Vec<CompiledRule>,
+}
+
+/// How one sample fared
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SampleReport {
+  /// Path of the sample, or its place in the rule file
+  pub sample: String,
+  /// Number of dependencies read from it
+  pub dependencies: usize,
+  /// Ways the dependencies read differ from the expected ones
+  pub problems: Vec<String>,
+}
+
+impl Rules {
+  /// Read and compile a rule file without checking its samples
+  pub fn parse(file: &RuleFile) -> Result<Self, GudError> {
+    let tree = parse_document(
+      &file.source_code,
+      file.language.as_deref(),
+      file.path.as_deref(),
+    )?;
+    let set: RuleSet = serde_json::from_value(tree.to_value()).map_err(|error| {
+      invalid_rules(
+        file.path.as_deref().unwrap_or("the rule file"),
+        format!("Cannot read the rule file: {error}"),
+      )
+    })?;
+    let name = set.name.clone();
+    if set.rules.is_empty() {
+      return Err(invalid_rules(
+        &name,
+        format!("Rule set '{name}' has no rules"),
+      ));
+    }
+    if let Some(format) = set.format.as_deref() {
+      if Format::from_name(format).is_none() {
+        return Err(
+          invalid_rules(&name, format!("Unknown data format '{format}'"))
+            .with_context(json!({ "rules": name, "supported": FORMATS })),
+        );
+      }
+    }
+    let ecosystem = match set.ecosystem.as_deref() {
+      None => None,
+      Some(ecosystem) => Some(ManifestKind::from_name(ecosystem).ok_or_else(|| {
+        invalid_rules(&name, format!("Unknown ecosystem '{ecosystem}'"))
+          .with_context(json!({ "rules": name, "supported": MANIFESTS }))
+      })?),
+    };
+    let compiled = set
+      .rules
+      .iter()
+      .map(|rule| {
+        let pattern = rule
+          .pattern
+          .as_deref()
+          .map(|pattern| {
+            let regex = Regex::new(pattern).map_err(|error| {
+              invalid_rules(
+                &name,
+                format!("Invalid pattern for '{}': {error}", rule.path),
+              )
+            })?;
+            if let Some(group) = regex
+              .capture_names()
+              .flatten()
+              .find(|group| !CAPTURES.contains(group))
+            {
+              return Err(
+                invalid_rules(
+                  &name,
+                  format!(
+                    "Pattern for '{}' captures unknown field '{group}'",
+                    rule.path
+                  ),
+                )
+                .with_context(json!({ "rules": name, "supported": CAPTURES })),
+              );
+            }
+            Ok(regex)
+          })
+          .transpose()?;
+        Ok(CompiledRule {
+          rule: rule.clone(),
+          path: key_path(&rule.path),
+          pattern,
+        })
+      })
+      .collect::<Result<_, GudError>>()?;
+    Ok(Self {
+      set,
+      ecosystem,
+      compiled,
+    })
+  }
+
+  /// Read a rule file and check that it reads its samples as expected
+  pub fn load(file: &RuleFile) -> Result<Self, GudError> {
+    let rules = Self::parse(file)?;
+    let (samples, _) = rules.check();
+    let problems: Vec<String> = samples
+      .iter()
+      .flat_map(|report| {
+        report
+          .problems
+          .iter()
+          .map(|problem| format!("{}: {problem}", report.sample))
+      })
+      .collect();
+    if !problems.is_empty() {
+      let name = &rules.set.name;
+      return Err(
+        invalid_rules(
+          name,
+          format!("Rule set '{name}' does not read its samples as expected"),
+        )
+        .with_context(json!({ "rules": name, "problems": problems })),
+      );
+    }
+    Ok(rules)
+  }
+
+  /// Name of the format the rules read
+  #[must_use]
+  pub fn name(&self) -> &str {
+    &self.set.name
+  }
+
+  /// Whether the input names the format or is in one of its files
+  #[must_use]
+  pub fn claims(&self, language: Option<&str>, path: Option<&str>) -> bool {
+    language.is_some_and(|language| language.eq_ignore_ascii_case(&self.set.name))
+      || path.is_some_and(|path| {
+        let file = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        self.set.files.iter().any(|pattern| glob(pattern, file))
+      })
+  }
+
+  /// Manifest in the data format these rules can parse, with whether each
+  /// rule found anything in it
+  pub fn extract(
+    &self,
+    source: &str,
+    language: Option<&str>,
+    path: Option<&str>,
+  ) -> Result<(ManifestOutput, Vec<bool>), GudError> {
+    let language = self.set.format.as_deref().or(language);
+    let tree = parse_document(source, language, path)?;
+    let mut manifest = ManifestOutput {
+      rules: Some(self.set.name.clone()),
+      name: self
+        .set
+        .package
+        .name
+        .as_deref()
+        .and_then(|at| lookup(&tree, at))
+        .and_then(text),
+      version: self
+        .set
+        .package
+        .version
+        .as_deref()
+        .and_then(|at| lookup(&tree, at))
+        .and_then(text),
+      ..ManifestOutput::default()
+    };
+    let mut used = Vec::new();
+    for rule in &self.compiled {
+      let pattern: Vec<&str> = rule.path.iter().map(String::as_str).collect();
+      let wildcard = rule.path.iter().position(|key| key == "*");
+      let mut blocks = Vec::new();
+      matching(&tree, &pattern, &mut Vec::new(), &mut |path, node| {
+        blocks.push((wildcard.map(|at| path[at].clone()), node));
+      });
+      used.push(!blocks.is_empty());
+      for (group, block) in blocks {
+        match block {
+          DataNode::Map { entries, .. } => {
+            for entry in entries {
+              let dependency = self.read(
+                rule,
+                Some(&entry.key),
+                &entry.value,
+                entry_span(entry),
+                group.clone(),
+              )?;
+              manifest.dependencies.push(dependency);
+            }
+          }
+          DataNode::List { items, .. } => {
+            for item in items {
+              let dependency = self.read(rule, None, item, item.span(), group.clone())?;
+              manifest.dependencies.push(dependency);
+            }
+          }
+          DataNode::Scalar { span, .. } => {
+            return Err(self.invalid(
+              *span,
+              format!("expected '{}' to hold dependencies", rule.rule.path),
+            ));
+          }
+        }
+      }
+    }
+    manifest.deps_file = DepsFile::new(&manifest.dependencies);
+    Ok((manifest, used))
+  }
+
+  /// The dependency `spec` declares, listed under `key` in a map
+  fn read(
+    &self,
+    rule: &CompiledRule,
+    key: Option<&str>,
+    spec: &DataNode,
+    span: Span,
+    group: Option<String>,
+  ) -> Result<Dependency, GudError> {
+    let fields = &rule.rule.fields;
+    let field = |at: &Option<String>| at.as_deref().and_then(|at| lookup(spec, at)).and_then(text);
+    let mut name = field(&fields.name);
+    let mut version = field(&fields.version);
+    let mut kind = field(&fields.kind);
+    let mut url = field(&fields.url);
+    let mut group = field(&fields.group).or(group);
+
+    if let DataNode::Map { .. } = spec {
+      name = name.or_else(|| spec.get("name").and_then(text));
+      version = version.or_else(|| spec.get("version").and_then(text));
+    } else if let Some(value) = text(spec) {
+      if let Some(pattern) = &rule.pattern {
+        let captures = pattern.captures(&value).ok_or_else(|| {
+          self.invalid(
+            span,
+            format!(
+              "'{value}' does not match the pattern for '{}'",
+              rule.rule.path
+            ),
+          )
+        })?;
+        let capture = |group: &str| {
+          captures
+            .name(group)
+            .map(|found| found.as_str().trim().to_string())
+            .filter(|found| !found.is_empty())
+        };
+        name = name.or_else(|| capture("name"));
+        version = version.or_else(|| capture("version"));
+        kind = kind.or_else(|| capture("kind"));
+        url = url.or_else(|| capture("url"));
+        group = group.or_else(|| capture("group"));
+      } else if key.is_some() {
+        version = version.or(Some(value));
+      } else {
+        name = name.or(Some(value));
+      }
+    }
+
+    let name = name
+      .or_else(|| key.map(str::to_string))
+      .ok_or_else(|| self.invalid(span, "a dependency has no name"))?;
+    let kind = match kind {
+      None => rule.rule.kind.unwrap_or(DependencyKind::Normal),
+      Some(value) => rule
+        .rule
+        .kinds
+        .get(&value)
+        .copied()
+        .or_else(|| serde_json::from_value(json!(value)).ok())
+        .ok_or_else(|| self.invalid(span, format!("'{name}' has an unknown kind '{value}'")))?,
+    };
+    let mut dependency = Dependency::new(name, version, kind, span);
+    dependency.group = group;
+    if let Some(url) = url {
+      dependency.source = source_of(&url);
+    }
+    self.complete(&mut dependency);
+    Ok(dependency)
+  }
+
+  /// Fill in the URL and normalized constraint, the ecosystem's way when
+  /// the rules name one
+  fn complete(&self, dependency: &mut Dependency) {
+    if let Some(ecosystem) = self.ecosystem {
+      ecosystem.complete(dependency);
+    } else {
+      dependency.url = match &dependency.source {
+        Source::Git { url, .. } | Source::Url { url } => url.clone(),
+        Source::Path { path } => path.clone(),
+        Source::Registry { .. } | Source::Workspace => dependency.name.clone(),
+      };
+      dependency.constraint = dependency
+        .version
+        .as_deref()
+        .and_then(|version| Constraint::parse(version).ok());
+    }
+    if let (Some(registry), Source::Registry { registry: None }) =
+      (&self.set.registry, &dependency.source)
+    {
+      dependency.url = registry.replace("{name}", &dependency.name);
+    }
+  }
+
+  /// Manifest these rules read, when it is not theirs by name or path but
+  /// they find dependencies in it
+  #[must_use]
+  pub fn recognize(
+    &self,
+    source: &str,
+    language: Option<&str>,
+    path: Option<&str>,
+  ) -> Option<ManifestOutput> {
+    let (manifest, _) = self.extract(source, language, path).ok()?;
+    (!manifest.dependencies.is_empty()).then_some(manifest)
+  }
+
+  /// Read every sample, reporting how each fared and the rules that found
+  /// nothing in any of them
+  #[must_use]
+  pub fn check(&self) -> (Vec<SampleReport>, Vec<String>) {
+    let mut used = vec![false; self.compiled.len()];
+    let reports = self
+      .set
+      .samples
+      .iter()
+      .enumerate()
+      .map(|(index, sample)| {
+        let name = sample
+          .path
+          .clone()
+          .unwrap_or_else(|| format!("sample {}", index + 1));
+        match self.extract(&sample.source_code, None, sample.path.as_deref()) {
+          Err(error) => SampleReport {
+            sample: name,
+            dependencies: 0,
+            problems: vec![error.message],
+          },
+          Ok((manifest, found)) => {
+            for (used, found) in used.iter_mut().zip(found) {
+              *used |= found;
+            }
+            SampleReport {
+              sample: name,
+              dependencies: manifest.dependencies.len(),
+              problems: compare(&sample.expect, &manifest.dependencies),
+            }
+          }
+        }
+      })
+      .collect();
+    let unused = if self.set.samples.is_empty() {
+      Vec::new()
+    } else {
+      self
+        .compiled
+        .iter()
+        .zip(used)
+        .filter(|(_, used)| !used)
+        .map(|(rule, _)| rule.rule.path.clone())
+        .collect()
+    };
+    (reports, unused)
+  }
+
+  /// Manifest that does not follow its rules
+  fn invalid(&self, span: Span, message: impl AsRef<str>) -> GudError {
+    GudError::new(
+      ErrorKind::Parsing,
+      "invalid-manifest",
+      format!(
+        "Invalid {} manifest at line {}, column {}: {}",
+        self.set.name,
+        span.start.line,
+        span.start.column,
+        message.as_ref()
+      ),
+    )
+    .with_context(json!({
+      "rules": self.set.name,
+      "line": span.start.line,
+      "column": span.start.column,
+    }))
+  }
+}
+
+/// Differences between what a sample expects and what was read from it
+fn compare(expected: &[Expected], dependencies: &[Dependency]) -> Vec<String> {
+  if expected.is_empty() {
+    return Vec::new();
+  }
+  let mut problems = Vec::new();
+  for expected in expected {
+    let Some(found) = dependencies
+      .iter()
+      .find(|dependency| dependency.name == expected.name)
+    else {
+      problems.push(format!("'{}' was not read", expected.name));
+      continue;
+    };
+    if expected.version.is_some() && found.version != expected.version {
+      problems.push(format!(
+        "'{}' has version {:?}, expected {:?}",
+        found.name,
+        found.version.as_deref().unwrap_or_default(),
+        expected.version.as_deref().unwrap_or_default()
+      ));
+    }
+    if let Some(kind) = expected.kind.filter(|kind| *kind != found.kind) {
+      problems.push(format!(
+        "'{}' is a {} dependency, expected {}",
+        found.name,
+        kind_name(found.kind),
+        kind_name(kind)
+      ));
+    }
+  }
+  for dependency in dependencies {
+    if !expected
+      .iter()
+      .any(|expected| expected.name == dependency.name)
+    {
+      problems.push(format!("'{}' was read but not expected", dependency.name));
+    }
+  }
+  problems
+}
+
+/// Output of `ast/check_rules`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CheckRulesOutput {
+  /// Name of the format the rules read
+  pub name: String,
+  /// Number of rules in the file
+  pub rules: usize,
+  /// One report per sample, in order
+  pub samples: Vec<SampleReport>,
+  /// Paths of rules that found nothing in any sample
+  pub unused: Vec<String>,
+  /// Whether every sample was read as expected, so that `to_manifest`
+  /// accepts the file
+  pub valid: bool,
+}
+
+/// Check a rule file against its samples
+#[allow(clippy::needless_pass_by_value)]
+pub fn check_rules(file: RuleFile) -> Result<CheckRulesOutput, Box<dyn std::error::Error>> {
+  let rules = Rules::parse(&file)?;
+  log::debug(&format!(
+    "Checking rule set '{}' against {} samples",
+    rules.name(),
+    rules.set.samples.len()
+  ));
+  let (samples, unused) = rules.check();
+  Ok(CheckRulesOutput {
+    name: rules.set.name.clone(),
+    rules: rules.compiled.len(),
+    valid: samples.iter().all(|sample| sample.problems.is_empty()),
+    samples,
+    unused,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::to_manifest::tests::summary;
+
+  const ACME: &str = r#"
+name = "acme"
+files = ["*.acme.yaml"]
+ecosystem = "npm"
+
+[package]
+name = "project.id"
+version = "project.rev"
+
+[[rules]]
+path = "modules.*.needs"
+pattern = '^(?P<name>[\w./@-]+)(?:\s+(?P<version>[^\s]+))?(?:\s+\[(?P<kind>\w+)\])?$'
+kind = "build"
+kinds = { test = "dev" }
+
+[[rules]]
+path = "vendored"
+fields = { url = "from", version = "at" }
+
+[[samples]]
+path = "app.acme.yaml"
+source_code = "modules:\n  web:\n    needs:\n      - lodash ^4.17.0\n"
+expect = [{ name = "lodash", version = "^4.17.0", kind = "build" }]
+"#;
+
+  fn rule_file(source: &str) -> RuleFile {
+    RuleFile {
+      source_code: source.to_string(),
+      language: Some("toml".to_string()),
+      path: None,
+    }
+  }
+
+  #[test]
+  fn reads_manifests_by_their_rules() {
+    let rules = Rules::load(&rule_file(ACME)).unwrap();
+    assert!(rules.claims(None, Some("svc/Web.ACME.yaml")));
+    assert!(rules.claims(Some("acme"), None));
+    assert!(!rules.claims(Some("yaml"), Some("web.yaml")));
+
+    let (manifest, used) = rules
+      .extract(
+        "project:\n  id: shop\n  rev: 2\nmodules:\n  web:\n    needs:\n      - react ^18.2.0\n      - vitest 1.6.0 [test]\n  cli:\n    needs:\n      - chalk\nvendored:\n  ui-kit:\n    from: git+https://git.acme.dev/ui-kit.git#v3\n    at: 3.1.0\n",
+        None,
+        Some("shop.acme.yaml"),
+      )
+      .unwrap();
+    assert_eq!(used, [true, true]);
+    assert_eq!(manifest.rules.as_deref(), Some("acme"));
+    assert_eq!(manifest.name.as_deref(), Some("shop"));
+    assert_eq!(manifest.version.as_deref(), Some("2"));
+    assert_eq!(
+      summary(&manifest.dependencies),
+      json!([
+        {"name": "react", "version": "^18.2.0", "kind": "build", "group": "web", "source": {"type": "registry"}},
+        {"name": "vitest", "version": "1.6.0", "kind": "dev", "group": "web", "source": {"type": "registry"}},
+        {"name": "chalk", "kind": "build", "group": "cli", "source": {"type": "registry"}},
+        {"name": "ui-kit", "version": "3.1.0", "kind": "normal",
+         "source": {"type": "git", "url": "https://git.acme.dev/ui-kit.git", "reference": "v3"}},
+      ])
+    );
+    assert_eq!(
+      manifest.dependencies[0].url,
+      "https://registry.npmjs.org/react"
+    );
+    assert!(manifest.dependencies[0].constraint.is_some());
+    assert!(manifest.deps_file.dev_dependencies.contains_key("vitest"));
+  }
+
+  #[test]
+  fn checks_rules_against_their_samples() {
+    let broken = ACME.replace("kind = \"build\"\nkinds", "kind = \"peer\"\nkinds");
+    let output = check_rules(rule_file(&broken)).unwrap();
+    assert!(!output.valid);
+    assert_eq!(output.samples[0].dependencies, 1);
+    assert_eq!(
+      output.samples[0].problems,
+      ["'lodash' is a peer dependency, expected build"]
+    );
+    assert_eq!(output.unused, ["vendored"]);
+
+    let error = Rules::load(&rule_file(&broken)).err().unwrap();
+    assert_eq!(error.code, "invalid-rules");
+    assert_eq!(
+      error.context["problems"][0],
+      "app.acme.yaml: 'lodash' is a peer dependency, expected build"
+    );
+  }
+
+  #[test]
+  fn rejects_unusable_rule_files() {
+    let code = |source: &str| Rules::parse(&rule_file(source)).err().unwrap().code;
+    assert_eq!(code("name = \"x\"\nrules = []"), "invalid-rules");
+    assert_eq!(
+      code("name = \"x\"\n[[rules]]\npath = \"deps\"\npattern = '(?P<nam>.*)'"),
+      "invalid-rules"
+    );
+    assert_eq!(
+      code("name = \"x\"\n[[rules]]\npath = \"deps\"\npattern = '(unclosed'"),
+      "invalid-rules"
+    );
+    assert_eq!(
+      code("name = \"x\"\nformat = \"csv\"\n[[rules]]\npath = \"\""),
+      "invalid-rules"
+    );
+    assert_eq!(
+      code("name = \"x\"\n[[rules]]\npaths = \"deps\""),
+      "invalid-rules"
+    );
+  }
+}
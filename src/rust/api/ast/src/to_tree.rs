@@ -0,0 +1,425 @@
+//! `ast/to_tree`: source code and data files parsed into a generic syntax
+//! tree.
+
+use gud_common::{log, ErrorKind, GudError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Instant;
+use tree_sitter::{Language, Node, Parser, TreeCursor};
+
+use crate::data::{DataNode, Format, FORMATS};
+
+/// Input of `ast/to_tree`
+#[derive(Deserialize, JsonSchema)]
+pub struct ParseInput {
+  /// Text to parse
+  pub source_code: String,
+  /// Programming language or data format, by name or file extension
+  pub language: String,
+  /// Whether to keep comment nodes; off by default
+  pub include_comments: Option<bool>,
+}
+
+/// Output of `ast/to_tree`
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct AstTree {
+  /// Root of the generic tree
+  pub root: AstNode,
+  /// Typed tree of data formats such as JSON or TOML; `root` is its generic
+  /// view
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub data: Option<DataNode>,
+  /// Facts about the parse
+  pub metadata: TreeMetadata,
+}
+
+/// Node of the generic tree
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AstNode {
+  /// Grammar rule or data type of the node, such as `call_expression` or
+  /// `map`
+  pub node_type: String,
+  /// Source text of leaf nodes; `None` for nodes with children
+  pub value: Option<String>,
+  /// Child nodes in source order
+  #[serde(default)]
+  pub children: Vec<Self>,
+  /// Where the node starts
+  #[serde(default)]
+  pub location: Location,
+}
+
+/// Start of a node: 1-based line and character column, 0-based byte offset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Location {
+  /// Line number, from 1
+  pub line: usize,
+  /// Character within the line, from 1
+  pub column: usize,
+  /// Bytes before the node, from 0
+  pub byte_offset: usize,
+}
+
+/// Facts about a parse
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TreeMetadata {
+  /// `language` as given
+  pub language: String,
+  /// Number of nodes in `root`
+  pub total_nodes: usize,
+  /// Time spent parsing and converting
+  pub parse_time_ms: f64,
+  /// Whether the parser had to recover from syntax errors, which show up as
+  /// `ERROR` nodes in the tree
+  pub has_errors: bool,
+}
+
+/// Deepest syntax tree [`parse_to_ast_tree`] converts.
+///
+/// Grammars nest a node per operator, so long chains such as `a + b + …`
+/// count against it too. Serializing the result still recurses, so the limit
+/// leaves room on the 2 MiB stack of a `--serve` worker thread.
+pub const MAX_TREE_DEPTH: usize = 512;
+
+/// Languages accepted by [`parse_to_ast_tree`], by their canonical names
+pub const LANGUAGES: &[&str] = &["typescript", "tsx", "javascript", "rust", "python", "bash"];
+
+/// Tree-sitter grammar for a language name or common file extension
+fn grammar(language: &str) -> Option<Language> {
+  match language.to_ascii_lowercase().as_str() {
+    "typescript" | "ts" => Some(tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into()),
+    // The TSX grammar is a superset of JavaScript, including JSX
+    "tsx" | "javascript" | "js" | "jsx" => Some(tree_sitter_typescript::LANGUAGE_TSX.into()),
+    "rust" | "rs" => Some(tree_sitter_rust::LANGUAGE.into()),
+    "python" | "py" => Some(tree_sitter_python::LANGUAGE.into()),
+    "bash" | "sh" | "shell" => Some(tree_sitter_bash::LANGUAGE.into()),
+    _ => None,
+  }
+}
+
+fn is_comment(node: &Node) -> bool {
+  node.kind().ends_with("comment")
+}
+
+fn too_deep(line: usize, byte_offset: usize) -> GudError {
+  GudError::new(
+    ErrorKind::Parsing,
+    "tree-too-deep",
+    format!("The syntax tree is nested deeper than {MAX_TREE_DEPTH} levels"),
+  )
+  .with_context(json!({
+    "line": line,
+    "byte_offset": byte_offset,
+    "max_depth": MAX_TREE_DEPTH,
+  }))
+}
+
+/// A node nested deeper than [`MAX_TREE_DEPTH`], if any
+fn nested_too_deep(root: &AstNode) -> Option<&AstNode> {
+  let mut stack = vec![(root, 0)];
+  while let Some((node, depth)) = stack.pop() {
+    if depth > MAX_TREE_DEPTH {
+      return Some(node);
+    }
+    stack.extend(node.children.iter().map(|child| (child, depth + 1)));
+  }
+  None
+}
+
+/// Move the cursor from its current node to the next sibling that is kept,
+/// staying put when there is none
+fn next_kept(cursor: &mut TreeCursor, keep: impl Fn(&Node) -> bool) -> bool {
+  while cursor.goto_next_sibling() {
+    if keep(&cursor.node()) {
+      return true;
+    }
+  }
+  false
+}
+
+/// Move the cursor to the first kept child of its current node, staying put
+/// when there is none
+fn first_kept_child(cursor: &mut TreeCursor, keep: impl Fn(&Node) -> bool) -> bool {
+  if !cursor.goto_first_child() {
+    return false;
+  }
+  if keep(&cursor.node()) || next_kept(cursor, &keep) {
+    return true;
+  }
+  cursor.goto_parent();
+  false
+}
+
+fn ast_node(node: &Node, source: &str, children: Vec<AstNode>) -> AstNode {
+  let start = node.start_byte();
+  let position = node.start_position();
+  let line_start = start - position.column;
+  AstNode {
+    node_type: node.kind().to_string(),
+    value: if node.named_child_count() == 0 {
+      source.get(start..node.end_byte()).map(str::to_string)
+    } else {
+      None
+    },
+    children,
+    location: Location {
+      line: position.row + 1,
+      column: source
+        .get(line_start..start)
+        .map_or(position.column, |prefix| prefix.chars().count())
+        + 1,
+      byte_offset: start,
+    },
+  }
+}
+
+/// Convert the named nodes below `root`; anonymous tokens such as
+/// punctuation and keywords are left out. The walk keeps its own stack, so
+/// only [`MAX_TREE_DEPTH`] bounds how deep a tree may be.
+fn convert(
+  root: &Node,
+  source: &str,
+  include_comments: bool,
+  total: &mut usize,
+) -> Result<AstNode, GudError> {
+  let keep = |node: &Node| node.is_named() && (include_comments || !is_comment(node));
+  let mut cursor = root.walk();
+  // Children converted so far of each node from the root down to the cursor
+  let mut open: Vec<Vec<AstNode>> = vec![Vec::new()];
+  loop {
+    if first_kept_child(&mut cursor, keep) {
+      if open.len() > MAX_TREE_DEPTH {
+        let node = cursor.node();
+        return Err(too_deep(node.start_position().row + 1, node.start_byte()));
+      }
+      open.push(Vec::new());
+      continue;
+    }
+
+    // The cursor's node has no children left, so close it and every
+    // ancestor whose last child it was
+    loop {
+      let children = open.pop().unwrap_or_default();
+      let node = ast_node(&cursor.node(), source, children);
+      *total += 1;
+      let Some(siblings) = open.last_mut() else {
+        return Ok(node);
+      };
+      siblings.push(node);
+      if next_kept(&mut cursor, keep) {
+        open.push(Vec::new());
+        break;
+      }
+      cursor.goto_parent();
+    }
+  }
+}
+
+fn parse_data(input: ParseInput, format: Format) -> Result<AstTree, Box<dyn std::error::Error>> {
+  let started = Instant::now();
+  let data = format.parse(&input.source_code)?;
+  let root = data.to_ast();
+  // Every map level of the data is two levels of the tree, a map and an entry
+  if let Some(node) = nested_too_deep(&root) {
+    return Err(too_deep(node.location.line, node.location.byte_offset).into());
+  }
+  let parse_time_ms = started.elapsed().as_secs_f64() * 1000.0;
+  Ok(AstTree {
+    metadata: TreeMetadata {
+      language: input.language,
+      total_nodes: count(&root),
+      parse_time_ms,
+      has_errors: false,
+    },
+    root,
+    data: Some(data),
+  })
+}
+
+fn count(node: &AstNode) -> usize {
+  1 + node.children.iter().map(count).sum::<usize>()
+}
+
+/// Parse source code with the tree-sitter grammar for `language`, or a data
+/// file into the intermediate tree when `language` names a data format.
+/// Comments are never kept for data formats.
+pub fn parse_to_ast_tree(input: ParseInput) -> Result<AstTree, Box<dyn std::error::Error>> {
+  let include_comments = input.include_comments.unwrap_or(false);
+
+  log::debug(&format!(
+    "Parsing {} code ({} chars) | Include comments: {}",
+    input.language,
+    input.source_code.len(),
+    include_comments
+  ));
+
+  if let Some(format) = Format::from_name(&input.language) {
+    return parse_data(input, format);
+  }
+
+  let language = grammar(&input.language).ok_or_else(|| {
+    GudError::new(
+      ErrorKind::Argument,
+      "unsupported-language",
+      format!("Cannot parse {} code", input.language),
+    )
+    .with_context(json!({
+      "language": input.language,
+      "supported": LANGUAGES.iter().chain(FORMATS).collect::<Vec<_>>(),
+    }))
+  })?;
+
+  let started = Instant::now();
+  let mut parser = Parser::new();
+  parser.set_language(&language)?;
+  let tree = parser.parse(&input.source_code, None).ok_or_else(|| {
+    GudError::new(
+      ErrorKind::Parsing,
+      "parse-failed",
+      format!("The {} parser gave up", input.language),
+    )
+  })?;
+
+  let mut total_nodes = 0;
+  let root = convert(
+    &tree.root_node(),
+    &input.source_code,
+    include_comments,
+    &mut total_nodes,
+  )?;
+  let parsed = AstTree {
+    root,
+    data: None,
+    metadata: TreeMetadata {
+      language: input.language,
+      total_nodes,
+      parse_time_ms: started.elapsed().as_secs_f64() * 1000.0,
+      has_errors: tree.root_node().has_error(),
+    },
+  };
+
+  log::debug(&format!(
+    "Successfully parsed into {} nodes{}",
+    parsed.metadata.total_nodes,
+    if include_comments {
+      " (including comments)"
+    } else {
+      ""
+    }
+  ));
+  Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(language: &str, source: &str, include_comments: bool) -> AstTree {
+    parse_to_ast_tree(ParseInput {
+      source_code: source.to_string(),
+      language: language.to_string(),
+      include_comments: Some(include_comments),
+    })
+    .unwrap()
+  }
+
+  fn kinds(node: &AstNode) -> Vec<&str> {
+    node.children.iter().map(|n| n.node_type.as_str()).collect()
+  }
+
+  #[test]
+  fn parses_real_nodes_with_positions() {
+    let tree = parse("rust", "// entry\nfn main() {\n  let x = 1;\n}\n", false);
+    assert_eq!(tree.root.node_type, "source_file");
+    assert_eq!(kinds(&tree.root), ["function_item"]);
+
+    let function = &tree.root.children[0];
+    assert_eq!(function.location.line, 2);
+    assert_eq!(function.location.byte_offset, 9);
+    assert_eq!(function.children[0].node_type, "identifier");
+    assert_eq!(function.children[0].value.as_deref(), Some("main"));
+    assert!(!tree.metadata.has_errors);
+  }
+
+  #[test]
+  fn comments_are_optional() {
+    let source = "# setup\nimport os  # stdlib\n";
+    let without = parse("python", source, false);
+    let with = parse("py", source, true);
+    assert_eq!(kinds(&without.root), ["import_statement"]);
+    assert_eq!(
+      kinds(&with.root),
+      ["comment", "import_statement", "comment"]
+    );
+    assert_eq!(with.metadata.total_nodes, without.metadata.total_nodes + 2);
+  }
+
+  #[test]
+  fn columns_count_characters() {
+    let tree = parse("ts", "const s = 'é'; let t = 1;", false);
+    let second = &tree.root.children[1];
+    assert_eq!(second.location.column, 16);
+    assert_eq!(second.location.byte_offset, 16);
+  }
+
+  #[test]
+  fn parses_data_formats() {
+    let tree = parse("toml", "[deps]\nzlib = \"1.2\"\n", true);
+    assert_eq!(tree.root.node_type, "map");
+    assert_eq!(tree.metadata.total_nodes, 5);
+    let data = tree.data.unwrap();
+    assert_eq!(
+      data.get("deps").unwrap().get("zlib").unwrap().as_str(),
+      Some("1.2")
+    );
+  }
+
+  #[test]
+  fn reports_syntax_errors_and_unknown_languages() {
+    assert!(parse("bash", "if then fi fi", false).metadata.has_errors);
+
+    let error = parse_to_ast_tree(ParseInput {
+      source_code: String::new(),
+      language: "cobol".to_string(),
+      include_comments: None,
+    })
+    .map_err(GudError::from_boxed)
+    .unwrap_err();
+    assert_eq!(error.code, "unsupported-language");
+  }
+
+  #[test]
+  fn rejects_trees_nested_too_deeply() {
+    let depth = 20_000;
+    let error = parse_to_ast_tree(ParseInput {
+      source_code: format!("x = {}{}\n", "[".repeat(depth), "]".repeat(depth)),
+      language: "python".to_string(),
+      include_comments: None,
+    })
+    .map_err(GudError::from_boxed)
+    .unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Parsing);
+    assert_eq!(error.code, "tree-too-deep");
+
+    // Trees just below the limit still convert and serialize
+    let depth = MAX_TREE_DEPTH - 2;
+    let source = format!("x = {}{}\n", "[".repeat(depth), "]".repeat(depth));
+    let tree = parse("python", &source, false);
+    assert!(!tree.metadata.has_errors);
+    assert!(serde_json::to_string(&tree).is_ok());
+  }
+
+  #[test]
+  fn finds_data_trees_nested_too_deeply() {
+    let node = |children| AstNode {
+      node_type: "list".to_string(),
+      value: None,
+      children,
+      location: Location::default(),
+    };
+    let chain = |depth: usize| (0..depth).fold(node(Vec::new()), |child, _| node(vec![child]));
+    assert!(nested_too_deep(&chain(MAX_TREE_DEPTH)).is_none());
+    assert!(nested_too_deep(&chain(MAX_TREE_DEPTH + 1)).is_some());
+  }
+}
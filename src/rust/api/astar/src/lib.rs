@@ -2,3 +2,12 @@
 //!
 //! This crate provides pathfinding functionality including A* search
 //! algorithms and related utilities for navigation and optimization.
+
+use gud_common::Registry;
+
+/// Name under which this crate's APIs are registered
+pub const MODULE: &str = "astar";
+
+/// Register every `astar` API with the given registry. The search APIs are
+/// not implemented yet, so there is nothing to register.
+pub const fn register(_registry: &mut Registry) {}
@@ -0,0 +1,408 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use crate::ipc::IpcError;
+
+/// Environment variable selecting the reply channel, e.g. `fd:3`,
+/// `pipe:/tmp/gud.fifo`, `socket:/tmp/gud.sock` or `stdout`
+pub const CHANNEL_ENV_VAR: &str = "GUD_IPC_CHANNEL";
+
+/// Marker that starts every frame written to a `stdout` channel.
+///
+/// A frame is the marker, the payload length in bytes and a newline, followed
+/// by the payload and a closing newline: `\x1egud-frame 11\n{"ok":true}\n`.
+pub const FRAME_MARKER: &str = "\x1egud-frame ";
+
+/// Where replies are written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelSpec {
+  /// An inherited file descriptor (Unix only)
+  Fd(i32),
+  /// A path opened for writing, such as a FIFO or a Windows named pipe
+  Pipe(PathBuf),
+  /// A Unix domain socket to connect to (Unix only)
+  Socket(PathBuf),
+  /// Stdout, with each flushed message wrapped in a frame so it can be told
+  /// apart from anything else printed there
+  Stdout,
+}
+
+impl Default for ChannelSpec {
+  #[cfg(unix)]
+  fn default() -> Self {
+    Self::Fd(3)
+  }
+
+  #[cfg(not(unix))]
+  fn default() -> Self {
+    Self::Stdout
+  }
+}
+
+impl ChannelSpec {
+  /// Parse a channel description as accepted by [`CHANNEL_ENV_VAR`]
+  pub fn parse(spec: &str) -> Result<Self, IpcError> {
+    let invalid = |reason: &str| {
+      IpcError::InvalidInput(format!(
+        "Invalid {CHANNEL_ENV_VAR} value '{spec}': {reason}"
+      ))
+    };
+
+    match spec.split_once(':') {
+      _ if spec == "stdout" => Ok(Self::Stdout),
+      Some(("fd", fd)) => match fd.parse() {
+        Ok(fd) if fd >= 0 => Ok(Self::Fd(fd)),
+        _ => Err(invalid("expected a non-negative file descriptor number")),
+      },
+      Some(("pipe", path)) if !path.is_empty() => Ok(Self::Pipe(path.into())),
+      Some(("socket", path)) if !path.is_empty() => Ok(Self::Socket(path.into())),
+      _ => Err(invalid(
+        "expected fd:<number>, pipe:<path>, socket:<path> or stdout",
+      )),
+    }
+  }
+
+  /// Read the channel from [`CHANNEL_ENV_VAR`], falling back to the platform
+  /// default (fd 3 on Unix, framed stdout elsewhere)
+  pub fn from_env() -> Result<Self, IpcError> {
+    std::env::var(CHANNEL_ENV_VAR).map_or_else(|_| Ok(Self::default()), |spec| Self::parse(&spec))
+  }
+
+  /// Open the channel, failing with a descriptive error when it is unusable
+  pub fn open(&self) -> Result<Box<dyn Write + Send>, IpcError> {
+    match self {
+      Self::Fd(fd) => open_fd(*fd),
+      Self::Pipe(path) => {
+        if !path.exists() {
+          return Err(IpcError::InvalidInput(format!(
+            "Reply pipe {} does not exist",
+            path.display()
+          )));
+        }
+        Ok(Box::new(
+          std::fs::OpenOptions::new().write(true).open(path)?,
+        ))
+      }
+      Self::Socket(path) => open_socket(path),
+      Self::Stdout => Ok(Box::new(FramedWriter::new(StdoutWriter))),
+    }
+  }
+}
+
+#[cfg(unix)]
+fn open_fd(fd: i32) -> Result<Box<dyn Write + Send>, IpcError> {
+  use std::os::fd::FromRawFd;
+
+  // Probe the descriptor before taking ownership of it, since wrapping a
+  // closed or invalid one in a `File` is undefined behavior
+  if fd < 0 || unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+    let e = io::Error::last_os_error();
+    return Err(IpcError::InvalidInput(format!(
+      "Reply descriptor {fd} is not open ({e}); start the process with fd {fd} \
+       attached or set {CHANNEL_ENV_VAR}"
+    )));
+  }
+
+  // The descriptor is inherited, so it must stay open when the writer drops
+  let file = std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
+  let metadata = file
+    .metadata()
+    .map_err(|e| IpcError::InvalidInput(format!("Reply descriptor {fd} is unusable ({e})")))?;
+
+  if metadata.is_dir() {
+    return Err(IpcError::InvalidInput(format!(
+      "Reply descriptor {fd} refers to a directory"
+    )));
+  }
+
+  Ok(Box::new(InheritedFd(file)))
+}
+
+#[cfg(not(unix))]
+fn open_fd(fd: i32) -> Result<Box<dyn Write + Send>, IpcError> {
+  Err(IpcError::InvalidInput(format!(
+    "Reply descriptor {fd} cannot be used on this platform; set {CHANNEL_ENV_VAR} to pipe:<path> \
+     or stdout"
+  )))
+}
+
+#[cfg(unix)]
+fn open_socket(path: &std::path::Path) -> Result<Box<dyn Write + Send>, IpcError> {
+  let stream = std::os::unix::net::UnixStream::connect(path).map_err(|e| {
+    IpcError::InvalidInput(format!(
+      "Cannot connect to reply socket {}: {e}",
+      path.display()
+    ))
+  })?;
+  Ok(Box::new(stream))
+}
+
+#[cfg(not(unix))]
+fn open_socket(path: &std::path::Path) -> Result<Box<dyn Write + Send>, IpcError> {
+  Err(IpcError::InvalidInput(format!(
+    "Reply socket {} cannot be used on this platform; use pipe:<path> instead",
+    path.display()
+  )))
+}
+
+#[cfg(unix)]
+struct InheritedFd(std::mem::ManuallyDrop<std::fs::File>);
+
+#[cfg(unix)]
+impl Write for InheritedFd {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.0.flush()
+  }
+}
+
+struct StdoutWriter;
+
+impl Write for StdoutWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    io::stdout().write(buf)
+  }
+
+  fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+    io::stdout().write_all(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    io::stdout().flush()
+  }
+}
+
+/// Writer that collects everything written between flushes and emits it as a
+/// single [`FRAME_MARKER`] frame
+pub struct FramedWriter<W: Write> {
+  inner: W,
+  pending: Vec<u8>,
+}
+
+impl<W: Write> FramedWriter<W> {
+  /// Frame everything written to `inner`
+  pub const fn new(inner: W) -> Self {
+    Self {
+      inner,
+      pending: Vec::new(),
+    }
+  }
+}
+
+impl<W: Write> Write for FramedWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.pending.extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    if self.pending.is_empty() {
+      return self.inner.flush();
+    }
+
+    let mut frame = format!("{FRAME_MARKER}{}\n", self.pending.len()).into_bytes();
+    frame.append(&mut self.pending);
+    frame.push(b'\n');
+    self.inner.write_all(&frame)?;
+    self.inner.flush()
+  }
+}
+
+impl<W: Write> Drop for FramedWriter<W> {
+  fn drop(&mut self) {
+    let _ = self.flush();
+  }
+}
+
+type SharedChannel = Mutex<Box<dyn Write + Send>>;
+
+static CHANNEL: OnceLock<Result<SharedChannel, String>> = OnceLock::new();
+
+fn shared_channel() -> Result<&'static SharedChannel, IpcError> {
+  CHANNEL
+    .get_or_init(|| {
+      ChannelSpec::from_env()
+        .and_then(|spec| spec.open())
+        .map(Mutex::new)
+        .map_err(|e| match e {
+          IpcError::InvalidInput(message) => message,
+          other => other.to_string(),
+        })
+    })
+    .as_ref()
+    .map_err(|e| IpcError::InvalidInput(e.clone()))
+}
+
+/// Resolve and open the reply channel configured for this process.
+///
+/// Called before a handler runs so a misconfigured channel is reported on
+/// stderr up front, since it cannot be reported on the channel itself.
+pub fn validate_channel() -> Result<(), IpcError> {
+  shared_channel().map(|_| ())
+}
+
+/// Writer for this process's reply channel. Every writer shares the channel
+/// opened on first use; writes are serialized through a lock.
+pub struct ChannelWriter;
+
+/// Get a writer for the reply channel
+#[must_use]
+pub const fn channel_writer() -> ChannelWriter {
+  ChannelWriter
+}
+
+impl Write for ChannelWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let channel = shared_channel().map_err(io::Error::other)?;
+    let mut writer = channel
+      .lock()
+      .map_err(|e| io::Error::other(e.to_string()))?;
+    writer.write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    let channel = shared_channel().map_err(io::Error::other)?;
+    let mut writer = channel
+      .lock()
+      .map_err(|e| io::Error::other(e.to_string()))?;
+    writer.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Read;
+
+  #[test]
+  fn parses_every_channel_kind() {
+    assert_eq!(ChannelSpec::parse("fd:4").unwrap(), ChannelSpec::Fd(4));
+    assert_eq!(
+      ChannelSpec::parse("pipe:/tmp/reply").unwrap(),
+      ChannelSpec::Pipe("/tmp/reply".into())
+    );
+    assert_eq!(
+      ChannelSpec::parse("socket:/tmp/reply.sock").unwrap(),
+      ChannelSpec::Socket("/tmp/reply.sock".into())
+    );
+    assert_eq!(ChannelSpec::parse("stdout").unwrap(), ChannelSpec::Stdout);
+  }
+
+  #[test]
+  fn rejects_malformed_channels() {
+    for spec in ["fd:three", "fd:-1", "pipe:", "carrier-pigeon", ""] {
+      let error = ChannelSpec::parse(spec).unwrap_err().to_string();
+      assert!(error.contains(CHANNEL_ENV_VAR), "{error}");
+    }
+  }
+
+  #[test]
+  fn frames_each_flushed_message() {
+    let mut output = Vec::new();
+    {
+      let mut writer = FramedWriter::new(&mut output);
+      writer.write_all(b"{\"ok\":").unwrap();
+      writer.write_all(b"true}").unwrap();
+      writer.flush().unwrap();
+      writer.write_all(b"1").unwrap();
+    }
+
+    assert_eq!(
+      String::from_utf8(output).unwrap(),
+      "\u{1e}gud-frame 11\n{\"ok\":true}\n\u{1e}gud-frame 1\n1\n"
+    );
+  }
+
+  #[test]
+  fn reports_missing_pipes() {
+    let dir = tempfile::tempdir().unwrap();
+    let spec = ChannelSpec::Pipe(dir.path().join("missing"));
+    assert!(spec.open().is_err());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn reports_closed_and_invalid_descriptors() {
+    for fd in [-1, i32::MAX] {
+      let error = ChannelSpec::Fd(fd).open().err().unwrap().to_string();
+      assert!(error.contains("is not open"), "{error}");
+    }
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn writes_to_inherited_descriptors() {
+    use std::os::fd::AsRawFd;
+
+    let mut file = tempfile::tempfile().unwrap();
+    let mut writer = ChannelSpec::Fd(file.as_raw_fd()).open().unwrap();
+    writer.write_all(b"reply").unwrap();
+    writer.flush().unwrap();
+    drop(writer);
+
+    let mut contents = String::new();
+    std::io::Seek::rewind(&mut file).unwrap();
+    file.read_to_string(&mut contents).unwrap();
+    assert_eq!(contents, "reply");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn writes_to_fifos() {
+    let dir = tempfile::tempdir().unwrap();
+    let fifo = dir.path().join("reply.fifo");
+    let status = std::process::Command::new("mkfifo")
+      .arg(&fifo)
+      .status()
+      .unwrap();
+    assert!(status.success());
+
+    let reader = {
+      let fifo = fifo.clone();
+      std::thread::spawn(move || std::fs::read_to_string(fifo).unwrap())
+    };
+
+    let mut writer = ChannelSpec::Pipe(fifo).open().unwrap();
+    writer.write_all(b"through the pipe").unwrap();
+    drop(writer);
+
+    assert_eq!(reader.join().unwrap(), "through the pipe");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn writes_to_unix_sockets() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("reply.sock");
+    let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+
+    let reader = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut contents = String::new();
+      stream.read_to_string(&mut contents).unwrap();
+      contents
+    });
+
+    let mut writer = ChannelSpec::Socket(path).open().unwrap();
+    writer.write_all(b"over the socket").unwrap();
+    drop(writer);
+
+    assert_eq!(reader.join().unwrap(), "over the socket");
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn reports_unreachable_sockets() {
+    let dir = tempfile::tempdir().unwrap();
+    let error = ChannelSpec::Socket(dir.path().join("nobody.sock"))
+      .open()
+      .err()
+      .unwrap()
+      .to_string();
+    assert!(error.contains("Cannot connect"), "{error}");
+  }
+}
@@ -0,0 +1,368 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+use std::marker::PhantomData;
+
+use crate::ipc::IpcError;
+
+/// Prefix of the optional header line that negotiates the payload encoding,
+/// e.g. `%gud encoding=msgpack`
+pub const HEADER_PREFIX: &str = "%gud";
+
+/// Wire encoding of request and response payloads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+  /// UTF-8 JSON; records are newline-delimited (NDJSON)
+  #[default]
+  Json,
+  /// `MessagePack` with named struct fields; records are self-delimiting
+  MessagePack,
+}
+
+impl Encoding {
+  /// Parse the name used in a header line
+  #[must_use]
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "json" => Some(Self::Json),
+      "msgpack" => Some(Self::MessagePack),
+      _ => None,
+    }
+  }
+
+  /// Name used in a header line
+  #[must_use]
+  pub const fn name(self) -> &'static str {
+    match self {
+      Self::Json => "json",
+      Self::MessagePack => "msgpack",
+    }
+  }
+}
+
+fn msgpack_error(error: impl std::fmt::Display) -> IpcError {
+  IpcError::EncodingError(error.to_string())
+}
+
+/// Options negotiated by the header line at the start of a request stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Header {
+  /// Encoding of the request and response payloads
+  pub encoding: Encoding,
+  /// Milliseconds the caller is willing to wait for a one-shot request
+  pub deadline_ms: Option<u64>,
+  /// Whether a one-shot request wants progress and partial-result events
+  /// written ahead of its response
+  pub events: bool,
+}
+
+/// Consume the optional header line at the start of a request stream, e.g.
+/// `%gud encoding=msgpack deadline_ms=5000 events=true`.
+///
+/// Returns `None` when the stream does not start with [`HEADER_PREFIX`], in
+/// which case nothing is consumed and the payload is plain JSON.
+pub fn read_header<R: BufRead>(reader: &mut R) -> Result<Option<Header>, IpcError> {
+  if !reader.fill_buf()?.starts_with(HEADER_PREFIX.as_bytes()) {
+    return Ok(None);
+  }
+
+  let mut line = String::new();
+  reader.read_line(&mut line)?;
+
+  let mut header = Header::default();
+  for field in line[HEADER_PREFIX.len()..].split_whitespace() {
+    let malformed = || IpcError::InvalidInput(format!("Malformed header field: {field}"));
+    match field.split_once('=') {
+      Some(("encoding", name)) => {
+        header.encoding = Encoding::from_name(name).ok_or_else(|| {
+          IpcError::InvalidInput(format!("Unsupported encoding in header: {name}"))
+        })?;
+      }
+      Some(("deadline_ms", ms)) => header.deadline_ms = Some(ms.parse().map_err(|_| malformed())?),
+      Some(("events", events)) => header.events = events.parse().map_err(|_| malformed())?,
+      _ => return Err(malformed()),
+    }
+  }
+
+  Ok(Some(header))
+}
+
+/// Write a header line acknowledging the negotiated encoding
+pub fn write_header<W: Write>(writer: &mut W, encoding: Encoding) -> Result<(), IpcError> {
+  writeln!(writer, "{HEADER_PREFIX} encoding={}", encoding.name())?;
+  Ok(())
+}
+
+/// Skip leading ASCII whitespace, returning `false` once the stream is
+/// exhausted
+fn skip_whitespace<R: BufRead>(reader: &mut R) -> Result<bool, IpcError> {
+  loop {
+    let buffer = reader.fill_buf()?;
+    if buffer.is_empty() {
+      return Ok(false);
+    }
+
+    let skipped = buffer
+      .iter()
+      .take_while(|b| b.is_ascii_whitespace())
+      .count();
+    if skipped < buffer.len() {
+      reader.consume(skipped);
+      return Ok(true);
+    }
+
+    let len = buffer.len();
+    reader.consume(len);
+  }
+}
+
+/// Decode a whole payload straight from the reader without buffering it into
+/// a string first. Returns `None` when the stream holds no payload.
+pub fn read_value<T, R>(reader: &mut R, encoding: Encoding) -> Result<Option<T>, IpcError>
+where
+  T: for<'de> Deserialize<'de>,
+  R: BufRead,
+{
+  match encoding {
+    Encoding::Json => {
+      if !skip_whitespace(reader)? {
+        return Ok(None);
+      }
+
+      let mut deserializer = serde_json::Deserializer::from_reader(reader);
+      let value = T::deserialize(&mut deserializer)?;
+      deserializer.end()?;
+      Ok(Some(value))
+    }
+    Encoding::MessagePack => {
+      if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+      }
+
+      rmp_serde::from_read(reader)
+        .map(Some)
+        .map_err(msgpack_error)
+    }
+  }
+}
+
+/// Encode a whole payload straight into the writer
+pub fn write_value<T, W>(writer: &mut W, encoding: Encoding, value: &T) -> Result<(), IpcError>
+where
+  T: Serialize + ?Sized,
+  W: Write,
+{
+  match encoding {
+    Encoding::Json => serde_json::to_writer(writer, value)?,
+    Encoding::MessagePack => {
+      rmp_serde::encode::write_named(writer, value).map_err(msgpack_error)?;
+    }
+  }
+  Ok(())
+}
+
+/// Incremental reader over a stream of records: one JSON document per line
+/// (NDJSON), or consecutive `MessagePack` values.
+///
+/// A malformed JSON line is reported and reading continues with the next
+/// line; a malformed `MessagePack` value ends the stream since record
+/// boundaries can no longer be found.
+pub struct RecordReader<R, T> {
+  reader: R,
+  encoding: Encoding,
+  done: bool,
+  record: PhantomData<fn() -> T>,
+}
+
+impl<R: BufRead, T: for<'de> Deserialize<'de>> RecordReader<R, T> {
+  /// Read records of the given encoding from `reader`
+  pub const fn new(reader: R, encoding: Encoding) -> Self {
+    Self {
+      reader,
+      encoding,
+      done: false,
+      record: PhantomData,
+    }
+  }
+
+  fn next_json(&mut self) -> Option<Result<T, IpcError>> {
+    let mut line = String::new();
+    loop {
+      line.clear();
+      match self.reader.read_line(&mut line) {
+        Ok(0) => return None,
+        Ok(_) if line.trim().is_empty() => {}
+        Ok(_) => return Some(serde_json::from_str(&line).map_err(IpcError::from)),
+        Err(e) => {
+          self.done = true;
+          return Some(Err(e.into()));
+        }
+      }
+    }
+  }
+
+  fn next_msgpack(&mut self) -> Option<Result<T, IpcError>> {
+    match self.reader.fill_buf() {
+      Ok([]) => None,
+      Ok(_) => {
+        let record = rmp_serde::from_read(&mut self.reader).map_err(msgpack_error);
+        self.done = record.is_err();
+        Some(record)
+      }
+      Err(e) => {
+        self.done = true;
+        Some(Err(e.into()))
+      }
+    }
+  }
+}
+
+impl<R: BufRead, T: for<'de> Deserialize<'de>> Iterator for RecordReader<R, T> {
+  type Item = Result<T, IpcError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+
+    match self.encoding {
+      Encoding::Json => self.next_json(),
+      Encoding::MessagePack => self.next_msgpack(),
+    }
+  }
+}
+
+/// Incremental writer for a stream of records, flushing after each record so
+/// the reader sees it immediately
+pub struct RecordWriter<W> {
+  writer: W,
+  encoding: Encoding,
+}
+
+impl<W: Write> RecordWriter<W> {
+  /// Write records of the given encoding to `writer`
+  pub const fn new(writer: W, encoding: Encoding) -> Self {
+    Self { writer, encoding }
+  }
+
+  /// Write a header line acknowledging this writer's encoding
+  pub fn write_header(&mut self) -> Result<(), IpcError> {
+    write_header(&mut self.writer, self.encoding)?;
+    self.writer.flush()?;
+    Ok(())
+  }
+
+  /// Write and flush a single record
+  pub fn write<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), IpcError> {
+    write_value(&mut self.writer, self.encoding, record)?;
+    if self.encoding == Encoding::Json {
+      self.writer.write_all(b"\n")?;
+    }
+    self.writer.flush()?;
+    Ok(())
+  }
+
+  /// Recover the underlying writer
+  pub fn into_inner(self) -> W {
+    self.writer
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::{json, Value};
+  use std::io::Cursor;
+
+  #[test]
+  fn read_header_is_optional() {
+    let mut reader = Cursor::new(b"{\"a\": 1}".to_vec());
+    assert_eq!(read_header(&mut reader).unwrap(), None);
+    assert_eq!(
+      read_value::<Value, _>(&mut reader, Encoding::Json).unwrap(),
+      Some(json!({"a": 1}))
+    );
+  }
+
+  #[test]
+  fn read_header_negotiates_encoding() {
+    let mut reader = Cursor::new(b"%gud encoding=msgpack\n".to_vec());
+    let header = read_header(&mut reader).unwrap().unwrap();
+    assert_eq!(header.encoding, Encoding::MessagePack);
+    assert_eq!(header.deadline_ms, None);
+  }
+
+  #[test]
+  fn read_header_carries_call_options() {
+    let mut reader = Cursor::new(b"%gud deadline_ms=250 events=true\n".to_vec());
+    assert_eq!(
+      read_header(&mut reader).unwrap(),
+      Some(Header {
+        encoding: Encoding::Json,
+        deadline_ms: Some(250),
+        events: true,
+      })
+    );
+
+    let mut reader = Cursor::new(b"%gud deadline_ms=soon\n".to_vec());
+    assert!(read_header(&mut reader).is_err());
+  }
+
+  #[test]
+  fn read_header_rejects_unknown_encodings() {
+    let mut reader = Cursor::new(b"%gud encoding=xml\n".to_vec());
+    assert!(read_header(&mut reader).is_err());
+  }
+
+  #[test]
+  fn read_value_treats_blank_input_as_missing() {
+    let mut reader = Cursor::new(b"  \n\t".to_vec());
+    assert_eq!(
+      read_value::<Value, _>(&mut reader, Encoding::Json).unwrap(),
+      None
+    );
+  }
+
+  #[test]
+  fn msgpack_values_round_trip() {
+    let mut buffer = Vec::new();
+    write_value(&mut buffer, Encoding::MessagePack, &json!({"deps": [1, 2]})).unwrap();
+    let decoded: Option<Value> =
+      read_value(&mut Cursor::new(buffer), Encoding::MessagePack).unwrap();
+    assert_eq!(decoded, Some(json!({"deps": [1, 2]})));
+  }
+
+  #[test]
+  fn json_records_stream_line_by_line() {
+    let reader = Cursor::new(b"1\n\nnope\n3\n".to_vec());
+    let records: Vec<_> = RecordReader::<_, i64>::new(reader, Encoding::Json)
+      .map(Result::ok)
+      .collect();
+    assert_eq!(records, [Some(1), None, Some(3)]);
+  }
+
+  #[test]
+  fn msgpack_records_stream_back_to_back() {
+    let mut writer = RecordWriter::new(Vec::new(), Encoding::MessagePack);
+    for record in ["a", "b", "c"] {
+      writer.write(record).unwrap();
+    }
+
+    let reader = Cursor::new(writer.into_inner());
+    let records: Vec<String> = RecordReader::new(reader, Encoding::MessagePack)
+      .collect::<Result<_, _>>()
+      .unwrap();
+    assert_eq!(records, ["a", "b", "c"]);
+  }
+
+  #[test]
+  fn json_record_writer_emits_ndjson() {
+    let mut writer = RecordWriter::new(Vec::new(), Encoding::Json);
+    writer.write_header().unwrap();
+    writer.write(&json!({"n": 1})).unwrap();
+    writer.write(&json!({"n": 2})).unwrap();
+    assert_eq!(
+      String::from_utf8(writer.into_inner()).unwrap(),
+      "%gud encoding=json\n{\"n\":1}\n{\"n\":2}\n"
+    );
+  }
+}
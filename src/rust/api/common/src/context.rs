@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::error::{ErrorKind, GudError};
+use crate::ipc::IpcError;
+use crate::registry::{ApiName, Handler, Registry};
+
+/// Flag shared between a caller and a running handler. Cancelling is a
+/// request, not an interrupt: the handler decides when to stop.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+  cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+  /// Create a token that has not been cancelled
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Ask every holder of this token to stop
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::SeqCst);
+  }
+
+  /// Whether [`Self::cancel`] has been called on any clone of this token
+  #[must_use]
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::SeqCst)
+  }
+}
+
+/// Kind of an intermediate event emitted while a request is running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+  /// How far along the handler is, e.g. `{"done": 10, "total": 40}`
+  Progress,
+  /// A piece of the final result that is already known
+  Partial,
+}
+
+/// Intermediate event written ahead of a request's final response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+  /// What the event reports
+  pub event: EventKind,
+  /// Handler-defined payload
+  pub data: Value,
+}
+
+/// Callback that delivers events to the caller
+pub type EventSink = Arc<dyn Fn(&Event) -> Result<(), IpcError> + Send + Sync>;
+
+/// Per-request state handed to context-aware processors: cancellation,
+/// deadline and an event sink.
+///
+/// The default context is never cancelled, has no deadline and drops events,
+/// so handlers behave the same when called in-process.
+#[derive(Clone, Default)]
+pub struct CallContext {
+  token: CancellationToken,
+  deadline: Option<Instant>,
+  events: Option<EventSink>,
+  registry: Option<Arc<Registry>>,
+}
+
+impl CallContext {
+  /// Create a context that is never cancelled and drops events
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Stop the request once `deadline` has passed
+  #[must_use]
+  pub const fn with_deadline(mut self, deadline: Instant) -> Self {
+    self.deadline = Some(deadline);
+    self
+  }
+
+  /// Stop the request once `timeout` has elapsed from now
+  #[must_use]
+  pub fn with_timeout(self, timeout: Duration) -> Self {
+    self.with_deadline(Instant::now() + timeout)
+  }
+
+  /// Observe `token` for cancellation
+  #[must_use]
+  pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+    self.token = token;
+    self
+  }
+
+  /// Deliver events emitted by the handler to `sink`
+  #[must_use]
+  pub fn with_events(mut self, sink: EventSink) -> Self {
+    self.events = Some(sink);
+    self
+  }
+
+  /// Make the APIs of `registry` callable through [`Self::call`]
+  #[must_use]
+  pub fn with_registry(mut self, registry: Arc<Registry>) -> Self {
+    self.registry = Some(registry);
+    self
+  }
+
+  /// Registry the request was dispatched from, if any
+  #[must_use]
+  pub fn registry(&self) -> Option<&Registry> {
+    self.registry.as_deref()
+  }
+
+  /// Look up another registered API, failing with an `unknown-api` error
+  /// when it is missing or the context has no registry to resolve it in
+  pub fn resolve(&self, name: &ApiName) -> Result<&Handler, GudError> {
+    let registry = self.registry().ok_or_else(|| {
+      GudError::new(
+        ErrorKind::Argument,
+        "unknown-api",
+        format!("No registry is available to resolve {name}"),
+      )
+      .with_context(serde_json::json!({ "module": name.module, "api": name.api }))
+    })?;
+
+    registry.resolve_name(name)
+  }
+
+  /// Run another registered API in-process. The nested call shares this
+  /// context's cancellation, deadline and event sink.
+  pub fn call(
+    &self,
+    name: &ApiName,
+    input: Option<Value>,
+  ) -> Result<Value, Box<dyn std::error::Error>> {
+    self.resolve(name)?.call_with(input, self)
+  }
+
+  /// Token observed by this context
+  #[must_use]
+  pub const fn cancellation_token(&self) -> &CancellationToken {
+    &self.token
+  }
+
+  /// Point in time after which the request should stop, if any
+  #[must_use]
+  pub const fn deadline(&self) -> Option<Instant> {
+    self.deadline
+  }
+
+  /// Whether the request was cancelled or its deadline has passed
+  #[must_use]
+  pub fn is_cancelled(&self) -> bool {
+    self.token.is_cancelled()
+      || self
+        .deadline
+        .is_some_and(|deadline| Instant::now() >= deadline)
+  }
+
+  /// Fail with a `Task` error once the request was cancelled or ran out of
+  /// time, so long loops can bail out with `ctx.check()?`
+  pub fn check(&self) -> Result<(), GudError> {
+    if self.token.is_cancelled() {
+      Err(GudError::new(
+        ErrorKind::Task,
+        "cancelled",
+        "The request was cancelled by the caller",
+      ))
+    } else if self.is_cancelled() {
+      Err(GudError::new(
+        ErrorKind::Task,
+        "deadline-exceeded",
+        "The request did not finish before its deadline",
+      ))
+    } else {
+      Ok(())
+    }
+  }
+
+  /// Report how far along the handler is
+  pub fn progress<T: Serialize + ?Sized>(&self, data: &T) -> Result<(), IpcError> {
+    self.emit(EventKind::Progress, data)
+  }
+
+  /// Report part of the result before the request completes
+  pub fn partial<T: Serialize + ?Sized>(&self, data: &T) -> Result<(), IpcError> {
+    self.emit(EventKind::Partial, data)
+  }
+
+  fn emit<T: Serialize + ?Sized>(&self, event: EventKind, data: &T) -> Result<(), IpcError> {
+    let Some(sink) = &self.events else {
+      return Ok(());
+    };
+
+    sink(&Event {
+      event,
+      data: serde_json::to_value(data)?,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::Mutex;
+
+  #[test]
+  fn cancellation_is_shared_between_clones() {
+    let token = CancellationToken::new();
+    let ctx = CallContext::new().with_cancellation(token.clone());
+    assert!(ctx.check().is_ok());
+
+    token.cancel();
+    assert!(ctx.is_cancelled());
+    assert_eq!(ctx.check().unwrap_err().code, "cancelled");
+  }
+
+  #[test]
+  fn expired_deadlines_cancel_the_request() {
+    let ctx = CallContext::new().with_deadline(Instant::now());
+    assert_eq!(ctx.check().unwrap_err().code, "deadline-exceeded");
+
+    let ctx = CallContext::new().with_timeout(Duration::from_mins(1));
+    assert!(ctx.check().is_ok());
+  }
+
+  #[test]
+  fn events_reach_the_sink() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = {
+      let seen = Arc::clone(&seen);
+      Arc::new(move |event: &Event| {
+        seen.lock().unwrap().push(event.clone());
+        Ok(())
+      })
+    };
+
+    let ctx = CallContext::new().with_events(sink);
+    ctx.progress(&serde_json::json!({"done": 1})).unwrap();
+    ctx.partial("first").unwrap();
+
+    let seen = seen.lock().unwrap().clone();
+    assert_eq!(seen[0].event, EventKind::Progress);
+    assert_eq!(seen[1].data, serde_json::json!("first"));
+  }
+
+  #[test]
+  fn nested_calls_resolve_through_the_registry() {
+    let mut registry = Registry::new();
+    registry.register(crate::registry::Handler::required_input(
+      "math",
+      "double",
+      "1.0.0",
+      |n: i64| Ok(n * 2),
+    ));
+
+    let name = ApiName::new("math", "double");
+    let error = GudError::from_boxed(CallContext::new().call(&name, None).unwrap_err());
+    assert_eq!(error.code, "unknown-api");
+
+    let ctx = CallContext::new().with_registry(Arc::new(registry));
+    assert_eq!(
+      ctx.call(&name, Some(serde_json::json!(4))).unwrap(),
+      serde_json::json!(8)
+    );
+  }
+
+  #[test]
+  fn default_context_drops_events() {
+    assert!(CallContext::new().progress(&1).is_ok());
+  }
+}
@@ -0,0 +1,212 @@
+use schemars::JsonSchema;
+use serde::de::Error as _;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::ipc::IpcError;
+
+/// Broad category of a failure, mirroring the CLI's kerror `Type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ErrorKind {
+  /// Input could not be parsed
+  Parsing,
+  /// Input was well-formed but not acceptable
+  Argument,
+  /// A git operation failed
+  Git,
+  /// A task failed to run
+  Task,
+  /// Anything else
+  Unknown,
+}
+
+/// Structured error reported to the caller, shaped like a kerror: `kind` is
+/// the kerror type, `code` the kerror id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct GudError {
+  /// Broad category of the failure
+  pub kind: ErrorKind,
+  /// Stable kebab-case identifier for the failure
+  pub code: String,
+  /// Human readable description
+  pub message: String,
+  /// Additional structured details
+  #[serde(default)]
+  pub context: Value,
+}
+
+impl GudError {
+  /// Create an error without context
+  pub fn new(kind: ErrorKind, code: impl Into<String>, message: impl Into<String>) -> Self {
+    Self {
+      kind,
+      code: code.into(),
+      message: message.into(),
+      context: Value::Null,
+    }
+  }
+
+  /// Attach structured context to the error
+  #[must_use]
+  pub fn with_context(mut self, context: Value) -> Self {
+    self.context = context;
+    self
+  }
+
+  /// Convert an error returned by a processor.
+  ///
+  /// Errors that already are a [`GudError`] or [`IpcError`] keep their kind
+  /// and code; anything else is reported as an `Unknown` `handler-error`.
+  #[must_use]
+  pub fn from_boxed(error: Box<dyn std::error::Error>) -> Self {
+    match error.downcast::<Self>() {
+      Ok(gud_error) => *gud_error,
+      Err(error) => match error.downcast::<IpcError>() {
+        Ok(ipc_error) => Self::from(*ipc_error),
+        Err(error) => Self::new(ErrorKind::Unknown, "handler-error", error.to_string()),
+      },
+    }
+  }
+}
+
+impl std::fmt::Display for GudError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:?} error {}: {}", self.kind, self.code, self.message)
+  }
+}
+
+impl std::error::Error for GudError {}
+
+impl From<IpcError> for GudError {
+  fn from(error: IpcError) -> Self {
+    let message = error.to_string();
+    match error {
+      IpcError::IoError(_) => Self::new(ErrorKind::Unknown, "ipc-io-error", message),
+      IpcError::SerializationError(_) => Self::new(ErrorKind::Parsing, "invalid-json", message),
+      IpcError::InvalidInput(_) => Self::new(ErrorKind::Argument, "invalid-input", message),
+      IpcError::EncodingError(_) => Self::new(ErrorKind::Parsing, "invalid-encoding", message),
+    }
+  }
+}
+
+/// Response envelope written to fd3: `{"ok": true, "value": ...}` on success
+/// or `{"ok": false, "error": {...}}` on failure
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Envelope {
+  /// The processor succeeded with this value
+  Ok(Value),
+  /// The request failed
+  Err(GudError),
+}
+
+impl Envelope {
+  /// Build an envelope from a processor result
+  #[must_use]
+  pub fn from_result<O: Serialize>(result: Result<O, Box<dyn std::error::Error>>) -> Self {
+    match result.and_then(|output| Ok(serde_json::to_value(output)?)) {
+      Ok(value) => Self::Ok(value),
+      Err(error) => Self::Err(GudError::from_boxed(error)),
+    }
+  }
+
+  /// Whether the envelope carries a successful value
+  #[must_use]
+  pub const fn is_ok(&self) -> bool {
+    matches!(self, Self::Ok(_))
+  }
+}
+
+impl Serialize for Envelope {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("Envelope", 2)?;
+    match self {
+      Self::Ok(value) => {
+        state.serialize_field("ok", &true)?;
+        state.serialize_field("value", value)?;
+      }
+      Self::Err(error) => {
+        state.serialize_field("ok", &false)?;
+        state.serialize_field("error", error)?;
+      }
+    }
+    state.end()
+  }
+}
+
+impl<'de> Deserialize<'de> for Envelope {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    #[derive(Deserialize)]
+    struct Raw {
+      ok: bool,
+      #[serde(default)]
+      value: Value,
+      error: Option<GudError>,
+    }
+
+    let raw = Raw::deserialize(deserializer)?;
+    match (raw.ok, raw.error) {
+      (true, _) => Ok(Self::Ok(raw.value)),
+      (false, Some(error)) => Ok(Self::Err(error)),
+      (false, None) => Err(D::Error::missing_field("error")),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn envelope_serializes_success_shape() {
+    let envelope = Envelope::Ok(json!({"a": 1}));
+    assert_eq!(
+      serde_json::to_value(&envelope).unwrap(),
+      json!({"ok": true, "value": {"a": 1}})
+    );
+  }
+
+  #[test]
+  fn envelope_serializes_error_shape() {
+    let error = GudError::new(ErrorKind::Argument, "missing-input", "Input is required")
+      .with_context(json!({"api": "to_tree"}));
+    assert_eq!(
+      serde_json::to_value(Envelope::Err(error)).unwrap(),
+      json!({
+        "ok": false,
+        "error": {
+          "kind": "Argument",
+          "code": "missing-input",
+          "message": "Input is required",
+          "context": {"api": "to_tree"},
+        },
+      })
+    );
+  }
+
+  #[test]
+  fn envelope_round_trips() {
+    let envelope = Envelope::Err(GudError::new(ErrorKind::Git, "clone-failed", "boom"));
+    let json = serde_json::to_string(&envelope).unwrap();
+    assert_eq!(serde_json::from_str::<Envelope>(&json).unwrap(), envelope);
+  }
+
+  #[test]
+  fn from_boxed_keeps_structured_errors() {
+    let boxed: Box<dyn std::error::Error> = Box::new(GudError::new(
+      ErrorKind::Parsing,
+      "bad-toml",
+      "unexpected =",
+    ));
+    assert_eq!(GudError::from_boxed(boxed).code, "bad-toml");
+  }
+
+  #[test]
+  fn from_boxed_wraps_plain_errors() {
+    let error = GudError::from_boxed("something broke".into());
+    assert_eq!(error.kind, ErrorKind::Unknown);
+    assert_eq!(error.code, "handler-error");
+    assert_eq!(error.message, "something broke");
+  }
+}
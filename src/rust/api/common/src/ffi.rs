@@ -0,0 +1,231 @@
+use serde_json::Value;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+
+use crate::context::CallContext;
+use crate::error::{Envelope, ErrorKind, GudError};
+use crate::ipc::IpcError;
+use crate::registry::Registry;
+
+/// Decode a C string input: null or blank means no input
+unsafe fn decode_input(input: *const c_char) -> Result<Option<Value>, IpcError> {
+  if input.is_null() {
+    return Ok(None);
+  }
+
+  let input = unsafe { CStr::from_ptr(input) }
+    .to_str()
+    .map_err(|e| IpcError::InvalidInput(format!("Input is not valid UTF-8: {e}")))?;
+  if input.trim().is_empty() {
+    return Ok(None);
+  }
+
+  Ok(Some(serde_json::from_str(input)?))
+}
+
+fn into_c_string(envelope: &Envelope) -> *mut c_char {
+  // serde_json escapes NUL, so the encoded envelope never contains one
+  let json = serde_json::to_string(envelope).unwrap_or_else(|e| {
+    format!(r#"{{"ok":false,"error":{{"kind":"Unknown","code":"ffi-error","message":"{e}","context":null}}}}"#)
+  });
+  CString::new(json).unwrap_or_default().into_raw()
+}
+
+/// Run `module`/`api` from `registry` on a JSON input and return its
+/// [`Envelope`] as a C string.
+///
+/// The rest of `registry` is available to the handler through
+/// [`CallContext::call`], so APIs such as `std/compose` can run nested calls.
+///
+/// Panics in the handler are reported as an `Unknown` `handler-panic` error
+/// instead of unwinding across the ABI.
+///
+/// # Safety
+///
+/// `input` must be null or point to a NUL-terminated string that stays valid
+/// for the duration of the call. The result must be released with
+/// [`gud_free`].
+#[must_use]
+pub unsafe fn call_json(
+  registry: &Arc<Registry>,
+  module: &str,
+  api: &str,
+  input: *const c_char,
+) -> *mut c_char {
+  let envelope = catch_unwind(AssertUnwindSafe(|| {
+    let handler = match registry.resolve(module, api) {
+      Ok(handler) => handler,
+      Err(error) => return Envelope::Err(error),
+    };
+
+    match unsafe { decode_input(input) } {
+      Ok(input) => {
+        let ctx = CallContext::new().with_registry(Arc::clone(registry));
+        Envelope::from_result(handler.call_with(input, &ctx))
+      }
+      Err(e) => Envelope::Err(GudError::from(e)),
+    }
+  }))
+  .unwrap_or_else(|_| {
+    Envelope::Err(GudError::new(
+      ErrorKind::Unknown,
+      "handler-panic",
+      format!("{module}/{api} panicked"),
+    ))
+  });
+
+  into_c_string(&envelope)
+}
+
+/// Release a string returned by an exported handler. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `ptr` must be null or a pointer returned by an exported handler that has
+/// not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn gud_free(ptr: *mut c_char) {
+  if !ptr.is_null() {
+    drop(unsafe { CString::from_raw(ptr) });
+  }
+}
+
+/// Export `gud_<module>_<api>` C functions for the listed APIs of a crate.
+///
+/// Each function takes a NUL-terminated UTF-8 JSON input (or null for no
+/// input) and returns a NUL-terminated JSON [`Envelope`], the same document
+/// the handler would write to fd3, which must be released with [`gud_free`].
+/// `register` is the crate's registration function; the registry it fills is
+/// built on first use and shared by every exported function. It only holds
+/// that crate's APIs, so nested calls to another module's APIs fail with
+/// `unknown-api`.
+///
+/// ```ignore
+/// gud_common::export_c_abi!(register, std, [identity, compose]);
+/// // extern "C" fn gud_std_identity(input: *const c_char) -> *mut c_char
+/// ```
+#[macro_export]
+macro_rules! export_c_abi {
+  ($register:path, $module:ident, [$($api:ident),* $(,)?]) => {
+    fn __gud_ffi_registry() -> &'static std::sync::Arc<$crate::Registry> {
+      static REGISTRY: std::sync::OnceLock<std::sync::Arc<$crate::Registry>> =
+        std::sync::OnceLock::new();
+      REGISTRY.get_or_init(|| {
+        let mut registry = $crate::Registry::new();
+        $register(&mut registry);
+        std::sync::Arc::new(registry)
+      })
+    }
+
+    $(
+      $crate::__paste! {
+        #[doc = concat!(
+          "C ABI entry point for `", stringify!($module), "/", stringify!($api), "`"
+        )]
+        ///
+        /// # Safety
+        ///
+        /// `input` must be null or a NUL-terminated string; the result must be
+        /// released with `gud_free`.
+        #[no_mangle]
+        pub unsafe extern "C" fn [<gud_ $module _ $api>](
+          input: *const std::ffi::c_char,
+        ) -> *mut std::ffi::c_char {
+          unsafe {
+            $crate::ffi::call_json(
+              __gud_ffi_registry(),
+              stringify!($module),
+              stringify!($api),
+              input,
+            )
+          }
+        }
+      }
+    )*
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::registry::Handler;
+
+  fn register(registry: &mut Registry) {
+    registry
+      .register(Handler::required_input(
+        "math",
+        "double",
+        "1.0.0",
+        |n: i64| Ok(n * 2),
+      ))
+      .register(Handler::no_input(
+        "math",
+        "boom",
+        "1.0.0",
+        || -> Result<i64, _> { panic!("boom") },
+      ))
+      .register(Handler::required_input_with_context(
+        "math",
+        "quadruple",
+        "1.0.0",
+        |n: i64, ctx: &CallContext| {
+          let double = crate::registry::ApiName::new("math", "double");
+          let twice = ctx.call(&double, Some(n.into()))?;
+          ctx.call(&double, Some(twice))
+        },
+      ));
+  }
+
+  crate::export_c_abi!(register, math, [double, boom, quadruple]);
+
+  fn call(function: unsafe extern "C" fn(*const c_char) -> *mut c_char, input: &str) -> Value {
+    let input = CString::new(input).unwrap();
+    unsafe {
+      let output = function(input.as_ptr());
+      let json = CStr::from_ptr(output).to_str().unwrap().to_string();
+      gud_free(output);
+      serde_json::from_str(&json).unwrap()
+    }
+  }
+
+  #[test]
+  fn exported_functions_return_envelopes() {
+    assert_eq!(
+      call(gud_math_double, "21"),
+      serde_json::json!({"ok": true, "value": 42})
+    );
+  }
+
+  #[test]
+  fn exported_functions_report_bad_input() {
+    assert_eq!(call(gud_math_double, "{")["error"]["code"], "invalid-json");
+    assert_eq!(call(gud_math_double, "")["error"]["code"], "missing-input");
+  }
+
+  #[test]
+  fn exported_functions_can_call_other_apis() {
+    assert_eq!(
+      call(gud_math_quadruple, "3"),
+      serde_json::json!({"ok": true, "value": 12})
+    );
+  }
+
+  #[test]
+  fn panics_do_not_cross_the_abi() {
+    assert_eq!(call(gud_math_boom, "")["error"]["code"], "handler-panic");
+  }
+
+  #[test]
+  fn unknown_apis_are_reported() {
+    let mut registry = Registry::new();
+    register(&mut registry);
+    let output = unsafe { call_json(&Arc::new(registry), "math", "triple", std::ptr::null()) };
+    let json = unsafe { CStr::from_ptr(output) }
+      .to_str()
+      .unwrap()
+      .to_string();
+    unsafe { gud_free(output) };
+    assert!(json.contains("unknown-api"));
+  }
+}
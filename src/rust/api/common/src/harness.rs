@@ -0,0 +1,546 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::context::CallContext;
+use crate::error::Envelope;
+use crate::ipc::IpcError;
+use crate::registry::{Handler, Registry};
+
+/// Environment variable that makes [`assert_fixtures`] rewrite fixtures whose
+/// response no longer matches instead of failing
+pub const UPDATE_ENV_VAR: &str = "GUD_UPDATE_FIXTURES";
+
+/// Environment variable naming a directory where [`crate::run_handler`]
+/// records every request it handles as a [`Fixture`]
+pub const RECORD_ENV_VAR: &str = "GUD_RECORD_DIR";
+
+/// A recorded request and the response it produced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fixture {
+  /// Module of the API that handled the request
+  pub module: String,
+  /// Name of the API that handled the request
+  pub api: String,
+  /// Input of the request; `None` when no input was sent
+  #[serde(default)]
+  pub input: Option<Value>,
+  /// Envelope the handler replied with
+  pub response: Envelope,
+  /// JSON Pointers into the response whose values vary between runs, such as
+  /// timings; they must exist in both responses but are not compared
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub ignore: Vec<String>,
+}
+
+impl Fixture {
+  /// Run `input` through `handler` in-process under `ctx` and capture the
+  /// exchange
+  #[must_use]
+  pub fn capture(handler: &Handler, input: Option<Value>, ctx: &CallContext) -> Self {
+    let spec = handler.spec();
+    Self {
+      module: spec.module.clone(),
+      api: spec.api.clone(),
+      response: Envelope::from_result(handler.call_with(input.clone(), ctx)),
+      input,
+      ignore: Vec::new(),
+    }
+  }
+
+  /// Read a fixture file
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, IpcError> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+  }
+
+  /// Write the fixture as pretty JSON, creating parent directories
+  pub fn save(&self, path: impl AsRef<Path>) -> Result<(), IpcError> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+    Ok(())
+  }
+
+  /// Replay the request in-process and compare the response.
+  ///
+  /// The registry is attached to the call so handlers can make nested calls.
+  pub fn replay(&self, registry: &Arc<Registry>) -> Result<(), ReplayError> {
+    let handler = registry
+      .resolve(&self.module, &self.api)
+      .map_err(|e| ReplayError::Io(IpcError::InvalidInput(e.to_string())))?;
+    let ctx = CallContext::new().with_registry(Arc::clone(registry));
+    self.compare(Envelope::from_result(
+      handler.call_with(self.input.clone(), &ctx),
+    ))
+  }
+
+  fn compare(&self, actual: Envelope) -> Result<(), ReplayError> {
+    if actual == self.response || self.matches_ignoring(&actual) {
+      Ok(())
+    } else {
+      Err(ReplayError::Mismatch {
+        expected: Box::new(self.response.clone()),
+        actual: Box::new(actual),
+      })
+    }
+  }
+
+  fn matches_ignoring(&self, actual: &Envelope) -> bool {
+    if self.ignore.is_empty() {
+      return false;
+    }
+    let mask = |envelope: &Envelope| {
+      let mut value = serde_json::to_value(envelope).ok()?;
+      for pointer in &self.ignore {
+        *value.pointer_mut(pointer)? = Value::Null;
+      }
+      Some(value)
+    };
+    matches!((mask(&self.response), mask(actual)), (Some(a), Some(b)) if a == b)
+  }
+}
+
+/// Why a fixture failed to replay
+#[derive(Debug)]
+pub enum ReplayError {
+  /// The fixture or the handler could not be loaded or run
+  Io(IpcError),
+  /// The handler replied differently than recorded
+  Mismatch {
+    /// Recorded response
+    expected: Box<Envelope>,
+    /// Response produced now
+    actual: Box<Envelope>,
+  },
+}
+
+impl From<IpcError> for ReplayError {
+  fn from(error: IpcError) -> Self {
+    Self::Io(error)
+  }
+}
+
+impl fmt::Display for ReplayError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(error) => write!(f, "{error}"),
+      Self::Mismatch { expected, actual } => {
+        let pretty =
+          |envelope: &Envelope| serde_json::to_string_pretty(envelope).unwrap_or_default();
+        write!(
+          f,
+          "response changed\n--- expected\n{}\n+++ actual\n{}",
+          pretty(expected),
+          pretty(actual)
+        )
+      }
+    }
+  }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Capture a request against a registered API and save it to `path`.
+///
+/// As in [`Fixture::replay`], the registry is attached to the call so
+/// handlers can make nested calls.
+pub fn record(
+  registry: &Arc<Registry>,
+  module: &str,
+  api: &str,
+  input: Option<Value>,
+  path: impl AsRef<Path>,
+) -> Result<Fixture, IpcError> {
+  let handler = registry
+    .resolve(module, api)
+    .map_err(|e| IpcError::InvalidInput(e.to_string()))?;
+  let ctx = CallContext::new().with_registry(Arc::clone(registry));
+  let fixture = Fixture::capture(handler, input, &ctx);
+  fixture.save(path)?;
+  Ok(fixture)
+}
+
+/// Every `*.json` file below `dir`, sorted
+pub fn fixture_paths(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>, IpcError> {
+  let mut paths = Vec::new();
+  let mut pending = vec![dir.as_ref().to_path_buf()];
+  while let Some(dir) = pending.pop() {
+    for entry in std::fs::read_dir(dir)? {
+      let path = entry?.path();
+      if path.is_dir() {
+        pending.push(path);
+      } else if path.extension().is_some_and(|ext| ext == "json") {
+        paths.push(path);
+      }
+    }
+  }
+  paths.sort();
+  Ok(paths)
+}
+
+/// Replay every fixture below `dir` as a golden test, panicking with a
+/// report of every fixture that no longer matches.
+///
+/// With [`UPDATE_ENV_VAR`] set, mismatching fixtures are rewritten with the
+/// current response instead.
+///
+/// # Panics
+///
+/// When `dir` holds no fixtures or any fixture fails to replay.
+pub fn assert_fixtures(registry: &Arc<Registry>, dir: impl AsRef<Path>) {
+  let update = std::env::var_os(UPDATE_ENV_VAR).is_some();
+  let paths = fixture_paths(&dir)
+    .unwrap_or_else(|e| panic!("cannot list fixtures in {}: {e}", dir.as_ref().display()));
+  assert!(
+    !paths.is_empty(),
+    "no fixtures found in {}",
+    dir.as_ref().display()
+  );
+
+  let mut failures = Vec::new();
+  for path in paths {
+    let result = Fixture::load(&path)
+      .map_err(ReplayError::from)
+      .and_then(|fixture| match fixture.replay(registry) {
+        Err(ReplayError::Mismatch { actual, .. }) if update => Fixture {
+          response: *actual,
+          ..fixture
+        }
+        .save(&path)
+        .map_err(ReplayError::from),
+        result => result,
+      });
+
+    if let Err(error) = result {
+      failures.push(format!("{}: {error}", path.display()));
+    }
+  }
+
+  assert!(
+    failures.is_empty(),
+    "{} fixture(s) failed (set {UPDATE_ENV_VAR}=1 to accept the new responses):\n\n{}",
+    failures.len(),
+    failures.join("\n\n")
+  );
+}
+
+/// Save an exchange handled by [`crate::run_handler`] under
+/// [`RECORD_ENV_VAR`], if it is set. Recording is best effort and never fails
+/// the request.
+pub(crate) fn record_exchange(fixture: &Fixture) {
+  static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+  let Some(dir) = std::env::var_os(RECORD_ENV_VAR) else {
+    return;
+  };
+
+  let millis = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |elapsed| elapsed.as_millis());
+  let name = format!(
+    "{millis}-{}-{}.json",
+    std::process::id(),
+    COUNTER.fetch_add(1, Ordering::Relaxed)
+  );
+  let path = Path::new(&dir)
+    .join(&fixture.module)
+    .join(&fixture.api)
+    .join(name);
+
+  if let Err(e) = fixture.save(&path) {
+    crate::log::warn(&format!("Cannot record fixture {}: {e}", path.display()));
+  }
+}
+
+/// Run a handler, recording the exchange when [`RECORD_ENV_VAR`] is set
+pub(crate) fn call_recorded(
+  handler: &Handler,
+  input: Option<Value>,
+  ctx: &CallContext,
+) -> Result<Value, Box<dyn std::error::Error>> {
+  if std::env::var_os(RECORD_ENV_VAR).is_none() {
+    return handler.call_with(input, ctx);
+  }
+
+  let response = Envelope::from_result(handler.call_with(input.clone(), ctx));
+  let spec = handler.spec();
+  record_exchange(&Fixture {
+    module: spec.module.clone(),
+    api: spec.api.clone(),
+    input,
+    response: response.clone(),
+    ignore: Vec::new(),
+  });
+
+  match response {
+    Envelope::Ok(value) => Ok(value),
+    Envelope::Err(error) => Err(error.into()),
+  }
+}
+
+/// Output of a binary driven by [`run_binary`]
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct BinaryRun {
+  /// Exit status of the process
+  pub status: std::process::ExitStatus,
+  /// Everything written to fd 3
+  pub reply: Vec<u8>,
+  /// Everything written to stdout
+  pub stdout: Vec<u8>,
+  /// Everything written to stderr
+  pub stderr: Vec<u8>,
+}
+
+#[cfg(unix)]
+impl BinaryRun {
+  /// The reply without a leading `%gud` header line
+  fn payload(&self) -> &[u8] {
+    if self
+      .reply
+      .starts_with(crate::codec::HEADER_PREFIX.as_bytes())
+    {
+      let start = self
+        .reply
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(self.reply.len(), |newline| newline + 1);
+      &self.reply[start..]
+    } else {
+      &self.reply
+    }
+  }
+
+  /// Decode the one-shot reply as an [`Envelope`]
+  pub fn envelope(&self) -> Result<Envelope, IpcError> {
+    Ok(serde_json::from_slice(self.payload())?)
+  }
+
+  /// Decode a server-mode or streaming reply as newline-delimited records
+  pub fn records<T: for<'de> Deserialize<'de>>(&self) -> Result<Vec<T>, IpcError> {
+    self
+      .payload()
+      .split(|&b| b == b'\n')
+      .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+      .map(|line| Ok(serde_json::from_slice(line)?))
+      .collect()
+  }
+}
+
+/// Spawn an IPC binary the way the CLI does: `stdin` is written to the
+/// child's stdin and its replies are read from a real pipe on fd 3.
+#[cfg(unix)]
+pub fn run_binary(
+  program: impl AsRef<Path>,
+  args: &[&str],
+  stdin: &[u8],
+) -> std::io::Result<BinaryRun> {
+  use std::io::{Read, Write};
+  use std::os::fd::AsRawFd;
+  use std::os::unix::process::CommandExt;
+  use std::process::{Command, Stdio};
+
+  let (mut reply_reader, reply_writer) = std::io::pipe()?;
+  let reply_fd = reply_writer.as_raw_fd();
+
+  let mut command = Command::new(program.as_ref());
+  command
+    .args(args)
+    .env_remove(crate::channel::CHANNEL_ENV_VAR)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped());
+
+  // SAFETY: only async-signal-safe calls between fork and exec
+  unsafe {
+    command.pre_exec(move || {
+      let result = if reply_fd == 3 {
+        libc::fcntl(3, libc::F_SETFD, 0)
+      } else {
+        libc::dup2(reply_fd, 3)
+      };
+      if result == -1 {
+        return Err(std::io::Error::last_os_error());
+      }
+      Ok(())
+    });
+  }
+
+  let mut child = command.spawn()?;
+  // Only the child may hold the write end, or reading would never see EOF
+  drop(reply_writer);
+
+  let reader = std::thread::spawn(move || {
+    let mut reply = Vec::new();
+    reply_reader.read_to_end(&mut reply).map(|_| reply)
+  });
+
+  if let Some(mut child_stdin) = child.stdin.take() {
+    // The child may exit without reading its input
+    let _ = child_stdin.write_all(stdin);
+  }
+
+  let output = child.wait_with_output()?;
+  let reply = reader
+    .join()
+    .map_err(|_| std::io::Error::other("fd 3 reader panicked"))??;
+
+  Ok(BinaryRun {
+    status: output.status,
+    reply,
+    stdout: output.stdout,
+    stderr: output.stderr,
+  })
+}
+
+/// Replay a fixture against a compiled binary started with `args`, over stdin
+/// and fd 3
+#[cfg(unix)]
+pub fn replay_binary(
+  program: impl AsRef<Path>,
+  args: &[&str],
+  fixture: &Fixture,
+) -> Result<(), ReplayError> {
+  let stdin = match &fixture.input {
+    Some(input) => serde_json::to_vec(input).map_err(IpcError::from)?,
+    None => Vec::new(),
+  };
+  let run = run_binary(program, args, &stdin).map_err(IpcError::from)?;
+  fixture.compare(run.envelope()?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::error::{ErrorKind, GudError};
+  use crate::registry::ApiName;
+  use serde_json::json;
+
+  fn registry() -> Registry {
+    let mut registry = Registry::new();
+    registry.register(Handler::required_input(
+      "math",
+      "double",
+      "1.0.0",
+      |n: i64| Ok(n * 2),
+    ));
+    registry.register(Handler::required_input_with_context(
+      "math",
+      "quadruple",
+      "1.0.0",
+      |n: i64, ctx| {
+        let double = ApiName::new("math", "double");
+        let twice = ctx.call(&double, Some(json!(n)))?;
+        ctx.call(&double, Some(twice))
+      },
+    ));
+    registry
+  }
+
+  #[test]
+  fn recorded_fixtures_replay() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("math/double/simple.json");
+    let fixture = record(
+      &Arc::new(registry()),
+      "math",
+      "double",
+      Some(json!(4)),
+      &path,
+    )
+    .unwrap();
+    assert_eq!(fixture.response, Envelope::Ok(json!(8)));
+
+    assert_eq!(Fixture::load(&path).unwrap(), fixture);
+    assert_fixtures(&Arc::new(registry()), dir.path());
+  }
+
+  #[test]
+  fn composing_handlers_record_and_replay() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("math/quadruple/nested.json");
+    let fixture = record(
+      &Arc::new(registry()),
+      "math",
+      "quadruple",
+      Some(json!(3)),
+      &path,
+    )
+    .unwrap();
+    assert_eq!(fixture.response, Envelope::Ok(json!(12)));
+
+    assert_fixtures(&Arc::new(registry()), dir.path());
+  }
+
+  #[test]
+  fn changed_responses_are_reported() {
+    let fixture = Fixture {
+      module: "math".to_string(),
+      api: "double".to_string(),
+      input: None,
+      response: Envelope::Ok(json!(0)),
+      ignore: Vec::new(),
+    };
+
+    let error = fixture.replay(&Arc::new(registry())).unwrap_err();
+    let ReplayError::Mismatch { actual, .. } = &error else {
+      panic!("expected a mismatch, got {error}");
+    };
+    assert!(matches!(
+      **actual,
+      Envelope::Err(GudError {
+        kind: ErrorKind::Argument,
+        ..
+      })
+    ));
+    assert!(error.to_string().contains("missing-input"));
+  }
+
+  #[test]
+  fn ignored_pointers_are_not_compared() {
+    let mut fixture = Fixture {
+      module: "math".to_string(),
+      api: "double".to_string(),
+      input: Some(json!(2)),
+      response: Envelope::Ok(json!(5)),
+      ignore: vec!["/value".to_string()],
+    };
+    assert!(fixture.replay(&Arc::new(registry())).is_ok());
+
+    fixture.ignore = vec!["/value/elapsed".to_string()];
+    assert!(fixture.replay(&Arc::new(registry())).is_err());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn binaries_reply_on_a_real_fd3_pipe() {
+    let run = run_binary(
+      "sh",
+      &["-c", "echo ignored; cat >&3"],
+      br#"{"ok": true, "value": 1}"#,
+    )
+    .unwrap();
+
+    assert!(run.status.success());
+    assert_eq!(run.stdout, b"ignored\n");
+    assert_eq!(run.envelope().unwrap(), Envelope::Ok(json!(1)));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn binary_replies_can_be_read_as_records() {
+    let run = run_binary(
+      "sh",
+      &["-c", "printf '%%gud encoding=json\\n1\\n2\\n' >&3"],
+      b"",
+    )
+    .unwrap();
+    assert_eq!(run.records::<i64>().unwrap(), [1, 2]);
+  }
+}
@@ -1,9 +1,18 @@
 use serde::{Deserialize, Serialize};
-use std::io::{self, Read};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{self, BufWriter, Read, Write};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
-// Platform-specific imports for file descriptor handling
-#[cfg(unix)]
-use std::os::fd::FromRawFd;
+use crate::channel::{channel_writer, validate_channel, ChannelWriter};
+use crate::codec::{read_header, read_value, Encoding, Header, RecordReader, RecordWriter};
+use crate::context::{CallContext, CancellationToken, Event};
+use crate::error::{Envelope, ErrorKind, GudError};
+use crate::harness;
+use crate::log;
+use crate::registry::{Handler, Registry};
+use crate::schema::{print_schema, schema_requested};
 
 /// Error type for IPC operations
 #[derive(Debug)]
@@ -14,6 +23,8 @@ pub enum IpcError {
   SerializationError(serde_json::Error),
   /// Invalid input provided to IPC function
   InvalidInput(String),
+  /// Binary (`MessagePack`) encoding or decoding failed
+  EncodingError(String),
 }
 
 impl From<io::Error> for IpcError {
@@ -34,26 +45,19 @@ impl std::fmt::Display for IpcError {
       Self::IoError(err) => write!(f, "IO error: {err}"),
       Self::SerializationError(err) => write!(f, "Serialization error: {err}"),
       Self::InvalidInput(msg) => write!(f, "Invalid input: {msg}"),
+      Self::EncodingError(msg) => write!(f, "Encoding error: {msg}"),
     }
   }
 }
 
 impl std::error::Error for IpcError {}
 
-/// Read JSON input from stdin
+/// Read JSON input from stdin, decoding it directly from the stream
 pub fn read_stdin_json<T>() -> Result<Option<T>, IpcError>
 where
   T: for<'de> Deserialize<'de>,
 {
-  let mut input = String::new();
-  io::stdin().read_to_string(&mut input)?;
-
-  if input.trim().is_empty() {
-    return Ok(None);
-  }
-
-  let parsed: T = serde_json::from_str(&input)?;
-  Ok(Some(parsed))
+  read_value(&mut io::stdin().lock(), Encoding::Json)
 }
 
 /// Read raw string input from stdin
@@ -63,56 +67,124 @@ pub fn read_stdin_raw() -> Result<String, IpcError> {
   Ok(input)
 }
 
-/// Write JSON output to file descriptor 3
+/// Write JSON output to the reply channel (fd 3 unless `GUD_IPC_CHANNEL`
+/// says otherwise), serializing straight into the channel
 pub fn write_fd3_json<T>(data: &T) -> Result<(), IpcError>
 where
   T: Serialize,
 {
-  let json = serde_json::to_string(data)?;
-  write_fd3_raw(&json)
+  let mut writer = BufWriter::new(channel_writer());
+  serde_json::to_writer(&mut writer, data)?;
+  writer.flush()?;
+  Ok(())
 }
 
-/// Write raw string output to file descriptor 3
+/// Write raw string output to the reply channel
 pub fn write_fd3_raw(data: &str) -> Result<(), IpcError> {
-  #[cfg(unix)]
-  unsafe {
-    use std::io::Write;
-    let mut fd3 = std::fs::File::from_raw_fd(3);
-    fd3.write_all(data.as_bytes())?;
-    fd3.flush()?;
-    // Don't let the file be dropped and closed
-    std::mem::forget(fd3);
+  let mut writer = channel_writer();
+  writer.write_all(data.as_bytes())?;
+  writer.flush()?;
+  Ok(())
+}
+
+/// Build the [`GudError`] reported when a required input is missing
+pub(crate) fn missing_input_error() -> GudError {
+  GudError::new(
+    ErrorKind::Argument,
+    "missing-input",
+    "Input is required but was not provided",
+  )
+}
+
+/// Read a whole request from stdin along with its optional header
+fn read_request<I>() -> (Option<Header>, Result<Option<I>, IpcError>)
+where
+  I: for<'de> Deserialize<'de>,
+{
+  let mut stdin = io::stdin().lock();
+  match read_header(&mut stdin) {
+    Ok(header) => {
+      let encoding = header.unwrap_or_default().encoding;
+      (header, read_value(&mut stdin, encoding))
+    }
+    Err(e) => (None, Err(e)),
   }
+}
+
+/// Record writer over the reply channel, shared between a request's final
+/// response and the events emitted while it runs
+type ReplyWriter = RecordWriter<BufWriter<ChannelWriter>>;
 
-  #[cfg(windows)]
-  {
-    // On Windows, we'll use stdout as a fallback since fd3 doesn't exist
-    // In a real Windows environment, you might want to use named pipes or other IPC mechanisms
-    use std::io::Write;
-    std::io::stdout().write_all(data.as_bytes())?;
-    std::io::stdout().flush()?;
+fn reply_writer(header: Option<Header>) -> Result<Arc<Mutex<ReplyWriter>>, IpcError> {
+  let mut writer = RecordWriter::new(
+    BufWriter::new(channel_writer()),
+    header.unwrap_or_default().encoding,
+  );
+  if header.is_some() {
+    writer.write_header()?;
   }
+  Ok(Arc::new(Mutex::new(writer)))
+}
 
-  Ok(())
+fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, IpcError> {
+  mutex
+    .lock()
+    .map_err(|_| IpcError::IoError(io::Error::other("reply channel lock poisoned")))
 }
 
-/// High-level handler function that reads JSON from stdin, processes it, and writes JSON to fd3
+/// High-level handler function that reads JSON from stdin, processes it, and
+/// writes an [`Envelope`] to fd3.
+///
+/// A leading `%gud encoding=...` header (see [`crate::codec::read_header`])
+/// switches both the request and the response to that encoding and is
+/// acknowledged on fd3. Processor failures are reported inside the envelope,
+/// so this only returns an error when the envelope itself could not be
+/// written.
 pub fn handle_json_ipc<I, O, F>(processor: F) -> Result<(), IpcError>
 where
   I: for<'de> Deserialize<'de>,
   O: Serialize,
   F: FnOnce(Option<I>) -> Result<O, Box<dyn std::error::Error>>,
 {
-  // Read input from stdin
-  let input: Option<I> = read_stdin_json()?;
+  handle_context_ipc(|input: Option<I>, _: &CallContext| processor(input))
+}
+
+/// Context-aware counterpart to [`handle_json_ipc`].
+///
+/// The header's `deadline_ms` bounds the request, and with `events=true` the
+/// [`Event`]s emitted through the [`CallContext`] are written as records
+/// ahead of the final envelope. Without a header events are dropped, so
+/// callers that expect a single JSON document are unaffected.
+pub fn handle_context_ipc<I, O, F>(processor: F) -> Result<(), IpcError>
+where
+  I: for<'de> Deserialize<'de>,
+  O: Serialize,
+  F: FnOnce(Option<I>, &CallContext) -> Result<O, Box<dyn std::error::Error>>,
+{
+  let (header, input) = read_request::<I>();
+  let writer = reply_writer(header)?;
+
+  let mut ctx = CallContext::new();
+  if let Some(ms) = header.and_then(|header| header.deadline_ms) {
+    ctx = ctx.with_timeout(Duration::from_millis(ms));
+  }
+  if header.is_some_and(|header| header.events) {
+    let writer = Arc::clone(&writer);
+    ctx = ctx.with_events(Arc::new(move |event: &Event| lock(&writer)?.write(event)));
+  }
 
-  // Process the input
-  let output = processor(input).map_err(|e| IpcError::InvalidInput(e.to_string()))?;
+  let envelope = match (input, ctx.check()) {
+    (Ok(input), Ok(())) => Envelope::from_result(processor(input, &ctx)),
+    (Ok(_), Err(error)) => Envelope::Err(error),
+    (Err(e), _) => Envelope::Err(GudError::from(e)),
+  };
 
-  // Write output to fd3
-  write_fd3_json(&output)?;
+  if let Envelope::Err(error) = &envelope {
+    log_failure(error);
+  }
 
-  Ok(())
+  let written = lock(&writer)?.write(&envelope);
+  written
 }
 
 /// Simpler handler for functions that don't need input
@@ -121,7 +193,7 @@ where
   O: Serialize,
   F: FnOnce() -> Result<O, Box<dyn std::error::Error>>,
 {
-  handle_json_ipc(|_: Option<()>| processor())
+  handle_json_ipc(|_: Option<Value>| processor())
 }
 
 /// Handler for functions that always expect input
@@ -132,39 +204,510 @@ where
   F: FnOnce(I) -> Result<O, Box<dyn std::error::Error>>,
 {
   handle_json_ipc(|input: Option<I>| {
-    let input = input.ok_or("Input is required but was not provided")?;
+    let input = input.ok_or_else(missing_input_error)?;
+    processor(input)
+  })
+}
+
+/// Command-line flag that switches an IPC binary into server mode
+pub const SERVE_FLAG: &str = "--serve";
+
+/// Environment variable that switches an IPC binary into server mode when set
+/// to `serve`
+pub const MODE_ENV_VAR: &str = "GUD_IPC_MODE";
+
+/// A single request read from stdin while running in server mode
+#[derive(Debug, Deserialize)]
+pub struct ServeRequest {
+  /// Correlation ID, echoed back unchanged on the matching response
+  #[serde(default)]
+  pub id: Value,
+  /// Input handed to the processor; a missing or `null` input is `None`
+  #[serde(default)]
+  pub input: Option<Value>,
+  /// Milliseconds, counted from when the request is read, after which the
+  /// handler is asked to stop
+  #[serde(default)]
+  pub deadline_ms: Option<u64>,
+}
+
+/// Server-mode message asking the handler of an in-flight or queued request
+/// to stop: `{"cancel": <id>}`
+#[derive(Debug, Deserialize)]
+pub struct CancelRequest {
+  /// Correlation ID of the request to cancel
+  pub cancel: Value,
+}
+
+/// Any record read from stdin while running in server mode
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ServeMessage {
+  /// Cancel an earlier request
+  Cancel(CancelRequest),
+  /// Run a new request
+  Request(ServeRequest),
+}
+
+/// A single response written to fd3 while running in server mode: the
+/// request's correlation ID alongside the fields of its [`Envelope`]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServeResponse {
+  /// Correlation ID of the request this response answers
+  pub id: Value,
+  /// Outcome of the request
+  #[serde(flatten)]
+  pub envelope: Envelope,
+}
+
+/// An [`Event`] emitted while a server-mode request runs, written to fd3
+/// ahead of the request's [`ServeResponse`]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServeEvent {
+  /// Correlation ID of the request that emitted the event
+  pub id: Value,
+  /// The event itself
+  #[serde(flatten)]
+  pub event: Event,
+}
+
+/// Whether the current process was asked to run as a long-lived server,
+/// either through `--serve` or `GUD_IPC_MODE=serve`
+#[must_use]
+pub fn serve_mode_requested() -> bool {
+  std::env::args().skip(1).any(|arg| arg == SERVE_FLAG)
+    || std::env::var(MODE_ENV_VAR).is_ok_and(|mode| mode == "serve")
+}
+
+/// Process a single decoded server-mode request and build its response.
+/// Requests cancelled or expired while queued are answered without running.
+fn serve_request<I, O, F>(
+  request: Result<ServeRequest, IpcError>,
+  ctx: &CallContext,
+  processor: &F,
+) -> ServeResponse
+where
+  I: for<'de> Deserialize<'de>,
+  O: Serialize,
+  F: Fn(Option<I>, &CallContext) -> Result<O, Box<dyn std::error::Error>>,
+{
+  let request = match request {
+    Ok(request) => request,
+    Err(e) => {
+      return ServeResponse {
+        id: Value::Null,
+        envelope: Envelope::Err(GudError::from(e)),
+      }
+    }
+  };
+
+  if let Err(error) = ctx.check() {
+    return ServeResponse {
+      id: request.id,
+      envelope: Envelope::Err(error),
+    };
+  }
+
+  let input = request.input.map(serde_json::from_value::<I>).transpose();
+  let envelope = match input {
+    Ok(input) => Envelope::from_result(processor(input, ctx)),
+    Err(e) => Envelope::Err(GudError::from(IpcError::from(e))),
+  };
+
+  ServeResponse {
+    id: request.id,
+    envelope,
+  }
+}
+
+/// Build the context of a server-mode request: its deadline, cancellation
+/// token and an event sink tagging events with the request's ID
+fn request_context(
+  request: &ServeRequest,
+  token: CancellationToken,
+  writer: &Arc<Mutex<ReplyWriter>>,
+) -> CallContext {
+  let id = request.id.clone();
+  let writer = Arc::clone(writer);
+  let ctx = CallContext::new()
+    .with_cancellation(token)
+    .with_events(Arc::new(move |event: &Event| {
+      lock(&writer)?.write(&ServeEvent {
+        id: id.clone(),
+        event: event.clone(),
+      })
+    }));
+
+  match request.deadline_ms {
+    Some(ms) => ctx.with_timeout(Duration::from_millis(ms)),
+    None => ctx,
+  }
+}
+
+/// Requests waiting for or running on the server's worker.
+///
+/// Entries are keyed by a sequence number assigned as requests are read, so
+/// requests sharing a correlation ID (or all sending `null`) keep their own
+/// cancellation tokens. A cancel message reaches every such request.
+#[derive(Default)]
+struct InFlight {
+  next: u64,
+  requests: HashMap<u64, (Value, CancellationToken)>,
+}
+
+impl InFlight {
+  /// Track a new request and return the sequence number it is known by
+  fn insert(&mut self, id: Value, token: CancellationToken) -> u64 {
+    let seq = self.next;
+    self.next += 1;
+    self.requests.insert(seq, (id, token));
+    seq
+  }
+
+  /// Cancel every tracked request with correlation ID `id`, returning whether
+  /// any was found
+  fn cancel(&self, id: &Value) -> bool {
+    let mut found = false;
+    for (request_id, token) in self.requests.values() {
+      if request_id == id {
+        token.cancel();
+        found = true;
+      }
+    }
+    found
+  }
+
+  /// Stop tracking the request with sequence number `seq`
+  fn remove(&mut self, seq: u64) {
+    self.requests.remove(&seq);
+  }
+}
+
+/// A request handed to the server's worker along with its in-flight sequence
+/// number, if it was tracked
+type Queued = (Option<u64>, Result<ServeRequest, IpcError>, CallContext);
+
+/// Queue of requests waiting for the server's worker
+type RequestQueue = mpsc::Sender<Queued>;
+
+/// Read server-mode messages from stdin, queueing requests for the worker and
+/// cancelling in-flight requests as soon as their cancel message arrives
+fn read_messages(
+  messages: StdinRecords<ServeMessage>,
+  queue: &RequestQueue,
+  in_flight: &Mutex<InFlight>,
+  writer: &Arc<Mutex<ReplyWriter>>,
+) -> Result<(), IpcError> {
+  for message in messages {
+    let queued = match message {
+      Ok(ServeMessage::Cancel(CancelRequest { cancel })) => {
+        if !lock(in_flight)?.cancel(&cancel) {
+          log::debug(&format!("Ignoring cancel for unknown request {cancel}"));
+        }
+        continue;
+      }
+      Ok(ServeMessage::Request(request)) => {
+        let token = CancellationToken::new();
+        let seq = lock(in_flight)?.insert(request.id.clone(), token.clone());
+        let ctx = request_context(&request, token, writer);
+        (Some(seq), Ok(request), ctx)
+      }
+      Err(e) => (None, Err(e), CallContext::new()),
+    };
+
+    // The worker only hangs up after failing to write, which it reports itself
+    if queue.send(queued).is_err() {
+      break;
+    }
+  }
+
+  Ok(())
+}
+
+/// Lock stdin, consume its optional encoding header and read the rest as a
+/// stream of records
+// The lock is moved into the returned reader, not held past its last use
+#[allow(clippy::significant_drop_tightening)]
+fn open_stdin_records<T>() -> Result<(Option<Header>, StdinRecords<T>), IpcError>
+where
+  T: for<'de> Deserialize<'de>,
+{
+  let mut stdin = io::stdin().lock();
+  let header = read_header(&mut stdin)?;
+  let encoding = header.unwrap_or_default().encoding;
+  Ok((header, RecordReader::new(stdin, encoding)))
+}
+
+/// Records read incrementally from stdin
+type StdinRecords<T> = RecordReader<io::StdinLock<'static>, T>;
+
+/// Long-lived counterpart to [`handle_json_ipc`].
+///
+/// Reads [`ServeRequest`] records from stdin until EOF and writes one
+/// [`ServeResponse`] record per request to fd3. Records are newline-delimited
+/// JSON unless a `%gud encoding=...` header selects another encoding. A
+/// failing request is reported on its response and does not stop the server.
+pub fn serve_json_ipc<I, O, F>(processor: F) -> Result<(), IpcError>
+where
+  I: for<'de> Deserialize<'de>,
+  O: Serialize,
+  F: Fn(Option<I>) -> Result<O, Box<dyn std::error::Error>> + Sync,
+{
+  serve_context_ipc(|input: Option<I>, _: &CallContext| processor(input))
+}
+
+/// Context-aware counterpart to [`serve_json_ipc`].
+///
+/// Requests run one at a time, in order, on a worker thread while stdin keeps
+/// being read, so a `{"cancel": <id>}` message reaches the handler's
+/// [`CallContext`] while it is still running. Each request may carry a
+/// `deadline_ms`, and events emitted by the handler are written to fd3 as
+/// [`ServeEvent`] records ahead of its response.
+pub fn serve_context_ipc<I, O, F>(processor: F) -> Result<(), IpcError>
+where
+  I: for<'de> Deserialize<'de>,
+  O: Serialize,
+  F: Fn(Option<I>, &CallContext) -> Result<O, Box<dyn std::error::Error>> + Sync,
+{
+  let (header, messages) = open_stdin_records()?;
+  let writer = reply_writer(header)?;
+  let in_flight = Mutex::new(InFlight::default());
+  let (queue, pending): (RequestQueue, _) = mpsc::channel();
+
+  std::thread::scope(|scope| {
+    let worker = scope.spawn(|| -> Result<(), IpcError> {
+      for (seq, request, ctx) in pending {
+        let id = request
+          .as_ref()
+          .map_or(Value::Null, |request| request.id.clone());
+        let _request = log::enter_request(&id);
+
+        let response = serve_request(request, &ctx, &processor);
+        if let Some(seq) = seq {
+          lock(&in_flight)?.remove(seq);
+        }
+        if let Envelope::Err(error) = &response.envelope {
+          log_failure(error);
+        }
+
+        lock(&writer)?.write(&response)?;
+      }
+      Ok(())
+    });
+
+    let read = read_messages(messages, &queue, &in_flight, &writer);
+    drop(queue);
+    let served = worker
+      .join()
+      .unwrap_or_else(|_| Err(IpcError::IoError(io::Error::other("serve worker panicked"))));
+    read.and(served)
+  })
+}
+
+/// Command-line flag that switches an IPC binary into streaming mode
+pub const STREAM_FLAG: &str = "--stream";
+
+/// Whether the current process was asked to stream records, either through
+/// `--stream` or `GUD_IPC_MODE=stream`
+#[must_use]
+pub fn stream_mode_requested() -> bool {
+  std::env::args().skip(1).any(|arg| arg == STREAM_FLAG)
+    || std::env::var(MODE_ENV_VAR).is_ok_and(|mode| mode == "stream")
+}
+
+/// Streaming counterpart to [`handle_context_ipc`] for payloads too large to
+/// hold in memory at once.
+///
+/// Input records are read from stdin one at a time, as NDJSON unless a
+/// `%gud encoding=...` header selects another encoding. Each record is
+/// handed to the processor as soon as it arrives and its [`Envelope`] is
+/// written to fd3 as a record before the next one is read, so neither the
+/// input nor the output is ever held whole. Events emitted through the
+/// [`CallContext`] are written as records ahead of the envelope they belong
+/// to. A failing record is reported on its envelope and the stream goes on;
+/// the header's `deadline_ms` bounds the whole stream.
+pub fn stream_context_ipc<I, O, F>(processor: F) -> Result<(), IpcError>
+where
+  I: for<'de> Deserialize<'de>,
+  O: Serialize,
+  F: Fn(Option<I>, &CallContext) -> Result<O, Box<dyn std::error::Error>>,
+{
+  let (header, records) = match open_stdin_records::<I>() {
+    Ok(opened) => opened,
+    Err(e) => return write_fd3_json(&Envelope::Err(GudError::from(e))),
+  };
+  let writer = reply_writer(header)?;
+
+  let mut ctx = CallContext::new();
+  if let Some(ms) = header.and_then(|header| header.deadline_ms) {
+    ctx = ctx.with_timeout(Duration::from_millis(ms));
+  }
+  let events = Arc::clone(&writer);
+  let ctx = ctx.with_events(Arc::new(move |event: &Event| lock(&events)?.write(event)));
+
+  for record in records {
+    let input = record
+      .map_err(GudError::from)
+      .and_then(|input| ctx.check().map(|()| input));
+    let envelope = match input {
+      Ok(input) => Envelope::from_result(processor(Some(input), &ctx)),
+      Err(error) => Envelope::Err(error),
+    };
+
+    if let Envelope::Err(error) = &envelope {
+      log_failure(error);
+    }
+    lock(&writer)?.write(&envelope)?;
+  }
+
+  Ok(())
+}
+
+/// Server-mode counterpart to [`handle_no_input_ipc`]
+pub fn serve_no_input_ipc<O, F>(processor: F) -> Result<(), IpcError>
+where
+  O: Serialize,
+  F: Fn() -> Result<O, Box<dyn std::error::Error>> + Sync,
+{
+  serve_json_ipc(|_: Option<Value>| processor())
+}
+
+/// Server-mode counterpart to [`handle_required_input_ipc`]
+pub fn serve_required_input_ipc<I, O, F>(processor: F) -> Result<(), IpcError>
+where
+  I: for<'de> Deserialize<'de>,
+  O: Serialize,
+  F: Fn(I) -> Result<O, Box<dyn std::error::Error>> + Sync,
+{
+  serve_json_ipc(|input: Option<I>| {
+    let input = input.ok_or_else(missing_input_error)?;
     processor(input)
   })
 }
 
 /// Debug helper - write to stderr for debugging without interfering with fd3
+#[deprecated(note = "use the leveled functions in `gud_common::log`")]
 pub fn debug_log(message: &str) {
-  eprintln!("[DEBUG] {message}");
+  log::debug(message);
+}
+
+/// Log a request that ended in an error envelope, keeping the error's fields
+/// so the caller can attach them to its own error context
+pub fn log_failure(error: &GudError) {
+  log::log_with(
+    log::Level::Error,
+    "Request failed",
+    serde_json::json!({ "error": error }),
+  );
+}
+
+/// Split a `bin-<module>--<api>` binary name into its module and API names.
+/// Names that don't follow the convention are returned as the API name of an
+/// empty module.
+#[must_use]
+pub fn bin_api_name(bin_name: &str) -> (String, String) {
+  bin_name
+    .strip_prefix("bin-")
+    .and_then(|name| name.split_once("--"))
+    .map_or_else(
+      || (String::new(), bin_name.to_string()),
+      |(module, api)| (module.to_string(), api.to_string()),
+    )
 }
 
-/// Macro to create a simple main function with IPC handling
+/// Run a handler as the whole process.
+///
+/// With `--schema` it prints its schemas, with `--serve` it serves requests
+/// until EOF, with `--stream` it streams records through the handler, and
+/// otherwise it handles a single request.
+///
+/// Every handled request is saved as a [`harness::Fixture`] when
+/// `GUD_RECORD_DIR` is set.
+///
+/// The reply channel is opened before any input is read, so a misconfigured
+/// channel fails fast instead of after the request has been processed.
+pub fn run_handler(handler: &Handler) -> Result<(), IpcError> {
+  run_in(handler, None)
+}
+
+/// Run `module`/`api` from `registry` as the whole process, like
+/// [`run_handler`].
+///
+/// The rest of the registry is available to the handler through
+/// [`CallContext::call`]. An unknown API is reported as an error envelope on
+/// fd3.
+pub fn run_api(registry: &Arc<Registry>, module: &str, api: &str) -> Result<(), IpcError> {
+  match registry.resolve(module, api) {
+    Ok(handler) => run_in(handler, Some(registry)),
+    Err(error) => {
+      log_failure(&error);
+      write_fd3_json(&Envelope::Err(error))
+    }
+  }
+}
+
+fn run_in(handler: &Handler, registry: Option<&Arc<Registry>>) -> Result<(), IpcError> {
+  let processor = |input: Option<Value>, ctx: &CallContext| match registry {
+    Some(registry) => {
+      let ctx = ctx.clone().with_registry(Arc::clone(registry));
+      harness::call_recorded(handler, input, &ctx)
+    }
+    None => harness::call_recorded(handler, input, ctx),
+  };
+
+  if schema_requested() {
+    return print_schema(&handler.schema());
+  }
+
+  let spec = handler.spec();
+  log::set_api(&spec.module, &spec.api);
+  validate_channel()?;
+  if serve_mode_requested() {
+    serve_context_ipc(processor)
+  } else if stream_mode_requested() {
+    stream_context_ipc(processor)
+  } else {
+    handle_context_ipc(processor)
+  }
+}
+
+/// Shared expansion of the `ipc_main*` macros
+#[doc(hidden)]
 #[macro_export]
-macro_rules! ipc_main {
-  ($processor:expr) => {
+macro_rules! __ipc_main_with {
+  ($constructor:ident, $processor:expr) => {
     fn main() {
-      if let Err(e) = gud_common::handle_json_ipc($processor) {
-        gud_common::debug_log(&format!("IPC error: {}", e));
+      let (module, api) = gud_common::bin_api_name(env!("CARGO_BIN_NAME"));
+      let handler =
+        gud_common::Handler::$constructor(&module, &api, env!("CARGO_PKG_VERSION"), $processor);
+
+      if let Err(e) = gud_common::run_handler(&handler) {
+        gud_common::log::error(&format!("IPC error: {}", e));
         std::process::exit(1);
       }
     }
   };
 }
 
+/// Macro to create a simple main function with IPC handling.
+///
+/// The generated binary handles a single request by default, serves requests
+/// until EOF when started with `--serve` (see [`serve_json_ipc`]), and prints
+/// the JSON Schemas of its input and output when started with `--schema`.
+/// Handler failures are reported on fd3 as an [`Envelope`]; the process only
+/// exits with an error when the envelope cannot be delivered.
+#[macro_export]
+macro_rules! ipc_main {
+  ($processor:expr) => {
+    gud_common::__ipc_main_with!(new, $processor);
+  };
+}
+
 /// Macro for no-input handlers
 #[macro_export]
 macro_rules! ipc_main_no_input {
   ($processor:expr) => {
-    fn main() {
-      if let Err(e) = gud_common::handle_no_input_ipc($processor) {
-        gud_common::debug_log(&format!("IPC error: {}", e));
-        std::process::exit(1);
-      }
-    }
+    gud_common::__ipc_main_with!(no_input, $processor);
   };
 }
 
@@ -172,11 +715,144 @@ macro_rules! ipc_main_no_input {
 #[macro_export]
 macro_rules! ipc_main_required_input {
   ($processor:expr) => {
-    fn main() {
-      if let Err(e) = gud_common::handle_required_input_ipc($processor) {
-        gud_common::debug_log(&format!("IPC error: {}", e));
-        std::process::exit(1);
-      }
-    }
+    gud_common::__ipc_main_with!(required_input, $processor);
+  };
+}
+
+/// Macro for handlers that take a [`CallContext`] to honor cancellation and
+/// deadlines or to emit progress events
+#[macro_export]
+macro_rules! ipc_main_with_context {
+  ($processor:expr) => {
+    gud_common::__ipc_main_with!(with_context, $processor);
   };
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn double(input: Option<i64>) -> Result<i64, Box<dyn std::error::Error>> {
+    Ok(input.ok_or("missing number")? * 2)
+  }
+
+  fn serve_line(
+    line: &str,
+    processor: &impl Fn(Option<i64>) -> Result<i64, Box<dyn std::error::Error>>,
+  ) -> ServeResponse {
+    serve_request(
+      serde_json::from_str(line).map_err(IpcError::from),
+      &CallContext::new(),
+      &|input: Option<i64>, _: &CallContext| processor(input),
+    )
+  }
+
+  #[test]
+  fn serve_line_echoes_correlation_id() {
+    let response = serve_line(r#"{"id": "abc", "input": 21}"#, &double);
+    assert_eq!(response.id, json!("abc"));
+    assert_eq!(response.envelope, Envelope::Ok(json!(42)));
+  }
+
+  #[test]
+  fn serve_line_reports_processor_errors() {
+    let response = serve_line(r#"{"id": 7}"#, &double);
+    assert_eq!(response.id, json!(7));
+    assert_eq!(
+      serde_json::to_value(&response).unwrap(),
+      json!({
+        "id": 7,
+        "ok": false,
+        "error": {
+          "kind": "Unknown",
+          "code": "handler-error",
+          "message": "missing number",
+          "context": null,
+        },
+      })
+    );
+  }
+
+  #[test]
+  fn serve_line_reports_malformed_requests() {
+    let response = serve_line("not json", &double);
+    assert_eq!(response.id, Value::Null);
+    let Envelope::Err(error) = response.envelope else {
+      panic!("expected an error envelope");
+    };
+    assert_eq!(error.kind, ErrorKind::Parsing);
+    assert_eq!(error.code, "invalid-json");
+  }
+
+  #[test]
+  fn serve_messages_distinguish_cancels_from_requests() {
+    let cancel: ServeMessage = serde_json::from_str(r#"{"cancel": "abc"}"#).unwrap();
+    assert!(matches!(cancel, ServeMessage::Cancel(CancelRequest { cancel }) if cancel == "abc"));
+
+    let request: ServeMessage =
+      serde_json::from_str(r#"{"id": 1, "input": {"cancel": 2}, "deadline_ms": 50}"#).unwrap();
+    let ServeMessage::Request(request) = request else {
+      panic!("expected a request");
+    };
+    assert_eq!(request.deadline_ms, Some(50));
+  }
+
+  #[test]
+  fn serve_request_skips_cancelled_requests() {
+    let token = CancellationToken::new();
+    token.cancel();
+    let ctx = CallContext::new().with_cancellation(token);
+
+    let response = serve_request(
+      serde_json::from_str(r#"{"id": 3, "input": 1}"#).map_err(IpcError::from),
+      &ctx,
+      &|_: Option<i64>, _: &CallContext| -> Result<i64, Box<dyn std::error::Error>> {
+        panic!("cancelled requests must not run")
+      },
+    );
+    let Envelope::Err(error) = response.envelope else {
+      panic!("expected an error envelope");
+    };
+    assert_eq!(error.kind, ErrorKind::Task);
+    assert_eq!(error.code, "cancelled");
+  }
+
+  #[test]
+  fn in_flight_requests_sharing_an_id_keep_their_tokens() {
+    let mut in_flight = InFlight::default();
+    let (first, second) = (CancellationToken::new(), CancellationToken::new());
+    let first_seq = in_flight.insert(Value::Null, first.clone());
+    in_flight.insert(Value::Null, second.clone());
+
+    in_flight.remove(first_seq);
+    assert!(in_flight.cancel(&Value::Null));
+    assert!(second.is_cancelled());
+    assert!(!first.is_cancelled());
+    assert!(!in_flight.cancel(&json!("unknown")));
+  }
+
+  #[test]
+  fn serve_events_carry_the_request_id() {
+    let event = ServeEvent {
+      id: json!("abc"),
+      event: Event {
+        event: crate::context::EventKind::Progress,
+        data: json!({"done": 1, "total": 4}),
+      },
+    };
+    assert_eq!(
+      serde_json::to_value(event).unwrap(),
+      json!({"id": "abc", "event": "progress", "data": {"done": 1, "total": 4}})
+    );
+  }
+
+  #[test]
+  fn bin_api_name_splits_conventional_names() {
+    assert_eq!(
+      bin_api_name("bin-ast--to_tree"),
+      ("ast".to_string(), "to_tree".to_string())
+    );
+    assert_eq!(bin_api_name("gud"), (String::new(), "gud".to_string()));
+  }
+}
@@ -3,7 +3,35 @@
 //! This crate provides shared functionality including IPC utilities
 //! and common data structures used across the Kleptool ecosystem.
 
+/// Configurable reply channel (fd 3, pipe, socket or framed stdout)
+pub mod channel;
+/// Payload encodings and incremental record streams
+pub mod codec;
+/// Cancellation, deadlines and progress events for running requests
+pub mod context;
+/// Structured errors and response envelopes
+pub mod error;
+/// C ABI for calling registered handlers in-process, e.g. through Bun's FFI
+pub mod ffi;
+/// Recording, replay and fd 3 drivers for testing IPC handlers
+pub mod harness;
 /// IPC (Inter-Process Communication) utilities
 pub mod ipc;
+/// Leveled, structured logging to stderr
+pub mod log;
+/// Named handler registry used for dispatching APIs by module and name
+pub mod registry;
+/// JSON Schema introspection for registered APIs
+pub mod schema;
 
+pub use channel::*;
+pub use codec::*;
+pub use context::*;
+pub use error::*;
+pub use ffi::gud_free;
 pub use ipc::*;
+pub use registry::*;
+pub use schema::*;
+
+#[doc(hidden)]
+pub use paste::paste as __paste;
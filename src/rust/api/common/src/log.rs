@@ -0,0 +1,322 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::{OnceLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable holding the most verbose level to log: `off`,
+/// `error`, `warn` (the default), `info`, `debug` or `trace`
+pub const LOG_ENV_VAR: &str = "GUD_LOG";
+
+/// Environment variable selecting the record format: `json` (the default)
+/// for one JSON object per line, or `text` for humans
+pub const LOG_FORMAT_ENV_VAR: &str = "GUD_LOG_FORMAT";
+
+/// Severity of a log record, from most to least severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+  /// The request or process failed
+  Error,
+  /// Something unexpected that the process recovered from
+  Warn,
+  /// Coarse progress
+  Info,
+  /// Details useful when debugging a handler
+  Debug,
+  /// Very fine-grained details
+  Trace,
+}
+
+impl Level {
+  /// Parse a level name as accepted by [`LOG_ENV_VAR`]
+  #[must_use]
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name.to_ascii_lowercase().as_str() {
+      "error" => Some(Self::Error),
+      "warn" | "warning" => Some(Self::Warn),
+      "info" => Some(Self::Info),
+      "debug" => Some(Self::Debug),
+      "trace" => Some(Self::Trace),
+      _ => None,
+    }
+  }
+
+  const fn name(self) -> &'static str {
+    match self {
+      Self::Error => "ERROR",
+      Self::Warn => "WARN",
+      Self::Info => "INFO",
+      Self::Debug => "DEBUG",
+      Self::Trace => "TRACE",
+    }
+  }
+}
+
+/// Output format of log records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  /// One [`LogRecord`] serialized as JSON per line
+  Json,
+  /// `[LEVEL module/api #id] message {fields}`
+  Text,
+}
+
+/// A single structured log line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogRecord {
+  /// Milliseconds since the Unix epoch
+  pub ts: u64,
+  /// Severity
+  pub level: Level,
+  /// Human readable description
+  pub message: String,
+  /// Module of the API being run, e.g. `ast`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub module: Option<String>,
+  /// Name of the API being run, e.g. `to_tree`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub api: Option<String>,
+  /// Correlation ID of the server-mode request being handled
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub request_id: Option<Value>,
+  /// Additional structured details
+  #[serde(default, skip_serializing_if = "Value::is_null")]
+  pub fields: Value,
+}
+
+impl LogRecord {
+  /// Render the record as a single line without the trailing newline
+  #[must_use]
+  pub fn render(&self, format: Format) -> String {
+    match format {
+      Format::Json => serde_json::to_string(self).unwrap_or_else(|_| self.message.clone()),
+      Format::Text => {
+        use std::fmt::Write as _;
+
+        let mut line = format!("[{}", self.level.name());
+        if let (Some(module), Some(api)) = (&self.module, &self.api) {
+          let _ = write!(line, " {module}/{api}");
+        }
+        if let Some(id) = &self.request_id {
+          let _ = write!(line, " #{id}");
+        }
+        let _ = write!(line, "] {}", self.message);
+        if !self.fields.is_null() {
+          let _ = write!(line, " {}", self.fields);
+        }
+        line
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Config {
+  max_level: Option<Level>,
+  format: Format,
+}
+
+fn config() -> Config {
+  static CONFIG: OnceLock<Config> = OnceLock::new();
+  *CONFIG.get_or_init(|| Config {
+    max_level: std::env::var(LOG_ENV_VAR).map_or(Some(Level::Warn), |level| {
+      if level.eq_ignore_ascii_case("off") {
+        None
+      } else {
+        Level::from_name(&level).or(Some(Level::Warn))
+      }
+    }),
+    format: match std::env::var(LOG_FORMAT_ENV_VAR).as_deref() {
+      Ok("text") => Format::Text,
+      _ => Format::Json,
+    },
+  })
+}
+
+/// The API a process runs is shared by all its threads
+static API: RwLock<Option<(String, String)>> = RwLock::new(None);
+
+thread_local! {
+  /// A request only ever runs on one thread, so records logged by other
+  /// threads, such as the one reading cancellations, are not tagged with it
+  static REQUEST_ID: RefCell<Option<Value>> = const { RefCell::new(None) };
+}
+
+/// Tag every following record with the API this process is running
+pub fn set_api(module: &str, api: &str) {
+  if let Ok(mut scope) = API.write() {
+    *scope = Some((module.to_string(), api.to_string()));
+  }
+}
+
+/// Guard returned by [`enter_request`]; records stop carrying the request ID
+/// once it is dropped
+#[must_use = "the request ID is cleared when the guard is dropped"]
+pub struct RequestGuard(());
+
+impl Drop for RequestGuard {
+  fn drop(&mut self) {
+    REQUEST_ID.set(None);
+  }
+}
+
+/// Tag every record this thread logs until the guard drops with a request's
+/// correlation ID
+pub fn enter_request(id: &Value) -> RequestGuard {
+  REQUEST_ID.set(Some(id.clone()));
+  RequestGuard(())
+}
+
+fn current_request() -> Option<Value> {
+  REQUEST_ID.with_borrow(Clone::clone)
+}
+
+/// Whether records of `level` are written
+#[must_use]
+pub fn enabled(level: Level) -> bool {
+  config().max_level.is_some_and(|max| level <= max)
+}
+
+/// Log a message with structured fields
+pub fn log_with(level: Level, message: &str, fields: Value) {
+  if !enabled(level) {
+    return;
+  }
+
+  let ts = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |elapsed| {
+      u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX)
+    });
+  let mut record = LogRecord {
+    ts,
+    level,
+    message: message.to_string(),
+    module: None,
+    api: None,
+    request_id: None,
+    fields,
+  };
+  if let Some((module, api)) = API.read().ok().as_deref().and_then(Option::as_ref) {
+    record.module = Some(module.clone());
+    record.api = Some(api.clone());
+  }
+  record.request_id = current_request();
+
+  // Logging must never take down a request, so write failures are ignored
+  let _ = writeln!(
+    std::io::stderr().lock(),
+    "{}",
+    record.render(config().format)
+  );
+}
+
+/// Log a message
+pub fn log(level: Level, message: &str) {
+  log_with(level, message, Value::Null);
+}
+
+/// Log at [`Level::Error`]
+pub fn error(message: &str) {
+  log(Level::Error, message);
+}
+
+/// Log at [`Level::Warn`]
+pub fn warn(message: &str) {
+  log(Level::Warn, message);
+}
+
+/// Log at [`Level::Info`]
+pub fn info(message: &str) {
+  log(Level::Info, message);
+}
+
+/// Log at [`Level::Debug`]
+pub fn debug(message: &str) {
+  log(Level::Debug, message);
+}
+
+/// Log at [`Level::Trace`]
+pub fn trace(message: &str) {
+  log(Level::Trace, message);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn record() -> LogRecord {
+    LogRecord {
+      ts: 1,
+      level: Level::Warn,
+      message: "Request failed".to_string(),
+      module: Some("ast".to_string()),
+      api: Some("to_tree".to_string()),
+      request_id: Some(json!(7)),
+      fields: json!({"code": "missing-input"}),
+    }
+  }
+
+  #[test]
+  fn levels_are_ordered_by_verbosity() {
+    assert!(Level::Error < Level::Warn);
+    assert!(Level::Debug < Level::Trace);
+    assert_eq!(Level::from_name("WARNING"), Some(Level::Warn));
+    assert_eq!(Level::from_name("loud"), None);
+  }
+
+  #[test]
+  fn renders_json_lines() {
+    let line = record().render(Format::Json);
+    assert_eq!(
+      serde_json::from_str::<Value>(&line).unwrap(),
+      json!({
+        "ts": 1,
+        "level": "warn",
+        "message": "Request failed",
+        "module": "ast",
+        "api": "to_tree",
+        "request_id": 7,
+        "fields": {"code": "missing-input"},
+      })
+    );
+  }
+
+  #[test]
+  fn json_lines_omit_missing_scope() {
+    let record = LogRecord {
+      module: None,
+      api: None,
+      request_id: None,
+      fields: Value::Null,
+      ..record()
+    };
+    let line = record.render(Format::Json);
+    assert_eq!(
+      line,
+      r#"{"ts":1,"level":"warn","message":"Request failed"}"#
+    );
+  }
+
+  #[test]
+  fn renders_text_lines() {
+    assert_eq!(
+      record().render(Format::Text),
+      r#"[WARN ast/to_tree #7] Request failed {"code":"missing-input"}"#
+    );
+  }
+
+  #[test]
+  fn requests_are_scoped_to_their_thread() {
+    let guard = enter_request(&json!(7));
+    assert_eq!(current_request(), Some(json!(7)));
+    std::thread::spawn(|| assert_eq!(current_request(), None))
+      .join()
+      .unwrap();
+    drop(guard);
+    assert_eq!(current_request(), None);
+  }
+}
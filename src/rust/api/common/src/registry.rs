@@ -0,0 +1,394 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::context::CallContext;
+use crate::error::{Envelope, ErrorKind, GudError};
+use crate::ipc::IpcError;
+use crate::schema::{schema_of, ApiSchema};
+
+/// Type-erased processor stored in a [`Registry`]
+pub type DynProcessor = Box<
+  dyn Fn(Option<Value>, &CallContext) -> Result<Value, Box<dyn std::error::Error>> + Send + Sync,
+>;
+
+/// Fully qualified API name written as `module/api`, e.g. `ast/to_tree`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ApiName {
+  /// Module the API belongs to
+  pub module: String,
+  /// Name of the API within its module
+  pub api: String,
+}
+
+impl ApiName {
+  /// Build a name from its parts
+  pub fn new(module: impl Into<String>, api: impl Into<String>) -> Self {
+    Self {
+      module: module.into(),
+      api: api.into(),
+    }
+  }
+}
+
+impl std::str::FromStr for ApiName {
+  type Err = GudError;
+
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    match name.split_once('/') {
+      Some((module, api)) if !module.is_empty() && !api.is_empty() && !api.contains('/') => {
+        Ok(Self::new(module, api))
+      }
+      _ => Err(
+        GudError::new(
+          ErrorKind::Argument,
+          "invalid-api-name",
+          format!("Expected an API name of the form module/api, got '{name}'"),
+        )
+        .with_context(serde_json::json!({ "name": name })),
+      ),
+    }
+  }
+}
+
+impl std::fmt::Display for ApiName {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}/{}", self.module, self.api)
+  }
+}
+
+impl Serialize for ApiName {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
+}
+
+impl<'de> Deserialize<'de> for ApiName {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    String::deserialize(deserializer)?
+      .parse()
+      .map_err(serde::de::Error::custom)
+  }
+}
+
+impl JsonSchema for ApiName {
+  fn schema_name() -> std::borrow::Cow<'static, str> {
+    "ApiName".into()
+  }
+
+  fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+    schemars::json_schema!({
+      "type": "string",
+      "pattern": "^[^/]+/[^/]+$",
+      "description": "Registered API written as module/api",
+    })
+  }
+}
+
+/// Description of a registered API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiSpec {
+  /// Module the API belongs to, e.g. `std`
+  pub module: String,
+  /// Name of the API within its module, e.g. `identity`
+  pub api: String,
+  /// Version of the crate providing the API
+  pub version: String,
+  /// JSON Schema of the accepted input
+  pub input: Value,
+  /// JSON Schema of the produced output
+  pub output: Value,
+}
+
+/// A named processor that can be dispatched through a [`Registry`]
+pub struct Handler {
+  spec: ApiSpec,
+  processor: DynProcessor,
+}
+
+impl Handler {
+  /// Wrap a processor with optional input, the same shape accepted by
+  /// [`crate::handle_json_ipc`]
+  pub fn new<I, O, F>(module: &str, api: &str, version: &str, processor: F) -> Self
+  where
+    I: for<'de> Deserialize<'de> + JsonSchema + 'static,
+    O: Serialize + JsonSchema + 'static,
+    F: Fn(Option<I>) -> Result<O, Box<dyn std::error::Error>> + Send + Sync + 'static,
+  {
+    Self::with_context(module, api, version, move |input, _: &CallContext| {
+      processor(input)
+    })
+  }
+
+  /// Wrap a processor that observes its [`CallContext`] to honor
+  /// cancellation and deadlines or to emit progress events
+  pub fn with_context<I, O, F>(module: &str, api: &str, version: &str, processor: F) -> Self
+  where
+    I: for<'de> Deserialize<'de> + JsonSchema + 'static,
+    O: Serialize + JsonSchema + 'static,
+    F: Fn(Option<I>, &CallContext) -> Result<O, Box<dyn std::error::Error>> + Send + Sync + 'static,
+  {
+    Self {
+      spec: ApiSpec {
+        module: module.to_string(),
+        api: api.to_string(),
+        version: version.to_string(),
+        input: schema_of::<Option<I>>(),
+        output: schema_of::<O>(),
+      },
+      processor: Box::new(move |input, ctx| {
+        let input = input
+          .map(serde_json::from_value::<I>)
+          .transpose()
+          .map_err(IpcError::from)?;
+        Ok(serde_json::to_value(processor(input, ctx)?)?)
+      }),
+    }
+  }
+
+  /// Wrap a processor that takes no input, as accepted by
+  /// [`crate::handle_no_input_ipc`]
+  pub fn no_input<O, F>(module: &str, api: &str, version: &str, processor: F) -> Self
+  where
+    O: Serialize + JsonSchema + 'static,
+    F: Fn() -> Result<O, Box<dyn std::error::Error>> + Send + Sync + 'static,
+  {
+    let mut handler = Self::new(module, api, version, move |_: Option<Value>| processor());
+    handler.spec.input = schema_of::<()>();
+    handler
+  }
+
+  /// Wrap a processor that requires input, as accepted by
+  /// [`crate::handle_required_input_ipc`]
+  pub fn required_input<I, O, F>(module: &str, api: &str, version: &str, processor: F) -> Self
+  where
+    I: for<'de> Deserialize<'de> + JsonSchema + 'static,
+    O: Serialize + JsonSchema + 'static,
+    F: Fn(I) -> Result<O, Box<dyn std::error::Error>> + Send + Sync + 'static,
+  {
+    let mut handler = Self::new(module, api, version, move |input: Option<I>| {
+      let input = input.ok_or_else(crate::ipc::missing_input_error)?;
+      processor(input)
+    });
+    handler.spec.input = schema_of::<I>();
+    handler
+  }
+
+  /// Context-aware counterpart to [`Self::required_input`]
+  pub fn required_input_with_context<I, O, F>(
+    module: &str,
+    api: &str,
+    version: &str,
+    processor: F,
+  ) -> Self
+  where
+    I: for<'de> Deserialize<'de> + JsonSchema + 'static,
+    O: Serialize + JsonSchema + 'static,
+    F: Fn(I, &CallContext) -> Result<O, Box<dyn std::error::Error>> + Send + Sync + 'static,
+  {
+    let mut handler = Self::with_context(module, api, version, move |input: Option<I>, ctx| {
+      let input = input.ok_or_else(crate::ipc::missing_input_error)?;
+      processor(input, ctx)
+    });
+    handler.spec.input = schema_of::<I>();
+    handler
+  }
+
+  /// Description of this handler
+  #[must_use]
+  pub const fn spec(&self) -> &ApiSpec {
+    &self.spec
+  }
+
+  /// JSON Schemas of this handler's input and output
+  #[must_use]
+  pub fn schema(&self) -> ApiSchema {
+    ApiSchema {
+      input: self.spec.input.clone(),
+      output: self.spec.output.clone(),
+    }
+  }
+
+  /// Run the processor on a JSON input with a default [`CallContext`]
+  pub fn call(&self, input: Option<Value>) -> Result<Value, Box<dyn std::error::Error>> {
+    self.call_with(input, &CallContext::default())
+  }
+
+  /// Run the processor on a JSON input within the given context
+  pub fn call_with(
+    &self,
+    input: Option<Value>,
+    ctx: &CallContext,
+  ) -> Result<Value, Box<dyn std::error::Error>> {
+    (self.processor)(input, ctx)
+  }
+
+  /// Run the processor and wrap its result in an [`Envelope`]
+  #[must_use]
+  pub fn call_enveloped(&self, input: Option<Value>) -> Envelope {
+    Envelope::from_result(self.call(input))
+  }
+}
+
+/// Collection of handlers addressable by `module` and `api` name
+#[derive(Default)]
+pub struct Registry {
+  handlers: BTreeMap<(String, String), Handler>,
+}
+
+impl Registry {
+  /// Create an empty registry
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Add a handler, replacing any handler already registered under the same
+  /// module and API name
+  pub fn register(&mut self, handler: Handler) -> &mut Self {
+    let key = (handler.spec.module.clone(), handler.spec.api.clone());
+    self.handlers.insert(key, handler);
+    self
+  }
+
+  /// Look up a handler by module and API name
+  #[must_use]
+  pub fn get(&self, module: &str, api: &str) -> Option<&Handler> {
+    self.handlers.get(&(module.to_string(), api.to_string()))
+  }
+
+  /// Look up a handler by its `module/api` name, reporting an `unknown-api`
+  /// error when it is missing
+  pub fn resolve_name(&self, name: &ApiName) -> Result<&Handler, GudError> {
+    self.resolve(&name.module, &name.api)
+  }
+
+  /// Look up a handler, reporting an `unknown-api` error when it is missing
+  pub fn resolve(&self, module: &str, api: &str) -> Result<&Handler, GudError> {
+    self.get(module, api).ok_or_else(|| {
+      GudError::new(
+        ErrorKind::Argument,
+        "unknown-api",
+        format!("No API registered as {module}/{api}"),
+      )
+      .with_context(serde_json::json!({ "module": module, "api": api }))
+    })
+  }
+
+  /// Descriptions of every registered handler, ordered by module then API
+  #[must_use]
+  pub fn list(&self) -> Vec<&ApiSpec> {
+    self.handlers.values().map(Handler::spec).collect()
+  }
+
+  /// Number of registered handlers
+  #[must_use]
+  pub fn len(&self) -> usize {
+    self.handlers.len()
+  }
+
+  /// Whether no handlers are registered
+  #[must_use]
+  pub fn is_empty(&self) -> bool {
+    self.handlers.is_empty()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[allow(clippy::unnecessary_wraps)]
+  fn negate(input: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    Ok(!input)
+  }
+
+  fn registry() -> Registry {
+    let mut registry = Registry::new();
+    registry
+      .register(Handler::required_input("logic", "not", "1.0.0", negate))
+      .register(Handler::no_input("logic", "truth", "1.0.0", || Ok(true)));
+    registry
+  }
+
+  #[test]
+  fn dispatches_registered_handlers() {
+    let registry = registry();
+    let handler = registry.resolve("logic", "not").unwrap();
+    assert_eq!(handler.call(Some(json!(true))).unwrap(), json!(false));
+  }
+
+  #[test]
+  fn lists_handlers_in_order() {
+    let registry = registry();
+    let names: Vec<_> = registry
+      .list()
+      .iter()
+      .map(|spec| spec.api.as_str())
+      .collect();
+    assert_eq!(names, ["not", "truth"]);
+    assert_eq!(registry.list()[0].input["type"], json!("boolean"));
+    assert_eq!(registry.list()[1].input["type"], json!("null"));
+    assert_eq!(registry.list()[1].output["type"], json!("boolean"));
+  }
+
+  #[test]
+  fn reports_unknown_apis() {
+    let error = registry().resolve("logic", "xor").err().unwrap();
+    assert_eq!(error.code, "unknown-api");
+  }
+
+  #[test]
+  fn reports_missing_required_input() {
+    let envelope = registry()
+      .resolve("logic", "not")
+      .unwrap()
+      .call_enveloped(None);
+    let Envelope::Err(error) = envelope else {
+      panic!("expected an error envelope");
+    };
+    assert_eq!(error.code, "missing-input");
+  }
+
+  #[test]
+  fn context_aware_handlers_observe_cancellation() {
+    let handler = Handler::with_context(
+      "logic",
+      "wait",
+      "1.0.0",
+      |_: Option<Value>, ctx: &CallContext| Ok(ctx.check()?),
+    );
+    assert!(handler.call(None).is_ok());
+
+    let token = crate::context::CancellationToken::new();
+    token.cancel();
+    let ctx = CallContext::new().with_cancellation(token);
+    let error = GudError::from_boxed(handler.call_with(None, &ctx).unwrap_err());
+    assert_eq!(error.code, "cancelled");
+  }
+
+  #[test]
+  fn api_names_round_trip_as_strings() {
+    let name: ApiName = serde_json::from_value(json!("ast/to_tree")).unwrap();
+    assert_eq!(name, ApiName::new("ast", "to_tree"));
+    assert_eq!(serde_json::to_value(&name).unwrap(), json!("ast/to_tree"));
+
+    for bad in ["to_tree", "/to_tree", "ast/", "a/b/c"] {
+      assert!(bad.parse::<ApiName>().is_err(), "{bad}");
+    }
+  }
+
+  #[test]
+  fn reports_mistyped_input() {
+    let envelope = registry()
+      .resolve("logic", "not")
+      .unwrap()
+      .call_enveloped(Some(json!(1)));
+    let Envelope::Err(error) = envelope else {
+      panic!("expected an error envelope");
+    };
+    assert_eq!(error.kind, ErrorKind::Parsing);
+  }
+}
@@ -0,0 +1,57 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::ipc::IpcError;
+
+/// Command-line flag that makes an IPC binary print its JSON Schemas instead
+/// of handling a request
+pub const SCHEMA_FLAG: &str = "--schema";
+
+/// JSON Schemas describing the contract of a single API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiSchema {
+  /// Schema of the request payload
+  pub input: Value,
+  /// Schema of the `value` carried by a successful response envelope
+  pub output: Value,
+}
+
+/// Generate the JSON Schema for a type
+#[must_use]
+pub fn schema_of<T: JsonSchema>() -> Value {
+  schemars::schema_for!(T).to_value()
+}
+
+/// Whether the current process was started with `--schema`
+#[must_use]
+pub fn schema_requested() -> bool {
+  std::env::args().skip(1).any(|arg| arg == SCHEMA_FLAG)
+}
+
+/// Print a schema to stdout as pretty JSON
+pub fn print_schema(schema: &ApiSchema) -> Result<(), IpcError> {
+  println!("{}", serde_json::to_string_pretty(schema)?);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[derive(JsonSchema)]
+  #[allow(dead_code)]
+  struct Probe {
+    name: String,
+    depth: Option<usize>,
+  }
+
+  #[test]
+  fn schema_of_describes_struct_fields() {
+    let schema = schema_of::<Probe>();
+    assert_eq!(schema["type"], json!("object"));
+    assert_eq!(schema["required"], json!(["name"]));
+    assert!(schema["properties"]["depth"].is_object());
+  }
+}
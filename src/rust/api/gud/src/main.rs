@@ -0,0 +1,82 @@
+//! Single dispatcher binary for every gud API.
+//!
+//! `gud <module> <api>` runs a registered API over the stdin/fd3 protocol,
+//! including `--serve`, `--stream` and `--schema`. `gud list` writes the
+//! registered APIs and their JSON Schemas to fd3, and `gud help` prints them
+//! for humans.
+
+use gud_common::{log, run_api, write_fd3_json, Envelope, IpcError, Registry};
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Build a registry containing every API linked into this binary
+fn registry() -> Registry {
+  let mut registry = Registry::new();
+  gud_std::register(&mut registry);
+  gud_ast::register(&mut registry);
+  gud_astar::register(&mut registry);
+  registry
+}
+
+fn usage(registry: &Registry) -> String {
+  let mut usage = String::from(
+    "Usage:\n  gud <module> <api> [--serve | --stream | --schema]\n  gud list\n  gud help\n\nAvailable APIs:\n",
+  );
+  for spec in registry.list() {
+    let _ = writeln!(usage, "  {} {} (v{})", spec.module, spec.api, spec.version);
+  }
+  usage
+}
+
+fn list(registry: &Registry) -> Result<(), IpcError> {
+  let specs = serde_json::to_value(registry.list())?;
+  write_fd3_json(&Envelope::Ok(specs))
+}
+
+fn main() {
+  let registry = Arc::new(registry());
+  let args: Vec<String> = std::env::args()
+    .skip(1)
+    .filter(|arg| !arg.starts_with("--"))
+    .collect();
+
+  let result = match args.as_slice() {
+    [command] if command == "help" => {
+      print!("{}", usage(&registry));
+      Ok(())
+    }
+    [command] if command == "list" => list(&registry),
+    [module, api] => run_api(&registry, module, api),
+    _ => {
+      eprint!("{}", usage(&registry));
+      std::process::exit(2);
+    }
+  };
+
+  if let Err(e) = result {
+    log::error(&format!("IPC error: {e}"));
+    std::process::exit(1);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn every_registered_api_publishes_schemas() {
+    let registry = registry();
+    assert!(!registry.is_empty());
+
+    for spec in registry.list() {
+      for schema in [&spec.input, &spec.output] {
+        assert!(
+          schema.get("$schema").is_some(),
+          "{}/{} is missing a JSON Schema",
+          spec.module,
+          spec.api
+        );
+      }
+    }
+  }
+}
@@ -0,0 +1,118 @@
+//! Golden tests replaying the recorded requests of every gud crate through
+//! the compiled `gud` dispatcher over a real fd 3 pipe.
+//!
+//! The crates replay the same fixtures in-process; this checks that
+//! `gud <module> <api>` reaches each of them. The dispatcher's own fixtures
+//! need the full registry, e.g. pipelines that compose across modules.
+
+#![cfg(unix)]
+
+use gud_common::harness::{fixture_paths, replay_binary, run_binary, Fixture};
+use gud_common::{Envelope, ServeResponse};
+use serde_json::json;
+
+const GUD: &str = env!("CARGO_BIN_EXE_gud");
+
+/// Fixture directories of `gud` and the crates linked into it
+const FIXTURES: &[&str] = &[
+  concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures"),
+  concat!(env!("CARGO_MANIFEST_DIR"), "/../std/tests/fixtures"),
+  concat!(env!("CARGO_MANIFEST_DIR"), "/../ast/tests/fixtures"),
+];
+
+#[test]
+fn fixtures_replay_through_the_dispatcher() {
+  for dir in FIXTURES {
+    for path in fixture_paths(dir).unwrap() {
+      let fixture = Fixture::load(&path).unwrap();
+      if let Err(e) = replay_binary(GUD, &[&fixture.module, &fixture.api], &fixture) {
+        panic!("{}: {e}", path.display());
+      }
+    }
+  }
+}
+
+#[test]
+fn dispatcher_serves_requests_until_eof() {
+  let run = run_binary(
+    GUD,
+    &["std", "identity", "--serve"],
+    b"{\"id\": 1, \"input\": \"a\"}\n{\"id\": 2}\n",
+  )
+  .unwrap();
+  assert!(run.status.success());
+
+  let responses: Vec<ServeResponse> = run.records().unwrap();
+  let ids: Vec<_> = responses
+    .iter()
+    .map(|response| response.id.clone())
+    .collect();
+  assert_eq!(ids, [json!(1), json!(2)]);
+  assert_eq!(responses[0].envelope, Envelope::Ok(json!("a")));
+}
+
+#[test]
+fn dispatcher_streams_records_through_any_api() {
+  let run = run_binary(
+    GUD,
+    &["std", "canonicalize", "--stream"],
+    b"{\"b\": 1.0, \"a\": [2e0]}\nnot json\n\"x\"\n",
+  )
+  .unwrap();
+  assert!(run.status.success());
+
+  let envelopes: Vec<Envelope> = run.records().unwrap();
+  assert_eq!(envelopes.len(), 3);
+  assert_eq!(envelopes[0], Envelope::Ok(json!(r#"{"a":[2],"b":1}"#)));
+  let Envelope::Err(error) = &envelopes[1] else {
+    panic!("expected the malformed record to fail on its own");
+  };
+  assert_eq!(error.code, "invalid-json");
+  assert_eq!(envelopes[2], Envelope::Ok(json!(r#""x""#)));
+}
+
+#[test]
+fn dispatcher_serves_the_deepest_accepted_tree() {
+  let nested = |depth: usize| {
+    let source = format!("x = {}{}\n", "[".repeat(depth), "]".repeat(depth));
+    json!({"id": depth, "input": {"language": "python", "source_code": source}}).to_string()
+  };
+  // The module, statement and assignment sit above the outer list, which
+  // sits at depth 3
+  let deepest = gud_ast::to_tree::MAX_TREE_DEPTH - 2;
+  let stdin = format!("{}\n{}\n", nested(deepest), nested(deepest + 1));
+  let run = run_binary(GUD, &["ast", "to_tree", "--serve"], stdin.as_bytes()).unwrap();
+  assert!(run.status.success());
+
+  // The accepted tree nests deeper than serde_json reads by default
+  let lines: Vec<&[u8]> = run.reply.trim_ascii().split(|&b| b == b'\n').collect();
+  assert_eq!(lines.len(), 2);
+  let accepted = format!(r#"{{"id":{deepest},"ok":true,"#);
+  assert!(lines[0].starts_with(accepted.as_bytes()));
+  let rejected: ServeResponse = serde_json::from_slice(lines[1]).unwrap();
+  let Envelope::Err(error) = &rejected.envelope else {
+    panic!("expected the deeper tree to be rejected");
+  };
+  assert_eq!(error.code, "tree-too-deep");
+}
+
+#[test]
+fn dispatcher_lists_every_module() {
+  let run = run_binary(GUD, &["list"], b"").unwrap();
+  assert!(run.status.success());
+
+  let Envelope::Ok(specs) = run.envelope().unwrap() else {
+    panic!("expected a list of APIs");
+  };
+  for name in ["std/compose", "ast/to_tree", "ast/to_manifest"] {
+    let (module, api) = name.split_once('/').unwrap();
+    assert!(
+      specs
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|spec| spec["module"] == module && spec["api"] == api),
+      "{name} is not listed"
+    );
+  }
+}
@@ -1,5 +0,0 @@
-#![allow(missing_docs)]
-
-fn main() {
-  println!("Hello, world!");
-}
@@ -0,0 +1,189 @@
+//! Canonical JSON and content digests.
+//!
+//! The canonical form follows RFC 8785 (JSON Canonicalization Scheme): no
+//! insignificant whitespace, object keys sorted by their UTF-16 code units,
+//! strings escaped minimally and numbers written the way ECMAScript prints
+//! them, so `1.0`, `1` and `1e0` all become `1`. Every number is read as an
+//! IEEE 754 double first, so integers beyond 2^53 are rounded to the nearest
+//! double, e.g. `18446744073709551615` becomes `18446744073709552000`.
+
+use serde_json::{Number, Value};
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+
+/// Prefix of the digests returned by [`digest`]
+pub const DIGEST_ALGORITHM: &str = "sha256";
+
+/// Format a finite double as ECMAScript's `Number.prototype.toString` does
+fn write_float(out: &mut String, value: f64) {
+  if value == 0.0 {
+    out.push('0');
+    return;
+  }
+
+  // `{:e}` yields the shortest digits that round-trip, e.g. `1.5e-7`
+  let scientific = format!("{:e}", value.abs());
+  let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+  let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+  let exponent: i64 = exponent.parse().unwrap_or(0);
+  let length = i64::try_from(digits.len()).unwrap_or(i64::MAX);
+  // Position of the decimal point relative to the start of `digits`
+  let point = exponent + 1;
+
+  if value < 0.0 {
+    out.push('-');
+  }
+  if length <= point && point <= 21 {
+    out.push_str(&digits);
+    out.extend(std::iter::repeat_n(
+      '0',
+      usize::try_from(point - length).unwrap_or(0),
+    ));
+  } else if 0 < point && point <= 21 {
+    let (whole, fraction) = digits.split_at(usize::try_from(point).unwrap_or(0));
+    let _ = write!(out, "{whole}.{fraction}");
+  } else if -6 < point && point <= 0 {
+    out.push_str("0.");
+    out.extend(std::iter::repeat_n(
+      '0',
+      usize::try_from(-point).unwrap_or(0),
+    ));
+    out.push_str(&digits);
+  } else {
+    let (first, rest) = digits.split_at(1);
+    out.push_str(first);
+    if !rest.is_empty() {
+      let _ = write!(out, ".{rest}");
+    }
+    let sign = if point > 0 { '+' } else { '-' };
+    let _ = write!(out, "e{sign}{}", (point - 1).abs());
+  }
+}
+
+fn write_number(out: &mut String, number: &Number) {
+  if let Some(n) = number.as_f64() {
+    write_float(out, n);
+  }
+}
+
+fn write_string(out: &mut String, text: &str) {
+  // serde_json escapes exactly the characters RFC 8785 requires, using the
+  // short forms and lowercase `\u00xx` escapes
+  out.push_str(&serde_json::to_string(text).unwrap_or_default());
+}
+
+fn write_value(out: &mut String, value: &Value) {
+  match value {
+    Value::Null => out.push_str("null"),
+    Value::Bool(flag) => out.push_str(if *flag { "true" } else { "false" }),
+    Value::Number(number) => write_number(out, number),
+    Value::String(text) => write_string(out, text),
+    Value::Array(items) => {
+      out.push('[');
+      for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+          out.push(',');
+        }
+        write_value(out, item);
+      }
+      out.push(']');
+    }
+    Value::Object(map) => {
+      let mut entries: Vec<_> = map.iter().collect();
+      entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+      out.push('{');
+      for (i, (key, item)) in entries.into_iter().enumerate() {
+        if i > 0 {
+          out.push(',');
+        }
+        write_string(out, key);
+        out.push(':');
+        write_value(out, item);
+      }
+      out.push('}');
+    }
+  }
+}
+
+/// Serialize a value to canonical JSON
+#[must_use]
+pub fn to_canonical_string(value: &Value) -> String {
+  let mut out = String::new();
+  write_value(&mut out, value);
+  out
+}
+
+/// Digest of a value's canonical JSON, written `sha256:<hex>`
+#[must_use]
+pub fn digest(value: &Value) -> String {
+  let hash = Sha256::digest(to_canonical_string(value).as_bytes());
+  let mut out = format!("{DIGEST_ALGORITHM}:");
+  for byte in hash {
+    let _ = write!(out, "{byte:02x}");
+  }
+  out
+}
+
+/// Canonical JSON of the input; no input is treated as `null`
+#[allow(clippy::unnecessary_wraps)]
+pub fn canonicalize(input: Option<Value>) -> Result<String, Box<dyn std::error::Error>> {
+  Ok(to_canonical_string(&input.unwrap_or(Value::Null)))
+}
+
+/// Digest of the input's canonical JSON; no input is treated as `null`
+#[allow(clippy::unnecessary_wraps)]
+pub fn digest_value(input: Option<Value>) -> Result<String, Box<dyn std::error::Error>> {
+  Ok(digest(&input.unwrap_or(Value::Null)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn canonical(json: &str) -> String {
+    to_canonical_string(&serde_json::from_str(json).unwrap())
+  }
+
+  #[test]
+  fn sorts_keys_and_drops_whitespace() {
+    assert_eq!(
+      canonical(r#"{ "b": [1, {"z": null, "a": true}], "a": "x" }"#),
+      r#"{"a":"x","b":[1,{"a":true,"z":null}]}"#
+    );
+    // Keys compare by UTF-16 code units, so U+1F600 sorts before U+FB01
+    assert_eq!(
+      canonical(r#"{"ﬁ": 1, "😀": 2}"#),
+      "{\"\u{1f600}\":2,\"\u{fb01}\":1}"
+    );
+  }
+
+  #[test]
+  fn normalizes_numbers() {
+    assert_eq!(
+      canonical("[1.0, 1e0, -0.0, 10.50, 1e21, 1e-7, 123e-2]"),
+      "[1,1,0,10.5,1e+21,1e-7,1.23]"
+    );
+    assert_eq!(
+      canonical("[0.000001, 1e20, 9007199254740993, -9223372036854775807]"),
+      "[0.000001,100000000000000000000,9007199254740992,-9223372036854776000]"
+    );
+    assert_eq!(canonical("18446744073709551615"), "18446744073709552000");
+  }
+
+  #[test]
+  fn escapes_strings_minimally() {
+    assert_eq!(canonical(r#""a\u0001\n/é""#), r#""a\u0001\n/é""#);
+  }
+
+  #[test]
+  fn digests_are_stable_across_formatting() {
+    let a = digest(&json!({"name": "klep", "version": 1.0}));
+    let b = digest(&serde_json::from_str(r#"{ "version": 1, "name": "klep" }"#).unwrap());
+    assert_eq!(a, b);
+    assert_eq!(
+      digest(&json!({})),
+      "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+    );
+  }
+}
@@ -0,0 +1,216 @@
+//! `std/compose`: run a pipeline of registered APIs in-process.
+//!
+//! Stages resolve against the registry of the process running the pipeline.
+//! The `gud` dispatcher registers every module, but a library loaded over
+//! the C ABI only registers its own APIs, so `gud_std_compose` can only run
+//! `std` stages; any other stage fails with `unknown-api`.
+
+use gud_common::{log, ApiName, CallContext, ErrorKind, GudError};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::select::Selector;
+
+/// One step of a pipeline: an API name, or an API plus a selector that picks
+/// the part of its output passed to the next stage
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Stage {
+  /// e.g. `"ast/to_tree"`
+  Api(ApiName),
+  /// e.g. `{"api": "ast/to_tree", "select": "/root"}`
+  Step {
+    /// API run by this stage
+    api: ApiName,
+    /// JSON Pointer or JSON path applied to the stage's output
+    #[serde(default)]
+    select: Option<String>,
+  },
+}
+
+impl Stage {
+  const fn api(&self) -> &ApiName {
+    match self {
+      Self::Api(api) | Self::Step { api, .. } => api,
+    }
+  }
+
+  fn selector(&self) -> Result<Option<Selector>, GudError> {
+    match self {
+      Self::Step {
+        select: Some(selector),
+        ..
+      } => Selector::parse(selector).map(Some),
+      _ => Ok(None),
+    }
+  }
+}
+
+/// Input of `std/compose`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ComposeInput {
+  /// Input of the first stage
+  #[serde(default)]
+  pub input: Option<Value>,
+  /// Stages run in order, each receiving the previous stage's output
+  pub pipeline: Vec<Stage>,
+}
+
+fn stage_error(index: usize, api: &ApiName, error: GudError) -> GudError {
+  GudError::new(
+    error.kind,
+    error.code,
+    format!("Stage {index} ({api}) failed: {}", error.message),
+  )
+  .with_context(json!({ "stage": index, "api": api, "context": error.context }))
+}
+
+/// Run a pipeline of registered APIs in this process.
+///
+/// Every stage is resolved before the first one runs, so a misspelled stage
+/// fails without side effects. Errors keep the failing handler's kind and
+/// code, and name the stage in their message and context.
+pub fn compose(
+  input: ComposeInput,
+  ctx: &CallContext,
+) -> Result<Value, Box<dyn std::error::Error>> {
+  if input.pipeline.is_empty() {
+    return Err(
+      GudError::new(
+        ErrorKind::Argument,
+        "empty-pipeline",
+        "The pipeline must have at least one stage",
+      )
+      .into(),
+    );
+  }
+
+  let mut selectors = Vec::with_capacity(input.pipeline.len());
+  for (index, stage) in input.pipeline.iter().enumerate() {
+    ctx
+      .resolve(stage.api())
+      .map_err(|e| stage_error(index, stage.api(), e))?;
+    selectors.push(
+      stage
+        .selector()
+        .map_err(|e| stage_error(index, stage.api(), e))?,
+    );
+  }
+
+  let total = input.pipeline.len();
+  let mut value = input.input;
+  for (index, (stage, selector)) in input.pipeline.iter().zip(&selectors).enumerate() {
+    let api = stage.api();
+    log::debug(&format!("Running stage {index} ({api})"));
+    ctx.check().map_err(|e| stage_error(index, api, e))?;
+
+    let output = ctx
+      .call(api, value)
+      .map_err(|e| stage_error(index, api, GudError::from_boxed(e)))?;
+    value = Some(match selector {
+      Some(selector) => selector.select(&output),
+      None => output,
+    });
+    ctx.progress(&json!({ "done": index + 1, "total": total, "api": api }))?;
+  }
+
+  Ok(value.unwrap_or(Value::Null))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use gud_common::{Handler, Registry};
+  use std::sync::Arc;
+
+  fn ctx() -> CallContext {
+    let mut registry = Registry::new();
+    crate::register(&mut registry);
+    registry
+      .register(Handler::required_input(
+        "test",
+        "double",
+        "1.0.0",
+        |n: i64| Ok(n * 2),
+      ))
+      .register(Handler::required_input(
+        "test",
+        "wrap",
+        "1.0.0",
+        |n: i64| Ok(json!({ "n": n })),
+      ));
+    CallContext::new().with_registry(Arc::new(registry))
+  }
+
+  fn run(input: Value) -> Result<Value, GudError> {
+    compose(serde_json::from_value(input).unwrap(), &ctx()).map_err(GudError::from_boxed)
+  }
+
+  #[test]
+  fn passes_each_output_to_the_next_stage() {
+    let output = run(json!({
+      "input": 3,
+      "pipeline": ["test/double", {"api": "test/wrap", "select": "/n"}, "test/double"],
+    }));
+    assert_eq!(output.unwrap(), json!(12));
+  }
+
+  #[test]
+  fn reports_the_failing_stage() {
+    let error = run(json!({"input": 1, "pipeline": ["test/wrap", "test/double"]})).unwrap_err();
+    assert_eq!(error.code, "invalid-json");
+    assert_eq!(error.context["stage"], json!(1));
+    assert_eq!(error.context["api"], json!("test/double"));
+  }
+
+  #[test]
+  fn validates_every_stage_before_running() {
+    let error = run(json!({"input": 1, "pipeline": ["test/double", "test/missing"]})).unwrap_err();
+    assert_eq!(error.code, "unknown-api");
+    assert_eq!(error.context["stage"], json!(1));
+
+    let error = run(json!({"pipeline": []})).unwrap_err();
+    assert_eq!(error.code, "empty-pipeline");
+  }
+
+  #[test]
+  fn requires_a_registry() {
+    let input = serde_json::from_value(json!({"input": 1, "pipeline": ["std/identity"]}));
+    let error = GudError::from_boxed(compose(input.unwrap(), &CallContext::new()).unwrap_err());
+    assert_eq!(error.code, "unknown-api");
+    assert_eq!(error.context["stage"], json!(0));
+  }
+
+  fn compose_over_c_abi(input: &Value) -> Value {
+    use std::ffi::{CStr, CString};
+
+    let input = CString::new(input.to_string()).unwrap();
+    unsafe {
+      let output = crate::gud_std_compose(input.as_ptr());
+      let json = CStr::from_ptr(output).to_str().unwrap().to_string();
+      gud_common::gud_free(output);
+      serde_json::from_str(&json).unwrap()
+    }
+  }
+
+  #[test]
+  fn runs_nested_calls_over_the_c_abi() {
+    let envelope = compose_over_c_abi(&json!({
+      "input": {"b": 1, "a": 2},
+      "pipeline": ["std/identity", "std/canonicalize"],
+    }));
+    assert_eq!(envelope, json!({"ok": true, "value": r#"{"a":2,"b":1}"#}));
+  }
+
+  #[test]
+  fn only_reaches_its_own_module_over_the_c_abi() {
+    let envelope = compose_over_c_abi(&json!({
+      "input": {"language": "json", "source": "{}"},
+      "pipeline": ["std/identity", "ast/to_tree"],
+    }));
+    assert_eq!(envelope["ok"], json!(false));
+    assert_eq!(envelope["error"]["code"], json!("unknown-api"));
+    assert_eq!(envelope["error"]["context"]["stage"], json!(1));
+  }
+}
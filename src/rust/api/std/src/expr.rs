@@ -0,0 +1,887 @@
+//! A small, side-effect free expression language over JSON values, used by
+//! `std/filter` and `std/reduce`.
+//!
+//! ```text
+//! it.version != null && semver_satisfies(it.version, "^1.2") && !is_hash(it.ref)
+//! acc + len(it.deps)
+//! ```
+//!
+//! Expressions support literals (numbers, strings, `true`, `false`, `null`,
+//! `[lists]`), variables bound by the caller, field access (`it.name`,
+//! `it["odd key"]`, `it.deps[0]`, `it.deps[-1]`), arithmetic (`+ - * / %`),
+//! comparisons (`== != < <= > >=`), membership (`x in list`), boolean logic
+//! (`&& || !`) and the functions listed in [`Func`]. There are no loops,
+//! assignments or side effects, and both nesting and operator chains are
+//! bounded by [`MAX_DEPTH`].
+
+use gud_common::{ErrorKind, GudError};
+use serde_json::{json, Number, Value};
+use std::cmp::Ordering;
+
+use crate::version::{is_hash, Constraint, Version};
+
+/// Deepest nesting of sub-expressions accepted by [`Expr::parse`], where each
+/// link of a chain such as `a || b || c` or `it.a.b` counts as one level
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Number(Number),
+  Str(String),
+  Ident(String),
+  Op(&'static str),
+  End,
+}
+
+const OPERATORS: [&str; 20] = [
+  "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "+", "-", "*", "/", "%", ".", "[", "]", "(",
+  ")", ",",
+];
+
+fn parse_error(source: &str, position: usize, reason: &str) -> GudError {
+  GudError::new(
+    ErrorKind::Parsing,
+    "invalid-expression",
+    format!("Invalid expression at {position}: {reason}"),
+  )
+  .with_context(json!({ "expression": source, "position": position }))
+}
+
+fn eval_error(message: impl Into<String>) -> GudError {
+  GudError::new(ErrorKind::Argument, "expression-error", message)
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, GudError> {
+  let chars: Vec<(usize, char)> = source.char_indices().collect();
+  let mut tokens = Vec::new();
+  let mut i = 0;
+
+  while i < chars.len() {
+    let (position, c) = chars[i];
+    if c.is_whitespace() {
+      i += 1;
+    } else if c.is_ascii_digit() {
+      let start = i;
+      while i < chars.len() && chars[i].1.is_ascii_digit() {
+        i += 1;
+      }
+      if i + 1 < chars.len() && chars[i].1 == '.' && chars[i + 1].1.is_ascii_digit() {
+        i += 1;
+        while i < chars.len() && chars[i].1.is_ascii_digit() {
+          i += 1;
+        }
+      }
+      if i < chars.len() && matches!(chars[i].1, 'e' | 'E') {
+        i += 1;
+        if i < chars.len() && matches!(chars[i].1, '+' | '-') {
+          i += 1;
+        }
+        while i < chars.len() && chars[i].1.is_ascii_digit() {
+          i += 1;
+        }
+      }
+      let end = chars.get(i).map_or(source.len(), |&(end, _)| end);
+      let number = source[chars[start].0..end]
+        .parse()
+        .map_err(|_| parse_error(source, position, "malformed number"))?;
+      tokens.push((position, Token::Number(number)));
+    } else if c.is_alphabetic() || c == '_' {
+      let mut ident = String::new();
+      while i < chars.len() && (chars[i].1.is_alphanumeric() || chars[i].1 == '_') {
+        ident.push(chars[i].1);
+        i += 1;
+      }
+      tokens.push((position, Token::Ident(ident)));
+    } else if c == '"' || c == '\'' {
+      let mut text = String::new();
+      i += 1;
+      loop {
+        let Some(&(_, next)) = chars.get(i) else {
+          return Err(parse_error(source, position, "unterminated string"));
+        };
+        i += 1;
+        match next {
+          _ if next == c => break,
+          '\\' => {
+            let Some(&(_, escaped)) = chars.get(i) else {
+              return Err(parse_error(source, position, "unterminated string"));
+            };
+            i += 1;
+            text.push(match escaped {
+              'n' => '\n',
+              't' => '\t',
+              'r' => '\r',
+              other => other,
+            });
+          }
+          other => text.push(other),
+        }
+      }
+      tokens.push((position, Token::Str(text)));
+    } else {
+      let rest = &source[position..];
+      let op = OPERATORS
+        .iter()
+        .find(|op| rest.starts_with(**op))
+        .ok_or_else(|| parse_error(source, position, &format!("unexpected character '{c}'")))?;
+      tokens.push((position, Token::Op(op)));
+      i += op.len();
+    }
+  }
+
+  tokens.push((source.len(), Token::End));
+  Ok(tokens)
+}
+
+/// Built-in functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Func {
+  /// `len(x)`: characters of a string, items of an array or keys of an object
+  Len,
+  /// `contains(haystack, needle)`: substring, array item or object key
+  Contains,
+  /// `starts_with(text, prefix)`
+  StartsWith,
+  /// `ends_with(text, suffix)`
+  EndsWith,
+  /// `lower(text)`
+  Lower,
+  /// `upper(text)`
+  Upper,
+  /// `keys(object)`
+  Keys,
+  /// `values(object)`
+  Values,
+  /// `min(a, b)`
+  Min,
+  /// `max(a, b)`
+  Max,
+  /// `default(x, fallback)`: `fallback` when `x` is null
+  Default,
+  /// `is_hash(x)`: whether `x` is a full commit hash
+  IsHash,
+  /// `is_semver(x)`: whether `x` is a semantic version, with or without a `v`
+  IsSemver,
+  /// `semver_cmp(a, b)`: -1, 0 or 1, for semantic or calendar versions
+  SemverCmp,
+  /// `semver_satisfies(version, constraint)`, e.g. `semver_satisfies(v, "^1.2")`
+  SemverSatisfies,
+}
+
+impl Func {
+  fn from_name(name: &str) -> Option<(Self, usize)> {
+    Some(match name {
+      "len" => (Self::Len, 1),
+      "contains" => (Self::Contains, 2),
+      "starts_with" => (Self::StartsWith, 2),
+      "ends_with" => (Self::EndsWith, 2),
+      "lower" => (Self::Lower, 1),
+      "upper" => (Self::Upper, 1),
+      "keys" => (Self::Keys, 1),
+      "values" => (Self::Values, 1),
+      "min" => (Self::Min, 2),
+      "max" => (Self::Max, 2),
+      "default" => (Self::Default, 2),
+      "is_hash" => (Self::IsHash, 1),
+      "is_semver" => (Self::IsSemver, 1),
+      "semver_cmp" => (Self::SemverCmp, 2),
+      "semver_satisfies" => (Self::SemverSatisfies, 2),
+      _ => return None,
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinOp {
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge,
+  In,
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Rem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+  Literal(Value),
+  Var(String),
+  List(Vec<Self>),
+  Field(Box<Self>, String),
+  Index(Box<Self>, Box<Self>),
+  Not(Box<Self>),
+  Neg(Box<Self>),
+  And(Box<Self>, Box<Self>),
+  Or(Box<Self>, Box<Self>),
+  Binary(BinOp, Box<Self>, Box<Self>),
+  Call(Func, Vec<Self>),
+}
+
+struct Parser<'a> {
+  source: &'a str,
+  tokens: Vec<(usize, Token)>,
+  next: usize,
+  depth: usize,
+  variables: &'a [&'a str],
+}
+
+impl Parser<'_> {
+  fn peek(&self) -> &Token {
+    &self.tokens[self.next].1
+  }
+
+  fn position(&self) -> usize {
+    self.tokens[self.next].0
+  }
+
+  fn advance(&mut self) -> Token {
+    let token = self.tokens[self.next].1.clone();
+    if token != Token::End {
+      self.next += 1;
+    }
+    token
+  }
+
+  fn eat(&mut self, op: &str) -> bool {
+    if matches!(self.peek(), Token::Op(next) if *next == op) {
+      self.next += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect(&mut self, op: &str) -> Result<(), GudError> {
+    if self.eat(op) {
+      Ok(())
+    } else {
+      Err(self.error(&format!("expected '{op}'")))
+    }
+  }
+
+  fn error(&self, reason: &str) -> GudError {
+    parse_error(self.source, self.position(), reason)
+  }
+
+  fn expression(&mut self) -> Result<Node, GudError> {
+    self.nested(Self::or)
+  }
+
+  fn or(&mut self) -> Result<Node, GudError> {
+    let depth = self.depth;
+    let mut node = self.and()?;
+    while self.eat("||") {
+      self.deepen()?;
+      node = Node::Or(Box::new(node), Box::new(self.and()?));
+    }
+    self.depth = depth;
+    Ok(node)
+  }
+
+  fn and(&mut self) -> Result<Node, GudError> {
+    let depth = self.depth;
+    let mut node = self.comparison()?;
+    while self.eat("&&") {
+      self.deepen()?;
+      node = Node::And(Box::new(node), Box::new(self.comparison()?));
+    }
+    self.depth = depth;
+    Ok(node)
+  }
+
+  fn comparison(&mut self) -> Result<Node, GudError> {
+    let left = self.sum()?;
+    let op = match self.peek() {
+      Token::Op("==") => BinOp::Eq,
+      Token::Op("!=") => BinOp::Ne,
+      Token::Op("<") => BinOp::Lt,
+      Token::Op("<=") => BinOp::Le,
+      Token::Op(">") => BinOp::Gt,
+      Token::Op(">=") => BinOp::Ge,
+      Token::Ident(word) if word == "in" => BinOp::In,
+      _ => return Ok(left),
+    };
+    self.advance();
+    Ok(Node::Binary(op, Box::new(left), Box::new(self.sum()?)))
+  }
+
+  fn sum(&mut self) -> Result<Node, GudError> {
+    let depth = self.depth;
+    let mut node = self.product()?;
+    loop {
+      let op = if self.eat("+") {
+        BinOp::Add
+      } else if self.eat("-") {
+        BinOp::Sub
+      } else {
+        self.depth = depth;
+        return Ok(node);
+      };
+      self.deepen()?;
+      node = Node::Binary(op, Box::new(node), Box::new(self.product()?));
+    }
+  }
+
+  fn product(&mut self) -> Result<Node, GudError> {
+    let depth = self.depth;
+    let mut node = self.unary()?;
+    loop {
+      let op = if self.eat("*") {
+        BinOp::Mul
+      } else if self.eat("/") {
+        BinOp::Div
+      } else if self.eat("%") {
+        BinOp::Rem
+      } else {
+        self.depth = depth;
+        return Ok(node);
+      };
+      self.deepen()?;
+      node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+    }
+  }
+
+  fn unary(&mut self) -> Result<Node, GudError> {
+    if self.eat("!") {
+      Ok(Node::Not(Box::new(self.nested(Self::unary)?)))
+    } else if self.eat("-") {
+      Ok(Node::Neg(Box::new(self.nested(Self::unary)?)))
+    } else {
+      self.postfix()
+    }
+  }
+
+  fn nested(&mut self, rule: fn(&mut Self) -> Result<Node, GudError>) -> Result<Node, GudError> {
+    self.deepen()?;
+    let node = rule(self);
+    self.depth -= 1;
+    node
+  }
+
+  /// Count one more level of the tree being built. Operator and postfix
+  /// chains build left-deep trees, so each link counts like a parenthesis.
+  fn deepen(&mut self) -> Result<(), GudError> {
+    self.depth += 1;
+    if self.depth > MAX_DEPTH {
+      return Err(self.error("expression is nested too deeply"));
+    }
+    Ok(())
+  }
+
+  fn postfix(&mut self) -> Result<Node, GudError> {
+    let depth = self.depth;
+    let mut node = self.primary()?;
+    loop {
+      if self.eat(".") {
+        self.deepen()?;
+        match self.advance() {
+          Token::Ident(name) => node = Node::Field(Box::new(node), name),
+          _ => return Err(self.error("expected a field name after '.'")),
+        }
+      } else if self.eat("[") {
+        self.deepen()?;
+        let index = self.expression()?;
+        self.expect("]")?;
+        node = Node::Index(Box::new(node), Box::new(index));
+      } else {
+        self.depth = depth;
+        return Ok(node);
+      }
+    }
+  }
+
+  fn list(&mut self, close: &str) -> Result<Vec<Node>, GudError> {
+    let mut items = Vec::new();
+    if self.eat(close) {
+      return Ok(items);
+    }
+    loop {
+      items.push(self.expression()?);
+      if self.eat(close) {
+        return Ok(items);
+      }
+      self.expect(",")?;
+    }
+  }
+
+  fn primary(&mut self) -> Result<Node, GudError> {
+    let position = self.position();
+    match self.advance() {
+      Token::Number(number) => Ok(Node::Literal(Value::Number(number))),
+      Token::Str(text) => Ok(Node::Literal(Value::String(text))),
+      Token::Op("(") => {
+        let node = self.expression()?;
+        self.expect(")")?;
+        Ok(node)
+      }
+      Token::Op("[") => Ok(Node::List(self.list("]")?)),
+      Token::Ident(word) => match word.as_str() {
+        "true" => Ok(Node::Literal(Value::Bool(true))),
+        "false" => Ok(Node::Literal(Value::Bool(false))),
+        "null" => Ok(Node::Literal(Value::Null)),
+        _ if self.eat("(") => {
+          let (func, arity) = Func::from_name(&word).ok_or_else(|| {
+            parse_error(self.source, position, &format!("unknown function '{word}'"))
+          })?;
+          let args = self.list(")")?;
+          if args.len() != arity {
+            return Err(parse_error(
+              self.source,
+              position,
+              &format!("{word} takes {arity} argument(s), got {}", args.len()),
+            ));
+          }
+          Ok(Node::Call(func, args))
+        }
+        _ if self.variables.contains(&word.as_str()) => Ok(Node::Var(word)),
+        _ => Err(parse_error(
+          self.source,
+          position,
+          &format!(
+            "unknown variable '{word}', expected one of: {}",
+            self.variables.join(", ")
+          ),
+        )),
+      },
+      Token::End => Err(parse_error(
+        self.source,
+        position,
+        "unexpected end of expression",
+      )),
+      Token::Op(op) => Err(parse_error(
+        self.source,
+        position,
+        &format!("unexpected '{op}'"),
+      )),
+    }
+  }
+}
+
+const fn type_name(value: &Value) -> &'static str {
+  match value {
+    Value::Null => "null",
+    Value::Bool(_) => "boolean",
+    Value::Number(_) => "number",
+    Value::String(_) => "string",
+    Value::Array(_) => "array",
+    Value::Object(_) => "object",
+  }
+}
+
+fn boolean(value: &Value, what: &str) -> Result<bool, GudError> {
+  value.as_bool().ok_or_else(|| {
+    eval_error(format!(
+      "{what} expects a boolean, got {}",
+      type_name(value)
+    ))
+  })
+}
+
+fn text<'a>(value: &'a Value, what: &str) -> Result<&'a str, GudError> {
+  value
+    .as_str()
+    .ok_or_else(|| eval_error(format!("{what} expects a string, got {}", type_name(value))))
+}
+
+fn float(value: &Value, what: &str) -> Result<f64, GudError> {
+  value
+    .as_f64()
+    .ok_or_else(|| eval_error(format!("{what} expects a number, got {}", type_name(value))))
+}
+
+fn from_float(value: f64) -> Result<Value, GudError> {
+  Number::from_f64(value)
+    .map(Value::Number)
+    .ok_or_else(|| eval_error("arithmetic produced a non-finite number"))
+}
+
+/// JSON equality where numbers compare by value, so `1 == 1.0`
+fn equal(a: &Value, b: &Value) -> bool {
+  match (a, b) {
+    (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+    (Value::Array(a), Value::Array(b)) => {
+      a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b))
+    }
+    (Value::Object(a), Value::Object(b)) => {
+      a.len() == b.len()
+        && a
+          .iter()
+          .all(|(key, a)| b.get(key).is_some_and(|b| equal(a, b)))
+    }
+    _ => a == b,
+  }
+}
+
+fn compare(a: &Value, b: &Value) -> Result<Ordering, GudError> {
+  match (a, b) {
+    (Value::Number(x), Value::Number(y)) => x
+      .as_f64()
+      .zip(y.as_f64())
+      .and_then(|(x, y)| x.partial_cmp(&y))
+      .ok_or_else(|| eval_error("numbers cannot be compared")),
+    (Value::String(x), Value::String(y)) => Ok(x.cmp(y)),
+    _ => Err(eval_error(format!(
+      "cannot compare {} with {}",
+      type_name(a),
+      type_name(b)
+    ))),
+  }
+}
+
+fn contains(haystack: &Value, needle: &Value) -> Result<bool, GudError> {
+  match haystack {
+    Value::Array(items) => Ok(items.iter().any(|item| equal(item, needle))),
+    Value::String(haystack) => Ok(haystack.contains(text(needle, "contains")?)),
+    Value::Object(map) => Ok(map.contains_key(text(needle, "contains")?)),
+    other => Err(eval_error(format!(
+      "cannot look for a value in {}",
+      type_name(other)
+    ))),
+  }
+}
+
+fn arithmetic(op: BinOp, a: &Value, b: &Value) -> Result<Value, GudError> {
+  match (op, a, b) {
+    (BinOp::Add, Value::String(a), Value::String(b)) => {
+      return Ok(Value::String(format!("{a}{b}")))
+    }
+    (BinOp::Add, Value::Array(a), Value::Array(b)) => {
+      return Ok(Value::Array(a.iter().chain(b).cloned().collect()))
+    }
+    _ => {}
+  }
+
+  if let (Some(x), Some(y)) = (a.as_i64(), b.as_i64()) {
+    let exact = match op {
+      BinOp::Add => x.checked_add(y),
+      BinOp::Sub => x.checked_sub(y),
+      BinOp::Mul => x.checked_mul(y),
+      BinOp::Div if y != 0 && x % y == 0 => x.checked_div(y),
+      BinOp::Rem if y != 0 => x.checked_rem(y),
+      _ => None,
+    };
+    if let Some(result) = exact {
+      return Ok(json!(result));
+    }
+  }
+
+  let (x, y) = (float(a, "arithmetic")?, float(b, "arithmetic")?);
+  match op {
+    BinOp::Add => from_float(x + y),
+    BinOp::Sub => from_float(x - y),
+    BinOp::Mul => from_float(x * y),
+    BinOp::Div | BinOp::Rem if y == 0.0 => Err(eval_error("division by zero")),
+    BinOp::Div => from_float(x / y),
+    _ => from_float(x % y),
+  }
+}
+
+fn index(base: &Value, index: &Value) -> Value {
+  match (base, index) {
+    (Value::Array(items), Value::Number(n)) => n
+      .as_i64()
+      .and_then(|n| {
+        if n < 0 {
+          i64::try_from(items.len()).ok().map(|len| len + n)
+        } else {
+          Some(n)
+        }
+      })
+      .and_then(|n| usize::try_from(n).ok())
+      .and_then(|n| items.get(n))
+      .cloned()
+      .unwrap_or(Value::Null),
+    (Value::Object(map), Value::String(key)) => map.get(key).cloned().unwrap_or(Value::Null),
+    _ => Value::Null,
+  }
+}
+
+fn version(value: &Value, what: &str) -> Result<Version, GudError> {
+  Version::parse(text(value, what)?).map_err(|e| eval_error(format!("{what}: {}", e.message)))
+}
+
+fn call(func: Func, args: &[Value]) -> Result<Value, GudError> {
+  let arg = |n: usize| &args[n];
+  Ok(match func {
+    Func::Len => json!(match arg(0) {
+      Value::String(text) => text.chars().count(),
+      Value::Array(items) => items.len(),
+      Value::Object(map) => map.len(),
+      other =>
+        return Err(eval_error(format!(
+          "len cannot measure {}",
+          type_name(other)
+        ))),
+    }),
+    Func::Contains => Value::Bool(contains(arg(0), arg(1))?),
+    Func::StartsWith => {
+      Value::Bool(text(arg(0), "starts_with")?.starts_with(text(arg(1), "starts_with")?))
+    }
+    Func::EndsWith => Value::Bool(text(arg(0), "ends_with")?.ends_with(text(arg(1), "ends_with")?)),
+    Func::Lower => Value::String(text(arg(0), "lower")?.to_lowercase()),
+    Func::Upper => Value::String(text(arg(0), "upper")?.to_uppercase()),
+    Func::Keys | Func::Values => {
+      let Value::Object(map) = arg(0) else {
+        return Err(eval_error(format!(
+          "keys and values expect an object, got {}",
+          type_name(arg(0))
+        )));
+      };
+      Value::Array(if func == Func::Keys {
+        map.keys().cloned().map(Value::String).collect()
+      } else {
+        map.values().cloned().collect()
+      })
+    }
+    Func::Min | Func::Max => {
+      let order = compare(arg(0), arg(1))?;
+      let first = if func == Func::Max {
+        order.is_ge()
+      } else {
+        order.is_le()
+      };
+      if first { arg(0) } else { arg(1) }.clone()
+    }
+    Func::Default => if arg(0).is_null() { arg(1) } else { arg(0) }.clone(),
+    Func::IsHash => Value::Bool(arg(0).as_str().is_some_and(is_hash)),
+    Func::IsSemver => Value::Bool(matches!(
+      arg(0).as_str().map(Version::parse),
+      Some(Ok(Version::Semver { .. }))
+    )),
+    Func::SemverCmp => {
+      let (a, b) = (
+        version(arg(0), "semver_cmp")?,
+        version(arg(1), "semver_cmp")?,
+      );
+      let order = a
+        .compare(&b)
+        .ok_or_else(|| eval_error(format!("semver_cmp: {a} and {b} cannot be ordered")))?;
+      json!(order as i8)
+    }
+    Func::SemverSatisfies => {
+      let constraint = Constraint::parse(text(arg(1), "semver_satisfies")?)
+        .map_err(|e| eval_error(format!("semver_satisfies: {}", e.message)))?;
+      Value::Bool(constraint.matches(&version(arg(0), "semver_satisfies")?))
+    }
+  })
+}
+
+/// Variables visible to an expression while it is evaluated
+pub type Bindings<'a> = [(&'a str, &'a Value)];
+
+/// A parsed expression, ready to be evaluated against many values
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+  source: String,
+  root: Node,
+}
+
+impl Expr {
+  /// Parse `source`, allowing only the given variable names
+  pub fn parse(source: &str, variables: &[&str]) -> Result<Self, GudError> {
+    let mut parser = Parser {
+      source,
+      tokens: tokenize(source)?,
+      next: 0,
+      depth: 0,
+      variables,
+    };
+    let root = parser.expression()?;
+    if *parser.peek() != Token::End {
+      return Err(parser.error("unexpected trailing input"));
+    }
+
+    Ok(Self {
+      source: source.to_string(),
+      root,
+    })
+  }
+
+  /// The text the expression was parsed from
+  #[must_use]
+  pub fn source(&self) -> &str {
+    &self.source
+  }
+
+  /// Evaluate the expression with the given variable values
+  pub fn eval(&self, bindings: &Bindings) -> Result<Value, GudError> {
+    eval(&self.root, bindings)
+  }
+
+  /// Evaluate an expression that must produce a boolean, such as a predicate
+  pub fn test(&self, bindings: &Bindings) -> Result<bool, GudError> {
+    boolean(&self.eval(bindings)?, "a predicate")
+  }
+}
+
+fn eval(node: &Node, bindings: &Bindings) -> Result<Value, GudError> {
+  Ok(match node {
+    Node::Literal(value) => value.clone(),
+    Node::Var(name) => bindings
+      .iter()
+      .find(|(bound, _)| bound == name)
+      .map(|(_, value)| (*value).clone())
+      .ok_or_else(|| eval_error(format!("variable '{name}' is not bound")))?,
+    Node::List(items) => Value::Array(
+      items
+        .iter()
+        .map(|item| eval(item, bindings))
+        .collect::<Result<_, _>>()?,
+    ),
+    Node::Field(base, name) => match eval(base, bindings)? {
+      Value::Object(mut map) => map.remove(name).unwrap_or(Value::Null),
+      _ => Value::Null,
+    },
+    Node::Index(base, key) => index(&eval(base, bindings)?, &eval(key, bindings)?),
+    Node::Not(operand) => Value::Bool(!boolean(&eval(operand, bindings)?, "'!'")?),
+    Node::Neg(operand) => arithmetic(BinOp::Sub, &json!(0), &eval(operand, bindings)?)?,
+    Node::And(left, right) => Value::Bool(
+      boolean(&eval(left, bindings)?, "'&&'")? && boolean(&eval(right, bindings)?, "'&&'")?,
+    ),
+    Node::Or(left, right) => Value::Bool(
+      boolean(&eval(left, bindings)?, "'||'")? || boolean(&eval(right, bindings)?, "'||'")?,
+    ),
+    Node::Binary(op, left, right) => {
+      let (left, right) = (eval(left, bindings)?, eval(right, bindings)?);
+      match op {
+        BinOp::Eq => Value::Bool(equal(&left, &right)),
+        BinOp::Ne => Value::Bool(!equal(&left, &right)),
+        BinOp::Lt => Value::Bool(compare(&left, &right)?.is_lt()),
+        BinOp::Le => Value::Bool(compare(&left, &right)?.is_le()),
+        BinOp::Gt => Value::Bool(compare(&left, &right)?.is_gt()),
+        BinOp::Ge => Value::Bool(compare(&left, &right)?.is_ge()),
+        BinOp::In => Value::Bool(contains(&right, &left)?),
+        _ => arithmetic(*op, &left, &right)?,
+      }
+    }
+    Node::Call(func, args) => call(
+      *func,
+      &args
+        .iter()
+        .map(|arg| eval(arg, bindings))
+        .collect::<Result<Vec<_>, _>>()?,
+    )?,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn eval(source: &str, it: &Value) -> Result<Value, GudError> {
+    Expr::parse(source, &["it"])?.eval(&[("it", it)])
+  }
+
+  fn dep() -> Value {
+    json!({
+      "name": "serde",
+      "version": "v1.0.197",
+      "ref": "0a1b2c3d4e5f60718293a4b5c6d7e8f901234567",
+      "deps": ["serde_derive", "itoa"],
+      "odd key": 2,
+    })
+  }
+
+  #[test]
+  fn evaluates_arithmetic_and_comparisons() {
+    assert_eq!(eval("1 + 2 * 3", &Value::Null).unwrap(), json!(7));
+    assert_eq!(eval("(1 + 2) * 3 % 4", &Value::Null).unwrap(), json!(1));
+    assert_eq!(eval("7 / 2", &Value::Null).unwrap(), json!(3.5));
+    assert_eq!(eval("-it", &json!(2)).unwrap(), json!(-2));
+    assert_eq!(
+      eval("'a' + \"b\" < 'b'", &Value::Null).unwrap(),
+      json!(true)
+    );
+    assert_eq!(
+      eval("1 == 1.0 && [1, 'x'] != [1]", &Value::Null).unwrap(),
+      json!(true)
+    );
+  }
+
+  #[test]
+  fn accesses_fields_and_indexes() {
+    assert_eq!(eval("it.name", &dep()).unwrap(), json!("serde"));
+    assert_eq!(eval("it.deps[-1]", &dep()).unwrap(), json!("itoa"));
+    assert_eq!(eval("it['odd key'] * 2", &dep()).unwrap(), json!(4));
+    assert_eq!(eval("it.missing.deeper", &dep()).unwrap(), Value::Null);
+    assert_eq!(eval("'itoa' in it.deps", &dep()).unwrap(), json!(true));
+  }
+
+  #[test]
+  fn calls_version_and_hash_predicates() {
+    let source = "is_semver(it.version) && semver_satisfies(it.version, '^1.0') && is_hash(it.ref)";
+    assert_eq!(eval(source, &dep()).unwrap(), json!(true));
+    assert_eq!(
+      eval("semver_cmp('1.2.0', 'v1.10.0')", &dep()).unwrap(),
+      json!(-1)
+    );
+    assert_eq!(eval("is_hash(it.name)", &dep()).unwrap(), json!(false));
+    assert_eq!(
+      eval("len(it.deps) + len(it.name)", &dep()).unwrap(),
+      json!(7)
+    );
+    assert_eq!(
+      eval("default(it.missing, max(1, 3))", &dep()).unwrap(),
+      json!(3)
+    );
+  }
+
+  #[test]
+  fn rejects_invalid_expressions() {
+    for source in [
+      "1 +",
+      "it.",
+      "nope",
+      "len(1, 2)",
+      "explode(it)",
+      "'open",
+      "1 2",
+      "it ~ 1",
+    ] {
+      let error = Expr::parse(source, &["it"]).unwrap_err();
+      assert_eq!(error.code, "invalid-expression", "{source}");
+    }
+
+    let deep = format!(
+      "{}1{}",
+      "(".repeat(MAX_DEPTH + 1),
+      ")".repeat(MAX_DEPTH + 1)
+    );
+    assert_eq!(
+      Expr::parse(&deep, &[]).unwrap_err().code,
+      "invalid-expression"
+    );
+
+    for op in ["||", "&&", "+", "*"] {
+      let long = vec!["it"; 200_000].join(op);
+      let error = Expr::parse(&long, &["it"]).unwrap_err();
+      assert_eq!(error.code, "invalid-expression", "{op}");
+    }
+    let long = format!("it{}", ".a".repeat(200_000));
+    assert_eq!(
+      Expr::parse(&long, &["it"]).unwrap_err().code,
+      "invalid-expression"
+    );
+    let chain = vec!["it"; MAX_DEPTH].join(" + ");
+    assert!(Expr::parse(&chain, &["it"]).is_ok());
+  }
+
+  #[test]
+  fn reports_type_errors() {
+    for source in [
+      "1 && true",
+      "'a' < 1",
+      "1 / 0",
+      "!it",
+      "semver_cmp('x', '1.0.0')",
+    ] {
+      let error = eval(source, &json!(1)).unwrap_err();
+      assert_eq!(error.code, "expression-error", "{source}");
+    }
+  }
+}
@@ -0,0 +1,96 @@
+//! `std/filter`: keep the elements of a collection matching an expression.
+
+use gud_common::{CallContext, GudError};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::expr::Expr;
+use crate::map::{collect, entries};
+
+/// Input of `std/filter`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct FilterInput {
+  /// Array or object to filter
+  pub collection: Value,
+  /// Boolean expression over `it` (the element) and `key` (its index or
+  /// key), e.g. `semver_satisfies(it.version, "^1.2")`
+  pub predicate: String,
+}
+
+/// Wrap an expression failure with the element it failed on
+pub(crate) fn element_failure(expr: &Expr, key: &Value, error: GudError) -> GudError {
+  let message = format!("{} (element {key})", error.message);
+  GudError::new(error.kind, error.code, message)
+    .with_context(json!({ "key": key, "expression": expr.source() }))
+}
+
+/// Keep the elements of an array or object for which the predicate holds.
+///
+/// Arrays stay arrays and objects keep the keys of the retained entries.
+pub fn filter_collection(
+  input: FilterInput,
+  ctx: &CallContext,
+) -> Result<Value, Box<dyn std::error::Error>> {
+  let predicate = Expr::parse(&input.predicate, &["it", "key"])?;
+  let array = input.collection.is_array();
+
+  let mut kept = Vec::new();
+  for (key, element) in entries(input.collection)? {
+    ctx.check()?;
+    if predicate
+      .test(&[("it", &element), ("key", &key)])
+      .map_err(|e| element_failure(&predicate, &key, e))?
+    {
+      kept.push((key, element));
+    }
+  }
+
+  Ok(collect(array, kept))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn filter(collection: Value, predicate: &str) -> Result<Value, GudError> {
+    let input = FilterInput {
+      collection,
+      predicate: predicate.to_string(),
+    };
+    filter_collection(input, &CallContext::new()).map_err(GudError::from_boxed)
+  }
+
+  #[test]
+  fn keeps_matching_elements() {
+    let commits = json!([
+      {"ref": "v1.2.0", "stable": true},
+      {"ref": "v2.0.0-rc.1", "stable": false},
+      {"ref": "9fceb02", "stable": true},
+    ]);
+    assert_eq!(
+      filter(
+        commits,
+        "is_semver(it.ref) && semver_satisfies(it.ref, '^1')"
+      )
+      .unwrap(),
+      json!([{"ref": "v1.2.0", "stable": true}])
+    );
+  }
+
+  #[test]
+  fn keeps_object_keys() {
+    let deps = json!({"serde": "1.0.0", "tokio": "0.2.1", "rand": "0.8.5"});
+    assert_eq!(
+      filter(deps, "key != 'rand' && semver_cmp(it, '0.5.0') > 0").unwrap(),
+      json!({"serde": "1.0.0"})
+    );
+  }
+
+  #[test]
+  fn rejects_non_boolean_predicates() {
+    let error = filter(json!([1, 2]), "it + 1").unwrap_err();
+    assert_eq!(error.code, "expression-error");
+    assert_eq!(error.context["key"], json!(0));
+  }
+}
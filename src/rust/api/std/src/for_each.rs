@@ -0,0 +1,291 @@
+//! `std/for_each`: run a registered API on every element of a collection
+//! and report each outcome.
+
+use gud_common::{ApiName, CallContext, ErrorKind, GudError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::map::{element_error, entries};
+
+/// Upper bound on `concurrency`, so a typo cannot spawn thousands of threads
+pub const MAX_CONCURRENCY: usize = 64;
+
+/// Input of `std/for_each`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ForEachInput {
+  /// Array or object whose elements are passed to `api` one at a time
+  pub collection: Value,
+  /// Registered API to run per element, e.g. `ast/to_manifest`
+  pub api: ApiName,
+  /// Number of elements processed at once; defaults to the number of CPUs
+  #[serde(default)]
+  pub concurrency: Option<usize>,
+}
+
+/// Outcome of one element: `value` when `ok`, `error` otherwise
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ElementResult {
+  /// Array index or object key of the element
+  pub key: Value,
+  /// Whether the API succeeded on this element
+  pub ok: bool,
+  /// Output of the API
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub value: Option<Value>,
+  /// Error the API failed with
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<GudError>,
+}
+
+impl ElementResult {
+  fn new(key: Value, result: Result<Value, GudError>) -> Self {
+    match result {
+      Ok(value) => Self {
+        key,
+        ok: true,
+        value: Some(value),
+        error: None,
+      },
+      Err(error) => Self {
+        key,
+        ok: false,
+        value: None,
+        error: Some(error),
+      },
+    }
+  }
+}
+
+/// Counts of the elements processed by `std/for_each`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Summary {
+  /// Elements in the collection
+  pub total: usize,
+  /// Elements the API succeeded on
+  pub succeeded: usize,
+  /// Elements the API failed on, including cancelled ones
+  pub failed: usize,
+}
+
+/// Output of `std/for_each`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ForEachOutput {
+  /// Counts over every element
+  pub summary: Summary,
+  /// One result per element, in collection order
+  pub results: Vec<ElementResult>,
+}
+
+fn concurrency(requested: Option<usize>, total: usize) -> Result<usize, GudError> {
+  let limit = match requested {
+    Some(0) => {
+      return Err(GudError::new(
+        ErrorKind::Argument,
+        "invalid-concurrency",
+        "concurrency must be at least 1",
+      ))
+    }
+    Some(limit) => limit.min(MAX_CONCURRENCY),
+    None => thread::available_parallelism().map_or(1, usize::from),
+  };
+  Ok(limit.min(total).max(1))
+}
+
+fn run_element(
+  name: &ApiName,
+  key: &Value,
+  element: Value,
+  ctx: &CallContext,
+) -> Result<Value, GudError> {
+  ctx.check()?;
+  catch_unwind(AssertUnwindSafe(|| ctx.call(name, Some(element))))
+    .unwrap_or_else(|_| {
+      Err(
+        GudError::new(
+          ErrorKind::Unknown,
+          "handler-panic",
+          format!("{name} panicked"),
+        )
+        .into(),
+      )
+    })
+    .map_err(|e| element_error(key, name, GudError::from_boxed(e)))
+}
+
+/// Run a registered API on every element of a collection, a few at a time.
+///
+/// Failing elements do not stop the others; each outcome is reported in
+/// `results`. Once the call is cancelled or its deadline passes, the
+/// remaining elements fail with that error instead of running.
+pub fn for_each(
+  input: ForEachInput,
+  ctx: &CallContext,
+) -> Result<ForEachOutput, Box<dyn std::error::Error>> {
+  // An unknown API would fail every element the same way, so report it once
+  ctx.resolve(&input.api)?;
+
+  let elements = entries(input.collection)?;
+  let total = elements.len();
+  let workers = concurrency(input.concurrency, total)?;
+
+  let next = AtomicUsize::new(0);
+  let done = AtomicUsize::new(0);
+  let slots: Vec<Mutex<Option<(Value, Value)>>> = elements
+    .into_iter()
+    .map(|element| Mutex::new(Some(element)))
+    .collect();
+  let results: Mutex<Vec<Option<ElementResult>>> = Mutex::new(vec![None; total]);
+
+  thread::scope(|scope| {
+    for _ in 0..workers {
+      scope.spawn(|| loop {
+        let index = next.fetch_add(1, Ordering::SeqCst);
+        let Some(slot) = slots.get(index) else {
+          break;
+        };
+        let Some((key, element)) = slot.lock().ok().and_then(|mut slot| slot.take()) else {
+          continue;
+        };
+
+        let result = ElementResult::new(key.clone(), run_element(&input.api, &key, element, ctx));
+        // Events are best effort: a closed channel must not fail the batch
+        let _ = ctx.partial(&result);
+        if let Ok(mut results) = results.lock() {
+          results[index] = Some(result);
+        }
+        let done = done.fetch_add(1, Ordering::SeqCst) + 1;
+        let _ = ctx.progress(&json!({ "done": done, "total": total }));
+      });
+    }
+  });
+
+  let results: Vec<ElementResult> = results
+    .into_inner()
+    .map_err(|_| {
+      GudError::new(
+        ErrorKind::Unknown,
+        "handler-panic",
+        "for_each worker panicked",
+      )
+    })?
+    .into_iter()
+    .flatten()
+    .collect();
+  let succeeded = results.iter().filter(|result| result.ok).count();
+
+  Ok(ForEachOutput {
+    summary: Summary {
+      total,
+      succeeded,
+      failed: total - succeeded,
+    },
+    results,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use gud_common::{Handler, Registry};
+  use std::sync::Arc;
+  use std::time::Duration;
+
+  fn ctx() -> CallContext {
+    let mut registry = Registry::new();
+    crate::register(&mut registry);
+    registry.register(Handler::required_input(
+      "test",
+      "check",
+      "1.0.0",
+      |n: i64| {
+        if n < 0 {
+          return Err(GudError::new(ErrorKind::Argument, "negative", "negative input").into());
+        }
+        assert_ne!(n, 13, "unlucky");
+        thread::sleep(Duration::from_millis(5));
+        Ok(n * 2)
+      },
+    ));
+    CallContext::new().with_registry(Arc::new(registry))
+  }
+
+  fn run(input: Value, ctx: &CallContext) -> Result<ForEachOutput, GudError> {
+    for_each(serde_json::from_value(input).unwrap(), ctx).map_err(GudError::from_boxed)
+  }
+
+  #[test]
+  fn collects_every_outcome_in_order() {
+    let output = run(
+      json!({"collection": [1, -1, 2, 13, 3], "api": "test/check", "concurrency": 3}),
+      &ctx(),
+    )
+    .unwrap();
+
+    assert_eq!(
+      output.summary,
+      Summary {
+        total: 5,
+        succeeded: 3,
+        failed: 2
+      }
+    );
+    let keys: Vec<_> = output
+      .results
+      .iter()
+      .map(|result| result.key.clone())
+      .collect();
+    assert_eq!(keys, [json!(0), json!(1), json!(2), json!(3), json!(4)]);
+    assert_eq!(output.results[2].value, Some(json!(4)));
+    assert_eq!(output.results[1].error.as_ref().unwrap().code, "negative");
+    assert_eq!(
+      output.results[3].error.as_ref().unwrap().code,
+      "handler-panic"
+    );
+  }
+
+  #[test]
+  fn objects_report_their_keys() {
+    let output = run(
+      json!({"collection": {"a": 1}, "api": "std/identity"}),
+      &ctx(),
+    )
+    .unwrap();
+    assert_eq!(output.results[0].key, json!("a"));
+    assert_eq!(output.results[0].value, Some(json!(1)));
+  }
+
+  #[test]
+  fn cancelled_batches_fail_remaining_elements() {
+    let ctx = ctx();
+    ctx.cancellation_token().cancel();
+    let output = run(json!({"collection": [1, 2], "api": "test/check"}), &ctx).unwrap();
+    assert_eq!(output.summary.failed, 2);
+    assert_eq!(output.results[0].error.as_ref().unwrap().code, "cancelled");
+  }
+
+  #[test]
+  fn rejects_bad_arguments() {
+    let error = run(
+      json!({"collection": [1], "api": "std/identity", "concurrency": 0}),
+      &ctx(),
+    );
+    assert_eq!(error.unwrap_err().code, "invalid-concurrency");
+
+    let error = run(json!({"collection": "x", "api": "std/identity"}), &ctx());
+    assert_eq!(error.unwrap_err().code, "invalid-collection");
+
+    let error = run(json!({"collection": [1], "api": "std/missing"}), &ctx());
+    assert_eq!(error.unwrap_err().code, "unknown-api");
+
+    let error = run(
+      json!({"collection": [1], "api": "std/identity"}),
+      &CallContext::new(),
+    );
+    assert_eq!(error.unwrap_err().code, "unknown-api");
+  }
+}
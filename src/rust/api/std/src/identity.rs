@@ -0,0 +1,18 @@
+//! `std/identity`: echo the input back.
+
+use gud_common::log;
+use serde_json::Value;
+
+/// Return the input unchanged, or `null` when no input was provided
+#[allow(clippy::unnecessary_wraps, clippy::option_if_let_else)]
+pub fn identity_function(input: Option<Value>) -> Result<Value, Box<dyn std::error::Error>> {
+  log::debug("Running identity function");
+
+  if let Some(value) = input {
+    log::debug(&format!("Returning input value: {value}"));
+    Ok(value)
+  } else {
+    log::debug("No input provided, returning null");
+    Ok(Value::Null)
+  }
+}
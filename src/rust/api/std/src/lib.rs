@@ -2,3 +2,116 @@
 //!
 //! This crate provides functional programming utilities and standard
 //! operations commonly used throughout the Kleptool ecosystem.
+
+use gud_common::{Handler, Registry};
+
+pub mod canonical;
+pub mod compose;
+pub mod expr;
+pub mod filter;
+pub mod for_each;
+pub mod identity;
+pub mod map;
+pub mod reduce;
+pub mod select;
+pub mod version;
+
+/// Name under which this crate's APIs are registered
+pub const MODULE: &str = "std";
+
+/// Register every `std` API with the given registry
+pub fn register(registry: &mut Registry) {
+  let version = env!("CARGO_PKG_VERSION");
+  registry
+    .register(Handler::new(
+      MODULE,
+      "identity",
+      version,
+      identity::identity_function,
+    ))
+    .register(Handler::required_input_with_context(
+      MODULE,
+      "compose",
+      version,
+      compose::compose,
+    ))
+    .register(Handler::required_input_with_context(
+      MODULE,
+      "map",
+      version,
+      map::map_collection,
+    ))
+    .register(Handler::required_input_with_context(
+      MODULE,
+      "for_each",
+      version,
+      for_each::for_each,
+    ))
+    .register(Handler::required_input_with_context(
+      MODULE,
+      "filter",
+      version,
+      filter::filter_collection,
+    ))
+    .register(Handler::required_input_with_context(
+      MODULE,
+      "reduce",
+      version,
+      reduce::reduce_collection,
+    ))
+    .register(Handler::required_input(
+      MODULE,
+      "parse_version",
+      version,
+      version::parse_version,
+    ))
+    .register(Handler::required_input(
+      MODULE,
+      "compare_versions",
+      version,
+      version::compare_versions,
+    ))
+    .register(Handler::required_input(
+      MODULE,
+      "parse_constraint",
+      version,
+      version::parse_constraint,
+    ))
+    .register(Handler::required_input(
+      MODULE,
+      "satisfies",
+      version,
+      version::satisfies,
+    ))
+    .register(Handler::new(
+      MODULE,
+      "canonicalize",
+      version,
+      canonical::canonicalize,
+    ))
+    .register(Handler::new(
+      MODULE,
+      "digest",
+      version,
+      canonical::digest_value,
+    ));
+}
+
+gud_common::export_c_abi!(
+  register,
+  std,
+  [
+    identity,
+    compose,
+    map,
+    for_each,
+    filter,
+    reduce,
+    parse_version,
+    compare_versions,
+    parse_constraint,
+    satisfies,
+    canonicalize,
+    digest,
+  ]
+);
@@ -0,0 +1,254 @@
+//! `std/map`: transform every element of a collection.
+
+use gud_common::{ApiName, CallContext, ErrorKind, GudError};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+use crate::select::Selector;
+
+/// How each element of the collection is transformed
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+  /// JSON Pointer applied to each element, e.g. `/name`
+  Pointer(String),
+  /// JSON path applied to each element, e.g. `$.deps[*].name`
+  Path(String),
+  /// Object built from named selectors (pointers or paths) per element
+  Project(BTreeMap<String, String>),
+  /// Registered API run with each element as its input, e.g. `ast/to_tree`
+  Api(ApiName),
+}
+
+/// Input of `std/map`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct MapInput {
+  /// Array or object whose elements are transformed
+  pub collection: Value,
+  /// How each element is turned into its output
+  pub transform: Transform,
+}
+
+enum Compiled {
+  Select(Selector),
+  Project(Vec<(String, Selector)>),
+  Api(ApiName),
+}
+
+impl Compiled {
+  fn new(transform: Transform) -> Result<Self, GudError> {
+    Ok(match transform {
+      Transform::Pointer(pointer) if !pointer.is_empty() && !pointer.starts_with('/') => {
+        return Err(GudError::new(
+          ErrorKind::Parsing,
+          "invalid-selector",
+          format!("JSON Pointer '{pointer}' must start with '/'"),
+        ))
+      }
+      Transform::Pointer(selector) | Transform::Path(selector) => {
+        Self::Select(Selector::parse(&selector)?)
+      }
+      Transform::Project(fields) => Self::Project(
+        fields
+          .into_iter()
+          .map(|(name, selector)| Ok((name, Selector::parse(&selector)?)))
+          .collect::<Result<_, GudError>>()?,
+      ),
+      Transform::Api(name) => Self::Api(name),
+    })
+  }
+
+  fn apply(&self, key: &Value, element: Value, ctx: &CallContext) -> Result<Value, GudError> {
+    ctx.check()?;
+    match self {
+      Self::Select(selector) => Ok(selector.select(&element)),
+      Self::Project(fields) => Ok(Value::Object(
+        fields
+          .iter()
+          .map(|(name, selector)| (name.clone(), selector.select(&element)))
+          .collect(),
+      )),
+      Self::Api(name) => ctx
+        .call(name, Some(element))
+        .map_err(|e| element_error(key, name, GudError::from_boxed(e))),
+    }
+  }
+}
+
+/// Wrap the failure of one element, keeping the handler's kind and code
+pub(crate) fn element_error(key: &Value, name: &ApiName, error: GudError) -> GudError {
+  GudError::new(
+    error.kind,
+    error.code,
+    format!("{name} failed on element {key}: {}", error.message),
+  )
+  .with_context(json!({ "key": key, "api": name, "context": error.context }))
+}
+
+/// Error for a collection that is neither an array nor an object
+pub(crate) fn invalid_collection(collection: &Value) -> GudError {
+  GudError::new(
+    ErrorKind::Argument,
+    "invalid-collection",
+    "The collection must be a JSON array or object",
+  )
+  .with_context(json!({ "collection": collection }))
+}
+
+/// Keys and elements of a collection, in order: indexes for arrays and keys
+/// for objects
+pub(crate) fn entries(collection: Value) -> Result<Vec<(Value, Value)>, GudError> {
+  match collection {
+    Value::Array(items) => Ok(
+      items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| (json!(index), item))
+        .collect(),
+    ),
+    Value::Object(entries) => Ok(
+      entries
+        .into_iter()
+        .map(|(key, value)| (json!(key), value))
+        .collect(),
+    ),
+    other => Err(invalid_collection(&other)),
+  }
+}
+
+/// Rebuild a collection of the same kind from some of its entries
+pub(crate) fn collect(array: bool, entries: Vec<(Value, Value)>) -> Value {
+  if array {
+    Value::Array(entries.into_iter().map(|(_, value)| value).collect())
+  } else {
+    Value::Object(
+      entries
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.as_str()?.to_string(), value)))
+        .collect::<Map<_, _>>(),
+    )
+  }
+}
+
+/// Transform every element of an array or object, keeping its shape.
+///
+/// A failing element fails the whole call; use `std/for_each` to collect
+/// per-element failures instead.
+pub fn map_collection(
+  input: MapInput,
+  ctx: &CallContext,
+) -> Result<Value, Box<dyn std::error::Error>> {
+  let transform = Compiled::new(input.transform)?;
+  let report = matches!(transform, Compiled::Api(_));
+
+  match input.collection {
+    Value::Array(items) => {
+      let total = items.len();
+      let mut mapped = Vec::with_capacity(total);
+      for (index, item) in items.into_iter().enumerate() {
+        mapped.push(transform.apply(&json!(index), item, ctx)?);
+        if report {
+          ctx.progress(&json!({ "done": index + 1, "total": total }))?;
+        }
+      }
+      Ok(Value::Array(mapped))
+    }
+    Value::Object(entries) => {
+      let total = entries.len();
+      let mut mapped = Map::with_capacity(total);
+      for (done, (key, value)) in entries.into_iter().enumerate() {
+        let value = transform.apply(&json!(key), value, ctx)?;
+        mapped.insert(key, value);
+        if report {
+          ctx.progress(&json!({ "done": done + 1, "total": total }))?;
+        }
+      }
+      Ok(Value::Object(mapped))
+    }
+    other => Err(invalid_collection(&other).into()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use gud_common::Registry;
+  use std::sync::Arc;
+
+  fn map(input: Value, ctx: &CallContext) -> Result<Value, GudError> {
+    map_collection(serde_json::from_value(input).unwrap(), ctx).map_err(GudError::from_boxed)
+  }
+
+  fn ctx() -> CallContext {
+    let mut registry = Registry::new();
+    crate::register(&mut registry);
+    CallContext::new().with_registry(Arc::new(registry))
+  }
+
+  #[test]
+  fn projects_array_elements() {
+    let deps = json!([
+      {"name": "serde", "version": "^1.0"},
+      {"name": "tokio", "version": "1.35"},
+    ]);
+
+    assert_eq!(
+      map(
+        json!({"collection": deps, "transform": {"pointer": "/name"}}),
+        &ctx()
+      )
+      .unwrap(),
+      json!(["serde", "tokio"])
+    );
+    assert_eq!(
+      map(
+        json!({"collection": deps, "transform": {"project": {"n": "$.name", "v": "/version"}}}),
+        &ctx()
+      )
+      .unwrap(),
+      json!([{"n": "serde", "v": "^1.0"}, {"n": "tokio", "v": "1.35"}])
+    );
+  }
+
+  #[test]
+  fn keeps_object_keys() {
+    let input = json!({
+      "collection": {"a": {"v": 1}, "b": {"v": 2}},
+      "transform": {"path": "$.v"},
+    });
+    assert_eq!(map(input, &ctx()).unwrap(), json!({"a": 1, "b": 2}));
+  }
+
+  #[test]
+  fn applies_registered_apis() {
+    let input = json!({"collection": [1, "two"], "transform": {"api": "std/identity"}});
+    assert_eq!(map(input, &ctx()).unwrap(), json!([1, "two"]));
+  }
+
+  #[test]
+  fn reports_the_failing_element() {
+    let input = json!({"collection": [1], "transform": {"api": "std/missing"}});
+    let error = map(input, &ctx()).unwrap_err();
+    assert_eq!(error.code, "unknown-api");
+    assert_eq!(error.context["key"], json!(0));
+  }
+
+  #[test]
+  fn rejects_scalars_and_bad_selectors() {
+    let error = map(
+      json!({"collection": 3, "transform": {"pointer": ""}}),
+      &ctx(),
+    )
+    .unwrap_err();
+    assert_eq!(error.code, "invalid-collection");
+
+    let error = map(
+      json!({"collection": [], "transform": {"pointer": "name"}}),
+      &ctx(),
+    )
+    .unwrap_err();
+    assert_eq!(error.code, "invalid-selector");
+  }
+}
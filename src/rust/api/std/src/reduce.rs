@@ -0,0 +1,81 @@
+//! `std/reduce`: fold a collection into one value with an expression.
+
+use gud_common::CallContext;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::expr::Expr;
+use crate::filter::element_failure;
+use crate::map::entries;
+
+/// Input of `std/reduce`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ReduceInput {
+  /// Array or object to fold
+  pub collection: Value,
+  /// Expression computing the next accumulator from `acc`, `it` (the
+  /// element) and `key` (its index or key), e.g. `acc + len(it.deps)`
+  pub reducer: String,
+  /// Initial accumulator; `null` when omitted
+  #[serde(default)]
+  pub init: Value,
+}
+
+/// Fold a collection into a single value, visiting elements in order
+pub fn reduce_collection(
+  input: ReduceInput,
+  ctx: &CallContext,
+) -> Result<Value, Box<dyn std::error::Error>> {
+  let reducer = Expr::parse(&input.reducer, &["acc", "it", "key"])?;
+
+  let mut acc = input.init;
+  for (key, element) in entries(input.collection)? {
+    ctx.check()?;
+    acc = reducer
+      .eval(&[("acc", &acc), ("it", &element), ("key", &key)])
+      .map_err(|e| element_failure(&reducer, &key, e))?;
+  }
+
+  Ok(acc)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use gud_common::GudError;
+  use serde_json::json;
+
+  fn reduce(input: Value) -> Result<Value, GudError> {
+    reduce_collection(serde_json::from_value(input).unwrap(), &CallContext::new())
+      .map_err(GudError::from_boxed)
+  }
+
+  #[test]
+  fn folds_arrays_and_objects() {
+    let deps = json!([{"deps": ["a", "b"]}, {"deps": []}, {"deps": ["c"]}]);
+    assert_eq!(
+      reduce(json!({"collection": deps, "reducer": "acc + len(it.deps)", "init": 0})).unwrap(),
+      json!(3)
+    );
+    assert_eq!(
+      reduce(json!({"collection": {"x": 1, "y": 2}, "reducer": "acc + [key]", "init": []}))
+        .unwrap(),
+      json!(["x", "y"])
+    );
+  }
+
+  #[test]
+  fn starts_from_null_without_init() {
+    let input = json!({"collection": [3, 9, 4], "reducer": "max(default(acc, it), it)"});
+    assert_eq!(reduce(input).unwrap(), json!(9));
+  }
+
+  #[test]
+  fn reports_the_failing_element() {
+    let input = json!({"collection": [1, "x"], "reducer": "acc + it", "init": 0});
+    let error = reduce(input).unwrap_err();
+    assert_eq!(error.code, "expression-error");
+    assert_eq!(error.context["key"], json!(1));
+  }
+}
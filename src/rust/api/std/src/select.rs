@@ -0,0 +1,221 @@
+//! JSON Pointer and JSON path selectors shared by the collection APIs.
+
+use gud_common::{ErrorKind, GudError};
+use serde_json::Value;
+
+/// One step of a JSON path expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+  /// `.name` or `['name']`
+  Field(String),
+  /// `[3]`, or `[-1]` counting from the end
+  Index(i64),
+  /// `.*` or `[*]`: every element of an array or value of an object
+  Wildcard,
+}
+
+/// Compiled reference into a JSON value.
+///
+/// Either a JSON Pointer (RFC 6901) such as `/deps/0/name`, or a JSON path
+/// subset starting with `$` that supports `.field`, `['field']`, `[index]`
+/// and `[*]`/`.*` wildcards, such as `$.deps[*].name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+  /// JSON Pointer; the empty pointer selects the whole value
+  Pointer(String),
+  /// JSON path expression
+  Path(Vec<Segment>),
+}
+
+fn invalid(selector: &str, position: usize, reason: &str) -> GudError {
+  GudError::new(
+    ErrorKind::Parsing,
+    "invalid-selector",
+    format!("Invalid selector '{selector}' at {position}: {reason}"),
+  )
+  .with_context(serde_json::json!({ "selector": selector, "position": position }))
+}
+
+fn parse_path(selector: &str) -> Result<Vec<Segment>, GudError> {
+  let chars: Vec<char> = selector.chars().collect();
+  let mut segments = Vec::new();
+  let mut i = 1;
+
+  while i < chars.len() {
+    match chars[i] {
+      '.' => {
+        i += 1;
+        let start = i;
+        while i < chars.len() && chars[i] != '.' && chars[i] != '[' {
+          i += 1;
+        }
+        let name: String = chars[start..i].iter().collect();
+        segments.push(match name.as_str() {
+          "" => return Err(invalid(selector, start, "expected a field name")),
+          "*" => Segment::Wildcard,
+          _ => Segment::Field(name),
+        });
+      }
+      '[' => {
+        let start = i;
+        let end = (i..chars.len())
+          .find(|&j| chars[j] == ']')
+          .ok_or_else(|| invalid(selector, start, "unclosed '['"))?;
+        let inner: String = chars[i + 1..end].iter().collect();
+        let quoted = inner.len() >= 2
+          && ((inner.starts_with('\'') && inner.ends_with('\''))
+            || (inner.starts_with('"') && inner.ends_with('"')));
+
+        segments.push(if inner == "*" {
+          Segment::Wildcard
+        } else if quoted {
+          Segment::Field(inner[1..inner.len() - 1].to_string())
+        } else {
+          Segment::Index(inner.parse().map_err(|_| {
+            invalid(
+              selector,
+              start + 1,
+              "expected an index, '*' or a quoted field",
+            )
+          })?)
+        });
+        i = end + 1;
+      }
+      _ => return Err(invalid(selector, i, "expected '.' or '['")),
+    }
+  }
+
+  Ok(segments)
+}
+
+fn step<'a>(segment: &Segment, value: &'a Value, out: &mut Vec<&'a Value>) {
+  match (segment, value) {
+    (Segment::Field(name), Value::Object(map)) => out.extend(map.get(name)),
+    (Segment::Index(index), Value::Array(items)) => {
+      let index = if *index < 0 {
+        i64::try_from(items.len()).ok().map(|len| len + index)
+      } else {
+        Some(*index)
+      };
+      out.extend(
+        index
+          .and_then(|index| usize::try_from(index).ok())
+          .and_then(|index| items.get(index)),
+      );
+    }
+    (Segment::Wildcard, Value::Array(items)) => out.extend(items),
+    (Segment::Wildcard, Value::Object(map)) => out.extend(map.values()),
+    _ => {}
+  }
+}
+
+impl Selector {
+  /// Compile a selector: a string starting with `$` is a JSON path
+  /// expression, and an empty string or one starting with `/` is a JSON
+  /// Pointer
+  pub fn parse(selector: &str) -> Result<Self, GudError> {
+    if selector.is_empty() || selector.starts_with('/') {
+      Ok(Self::Pointer(selector.to_string()))
+    } else if selector.starts_with('$') {
+      Ok(Self::Path(parse_path(selector)?))
+    } else {
+      Err(invalid(
+        selector,
+        0,
+        "expected a JSON Pointer starting with '/' or a JSON path starting with '$'",
+      ))
+    }
+  }
+
+  /// Whether the selector can match several values
+  #[must_use]
+  pub fn is_multi(&self) -> bool {
+    matches!(self, Self::Path(segments) if segments.contains(&Segment::Wildcard))
+  }
+
+  /// Every value the selector matches, in document order
+  #[must_use]
+  pub fn select_all<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+    match self {
+      Self::Pointer(pointer) => value.pointer(pointer).into_iter().collect(),
+      Self::Path(segments) => segments.iter().fold(vec![value], |current, segment| {
+        let mut next = Vec::new();
+        for value in current {
+          step(segment, value, &mut next);
+        }
+        next
+      }),
+    }
+  }
+
+  /// The selected value: an array of every match for selectors with a
+  /// wildcard, otherwise the single match or `null` when nothing matches
+  #[must_use]
+  pub fn select(&self, value: &Value) -> Value {
+    let matches = self.select_all(value);
+    if self.is_multi() {
+      Value::Array(matches.into_iter().cloned().collect())
+    } else {
+      matches.first().map_or(Value::Null, |&value| value.clone())
+    }
+  }
+}
+
+impl std::str::FromStr for Selector {
+  type Err = GudError;
+
+  fn from_str(selector: &str) -> Result<Self, Self::Err> {
+    Self::parse(selector)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn manifest() -> Value {
+    json!({
+      "name": "klep",
+      "deps": [
+        {"name": "serde", "version": "^1.0"},
+        {"name": "tokio", "version": "1.35"},
+      ],
+      "odd key": true,
+    })
+  }
+
+  fn select(selector: &str) -> Value {
+    Selector::parse(selector).unwrap().select(&manifest())
+  }
+
+  #[test]
+  fn pointers_select_single_values() {
+    assert_eq!(select("/deps/1/name"), json!("tokio"));
+    assert_eq!(select("/missing"), Value::Null);
+    assert_eq!(select(""), manifest());
+  }
+
+  #[test]
+  fn paths_select_fields_and_indexes() {
+    assert_eq!(select("$.name"), json!("klep"));
+    assert_eq!(select("$.deps[0].version"), json!("^1.0"));
+    assert_eq!(select("$.deps[-1].name"), json!("tokio"));
+    assert_eq!(select("$['odd key']"), json!(true));
+    assert_eq!(select("$"), manifest());
+  }
+
+  #[test]
+  fn wildcards_collect_every_match() {
+    assert_eq!(select("$.deps[*].name"), json!(["serde", "tokio"]));
+    assert_eq!(select("$.deps.*.missing"), json!([]));
+  }
+
+  #[test]
+  fn rejects_malformed_selectors() {
+    for selector in ["name", "$.", "$[0", "$[x]", "$name"] {
+      let error = Selector::parse(selector).unwrap_err();
+      assert_eq!(error.code, "invalid-selector", "{selector}");
+    }
+  }
+}
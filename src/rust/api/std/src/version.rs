@@ -0,0 +1,854 @@
+//! Versions and version constraints as written in `klep.deps`.
+//!
+//! A version is a semantic version (`1.2.3`, `v2.0.0-rc.1`), a calendar
+//! version (`v2021.3`, `2024.01.15`), a commit hash, a branch or tag name,
+//! or `latest`. Constraints follow npm's range syntax (`^1.2`, `~1.2.3`,
+//! `>=1.0 <2`, `1.x || 2.x`, `1.2 - 1.4`) and also accept calendar versions
+//! (`>=v2021.1`) and exact references (`=<hash>`, `main`).
+//!
+//! [`Constraint::parse`] normalizes every constraint into exact matches,
+//! half-open ranges and unions of those, so consumers such as the resolver
+//! never need to understand the surface syntax.
+
+use gud_common::{ErrorKind, GudError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// First component from which a leading number is read as a calendar year
+const CALVER_MIN_YEAR: u64 = 1000;
+
+/// A parsed version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Version {
+  /// `major.minor.patch`, with missing components filled with zeros
+  Semver {
+    /// Incremented for breaking changes
+    major: u64,
+    /// Incremented for new features
+    minor: u64,
+    /// Incremented for fixes
+    patch: u64,
+    /// e.g. `rc.1`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    prerelease: String,
+    /// Build metadata, ignored when comparing
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    build: String,
+  },
+  /// A year followed by any number of numeric components, e.g. `2021.3`
+  Calver {
+    /// Leading component, e.g. `2021`
+    year: u64,
+    /// Numeric components after the year
+    #[serde(default)]
+    parts: Vec<u64>,
+    /// e.g. `beta`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    prerelease: String,
+  },
+  /// A full commit hash, lowercased
+  Hash {
+    /// Hexadecimal digits of the hash
+    hash: String,
+  },
+  /// A branch name, or a tag that is not a version number
+  Ref {
+    /// Name of the branch or tag
+    name: String,
+  },
+  /// The newest commit of the default branch
+  Latest,
+}
+
+fn invalid_version(raw: &str, reason: &str) -> GudError {
+  GudError::new(
+    ErrorKind::Argument,
+    "invalid-version",
+    format!("'{raw}' is not a valid version: {reason}"),
+  )
+  .with_context(json!({ "version": raw }))
+}
+
+fn invalid_constraint(raw: &str, reason: &str) -> GudError {
+  GudError::new(
+    ErrorKind::Argument,
+    "invalid-constraint",
+    format!("'{raw}' is not a valid constraint: {reason}"),
+  )
+  .with_context(json!({ "constraint": raw }))
+}
+
+/// Whether `raw` is a full SHA-1 or SHA-256 commit hash, that is 40 or 64
+/// hexadecimal digits.
+///
+/// Abbreviated hashes are not recognized: a short hexadecimal string such as
+/// `cafe123` or `20210301` is as likely to be a branch, a tag or a version.
+#[must_use]
+pub fn is_hash(raw: &str) -> bool {
+  (raw.len() == 40 || raw.len() == 64) && raw.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Family {
+  Semver,
+  Calver,
+}
+
+/// The numeric part of a version, as written: `v1.2` has two components
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Numeric {
+  parts: Vec<u64>,
+  /// Whether a trailing `x` or `*` stood in for further components
+  wildcard: bool,
+  prerelease: String,
+  build: String,
+}
+
+impl Numeric {
+  fn parse(raw: &str) -> Option<Self> {
+    let raw = raw.strip_prefix(['v', 'V']).unwrap_or(raw);
+    let (rest, build) = raw.split_once('+').unwrap_or((raw, ""));
+    let (core, prerelease) = rest.split_once('-').unwrap_or((rest, ""));
+    let valid_label = |label: &str| {
+      label
+        .split('.')
+        .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+    };
+    if (!prerelease.is_empty() && !valid_label(prerelease))
+      || (!build.is_empty() && !valid_label(build))
+    {
+      return None;
+    }
+    if rest.contains('-') && prerelease.is_empty() {
+      return None;
+    }
+
+    let mut parts = Vec::new();
+    let mut wildcard = false;
+    for part in core.split('.') {
+      if matches!(part, "x" | "X" | "*") {
+        wildcard = true;
+      } else if wildcard || part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+      } else {
+        parts.push(part.parse().ok()?);
+      }
+    }
+    if parts.is_empty() || (wildcard && !prerelease.is_empty()) {
+      return None;
+    }
+
+    Some(Self {
+      parts,
+      wildcard,
+      prerelease: prerelease.to_string(),
+      build: build.to_string(),
+    })
+  }
+
+  fn family(&self) -> Family {
+    if self.parts[0] >= CALVER_MIN_YEAR {
+      Family::Calver
+    } else {
+      Family::Semver
+    }
+  }
+
+  /// Whether every component of the family was given
+  fn is_full(&self) -> bool {
+    !self.wildcard && (self.family() == Family::Calver || self.parts.len() == 3)
+  }
+
+  fn version(&self) -> Result<Version, &'static str> {
+    version_from(self.family(), &self.parts, &self.prerelease, &self.build)
+  }
+
+  /// The smallest version above every version matching the first `len`
+  /// components, e.g. `1.3.0` for `1.2` with `len == 2`
+  fn bump(&self, len: usize) -> Result<Version, &'static str> {
+    let mut parts = self.parts[..len].to_vec();
+    parts[len - 1] = parts[len - 1]
+      .checked_add(1)
+      .ok_or("a component is too large to have a next version")?;
+    version_from(self.family(), &parts, "", "")
+  }
+}
+
+fn version_from(
+  family: Family,
+  parts: &[u64],
+  prerelease: &str,
+  build: &str,
+) -> Result<Version, &'static str> {
+  match family {
+    Family::Semver if parts.len() > 3 => Err("semantic versions have at most three components"),
+    Family::Semver => Ok(Version::Semver {
+      major: parts[0],
+      minor: parts.get(1).copied().unwrap_or(0),
+      patch: parts.get(2).copied().unwrap_or(0),
+      prerelease: prerelease.to_string(),
+      build: build.to_string(),
+    }),
+    Family::Calver => Ok(Version::Calver {
+      year: parts[0],
+      parts: parts[1..].to_vec(),
+      prerelease: prerelease.to_string(),
+    }),
+  }
+}
+
+fn compare_prerelease(a: &str, b: &str) -> Ordering {
+  match (a.is_empty(), b.is_empty()) {
+    (true, true) => Ordering::Equal,
+    (true, false) => Ordering::Greater,
+    (false, true) => Ordering::Less,
+    (false, false) => match (semver::Prerelease::new(a), semver::Prerelease::new(b)) {
+      (Ok(a), Ok(b)) => a.cmp(&b),
+      _ => a.cmp(b),
+    },
+  }
+}
+
+fn compare_parts(a: &[u64], b: &[u64]) -> Ordering {
+  (0..a.len().max(b.len()))
+    .map(|i| a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)))
+    .find(|order| order.is_ne())
+    .unwrap_or(Ordering::Equal)
+}
+
+impl Version {
+  /// Classify and parse a version string.
+  ///
+  /// Anything that is not `latest`, a hash or a version number is taken as
+  /// a branch or tag name, as long as it contains no whitespace.
+  pub fn parse(raw: &str) -> Result<Self, GudError> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+      return Err(invalid_version(raw, "the version is empty"));
+    }
+    if raw == "latest" {
+      return Ok(Self::Latest);
+    }
+    if is_hash(raw) {
+      return Ok(Self::Hash {
+        hash: raw.to_ascii_lowercase(),
+      });
+    }
+    if let Some(numeric) = Numeric::parse(raw).filter(|numeric| !numeric.wildcard) {
+      return numeric
+        .version()
+        .map_err(|reason| invalid_version(raw, reason));
+    }
+    if raw.chars().any(|c| c.is_whitespace() || c.is_control()) {
+      return Err(invalid_version(raw, "references cannot contain whitespace"));
+    }
+
+    Ok(Self::Ref {
+      name: raw.to_string(),
+    })
+  }
+
+  /// Numeric components used for ordering, for semantic and calendar
+  /// versions
+  fn release(&self) -> Option<(Family, Vec<u64>)> {
+    match self {
+      Self::Semver {
+        major,
+        minor,
+        patch,
+        ..
+      } => Some((Family::Semver, vec![*major, *minor, *patch])),
+      Self::Calver { year, parts, .. } => Some((
+        Family::Calver,
+        std::iter::once(*year)
+          .chain(parts.iter().copied())
+          .collect(),
+      )),
+      _ => None,
+    }
+  }
+
+  fn prerelease(&self) -> &str {
+    match self {
+      Self::Semver { prerelease, .. } | Self::Calver { prerelease, .. } => prerelease,
+      _ => "",
+    }
+  }
+
+  /// Whether this is a pre-release such as `1.0.0-rc.1`
+  #[must_use]
+  pub fn is_prerelease(&self) -> bool {
+    !self.prerelease().is_empty()
+  }
+
+  /// Order two versions of the same family by precedence.
+  ///
+  /// Semantic versions only compare with semantic versions and calendar
+  /// versions with calendar versions; build metadata is ignored. Hashes,
+  /// references and `latest` are only equal to themselves, so `None` is
+  /// returned for any other pair.
+  #[must_use]
+  pub fn compare(&self, other: &Self) -> Option<Ordering> {
+    match (self.release(), other.release()) {
+      (Some((family, a)), Some((other_family, b))) if family == other_family => Some(
+        compare_parts(&a, &b)
+          .then_with(|| compare_prerelease(self.prerelease(), other.prerelease())),
+      ),
+      (Some(_), _) | (_, Some(_)) => None,
+      _ if self == other => Some(Ordering::Equal),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Version {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Semver {
+        major,
+        minor,
+        patch,
+        prerelease,
+        build,
+      } => {
+        write!(f, "{major}.{minor}.{patch}")?;
+        if !prerelease.is_empty() {
+          write!(f, "-{prerelease}")?;
+        }
+        if !build.is_empty() {
+          write!(f, "+{build}")?;
+        }
+        Ok(())
+      }
+      Self::Calver {
+        year,
+        parts,
+        prerelease,
+      } => {
+        write!(f, "{year}")?;
+        for part in parts {
+          write!(f, ".{part}")?;
+        }
+        if !prerelease.is_empty() {
+          write!(f, "-{prerelease}")?;
+        }
+        Ok(())
+      }
+      Self::Hash { hash } => f.write_str(hash),
+      Self::Ref { name } => f.write_str(name),
+      Self::Latest => f.write_str("latest"),
+    }
+  }
+}
+
+impl std::str::FromStr for Version {
+  type Err = GudError;
+
+  fn from_str(raw: &str) -> Result<Self, Self::Err> {
+    Self::parse(raw)
+  }
+}
+
+/// One end of a [`Constraint::Range`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Bound {
+  /// Version at this end of the range
+  pub version: Version,
+  /// Whether `version` itself is inside the range
+  pub inclusive: bool,
+}
+
+impl Bound {
+  const fn inclusive(version: Version) -> Self {
+    Self {
+      version,
+      inclusive: true,
+    }
+  }
+
+  const fn exclusive(version: Version) -> Self {
+    Self {
+      version,
+      inclusive: false,
+    }
+  }
+}
+
+/// A normalized version constraint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Constraint {
+  /// Every version, written `*`, `x` or left empty
+  Any,
+  /// Exactly this version, hash or reference
+  Exact {
+    /// The only version allowed
+    version: Version,
+  },
+  /// Versions between two bounds; a missing bound is unbounded
+  Range {
+    /// Smallest version allowed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lower: Option<Bound>,
+    /// Largest version allowed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    upper: Option<Bound>,
+  },
+  /// Versions matching any of the constraints
+  Union {
+    /// Alternatives, any of which is enough
+    any_of: Vec<Self>,
+  },
+}
+
+const OPERATORS: [&str; 9] = [">=", "<=", "!=", "==", ">", "<", "=", "^", "~"];
+
+const fn range(lower: Option<Bound>, upper: Option<Bound>) -> Constraint {
+  Constraint::Range { lower, upper }
+}
+
+/// Build the constraint for a single `op version` comparator
+fn comparator(raw: &str, op: &str, version: &str) -> Result<Constraint, GudError> {
+  if matches!(version, "" | "*" | "x" | "X") {
+    return Ok(Constraint::Any);
+  }
+
+  let Some(numeric) = Numeric::parse(version) else {
+    return if matches!(op, "" | "=" | "==") {
+      Ok(Constraint::Exact {
+        version: Version::parse(version).map_err(|e| invalid_constraint(raw, &e.message))?,
+      })
+    } else {
+      Err(invalid_constraint(
+        raw,
+        &format!("'{op}' needs a version number"),
+      ))
+    };
+  };
+
+  let fail = |reason: &str| invalid_constraint(raw, reason);
+  let given = numeric.parts.len();
+  let full = numeric.is_full();
+  let lower = numeric.version().map_err(fail)?;
+  let next = || numeric.bump(given).map_err(fail);
+  let caret_len = match numeric.family() {
+    Family::Calver => 1,
+    Family::Semver if numeric.parts[0] > 0 || given == 1 => 1,
+    Family::Semver if numeric.parts[1] > 0 || given == 2 => 2,
+    Family::Semver => 3,
+  };
+
+  Ok(match op {
+    "" | "=" | "==" if full => Constraint::Exact { version: lower },
+    "" | "=" | "==" => range(
+      Some(Bound::inclusive(lower)),
+      Some(Bound::exclusive(next()?)),
+    ),
+    "!=" if full => Constraint::Union {
+      any_of: vec![
+        range(None, Some(Bound::exclusive(lower.clone()))),
+        range(Some(Bound::exclusive(lower)), None),
+      ],
+    },
+    "!=" => Constraint::Union {
+      any_of: vec![
+        range(None, Some(Bound::exclusive(lower))),
+        range(Some(Bound::inclusive(next()?)), None),
+      ],
+    },
+    ">" if full => range(Some(Bound::exclusive(lower)), None),
+    ">" => range(Some(Bound::inclusive(next()?)), None),
+    ">=" => range(Some(Bound::inclusive(lower)), None),
+    "<" => range(None, Some(Bound::exclusive(lower))),
+    "<=" if full => range(None, Some(Bound::inclusive(lower))),
+    "<=" => range(None, Some(Bound::exclusive(next()?))),
+    "~" => {
+      let upper = numeric.bump(given.min(2)).map_err(fail)?;
+      range(Some(Bound::inclusive(lower)), Some(Bound::exclusive(upper)))
+    }
+    _ => {
+      let upper = numeric.bump(caret_len.min(given)).map_err(fail)?;
+      range(Some(Bound::inclusive(lower)), Some(Bound::exclusive(upper)))
+    }
+  })
+}
+
+/// Split one `||` alternative into `(operator, version)` comparators
+fn comparators(alternative: &str) -> Vec<(&str, &str)> {
+  let words: Vec<&str> = alternative
+    .split(|c: char| c.is_whitespace() || c == ',')
+    .filter(|word| !word.is_empty())
+    .collect();
+
+  let mut comparators = Vec::new();
+  let mut i = 0;
+  while i < words.len() {
+    if words.get(i + 1) == Some(&"-") && i + 2 < words.len() {
+      comparators.push((">=", words[i]));
+      comparators.push(("<=", words[i + 2]));
+      i += 3;
+      continue;
+    }
+
+    let word = words[i];
+    let op = OPERATORS
+      .iter()
+      .find(|op| word.starts_with(**op))
+      .copied()
+      .unwrap_or("");
+    let version = &word[op.len()..];
+    if version.is_empty() && !op.is_empty() && i + 1 < words.len() {
+      comparators.push((op, words[i + 1]));
+      i += 2;
+    } else {
+      comparators.push((op, version));
+      i += 1;
+    }
+  }
+  comparators
+}
+
+fn unsatisfiable(raw: &str) -> GudError {
+  GudError::new(
+    ErrorKind::Argument,
+    "unsatisfiable-constraint",
+    format!("No version can satisfy '{raw}'"),
+  )
+  .with_context(json!({ "constraint": raw }))
+}
+
+/// The tighter of two lower (`upper == false`) or upper bounds
+fn tighter(a: Option<Bound>, b: Option<Bound>, upper: bool) -> Result<Option<Bound>, ()> {
+  let (a, b) = match (a, b) {
+    (Some(a), Some(b)) => (a, b),
+    (a, b) => return Ok(a.or(b)),
+  };
+  let order = a.version.compare(&b.version).ok_or(())?;
+  Ok(Some(match (order, upper) {
+    (Ordering::Equal, _) => Bound {
+      inclusive: a.inclusive && b.inclusive,
+      ..a
+    },
+    (Ordering::Less, true) | (Ordering::Greater, false) => a,
+    _ => b,
+  }))
+}
+
+fn intersect(raw: &str, a: Constraint, b: Constraint) -> Result<Constraint, GudError> {
+  match (a, b) {
+    (Constraint::Any, other) | (other, Constraint::Any) => Ok(other),
+    (Constraint::Union { any_of }, other) | (other, Constraint::Union { any_of }) => {
+      let any_of: Vec<_> = any_of
+        .into_iter()
+        .filter_map(|alternative| intersect(raw, alternative, other.clone()).ok())
+        .collect();
+      match any_of.len() {
+        0 => Err(unsatisfiable(raw)),
+        1 => Ok(any_of.into_iter().next().unwrap_or(Constraint::Any)),
+        _ => Ok(Constraint::Union { any_of }),
+      }
+    }
+    (Constraint::Exact { version }, other) | (other, Constraint::Exact { version }) => {
+      if other.matches(&version) {
+        Ok(Constraint::Exact { version })
+      } else {
+        Err(unsatisfiable(raw))
+      }
+    }
+    (
+      Constraint::Range {
+        lower: a_lower,
+        upper: a_upper,
+      },
+      Constraint::Range {
+        lower: b_lower,
+        upper: b_upper,
+      },
+    ) => {
+      let incompatible =
+        |()| invalid_constraint(raw, "calendar and semantic versions cannot be combined");
+      let lower = tighter(a_lower, b_lower, false).map_err(incompatible)?;
+      let upper = tighter(a_upper, b_upper, true).map_err(incompatible)?;
+      if let (Some(lower), Some(upper)) = (&lower, &upper) {
+        match lower.version.compare(&upper.version) {
+          Some(Ordering::Less) => {}
+          Some(Ordering::Equal) if lower.inclusive && upper.inclusive => {}
+          Some(_) => return Err(unsatisfiable(raw)),
+          None => return Err(incompatible(())),
+        }
+      }
+      Ok(range(lower, upper))
+    }
+  }
+}
+
+fn bound_allows(bound: Option<&Bound>, version: &Version, allowed: Ordering) -> bool {
+  bound.is_none_or(|bound| match version.compare(&bound.version) {
+    Some(Ordering::Equal) => bound.inclusive,
+    Some(order) => order == allowed,
+    None => false,
+  })
+}
+
+impl Constraint {
+  /// Parse and normalize a constraint.
+  ///
+  /// Partial versions expand like npm ranges: `^1.2` is `>=1.2.0 <2.0.0`,
+  /// `~1.2` is `>=1.2.0 <1.3.0` and a bare `1.2` is `>=1.2.0 <1.3.0`.
+  /// Comparators separated by spaces or commas must all hold, and `||`
+  /// separates alternatives.
+  pub fn parse(raw: &str) -> Result<Self, GudError> {
+    let mut any_of = Vec::new();
+    for alternative in raw.split("||") {
+      let mut constraint = Self::Any;
+      for (op, version) in comparators(alternative) {
+        constraint = intersect(raw, constraint, comparator(raw, op, version)?)?;
+      }
+      if constraint == Self::Any {
+        return Ok(Self::Any);
+      }
+      match constraint {
+        Self::Union { any_of: nested } => any_of.extend(nested),
+        constraint => any_of.push(constraint),
+      }
+    }
+
+    Ok(if any_of.len() == 1 {
+      any_of.remove(0)
+    } else {
+      Self::Union { any_of }
+    })
+  }
+
+  /// Whether `version` satisfies the constraint.
+  ///
+  /// As with npm, a pre-release only falls inside a range when one of the
+  /// range's bounds is a pre-release of the same release, so `^1.2` does not
+  /// match `2.0.0-rc.1` or `1.3.0-beta`.
+  #[must_use]
+  pub fn matches(&self, version: &Version) -> bool {
+    match self {
+      Self::Any => true,
+      Self::Exact { version: exact } => exact.compare(version) == Some(Ordering::Equal),
+      Self::Range { lower, upper } => {
+        let prerelease_allowed = !version.is_prerelease()
+          || [lower, upper].into_iter().flatten().any(|bound| {
+            bound.version.is_prerelease() && bound.version.release() == version.release()
+          });
+        prerelease_allowed
+          && bound_allows(lower.as_ref(), version, Ordering::Greater)
+          && bound_allows(upper.as_ref(), version, Ordering::Less)
+      }
+      Self::Union { any_of } => any_of.iter().any(|constraint| constraint.matches(version)),
+    }
+  }
+}
+
+impl std::str::FromStr for Constraint {
+  type Err = GudError;
+
+  fn from_str(raw: &str) -> Result<Self, Self::Err> {
+    Self::parse(raw)
+  }
+}
+
+/// Input of `std/compare_versions`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct CompareInput {
+  /// Version on the left of the comparison
+  pub a: String,
+  /// Version on the right of the comparison
+  pub b: String,
+}
+
+/// Input of `std/satisfies`
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct SatisfiesInput {
+  /// Constraint in `klep.deps` syntax, e.g. `^1.2`
+  pub constraint: String,
+  /// Version checked against `constraint`
+  pub version: String,
+}
+
+/// Parse a version string into its classified form
+#[allow(clippy::needless_pass_by_value)]
+pub fn parse_version(raw: String) -> Result<Version, Box<dyn std::error::Error>> {
+  Ok(Version::parse(&raw)?)
+}
+
+/// Compare two versions: -1, 0 or 1, or `null` when they cannot be ordered
+#[allow(clippy::needless_pass_by_value)]
+pub fn compare_versions(input: CompareInput) -> Result<Option<i8>, Box<dyn std::error::Error>> {
+  let (a, b) = (Version::parse(&input.a)?, Version::parse(&input.b)?);
+  Ok(a.compare(&b).map(|order| order as i8))
+}
+
+/// Parse a constraint into its normalized form
+#[allow(clippy::needless_pass_by_value)]
+pub fn parse_constraint(raw: String) -> Result<Constraint, Box<dyn std::error::Error>> {
+  Ok(Constraint::parse(&raw)?)
+}
+
+/// Whether a version satisfies a constraint
+#[allow(clippy::needless_pass_by_value)]
+pub fn satisfies(input: SatisfiesInput) -> Result<bool, Box<dyn std::error::Error>> {
+  let constraint = Constraint::parse(&input.constraint)?;
+  Ok(constraint.matches(&Version::parse(&input.version)?))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HASH: &str = "9fceb02d6ae4c1e1d76b3b0b5d7a8c2b6f8e9a10";
+
+  fn version(raw: &str) -> Version {
+    Version::parse(raw).unwrap()
+  }
+
+  fn satisfied(constraint: &str, raw: &str) -> bool {
+    Constraint::parse(constraint)
+      .unwrap()
+      .matches(&version(raw))
+  }
+
+  #[test]
+  fn classifies_versions() {
+    assert_eq!(version("v1.2").to_string(), "1.2.0");
+    assert_eq!(
+      version("1.0.0-rc.1+build.5").to_string(),
+      "1.0.0-rc.1+build.5"
+    );
+    assert!(
+      matches!(version("v2021.3"), Version::Calver { year: 2021, ref parts, .. } if parts == &[3])
+    );
+    assert_eq!(
+      version(&"9FCEB02D".repeat(5)).to_string(),
+      "9fceb02d".repeat(5)
+    );
+    assert_eq!(
+      version("feature/ipc"),
+      Version::Ref {
+        name: "feature/ipc".to_string()
+      }
+    );
+    assert_eq!(version("latest"), Version::Latest);
+    assert_eq!(Version::parse(" ").unwrap_err().code, "invalid-version");
+    assert_eq!(
+      Version::parse("1.2.3.4").unwrap_err().code,
+      "invalid-version"
+    );
+  }
+
+  #[test]
+  fn only_full_hashes_are_hashes() {
+    assert!(is_hash(HASH));
+    assert!(is_hash(&"ab".repeat(32)));
+    assert!(!is_hash(&HASH[..7]));
+    assert!(!is_hash(&HASH[..39]));
+    assert_eq!(
+      version(&HASH[..7]),
+      Version::Ref {
+        name: HASH[..7].to_string()
+      }
+    );
+    assert!(matches!(version("20210301"), Version::Calver { .. }));
+  }
+
+  #[test]
+  fn orders_versions_within_a_family() {
+    let order = |a: &str, b: &str| version(a).compare(&version(b));
+    assert_eq!(order("1.10.0", "v1.9.9"), Some(Ordering::Greater));
+    assert_eq!(order("1.0.0-rc.2", "1.0.0-rc.10"), Some(Ordering::Less));
+    assert_eq!(order("1.0.0-rc.1", "1.0.0"), Some(Ordering::Less));
+    assert_eq!(order("1.0.0+a", "1.0.0+b"), Some(Ordering::Equal));
+    assert_eq!(order("v2021.3", "2021.03.0"), Some(Ordering::Equal));
+    assert_eq!(order(HASH, &HASH.to_uppercase()), Some(Ordering::Equal));
+    assert_eq!(order("v2021.3", "1.0.0"), None);
+    assert_eq!(order("main", "develop"), None);
+  }
+
+  #[test]
+  fn normalizes_npm_ranges() {
+    let range = |lower: &str, upper: &str| Constraint::Range {
+      lower: Some(Bound::inclusive(version(lower))),
+      upper: Some(Bound::exclusive(version(upper))),
+    };
+    assert_eq!(Constraint::parse("^1.2").unwrap(), range("1.2.0", "2.0.0"));
+    assert_eq!(
+      Constraint::parse("^0.2.3").unwrap(),
+      range("0.2.3", "0.3.0")
+    );
+    assert_eq!(
+      Constraint::parse("^0.0.3").unwrap(),
+      range("0.0.3", "0.0.4")
+    );
+    assert_eq!(
+      Constraint::parse("~1.2.3").unwrap(),
+      range("1.2.3", "1.3.0")
+    );
+    assert_eq!(Constraint::parse("1.x").unwrap(), range("1.0.0", "2.0.0"));
+    assert_eq!(
+      Constraint::parse(">= 1.2, < 1.5").unwrap(),
+      range("1.2.0", "1.5.0")
+    );
+    assert_eq!(
+      Constraint::parse("1.2 - 1.4").unwrap(),
+      range("1.2.0", "1.5.0")
+    );
+    assert_eq!(
+      Constraint::parse("^v2021.3").unwrap(),
+      range("2021.3", "2022")
+    );
+    assert_eq!(Constraint::parse("*").unwrap(), Constraint::Any);
+    assert_eq!(
+      Constraint::parse(&format!("={HASH}")).unwrap(),
+      Constraint::Exact {
+        version: version(HASH)
+      }
+    );
+  }
+
+  #[test]
+  fn matches_versions() {
+    assert!(satisfied("^1.2", "v1.9.0"));
+    assert!(!satisfied("^1.2", "2.0.0"));
+    assert!(!satisfied("^1.2", "2.0.0-rc.1"));
+    assert!(satisfied(">=1.0.0-beta <1.0.0", "1.0.0-rc.1"));
+    assert!(satisfied(">=v2021.1", "v2021.3"));
+    assert!(!satisfied(">=v2021.1", "1.0.0"));
+    assert!(satisfied("1.x || >=3", "3.1.0"));
+    assert!(!satisfied("!=1.2.3", "1.2.3"));
+    assert!(satisfied(&format!("={HASH}"), HASH));
+    assert!(satisfied("main", "main"));
+  }
+
+  #[test]
+  fn rejects_invalid_constraints() {
+    assert_eq!(
+      Constraint::parse("^main").unwrap_err().code,
+      "invalid-constraint"
+    );
+    assert_eq!(
+      Constraint::parse(">2 <1").unwrap_err().code,
+      "unsatisfiable-constraint"
+    );
+    assert_eq!(
+      Constraint::parse(">=1.0 <v2021").unwrap_err().code,
+      "invalid-constraint"
+    );
+    // There is no version above the largest component to bound the range
+    let max = u64::MAX;
+    for constraint in [format!("^{max}"), format!("~1.{max}"), format!(">1.{max}")] {
+      assert_eq!(
+        Constraint::parse(&constraint).unwrap_err().code,
+        "invalid-constraint",
+        "{constraint}"
+      );
+    }
+  }
+}
@@ -0,0 +1,22 @@
+//! Golden tests replaying the recorded requests in `tests/fixtures`
+//! in-process. The `gud` crate replays the same fixtures through the
+//! compiled dispatcher over a real fd 3 pipe.
+//!
+//! Run with `GUD_UPDATE_FIXTURES=1` to accept changed responses.
+
+use gud_common::harness::assert_fixtures;
+use gud_common::Registry;
+use std::sync::Arc;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn registry() -> Registry {
+  let mut registry = Registry::new();
+  gud_std::register(&mut registry);
+  registry
+}
+
+#[test]
+fn fixtures_replay_in_process() {
+  assert_fixtures(&Arc::new(registry()), FIXTURES);
+}